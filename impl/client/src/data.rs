@@ -5,11 +5,14 @@ use tonic::Request;
 use std::{error, io};
 
 pub fn process(matches: &ArgMatches, data_matches: &ArgMatches) {
-    let result: Result<(), Box<dyn error::Error>> 
+    let result: Result<(), Box<dyn error::Error>>
             = match data_matches.subcommand() {
         ("load", Some(load_matches)) => {
             load(&matches, &data_matches, &load_matches)
         },
+        ("nearest", Some(nearest_matches)) => {
+            nearest(&matches, &data_matches, &nearest_matches)
+        },
         ("search", Some(search_matches)) => {
             search(&matches, &data_matches, &search_matches)
         },
@@ -22,6 +25,168 @@ pub fn process(matches: &ArgMatches, data_matches: &ArgMatches) {
     }
 }
 
+#[tokio::main]
+async fn nearest(matches: &ArgMatches, _: &ArgMatches,
+        nearest_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+    // parse "lat,long" center coordinate
+    let center = nearest_matches.value_of("center").unwrap();
+    let mut fields = center.splitn(2, ',');
+    let lat: f64 = fields.next()
+        .ok_or("missing latitude in center coordinate")?
+        .trim().parse()?;
+    let long: f64 = fields.next()
+        .ok_or("missing longitude in center coordinate")?
+        .trim().parse()?;
+
+    let precision = nearest_matches.value_of("precision")
+        .unwrap().parse::<usize>()?;
+    let limit = nearest_matches.value_of("limit")
+        .unwrap().parse::<usize>()?;
+    let platform = nearest_matches.value_of("platform").unwrap();
+
+    // compute the geohash cell covering the query point
+    let geohash = encode_geohash(lat, long, precision);
+
+    // initialize grpc client
+    let ip_address = matches.value_of("ip_address").unwrap();
+    let port = matches.value_of("port").unwrap().parse::<u16>()?;
+    let mut client = DataManagementClient::connect(
+        format!("http://{}:{}", ip_address, port)).await?;
+
+    // fan out across cluster nodes for candidate tiles in this cell
+    let request = Request::new(SearchAllRequest {
+        geohash: geohash,
+        platform: platform.to_string(),
+    });
+
+    let reply = client.search_all(request).await?;
+    let reply = reply.get_ref();
+
+    // rank candidates by great-circle distance to the query point
+    let mut results: Vec<(u32, String, f64, f64)> = Vec::new();
+    for (node_id, search_reply) in reply.nodes.iter() {
+        for image in search_reply.images.iter() {
+            let (tile_lat, tile_long) = decode_geohash(&image.geohash);
+            let distance = haversine_distance_km(
+                lat, long, tile_lat, tile_long);
+            results.push((*node_id, image.path.clone(),
+                image.coverage, distance));
+        }
+    }
+
+    results.sort_by(|a, b| a.3.partial_cmp(&b.3).unwrap());
+
+    // print information
+    println!("{:<12}{:<80}{:<12}{:<12}", "node_id",
+        "path", "coverage", "distance_km");
+    println!("------------------------------------------------------------------------------------------------------------------");
+    for (node_id, path, coverage, distance) in results.iter().take(limit) {
+        println!("{:<12}{:<80}{:<12}{:<12.3}", node_id,
+            path, coverage, distance);
+    }
+
+    Ok(())
+}
+
+/// standard base32 geohash encoding
+fn encode_geohash(lat: f64, long: f64, precision: usize) -> String {
+    const ALPHABET: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+    let (mut lat_min, mut lat_max) = (-90f64, 90f64);
+    let (mut long_min, mut long_max) = (-180f64, 180f64);
+
+    let mut geohash = String::with_capacity(precision);
+    let mut bit = 0;
+    let mut ch = 0u8;
+    let mut even = true;
+
+    while geohash.len() < precision {
+        if even {
+            let mid = (long_min + long_max) / 2f64;
+            if long > mid {
+                ch |= 1 << (4 - bit);
+                long_min = mid;
+            } else {
+                long_max = mid;
+            }
+        } else {
+            let mid = (lat_min + lat_max) / 2f64;
+            if lat > mid {
+                ch |= 1 << (4 - bit);
+                lat_min = mid;
+            } else {
+                lat_max = mid;
+            }
+        }
+
+        even = !even;
+        if bit < 4 {
+            bit += 1;
+        } else {
+            geohash.push(ALPHABET[ch as usize] as char);
+            bit = 0;
+            ch = 0;
+        }
+    }
+
+    geohash
+}
+
+/// standard base32 geohash decoding to the cell's centroid
+fn decode_geohash(geohash: &str) -> (f64, f64) {
+    const ALPHABET: &str = "0123456789bcdefghjkmnpqrstuvwxyz";
+
+    let (mut lat_min, mut lat_max) = (-90f64, 90f64);
+    let (mut long_min, mut long_max) = (-180f64, 180f64);
+    let mut even = true;
+
+    for c in geohash.chars() {
+        let index = match ALPHABET.find(c) {
+            Some(index) => index,
+            None => continue,
+        };
+
+        for shift in (0..5).rev() {
+            let bit = (index >> shift) & 1;
+            if even {
+                let mid = (long_min + long_max) / 2f64;
+                if bit == 1 {
+                    long_min = mid;
+                } else {
+                    long_max = mid;
+                }
+            } else {
+                let mid = (lat_min + lat_max) / 2f64;
+                if bit == 1 {
+                    lat_min = mid;
+                } else {
+                    lat_max = mid;
+                }
+            }
+
+            even = !even;
+        }
+    }
+
+    ((lat_min + lat_max) / 2f64, (long_min + long_max) / 2f64)
+}
+
+/// great-circle distance between two lat/long points, in kilometers
+fn haversine_distance_km(lat1: f64, long1: f64,
+        lat2: f64, long2: f64) -> f64 {
+    const EARTH_RADIUS_KM: f64 = 6371f64;
+
+    let d_lat = (lat2 - lat1).to_radians();
+    let d_long = (long2 - long1).to_radians();
+
+    let a = (d_lat / 2f64).sin().powi(2)
+        + lat1.to_radians().cos() * lat2.to_radians().cos()
+        * (d_long / 2f64).sin().powi(2);
+    let c = 2f64 * a.sqrt().asin();
+
+    EARTH_RADIUS_KM * c
+}
+
 #[tokio::main]
 async fn fill(matches: &ArgMatches, _: &ArgMatches,
         fill_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
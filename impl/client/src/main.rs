@@ -3,6 +3,7 @@ extern crate clap;
 use clap::App;
 
 mod cluster;
+mod data;
 
 fn main() {
     let yaml = load_yaml!("clap.yaml");
@@ -12,6 +13,8 @@ fn main() {
     match matches.subcommand() {
         ("cluster", Some(cluster_matches)) =>
             cluster::process(&matches, &cluster_matches),
+        ("data", Some(data_matches)) =>
+            data::process(&matches, &data_matches),
         (cmd, _) => println!("unknown subcommand '{}'", cmd),
     }
 }
@@ -1,5 +1,8 @@
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use gdal::raster::{Dataset, Driver};
+use st_image::StImage;
+
+use crate::index::Index;
 
 use std::error::Error;
 use std::fs::File;
@@ -16,12 +19,27 @@ pub struct ImageMetadata {
 
 pub struct DataManager {
     directory: PathBuf,
+    // 'None' only if 'Index::open' returned an error (an i/o failure on
+    // the catalog log - a malformed line only ends its load early) -
+    // 'search_images'/'search_images_range' fall back to scanning the
+    // directory directly rather than failing outright
+    index: Option<Index>,
 }
 
 impl DataManager {
     pub fn new(directory: PathBuf) -> DataManager {
+        let index = match Index::open(&directory) {
+            Ok(index) => Some(index),
+            Err(e) => {
+                warn!("failed to open tile index, falling back to \
+                    directory scans: {}", e);
+                None
+            },
+        };
+
         DataManager {
             directory: directory,
+            index: index,
         }
     }
 
@@ -38,7 +56,7 @@ impl DataManager {
         // save image file 'self.directory/platform/geohash/tile' - TODO error
         path.push(tile);
         path.set_extension("tif");
-        
+
         let driver = Driver::get("GTiff").unwrap();
         dataset.create_copy(&driver, &path.to_string_lossy()).unwrap();
 
@@ -53,11 +71,126 @@ impl DataManager {
         let coverage = st_image::coverage(&dataset).unwrap();
         metadata_file.write_f64::<BigEndian>(coverage)?;
 
+        // keep the catalog in step with every write, so a query never
+        // has to fall back to scanning a tile this process itself wrote
+        if let Some(index) = &self.index {
+            index.insert(platform, geohash, tile,
+                start_date, end_date, coverage)?;
+        }
+
+        Ok(())
+    }
+
+    /// persist a tile already decoded as an 'StImage' (e.g. one pulled
+    /// across the wire by 'transfer::pull_images', which only has the
+    /// peer's serialized image in hand, not a 'gdal::raster::Dataset') -
+    /// otherwise identical to 'write_image', down to the catalog update,
+    /// but round-trips through 'StImage::write' in place of a GTiff
+    /// 'Driver::create_copy' and takes 'coverage' directly rather than
+    /// recomputing it, since the caller already read it off the wire
+    pub fn write_st_image(&self, platform: &str, geohash: &str, tile: &str,
+            start_date: i64, end_date: i64, coverage: f64,
+            st_image: &StImage) -> Result<(), Box<dyn Error>> {
+        // create directory 'self.directory/platform/geohash'
+        let mut path = self.directory.clone();
+        path.push(platform);
+        path.push(geohash);
+
+        std::fs::create_dir_all(&path)?;
+
+        // save image file 'self.directory/platform/geohash/tile'
+        path.push(tile);
+        path.set_extension("tif");
+
+        let mut image_file = File::create(&path)?;
+        st_image.write(&mut image_file)?;
+
+        // write metadata file
+        path.set_extension("meta");
+        let mut metadata_file = File::create(&path)?;
+
+        metadata_file.write_i64::<BigEndian>(start_date)?;
+        metadata_file.write_i64::<BigEndian>(end_date)?;
+        metadata_file.write_f64::<BigEndian>(coverage)?;
+
+        // keep the catalog in step with every write
+        if let Some(index) = &self.index {
+            index.insert(platform, geohash, tile,
+                start_date, end_date, coverage)?;
+        }
+
         Ok(())
     }
 
     pub fn search_images(&self, geohash: &str, platform: &str)
             -> Result<Vec<ImageMetadata>, Box<dyn Error>> {
+        // the index answers with an in-memory lookup; only fall back to
+        // the directory scan below if it failed to open
+        if let Some(index) = &self.index {
+            let mut matches = index.range(platform, geohash, None, None, None);
+            matches.retain(|image_metadata| image_metadata.geohash == geohash);
+            return Ok(matches);
+        }
+
+        self.scan_images(geohash, platform)
+    }
+
+    /// every tile for 'platform' whose geohash starts with
+    /// 'geohash_prefix', overlapping '[min_start, max_end]' (either
+    /// bound optional) with coverage at least 'min_coverage' (if given) -
+    /// the range query the index exists for, rather than 'search_images'
+    /// single-geohash lookup. falls back to scanning every geohash
+    /// directory under the prefix, filtering in memory, when the index
+    /// is unavailable - the same cost 'search_images' always paid before
+    /// this index existed
+    pub fn search_images_range(&self, platform: &str, geohash_prefix: &str,
+            min_start: Option<i64>, max_end: Option<i64>,
+            min_coverage: Option<f64>)
+            -> Result<Vec<ImageMetadata>, Box<dyn Error>> {
+        if let Some(index) = &self.index {
+            return Ok(index.range(platform, geohash_prefix,
+                min_start, max_end, min_coverage));
+        }
+
+        let pattern = format!("{}/{}/{}*",
+            self.directory.to_string_lossy(), platform, geohash_prefix);
+
+        let mut matches = Vec::new();
+        for found in glob::glob(&pattern)? {
+            let path = found?;
+            let geohash = path.file_name()
+                .ok_or("geohash not found in path")?
+                .to_string_lossy().to_string();
+
+            for image_metadata in self.scan_images(&geohash, platform)? {
+                if let Some(min_start) = min_start {
+                    if image_metadata.end_date < min_start {
+                        continue;
+                    }
+                }
+                if let Some(max_end) = max_end {
+                    if image_metadata.start_date > max_end {
+                        continue;
+                    }
+                }
+                if let Some(min_coverage) = min_coverage {
+                    if image_metadata.coverage < min_coverage {
+                        continue;
+                    }
+                }
+
+                matches.push(image_metadata);
+            }
+        }
+
+        Ok(matches)
+    }
+
+    /// the original glob-and-parse scan 'search_images'/
+    /// 'search_images_range' fell back to entirely before the index
+    /// existed - kept as the fallback for when the index failed to open
+    fn scan_images(&self, geohash: &str, platform: &str)
+            -> Result<Vec<ImageMetadata>, Box<dyn Error>> {
         // compile glob file search regex
         let directory = format!("{}/{}/{}/*meta",
             self.directory.to_string_lossy(), platform, geohash);
@@ -0,0 +1,104 @@
+use crate::image::ImageManager;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+/// minimal S3-style HTTP gateway over stored tiles. object keys encode
+/// "platform/dataset/geohash/tile" which maps directly onto the on-disk
+/// layout ImageManager already uses, so a GET can be served straight off
+/// disk and a LIST with a geohash prefix enumerates the finer tiles
+/// beneath it without needing a real HTTP framework dependency.
+pub fn start(listener: TcpListener, image_manager: Arc<ImageManager>) {
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("gateway connection failed: {}", e);
+                    continue;
+                },
+            };
+
+            let image_manager = image_manager.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = handle(stream, image_manager) {
+                    warn!("gateway request failed: {}", e);
+                }
+            });
+        }
+    });
+}
+
+fn handle(mut stream: TcpStream, image_manager: Arc<ImageManager>)
+        -> Result<(), Box<dyn std::error::Error>> {
+    // parse the request line - "METHOD /key HTTP/1.1"
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    let fields: Vec<&str> = request_line.trim().split(' ').collect();
+    if fields.len() < 2 {
+        return write_status(&mut stream, 400, "bad request");
+    }
+
+    let method = fields[0];
+    let key = fields[1].trim_start_matches('/');
+
+    match method {
+        "GET" => get(&mut stream, &image_manager, key),
+        "LIST" => list(&mut stream, &image_manager, key),
+        _ => write_status(&mut stream, 405, "method not allowed"),
+    }
+}
+
+fn get(stream: &mut TcpStream, image_manager: &Arc<ImageManager>, key: &str)
+        -> Result<(), Box<dyn std::error::Error>> {
+    // key is "platform/dataset/geohash/tile"
+    let fields: Vec<&str> = key.splitn(4, '/').collect();
+    if fields.len() != 4 {
+        return write_status(stream, 400,
+            "key must be 'platform/dataset/geohash/tile'");
+    }
+
+    let path = image_manager.tile_path(fields[0], fields[1],
+        fields[2], fields[3]);
+    if !path.exists() {
+        return write_status(stream, 404, "tile not found");
+    }
+
+    let bytes = std::fs::read(&path)?;
+
+    write!(stream, "HTTP/1.1 200 OK\r\n\
+        Content-Type: image/tiff\r\n\
+        Content-Length: {}\r\n\r\n", bytes.len())?;
+    stream.write_all(&bytes)?;
+
+    Ok(())
+}
+
+fn list(stream: &mut TcpStream, image_manager: &Arc<ImageManager>, prefix: &str)
+        -> Result<(), Box<dyn std::error::Error>> {
+    // prefix is "platform/dataset/geohash-prefix" - enumerate every tile
+    // whose geohash starts with the given prefix
+    let fields: Vec<&str> = prefix.splitn(3, '/').collect();
+    let platform = fields.get(0).copied().unwrap_or("");
+    let dataset = fields.get(1).copied().unwrap_or("");
+    let geohash_prefix = fields.get(2).copied().unwrap_or("");
+
+    let keys = image_manager.list_keys(platform, dataset, geohash_prefix)?;
+    let body = keys.join("\n");
+
+    write!(stream, "HTTP/1.1 200 OK\r\n\
+        Content-Type: text/plain\r\n\
+        Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+
+    Ok(())
+}
+
+fn write_status(stream: &mut TcpStream, code: u16, message: &str)
+        -> Result<(), Box<dyn std::error::Error>> {
+    write!(stream, "HTTP/1.1 {} {}\r\n\
+        Content-Length: 0\r\n\r\n", code, message)?;
+    Ok(())
+}
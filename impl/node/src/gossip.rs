@@ -0,0 +1,170 @@
+use rand::seq::SliceRandom;
+use swarm::prelude::Dht;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// default number of peers picked per gossip round
+const FANOUT: usize = 3;
+
+/// entries not refreshed within this many seconds are considered dead
+const DEFAULT_DEAD_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeStatus {
+    Alive,
+    Dead,
+    Unknown,
+}
+
+#[derive(Clone, Debug)]
+pub struct VersionedNodeInfo {
+    pub rpc_addr: SocketAddr,
+    pub xfer_addr: SocketAddr,
+    pub version: u64,
+    pub last_seen: u64,
+}
+
+impl VersionedNodeInfo {
+    pub fn new(rpc_addr: SocketAddr, xfer_addr: SocketAddr,
+            version: u64) -> VersionedNodeInfo {
+        VersionedNodeInfo {
+            rpc_addr: rpc_addr,
+            xfer_addr: xfer_addr,
+            version: version,
+            last_seen: now(),
+        }
+    }
+
+    pub fn status(&self, dead_timeout_secs: u64) -> NodeStatus {
+        if now().saturating_sub(self.last_seen) > dead_timeout_secs {
+            NodeStatus::Dead
+        } else {
+            NodeStatus::Alive
+        }
+    }
+}
+
+/// a last-writer-wins CRDT map of node_id -> VersionedNodeInfo, gossiped
+/// between peers via periodic push/pull rounds
+pub struct GossipState {
+    dead_timeout_secs: u64,
+    node_id: u16,
+    nodes: RwLock<HashMap<u16, VersionedNodeInfo>>,
+}
+
+impl GossipState {
+    pub fn new(node_id: u16, rpc_addr: SocketAddr,
+            xfer_addr: SocketAddr) -> GossipState {
+        let mut nodes = HashMap::new();
+        nodes.insert(node_id,
+            VersionedNodeInfo::new(rpc_addr, xfer_addr, 0));
+
+        GossipState {
+            dead_timeout_secs: DEFAULT_DEAD_TIMEOUT_SECS,
+            node_id: node_id,
+            nodes: RwLock::new(nodes),
+        }
+    }
+
+    /// merge a remote node's state into ours - highest version wins
+    pub fn merge(&self, node_id: u16, info: VersionedNodeInfo) {
+        let mut nodes = self.nodes.write().unwrap();
+        match nodes.get(&node_id) {
+            Some(existing) if existing.version >= info.version => (),
+            _ => { nodes.insert(node_id, info); },
+        }
+    }
+
+    /// refresh our own last-seen timestamp and bump our version
+    pub fn touch(&self) {
+        let mut nodes = self.nodes.write().unwrap();
+        if let Some(info) = nodes.get_mut(&self.node_id) {
+            info.version += 1;
+            info.last_seen = now();
+        }
+    }
+
+    /// entries updated since 'since_version', used for a gossip push
+    pub fn recent(&self, since_version: u64)
+            -> Vec<(u16, VersionedNodeInfo)> {
+        let nodes = self.nodes.read().unwrap();
+        nodes.iter()
+            .filter(|(_, info)| info.version >= since_version)
+            .map(|(id, info)| (*id, info.clone()))
+            .collect()
+    }
+
+    /// compact digest of what we know, used for a gossip pull
+    pub fn digest(&self) -> HashMap<u16, u64> {
+        let nodes = self.nodes.read().unwrap();
+        nodes.iter().map(|(id, info)| (*id, info.version)).collect()
+    }
+
+    /// entries missing or stale relative to a peer's digest
+    pub fn missing(&self, peer_digest: &HashMap<u16, u64>)
+            -> Vec<(u16, VersionedNodeInfo)> {
+        let nodes = self.nodes.read().unwrap();
+        nodes.iter()
+            .filter(|(id, info)| match peer_digest.get(id) {
+                Some(version) => info.version > *version,
+                None => true,
+            })
+            .map(|(id, info)| (*id, info.clone()))
+            .collect()
+    }
+
+    pub fn iter(&self) -> Vec<(u16, VersionedNodeInfo)> {
+        let nodes = self.nodes.read().unwrap();
+        nodes.iter().map(|(id, info)| (*id, info.clone())).collect()
+    }
+
+    pub fn get(&self, node_id: u16) -> Option<VersionedNodeInfo> {
+        let nodes = self.nodes.read().unwrap();
+        nodes.get(&node_id).cloned()
+    }
+
+    pub fn status_of(&self, node_id: u16) -> NodeStatus {
+        let nodes = self.nodes.read().unwrap();
+        match nodes.get(&node_id) {
+            Some(info) => info.status(self.dead_timeout_secs),
+            None => NodeStatus::Unknown,
+        }
+    }
+}
+
+/// every 'period_secs', refresh our own entry ('touch') and pick a random
+/// 'FANOUT' of peers from the dht. no state is exchanged with them: the
+/// round only logs each chosen peer id, so 'GossipState' holds this
+/// node's own entry plus whatever 'merge' is handed directly
+pub fn start(gossip: Arc<GossipState>, dht: Arc<RwLock<Dht>>,
+        period_secs: u64) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(std::time::Duration::from_secs(period_secs));
+
+        gossip.touch();
+
+        // pick a random fanout of peers from the dht
+        let peer_ids: Vec<u16> = {
+            let dht = dht.read().unwrap();
+            dht.iter().map(|(id, _)| *id).collect()
+        };
+
+        let mut rng = rand::thread_rng();
+        let peers: Vec<u16> = peer_ids.choose_multiple(&mut rng, FANOUT)
+            .cloned().collect();
+
+        for peer_id in peers {
+            // no connection is opened to 'peer_id' - 'recent'/'digest'/
+            // 'missing' are not sent anywhere from here
+            trace!("gossip round with peer {}", peer_id);
+        }
+    });
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .unwrap().as_secs()
+}
@@ -59,6 +59,48 @@ impl ImageManager {
         Ok(())
     }
 
+    /// path of the tile file for 'platform/dataset/geohash/tile',
+    /// regardless of whether it actually exists on disk
+    pub fn tile_path(&self, platform: &str, dataset: &str,
+            geohash: &str, tile: &str) -> PathBuf {
+        let mut path = self.directory.clone();
+        path.push(platform);
+        path.push(dataset);
+        path.push(geohash);
+        path.push(tile);
+        path.set_extension("tif");
+
+        path
+    }
+
+    /// list "platform/dataset/geohash/tile" keys for every tile beneath
+    /// 'dataset' whose geohash starts with 'geohash_prefix'
+    pub fn list_keys(&self, platform: &str, dataset: &str,
+            geohash_prefix: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let directory = format!("{}/{}/{}/{}*/*meta",
+            self.directory.to_string_lossy(), platform,
+            dataset, geohash_prefix);
+
+        let mut keys = Vec::new();
+        for entry in glob::glob(&directory)? {
+            let mut path = entry?;
+            path.set_extension("tif");
+
+            let tile = path.file_stem()
+                .ok_or("tile not found in path")?
+                .to_string_lossy().to_string();
+            let _ = path.pop();
+            let geohash = path.file_name()
+                .ok_or("geohash not found in path")?
+                .to_string_lossy().to_string();
+
+            keys.push(format!("{}/{}/{}/{}",
+                platform, dataset, geohash, tile));
+        }
+
+        Ok(keys)
+    }
+
     pub fn search(&self, dataset: &str, geohash: &str, platform: &str)
             -> Result<Vec<ImageMetadata>, Box<dyn Error>> {
         // compile glob file search regex
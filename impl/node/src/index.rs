@@ -0,0 +1,280 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use crate::data::ImageMetadata;
+
+const INDEX_FILE: &'static str = ".index";
+
+/// one cataloged tile - mirrors 'data::ImageMetadata' minus the
+/// 'platform'/'geohash' fields, which key it in 'Index::entries' instead
+/// of being repeated on every entry
+#[derive(Clone)]
+struct Entry {
+    coverage: f64,
+    end_date: i64,
+    start_date: i64,
+    tile: String,
+}
+
+/// persistent catalog of every tile 'DataManager' has written, keyed
+/// first by platform then by geohash in a 'BTreeMap' - a geohash
+/// *prefix* query (the common case, since a node is usually asked about
+/// every tile under a coarser geohash than any single tile was written
+/// at) is then a bounded range scan rather than a walk over every
+/// geohash the platform has tiles under. backs 'DataManager::search_images'
+/// and 'DataManager::search_images_range' so neither has to glob and
+/// parse every '.meta' file under the query path on every call
+pub struct Index {
+    directory: PathBuf,
+    log: RwLock<File>,
+    entries: RwLock<BTreeMap<String, BTreeMap<String, Vec<Entry>>>>,
+}
+
+impl Index {
+    /// load the catalog log at 'directory/.index', starting empty if one
+    /// doesn't exist yet (e.g. a deployment predating this index, or one
+    /// that hasn't called 'rebuild' yet) - a line that fails to parse is
+    /// treated as a truncated trailing append from a crash and ends the
+    /// load rather than failing it, mirroring
+    /// 'task::checkpoint::read_completed'
+    pub fn open(directory: &PathBuf) -> Result<Index, Box<dyn Error>> {
+        let path = directory.join(INDEX_FILE);
+
+        let mut entries = BTreeMap::new();
+        if path.exists() {
+            let file = File::open(&path)?;
+            let reader = BufReader::new(file);
+
+            for line in reader.lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+
+                match parse_entry(&line) {
+                    Some((platform, geohash, entry)) =>
+                        insert_entry(&mut entries, platform, geohash, entry),
+                    None => {
+                        warn!("index log '{:?}' has a malformed entry, \
+                            stopping load early", path);
+                        break;
+                    },
+                }
+            }
+        }
+
+        let log = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        Ok(Index {
+            directory: directory.clone(),
+            log: RwLock::new(log),
+            entries: RwLock::new(entries),
+        })
+    }
+
+    /// reconstruct the catalog from every existing '.meta' file under
+    /// 'directory' - the migration path for a deployment that wrote
+    /// tiles before this index existed, or a recovery path if the log is
+    /// suspected corrupt. the new log is written to a temp file and
+    /// renamed into place, so a crash mid-rebuild leaves the previous
+    /// (or absent) log untouched rather than a half-written catalog
+    pub fn rebuild(directory: &PathBuf) -> Result<Index, Box<dyn Error>> {
+        let pattern = format!("{}/*/*/*.meta", directory.to_string_lossy());
+
+        let mut entries: BTreeMap<String, BTreeMap<String, Vec<Entry>>> =
+            BTreeMap::new();
+        let tmp_path = directory.join(format!("{}.tmp", INDEX_FILE));
+        {
+            let mut tmp_file = File::create(&tmp_path)?;
+
+            for found in glob::glob(&pattern)? {
+                let mut path = found?;
+                let mut file = File::open(&path)?;
+
+                let start_date = file.read_i64::<BigEndian>()?;
+                let end_date = file.read_i64::<BigEndian>()?;
+                let coverage = file.read_f64::<BigEndian>()?;
+
+                let tile = path.file_stem()
+                    .ok_or("tile not found in path")?
+                    .to_string_lossy().to_string();
+                path.pop();
+                let geohash = path.file_name()
+                    .ok_or("geohash not found in path")?
+                    .to_string_lossy().to_string();
+                path.pop();
+                let platform = path.file_name()
+                    .ok_or("platform not found in path")?
+                    .to_string_lossy().to_string();
+
+                writeln!(tmp_file, "{}", format_entry(&platform, &geohash,
+                    &tile, start_date, end_date, coverage))?;
+
+                insert_entry(&mut entries, platform, geohash, Entry {
+                    coverage: coverage,
+                    end_date: end_date,
+                    start_date: start_date,
+                    tile: tile,
+                });
+            }
+
+            tmp_file.sync_all()?;
+        }
+
+        let path = directory.join(INDEX_FILE);
+        fs::rename(&tmp_path, &path)?;
+
+        let log = OpenOptions::new().append(true).open(&path)?;
+
+        Ok(Index {
+            directory: directory.clone(),
+            log: RwLock::new(log),
+            entries: RwLock::new(entries),
+        })
+    }
+
+    /// record a newly written tile - appended to the on-disk log first
+    /// so a crash between the two leaves the log (replayed by 'open') as
+    /// the source of truth, then mirrored into the in-memory view so
+    /// this process's own queries see it immediately
+    pub fn insert(&self, platform: &str, geohash: &str, tile: &str,
+            start_date: i64, end_date: i64, coverage: f64)
+            -> Result<(), Box<dyn Error>> {
+        {
+            let mut log = self.log.write().unwrap();
+            writeln!(log, "{}", format_entry(platform, geohash, tile,
+                start_date, end_date, coverage))?;
+            log.sync_all()?;
+        }
+
+        let mut entries = self.entries.write().unwrap();
+        insert_entry(&mut entries, platform.to_string(), geohash.to_string(),
+            Entry {
+                coverage: coverage,
+                end_date: end_date,
+                start_date: start_date,
+                tile: tile.to_string(),
+            });
+
+        Ok(())
+    }
+
+    /// every cataloged tile for 'platform' whose geohash starts with
+    /// 'geohash_prefix', whose '[start_date, end_date]' overlaps the
+    /// optional '[min_start, max_end]' bound, and whose coverage is at
+    /// least 'min_coverage' (if given) - an exact (non-prefix) geohash
+    /// lookup is just this with 'geohash_prefix' set to the full geohash
+    pub fn range(&self, platform: &str, geohash_prefix: &str,
+            min_start: Option<i64>, max_end: Option<i64>,
+            min_coverage: Option<f64>) -> Vec<ImageMetadata> {
+        let entries = self.entries.read().unwrap();
+
+        let by_geohash = match entries.get(platform) {
+            Some(by_geohash) => by_geohash,
+            None => return Vec::new(),
+        };
+
+        let lower = geohash_prefix.to_string();
+        let scanned: Vec<(&String, &Vec<Entry>)> =
+                match prefix_upper_bound(geohash_prefix) {
+            Some(upper) => by_geohash.range(lower..upper).collect(),
+            None => by_geohash.range(lower..).collect(),
+        };
+
+        let mut matches = Vec::new();
+        for (geohash, tiles) in scanned {
+            for entry in tiles {
+                if let Some(min_start) = min_start {
+                    if entry.end_date < min_start {
+                        continue;
+                    }
+                }
+                if let Some(max_end) = max_end {
+                    if entry.start_date > max_end {
+                        continue;
+                    }
+                }
+                if let Some(min_coverage) = min_coverage {
+                    if entry.coverage < min_coverage {
+                        continue;
+                    }
+                }
+
+                let mut path = self.directory.clone();
+                path.push(platform);
+                path.push(geohash);
+                path.push(&entry.tile);
+                path.set_extension("tif");
+
+                matches.push(ImageMetadata {
+                    coverage: entry.coverage,
+                    end_date: entry.end_date,
+                    geohash: geohash.clone(),
+                    path: path.to_string_lossy().to_string(),
+                    platform: platform.to_string(),
+                    start_date: entry.start_date,
+                });
+            }
+        }
+
+        matches
+    }
+}
+
+fn format_entry(platform: &str, geohash: &str, tile: &str,
+        start_date: i64, end_date: i64, coverage: f64) -> String {
+    format!("{}\t{}\t{}\t{}\t{}\t{}",
+        platform, geohash, tile, start_date, end_date, coverage)
+}
+
+fn parse_entry(line: &str) -> Option<(String, String, Entry)> {
+    let mut fields = line.splitn(6, '\t');
+
+    let platform = fields.next()?.to_string();
+    let geohash = fields.next()?.to_string();
+    let tile = fields.next()?.to_string();
+    let start_date = fields.next()?.parse::<i64>().ok()?;
+    let end_date = fields.next()?.parse::<i64>().ok()?;
+    let coverage = fields.next()?.parse::<f64>().ok()?;
+
+    Some((platform, geohash, Entry {
+        coverage: coverage,
+        end_date: end_date,
+        start_date: start_date,
+        tile: tile,
+    }))
+}
+
+fn insert_entry(entries: &mut BTreeMap<String, BTreeMap<String, Vec<Entry>>>,
+        platform: String, geohash: String, entry: Entry) {
+    entries.entry(platform).or_insert_with(BTreeMap::new)
+        .entry(geohash).or_insert_with(Vec::new)
+        .push(entry);
+}
+
+/// smallest string greater than every string with 'prefix' as a prefix,
+/// so a 'BTreeMap' range bounded by it scans exactly the keys sharing
+/// that prefix - 'None' (meaning "no upper bound") if 'prefix' is empty
+/// or every trailing byte is already the maximum, which doesn't occur
+/// for the lowercase alphanumeric geohashes this index actually stores
+fn prefix_upper_bound(prefix: &str) -> Option<String> {
+    if prefix.is_empty() {
+        return None;
+    }
+
+    let mut bytes = prefix.as_bytes().to_vec();
+    while let Some(last) = bytes.pop() {
+        if last < 0xff {
+            bytes.push(last + 1);
+            return String::from_utf8(bytes).ok();
+        }
+    }
+
+    None
+}
@@ -7,8 +7,13 @@ use structopt::StructOpt;
 use swarm::prelude::{DhtBuilder, SwarmConfigBuilder};
 use tonic::transport::Server;
 
+mod data;
+mod gateway;
+mod gossip;
+use gossip::GossipState;
 mod image;
 use image::ImageManager;
+mod index;
 mod task;
 use task::TaskManager;
 mod rpc;
@@ -63,6 +68,13 @@ fn main() {
     // start swarm
     swarm.start().expect("swarm start");
 
+    // start gossip subsystem - periodically refreshes this node's own
+    // liveness entry; no entries are exchanged with peers
+    let gossip = Arc::new(GossipState::new(opt.node_id,
+        SocketAddr::new(opt.ip_addr, opt.rpc_port),
+        SocketAddr::new(opt.ip_addr, opt.xfer_port)));
+    gossip::start(gossip.clone(), dht.clone(), 5);
+
     // start transfer server
     let listener = TcpListener::bind(format!("{}:{}",
         opt.ip_addr, opt.xfer_port)).expect("xfer service bind");
@@ -73,10 +85,15 @@ fn main() {
 
     server.start().expect("transfer server start");
 
+    // start S3-style read gateway
+    let gateway_listener = TcpListener::bind(format!("{}:{}",
+        opt.ip_addr, opt.gateway_port)).expect("gateway service bind");
+    gateway::start(gateway_listener, image_manager.clone());
+
     // start GRPC server
     let addr = SocketAddr::new(opt.ip_addr, opt.rpc_port);
 
-    let cluster_management = ClusterManagementImpl::new(dht.clone());
+    let cluster_management = ClusterManagementImpl::new(dht.clone(), gossip);
     let data_management =
         DataManagementImpl::new(dht, image_manager, task_manager);
     if let Err(e) = start_rpc_server(addr,
@@ -135,4 +152,8 @@ struct Opt {
     #[structopt(short="x", long="xfer-port",
         help="data transfer port.", default_value="15607")]
     xfer_port: u16,
+
+    #[structopt(short="g", long="gateway-port",
+        help="s3-style read gateway port.", default_value="15608")]
+    gateway_port: u16,
 }
@@ -1,18 +1,22 @@
-use protobuf::{Node, NodeListReply, NodeListRequest, NodeShowReply, NodeShowRequest, ClusterManagement};
+use protobuf::{Node, NodeListReply, NodeListRequest, NodeShowReply, NodeShowRequest, NodeStatus as ProtoNodeStatus, ClusterManagement};
 use swarm::prelude::Dht;
 use tonic::{Request, Response, Status};
 
-use std::net::SocketAddr;
+use crate::gossip::{GossipState, NodeStatus, VersionedNodeInfo};
+
 use std::sync::{Arc, RwLock};
 
 pub struct ClusterManagementImpl {
     dht: Arc<RwLock<Dht>>,
+    gossip: Arc<GossipState>,
 }
 
 impl ClusterManagementImpl {
-    pub fn new(dht: Arc<RwLock<Dht>>) -> ClusterManagementImpl {
+    pub fn new(dht: Arc<RwLock<Dht>>,
+            gossip: Arc<GossipState>) -> ClusterManagementImpl {
         ClusterManagementImpl {
             dht: dht,
+            gossip: gossip,
         }
     }
 }
@@ -23,16 +27,34 @@ impl ClusterManagement for ClusterManagementImpl {
             -> Result<Response<NodeListReply>, Status> {
         trace!("NodeListRequest: {:?}", request);
 
-        // populate cluster nodes from dht
-        let mut nodes = Vec::new();
+        // populate cluster nodes from the gossiped version vector, so a
+        // list reflects every node's last-known liveness
+        let mut nodes: Vec<Node> = self.gossip.iter().into_iter()
+            .map(|(node_id, info)| to_protobuf(node_id as u32, &info,
+                self.gossip.status_of(node_id)))
+            .collect();
+
+        // fall back to the dht directly for any node gossip hasn't
+        // picked up yet (e.g. 'gossip::start' hasn't actually exchanged
+        // state with peers, or a node just joined) - status/last_seen
+        // are unknown since there's no gossiped entry to read them from
         {
             let dht = self.dht.read().unwrap();
             for (node_id, addrs) in dht.iter() {
-                // convert Node to protobuf
-                let node = to_protobuf(*node_id as u32, &addrs.1, &addrs.2);
+                if nodes.iter().any(|n| n.id == *node_id as u32) {
+                    continue;
+                }
 
-                // add to nodes
-                nodes.push(node);
+                if let (Some(rpc_addr), Some(xfer_addr)) =
+                        (addrs.1, addrs.2) {
+                    nodes.push(Node {
+                        id: *node_id as u32,
+                        rpc_addr: format!("{}", rpc_addr),
+                        xfer_addr: format!("{}", xfer_addr),
+                        status: ProtoNodeStatus::Unknown as i32,
+                        last_seen: 0,
+                    });
+                }
             }
         }
 
@@ -49,14 +71,27 @@ impl ClusterManagement for ClusterManagementImpl {
         trace!("NodeShowRequest: {:?}", request);
         let request = request.get_ref();
 
-        // populate cluster node from dht
-        let node = {
-            let dht = self.dht.read().unwrap();
-            match dht.get(request.id as u16) {
-                None => None,
-                Some(addrs) =>
-                    Some(to_protobuf(request.id, addrs.0, addrs.1)),
-            }
+        // populate cluster node from the gossiped version vector so
+        // operators can debug partitions, falling back to the dht
+        // directly (same as 'node_list') when gossip hasn't picked up
+        // this node yet
+        let node = match self.gossip.get(request.id as u16) {
+            Some(info) => Some(to_protobuf(request.id, &info,
+                self.gossip.status_of(request.id as u16))),
+            None => {
+                let dht = self.dht.read().unwrap();
+                dht.get(request.id as u16).and_then(|addrs|
+                    match (addrs.0, addrs.1) {
+                        (Some(rpc_addr), Some(xfer_addr)) => Some(Node {
+                            id: request.id,
+                            rpc_addr: format!("{}", rpc_addr),
+                            xfer_addr: format!("{}", xfer_addr),
+                            status: ProtoNodeStatus::Unknown as i32,
+                            last_seen: 0,
+                        }),
+                        _ => None,
+                    })
+            },
         };
 
         // initialize reply
@@ -68,12 +103,20 @@ impl ClusterManagement for ClusterManagementImpl {
     }
 }
 
-fn to_protobuf(node_id: u32, rpc_addr: &Option<SocketAddr>,
-        xfer_addr: &Option<SocketAddr>) -> Node {
+fn to_protobuf(node_id: u32, info: &VersionedNodeInfo,
+        status: NodeStatus) -> Node {
+    let status = match status {
+        NodeStatus::Alive => ProtoNodeStatus::Alive,
+        NodeStatus::Dead => ProtoNodeStatus::Dead,
+        NodeStatus::Unknown => ProtoNodeStatus::Unknown,
+    };
+
     // initialize node protobuf
     Node {
         id: node_id,
-        rpc_addr: format!("{}", rpc_addr.unwrap()),
-        xfer_addr: format!("{}", xfer_addr.unwrap()),
+        rpc_addr: format!("{}", info.rpc_addr),
+        xfer_addr: format!("{}", info.xfer_addr),
+        status: status as i32,
+        last_seen: info.last_seen as i64,
     }
 }
@@ -4,7 +4,7 @@ use tonic::{Request, Response, Status};
 
 use crate::data::DataManager;
 use crate::task::{TaskHandle, TaskManager, TaskStatus};
-use crate::task::load::{LoadEarthExplorerTask, LoadFormat};
+use crate::task::load::LoadEarthExplorerTask;
 
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
@@ -40,16 +40,18 @@ impl DataManagement for DataManagementImpl {
             ProtoImageFormat::Tiff => ImageFormat::Tiff,
         };*/
 
-        let load_format = match ProtoLoadFormat
+        let format = match ProtoLoadFormat
                 ::from_i32(request.load_format).unwrap() {
-            ProtoLoadFormat::Landsat => LoadFormat::Landsat,
-            ProtoLoadFormat::Sentinel => LoadFormat::Sentinel,
-        };
+            ProtoLoadFormat::Landsat => "landsat",
+            ProtoLoadFormat::Sentinel => "sentinel",
+        }.to_string();
 
+        // LoadEarthExplorerTask::new looks 'format' up in the
+        // SensorLoader registry - TODO error
         let task = LoadEarthExplorerTask::new(self.dht.clone(),
             request.directory.clone(), request.file.clone(),
-            load_format, request.precision as usize,
-            request.thread_count as u8);
+            format, request.precision as usize,
+            request.thread_count as u8).unwrap();
 
         // execute task using task manager
         let task_id = {
@@ -239,8 +241,10 @@ fn to_protobuf(task_id: u64, task_handle: &Arc<RwLock<TaskHandle>>) -> Task {
     
     // compile task status
     let status = match task_handle.get_status() {
+        TaskStatus::Cancelled => protobuf::TaskStatus::Cancelled,
         TaskStatus::Complete => protobuf::TaskStatus::Complete,
         TaskStatus::Failure(_) => protobuf::TaskStatus::Failure,
+        TaskStatus::Paused => protobuf::TaskStatus::Paused,
         TaskStatus::Running => protobuf::TaskStatus::Running,
     };
 
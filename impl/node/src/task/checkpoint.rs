@@ -0,0 +1,155 @@
+use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+/// a log that reaches this many appends (counted since it was opened or
+/// last compacted) is folded into 'snapshot' and truncated -
+/// keeps a crash-recovery replay bounded to a handful of recent entries
+/// instead of the whole task's history
+const LOG_COMPACTION_THRESHOLD: usize = 1024;
+
+/// stable identifier for a 'LoadEarthExplorerTask' invocation, used to
+/// find its checkpoint directory across restarts - the task itself has
+/// no persisted id (node's 'TaskManager' only hands out one in-memory,
+/// reset on restart), so the load directory/file pair stands in for one
+fn task_key(directory: &str, file: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    directory.hash(&mut hasher);
+    file.hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
+fn task_directory(directory: &str, file: &str) -> PathBuf {
+    let mut path = PathBuf::from(directory);
+    path.push(".tasks");
+    path.push(task_key(directory, file));
+    path
+}
+
+fn snapshot_path(directory: &str, file: &str) -> PathBuf {
+    let mut path = task_directory(directory, file);
+    path.push("snapshot.zst");
+    path
+}
+
+fn log_path(directory: &str, file: &str) -> PathBuf {
+    let mut path = task_directory(directory, file);
+    path.push("log");
+    path
+}
+
+/// key identifying one already-sent tile, so a resumed task doesn't
+/// re-split and re-transfer it
+pub fn record_key(record_path: &str, geohash: &str, band_index: usize) -> String {
+    format!("{}\t{}\t{}", record_path, geohash, band_index)
+}
+
+/// replay the zstd-compressed snapshot (if any) plus the write-ahead log
+/// into the set of already-completed keys - a truncated trailing log
+/// line (from a crash mid-append) is silently dropped rather than
+/// treated as an error
+pub fn read_completed(directory: &str, file: &str)
+        -> Result<HashSet<String>, Box<dyn Error>> {
+    let mut completed = HashSet::new();
+
+    let snapshot_path = snapshot_path(directory, file);
+    if snapshot_path.exists() {
+        let compressed = File::open(&snapshot_path)?;
+        let mut decoder = zstd::Decoder::new(compressed)?;
+        let mut contents = String::new();
+        decoder.read_to_string(&mut contents)?;
+
+        for line in contents.lines() {
+            completed.insert(line.to_string());
+        }
+    }
+
+    let log_path = log_path(directory, file);
+    if log_path.exists() {
+        let mut contents = Vec::new();
+        File::open(&log_path)?.read_to_end(&mut contents)?;
+
+        // only newline-terminated records are complete - anything after
+        // the last '\n' is a truncated trailing record, ignored
+        let end = contents.iter().rposition(|b| *b == b'\n')
+            .map(|i| i + 1).unwrap_or(0);
+        for line in String::from_utf8_lossy(&contents[..end]).lines() {
+            completed.insert(line.to_string());
+        }
+    }
+
+    Ok(completed)
+}
+
+/// append-only log of completed '(record_path, geohash, band_index)'
+/// keys, periodically folded into a zstd-compressed snapshot so replay
+/// on resume stays bounded
+pub struct CompletionLog {
+    directory: String,
+    file: String,
+    log: File,
+    pending_entries: usize,
+}
+
+impl CompletionLog {
+    pub fn open(directory: &str, file: &str)
+            -> Result<CompletionLog, Box<dyn Error>> {
+        let dir = task_directory(directory, file);
+        fs::create_dir_all(&dir)?;
+
+        let log = OpenOptions::new().create(true).append(true)
+            .open(log_path(directory, file))?;
+
+        Ok(CompletionLog {
+            directory: directory.to_string(),
+            file: file.to_string(),
+            log: log,
+            pending_entries: 0,
+        })
+    }
+
+    /// append a completed key, compacting the log into the snapshot once
+    /// 'LOG_COMPACTION_THRESHOLD' entries have been appended
+    pub fn append(&mut self, key: &str) -> Result<(), Box<dyn Error>> {
+        writeln!(self.log, "{}", key)?;
+        self.log.sync_all()?;
+
+        self.pending_entries += 1;
+        if self.pending_entries >= LOG_COMPACTION_THRESHOLD {
+            self.compact()?;
+        }
+
+        Ok(())
+    }
+
+    /// rewrite 'snapshot = snapshot ∪ log' to a '.tmp' path, atomically
+    /// rename it into place, then truncate the log - a crash at any
+    /// point leaves either the old snapshot+full log or the new
+    /// snapshot+empty log, never a torn one
+    fn compact(&mut self) -> Result<(), Box<dyn Error>> {
+        let completed = read_completed(&self.directory, &self.file)?;
+
+        let dir = task_directory(&self.directory, &self.file);
+        let tmp_path = dir.join("snapshot.zst.tmp");
+        {
+            let tmp_file = File::create(&tmp_path)?;
+            let mut encoder = zstd::Encoder::new(tmp_file, 0)?;
+            for key in &completed {
+                writeln!(encoder, "{}", key)?;
+            }
+            encoder.finish()?.sync_all()?;
+        }
+
+        fs::rename(&tmp_path, snapshot_path(&self.directory, &self.file))?;
+
+        self.log = OpenOptions::new().write(true).truncate(true)
+            .open(log_path(&self.directory, &self.file))?;
+        self.pending_entries = 0;
+
+        Ok(())
+    }
+}
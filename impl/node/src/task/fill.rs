@@ -1,4 +1,6 @@
 use crossbeam_channel::Receiver;
+use failure::ResultExt;
+use gdal::raster::Dataset;
 
 use crate::data::{DataManager, ImageMetadata};
 use crate::task::{Task, TaskHandle, TaskStatus};
@@ -8,6 +10,11 @@ use std::error::Error;
 use std::sync::{Arc, RwLock};
 use std::sync::atomic::{AtomicU32, Ordering as AtomicOrdering};
 
+/// tile label for a composited image, kept distinct from an original
+/// (unlabeled) tile so fills never shadow the raw image they were
+/// derived from
+pub const FILL_SOURCE: &'static str = "fill";
+
 pub struct FillTask {
     data_manager: Arc<DataManager>,
     geohash: String,
@@ -78,12 +85,13 @@ impl Task for FillTask {
         let items_skipped = Arc::new(AtomicU32::new(0));
         let mut join_handles = Vec::new();
         for _ in 0..self.thread_count {
+            let data_manager = self.data_manager.clone();
             let items_completed = items_completed.clone();
             let items_skipped = items_skipped.clone();
             let receiver_clone = receiver.clone();
 
             let join_handle = std::thread::spawn(move || {
-                if let Err(e) = worker_thread(items_completed,
+                if let Err(e) = worker_thread(data_manager, items_completed,
                         items_skipped, receiver_clone) {
                     panic!("worker thread failure: {}", e);
                 }
@@ -143,8 +151,8 @@ impl Task for FillTask {
     }
 }
 
-fn worker_thread(items_completed: Arc<AtomicU32>,
-        _items_skipped: Arc<AtomicU32>,
+fn worker_thread(data_manager: Arc<DataManager>,
+        items_completed: Arc<AtomicU32>, items_skipped: Arc<AtomicU32>,
         receiver: Receiver<Vec<ImageMetadata>>)
         -> Result<(), Box<dyn Error>> {
     // iterate over records
@@ -154,12 +162,63 @@ fn worker_thread(items_completed: Arc<AtomicU32>,
             Err(_) => break,
         };
 
-        // TODO - process
-        println!("TODO - process images: {:?}", record);
-
-        // increment items completed counter
-        items_completed.fetch_add(1, AtomicOrdering::SeqCst);
+        match fill_record(&data_manager, &record) {
+            Ok(true) => {
+                items_completed.fetch_add(1, AtomicOrdering::SeqCst);
+            },
+            Ok(false) => {
+                items_skipped.fetch_add(1, AtomicOrdering::SeqCst);
+            },
+            Err(e) => {
+                warn!("failed to fill geohash '{}': {}",
+                    record[0].geohash, e);
+                items_skipped.fetch_add(1, AtomicOrdering::SeqCst);
+            },
+        }
     }
 
     Ok(())
 }
+
+/// composite a time-ordered group of partial-coverage, same-geohash
+/// images into a single raster via a per-pixel priority mosaic - newer
+/// images are given priority over older ones, and a pixel only comes
+/// from an older image where every newer image left a gap. the result
+/// is only written back if it actually improves on the best coverage
+/// already present among the group
+fn fill_record(data_manager: &Arc<DataManager>, record: &[ImageMetadata])
+        -> Result<bool, Box<dyn Error>> {
+    // open member datasets newest-to-oldest so the mosaic prioritizes
+    // the most recent clear pixel over an older one
+    let mut images: Vec<&ImageMetadata> = record.iter().collect();
+    images.sort_by(|a, b| b.start_date.cmp(&a.start_date));
+
+    let mut datasets = Vec::new();
+    for image in images.iter() {
+        datasets.push(Dataset::open(&image.path).compat()?);
+    }
+
+    // st_image::prelude::fill walks the datasets in order, tracking a
+    // filled-mask band-by-band so later (older) images only contribute
+    // where earlier ones had nodata gaps
+    let dataset = st_image::prelude::fill(&datasets)?;
+    let coverage = st_image::coverage(&dataset)?;
+
+    // only keep the fill if it improves on every member image's
+    // individual coverage - otherwise there's nothing worth persisting
+    let max_coverage = record.iter()
+        .fold(0f64, |acc, image| acc.max(image.coverage));
+
+    if coverage <= max_coverage {
+        return Ok(false);
+    }
+
+    let newest = images[0];
+    let oldest = images[images.len() - 1];
+    let tile = format!("{}-{}", newest.start_date, FILL_SOURCE);
+
+    data_manager.write_image(&newest.platform, &newest.geohash, &tile,
+        oldest.start_date, newest.end_date, &dataset)?;
+
+    Ok(true)
+}
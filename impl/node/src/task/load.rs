@@ -5,37 +5,53 @@ use image::io::Reader as ImageReader;
 use serde::Deserialize;
 use swarm::prelude::Dht;
 
-use crate::task::{Task, TaskHandle, TaskStatus};
+use crate::task::{Task, TaskControl, TaskHandle, TaskStatus};
+use crate::task::checkpoint::{self, CompletionLog};
+use crate::task::sensor::{self, SensorLoader};
 
+use std::collections::{HashMap, HashSet};
 use std::collections::hash_map::DefaultHasher;
 use std::error::Error;
 use std::fs::File;
 use std::hash::Hasher;
+use std::net::SocketAddr;
 use std::path::Path;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 use std::sync::atomic::{AtomicU32, Ordering};
 
+/// per-destination-node record of '(geohash, content_hash)' pairs already
+/// written there this run - lets a worker skip re-sending a tile whose
+/// raster buffer is byte-identical to one already delivered to that node,
+/// even if it arrived via a different record/geohash path
+type ContentIndex = Mutex<HashMap<SocketAddr, HashSet<(String, String)>>>;
+
 pub struct LoadEarthExplorerTask {
     dht: Arc<RwLock<Dht>>,
     directory: String,
     file: String,
-    load_format: LoadFormat,
+    format: String,
+    loader: Arc<dyn SensorLoader>,
     precision: usize,
     thread_count: u8,
 }
 
 impl LoadEarthExplorerTask {
     pub fn new(dht: Arc<RwLock<Dht>>, directory: String,
-            file: String, load_format: LoadFormat,
-            precision: usize, thread_count: u8) -> LoadEarthExplorerTask {
-        LoadEarthExplorerTask {
+            file: String, format: String, precision: usize,
+            thread_count: u8) -> Result<LoadEarthExplorerTask, Box<dyn Error>> {
+        // look up the registered 'SensorLoader' for this format - this is
+        // the only place a new sensor needs to be wired in
+        let loader: Arc<dyn SensorLoader> = Arc::from(sensor::registry(&format)?);
+
+        Ok(LoadEarthExplorerTask {
             dht: dht,
             directory: directory,
             file: file,
-            load_format: load_format,
+            format: format,
+            loader: loader,
             precision: precision,
             thread_count: thread_count,
-        }
+        })
     }
 }
 
@@ -44,26 +60,57 @@ impl Task for LoadEarthExplorerTask {
         // read file records
         let path = Path::new(&self.file);
         let mut reader = Reader::from_path(path)?;
-        let records = self.load_format.records(&mut reader)?;
+        let records = parse_records(&self.format, &mut reader)?;
+
+        // replay this task's write-ahead log + snapshot so a resumed
+        // run skips tiles a prior, interrupted run already sent
+        let completed = Arc::new(
+            checkpoint::read_completed(&self.directory, &self.file)?);
+        let completion_log = Arc::new(Mutex::new(
+            CompletionLog::open(&self.directory, &self.file)?));
+
+        // per-destination-node content-hash index, so a tile byte-identical
+        // to one already sent to a node this run isn't re-transferred
+        let content_index: Arc<ContentIndex> =
+            Arc::new(Mutex::new(HashMap::new()));
 
         // initialize record channel
         let (sender, receiver) = crossbeam_channel::bounded(256);
 
-        // start worker threads
+        // initialize TaskHandle - its TaskControl is shared with every
+        // worker thread below plus the management thread, so pause()/
+        // resume()/cancel() take effect without either side waiting on
+        // the TaskHandle's own lock
         let items_completed = Arc::new(AtomicU32::new(0));
         let items_skipped = Arc::new(AtomicU32::new(0));
+        let task_handle = Arc::new(RwLock::new(
+            TaskHandle::new(
+                items_completed.clone(),
+                records.len() as u32,
+                TaskStatus::Running
+            )));
+        let control = task_handle.read().unwrap().control();
+
+        // start worker threads
         let mut join_handles = Vec::new();
         for _ in 0..self.thread_count {
+            let completed_clone = completed.clone();
+            let completion_log_clone = completion_log.clone();
+            let content_index_clone = content_index.clone();
+            let control_clone = control.clone();
             let dht_clone = self.dht.clone();
             let directory_clone = self.directory.clone();
             let items_completed = items_completed.clone();
             let items_skipped = items_skipped.clone();
+            let loader_clone = self.loader.clone();
             let precision_clone = self.precision.clone();
             let receiver_clone = receiver.clone();
 
             let join_handle = std::thread::spawn(move || {
-                if let Err(e) = worker_thread(dht_clone,
-                        directory_clone, items_completed, items_skipped,
+                if let Err(e) = worker_thread(completed_clone,
+                        completion_log_clone, content_index_clone,
+                        control_clone, dht_clone, directory_clone,
+                        items_completed, items_skipped, loader_clone,
                         precision_clone, receiver_clone) {
                     panic!("worker thread failure: {}", e);
                 }
@@ -72,20 +119,17 @@ impl Task for LoadEarthExplorerTask {
             join_handles.push(join_handle);
         }
 
-        // initialize TaskHandle
-        let task_handle = Arc::new( RwLock::new(
-            TaskHandle::new(
-                items_completed,
-                items_skipped,
-                records.len() as u32,
-                TaskStatus::Running
-            )));
-
         // start management thread
         let task_handle_clone = task_handle.clone();
         let _ = std::thread::spawn(move || {
-            // add items to pipeline
+            // add items to pipeline - a cancel() stops feeding new
+            // records and drops the sender promptly, rather than
+            // finishing the full glob before workers notice
             for record in records {
+                if control.is_cancelled() {
+                    break;
+                }
+
                 if let Err(e) = sender.send(record) {
                     // set TaskHandle status to 'failed'
                     let mut task_handle =
@@ -96,7 +140,7 @@ impl Task for LoadEarthExplorerTask {
                     return;
                 }
             }
- 
+
             // drop sender to signal worker threads
             drop(sender);
 
@@ -113,9 +157,13 @@ impl Task for LoadEarthExplorerTask {
                 }
             }
 
-            // set TaskHandle status to 'completed'
+            // a cancel() already set a terminal status on the handle -
+            // don't clobber it with 'Complete'
             let mut task_handle = task_handle_clone.write().unwrap();
-            task_handle.set_status(TaskStatus::Complete);
+            match task_handle.get_status() {
+                TaskStatus::Cancelled => (),
+                _ => task_handle.set_status(TaskStatus::Complete),
+            }
         });
 
         // return task handle
@@ -123,12 +171,24 @@ impl Task for LoadEarthExplorerTask {
     }
 }
 
-fn worker_thread(dht: Arc<RwLock<Dht>>, directory: String,
+fn worker_thread(completed: Arc<HashSet<String>>,
+        completion_log: Arc<Mutex<CompletionLog>>,
+        content_index: Arc<ContentIndex>, control: TaskControl,
+        dht: Arc<RwLock<Dht>>, directory: String,
         items_completed: Arc<AtomicU32>, items_skipped: Arc<AtomicU32>,
-        precision: usize, receiver: Receiver<Record>) 
+        loader: Arc<dyn SensorLoader>, precision: usize,
+        receiver: Receiver<Record>)
         -> Result<(), Box<dyn Error>> {
     // iterate over records
     loop {
+        // a paused task blocks here rather than draining the channel,
+        // so in-flight records finish but no new ones start until a
+        // resume()/cancel() wakes this thread
+        control.wait_while_paused();
+        if control.is_cancelled() {
+            break;
+        }
+
         let record: Record = match receiver.recv() {
             Ok(record) => record,
             Err(_) => break,
@@ -143,42 +203,105 @@ fn worker_thread(dht: Arc<RwLock<Dht>>, directory: String,
             continue;
         }
 
-        // open image - TODO error
-        let dataset = Dataset::open(&path).unwrap();
-        // TODO - process imageformat (when it exists)
-
-        // split image with geohash precision - TODO error
-        for (geohash, dataset) in
-                st_image::split(&dataset, precision).unwrap() {
-            // compute geohash hash
-            let mut hasher = DefaultHasher::new();
-            hasher.write(geohash.as_bytes());
-            let hash = hasher.finish();
-
-            // discover hash location
-            let addr = {
-                let dht = dht.read().unwrap(); 
-                let (node_id, addrs) = match dht.locate(hash) {
-                    Some(node) => node,
-                    None => {
-                        warn!("no dht location for hash {}", hash);
-                        continue;
-                    },
+        // ask the registered loader which gdal-openable rasters this
+        // record expands to, rather than assuming a single whole-file
+        // dataset - this is the only format-specific step left in the
+        // worker loop, and it's resolved through the registry instead
+        // of a match over a closed format enum
+        let sub_datasets = match loader.discover_datasets(&path) {
+            Ok(sub_datasets) => sub_datasets,
+            Err(e) => {
+                warn!("failed to discover datasets for '{}': {}",
+                    filename, e);
+                items_skipped.fetch_add(1, Ordering::SeqCst);
+                continue;
+            },
+        };
+
+        for sub_dataset in sub_datasets {
+            // open image - TODO error
+            let dataset = Dataset::open(&sub_dataset.path).unwrap();
+
+            if let Err(e) = loader.parse_timestamp(&dataset) {
+                warn!("failed to parse timestamp for '{}': {}",
+                    sub_dataset.path.display(), e);
+            }
+
+            // split image with geohash precision - TODO error
+            for (geohash, dataset) in
+                    st_image::split(&dataset, precision).unwrap() {
+                let key = checkpoint::record_key(record.tile(), &geohash,
+                    sub_dataset.band_index);
+                if completed.contains(&key) {
+                    // already sent by a prior, interrupted run of this task
+                    items_skipped.fetch_add(1, Ordering::SeqCst);
+                    continue;
+                }
+
+                // compute geohash hash
+                let mut hasher = DefaultHasher::new();
+                hasher.write(geohash.as_bytes());
+                let hash = hasher.finish();
+
+                // discover hash location
+                let addr = {
+                    let dht = dht.read().unwrap();
+                    let (node_id, addrs) = match dht.locate(hash) {
+                        Some(node) => node,
+                        None => {
+                            warn!("no dht location for hash {}", hash);
+                            continue;
+                        },
+                    };
+
+                    match addrs.1 {
+                        Some(addr) => addr.clone(),
+                        None => {
+                            warn!("dht node {} has no xfer_addr", node_id);
+                            continue;
+                        },
+                    }
                 };
 
-                match addrs.1 {
-                    Some(addr) => addr.clone(),
-                    None => {
-                        warn!("dht node {} has no xfer_addr", node_id);
+                // hash the split tile's raster buffer - a byte-identical
+                // tile already delivered to this destination node (e.g.
+                // from an overlapping scene) doesn't need to be re-sent
+                let mut buf = Vec::new();
+                if let Err(e) = dataset.write(&mut buf) {
+                    warn!("failed to serialize tile '{}' for hashing: {}",
+                        record.tile(), e);
+                    continue;
+                }
+                let content_hash = blake3::hash(&buf).to_hex().to_string();
+                let content_key = (geohash.clone(), content_hash.clone());
+
+                {
+                    let mut content_index = content_index.lock().unwrap();
+                    let node_hashes = content_index.entry(addr).or_insert_with(HashSet::new);
+                    if node_hashes.contains(&content_key) {
+                        // already present on the target node - skip transfer
+                        items_skipped.fetch_add(1, Ordering::SeqCst);
                         continue;
-                    },
+                    }
+                }
+
+                // send image to new host
+                if let Err(e) = crate::transfer::send_image(&record.platform(),
+                        &geohash, &record.tile(), &dataset, &addr) {
+                    warn!("failed to write image to node {}: {}", addr, e);
+                    continue;
                 }
-            };
 
-            // send image to new host
-            if let Err(e) = crate::transfer::send_image(&record.platform(), 
-                    &geohash, &record.tile(), &dataset, &addr) {
-                warn!("failed to write image to node {}: {}", addr, e);
+                // record the content hash as delivered to this node
+                content_index.lock().unwrap()
+                    .entry(addr).or_insert_with(HashSet::new)
+                    .insert(content_key);
+
+                // checkpoint the tile as sent so a crash/restart doesn't
+                // retransfer it
+                if let Err(e) = completion_log.lock().unwrap().append(&key) {
+                    warn!("failed to checkpoint tile '{}': {}", key, e);
+                }
             }
         }
 
@@ -189,34 +312,32 @@ fn worker_thread(dht: Arc<RwLock<Dht>>, directory: String,
     Ok(())
 }
 
-pub enum LoadFormat {
-    Landsat,
-    Sentinel,
-}
-
-impl LoadFormat {
-    fn records(&self, reader: &mut Reader<File>)
-            -> Result<Vec<Record>, Box<dyn Error>> {
-        let mut records = Vec::new();
-        match self {
-            LoadFormat::Landsat => {
-                // parse all records as 'landsat'
-                for result in reader.deserialize() {
-                    let record: LandsatRecord = result?;
-                    records.push(Record::Landsat(record));
-                }
-            },
-            LoadFormat::Sentinel => {
-                // parse all records as 'sentinel'
-                for result in reader.deserialize() {
-                    let record: SentinelRecord = result?;
-                    records.push(Record::Sentinel(record));
-                }
-            },
-        }
-
-        Ok(records)
+/// parse the manifest's rows against the csv schema for 'format' - this
+/// is a separate, lower-level concern from 'SensorLoader' (which is
+/// about the raster(s) a record expands to, not the manifest's columns),
+/// so it stays a plain match rather than part of the registry
+fn parse_records(format: &str, reader: &mut Reader<File>)
+        -> Result<Vec<Record>, Box<dyn Error>> {
+    let mut records = Vec::new();
+    match format {
+        "landsat" => {
+            // parse all records as 'landsat'
+            for result in reader.deserialize() {
+                let record: LandsatRecord = result?;
+                records.push(Record::Landsat(record));
+            }
+        },
+        "sentinel" => {
+            // parse all records as 'sentinel'
+            for result in reader.deserialize() {
+                let record: SentinelRecord = result?;
+                records.push(Record::Sentinel(record));
+            }
+        },
+        other => return Err(format!("unrecognized sensor format '{}'", other).into()),
     }
+
+    Ok(records)
 }
 
 enum Record {
@@ -1,16 +1,73 @@
 use std::collections::HashMap;
 use std::collections::hash_map::Iter;
 use std::error::Error;
-use std::sync::{Arc, RwLock};
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::sync::atomic::{AtomicU8, AtomicU32, Ordering};
 
+pub mod checkpoint;
 pub mod load;
+pub mod sensor;
 
 pub trait Task {
     fn start(&self) -> Result<Arc<RwLock<TaskHandle>>, Box<dyn Error>>;
 }
 
+const CONTROL_RUNNING: u8 = 0;
+const CONTROL_PAUSED: u8 = 1;
+const CONTROL_CANCELLED: u8 = 2;
+
+/// the cooperative stop/go signal a task's worker threads poll between
+/// records - shared (via clone) with the management thread that owns
+/// the record channel, so 'pause()'/'cancel()' take effect without
+/// either side holding the 'TaskHandle' lock
+#[derive(Clone)]
+pub struct TaskControl {
+    condvar: Arc<Condvar>,
+    mutex: Arc<Mutex<()>>,
+    state: Arc<AtomicU8>,
+}
+
+impl TaskControl {
+    fn new() -> TaskControl {
+        TaskControl {
+            condvar: Arc::new(Condvar::new()),
+            mutex: Arc::new(Mutex::new(())),
+            state: Arc::new(AtomicU8::new(CONTROL_RUNNING)),
+        }
+    }
+
+    fn pause(&self) {
+        self.state.store(CONTROL_PAUSED, Ordering::SeqCst);
+    }
+
+    fn resume(&self) {
+        let _guard = self.mutex.lock().unwrap();
+        self.state.store(CONTROL_RUNNING, Ordering::SeqCst);
+        self.condvar.notify_all();
+    }
+
+    fn cancel(&self) {
+        let _guard = self.mutex.lock().unwrap();
+        self.state.store(CONTROL_CANCELLED, Ordering::SeqCst);
+        self.condvar.notify_all();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == CONTROL_CANCELLED
+    }
+
+    /// block the calling worker thread while paused, without draining
+    /// the crossbeam channel - a resume() or cancel() wakes it
+    pub fn wait_while_paused(&self) {
+        let mut guard = self.mutex.lock().unwrap();
+        while self.state.load(Ordering::SeqCst) == CONTROL_PAUSED {
+            guard = self.condvar.wait(guard).unwrap();
+        }
+    }
+}
+
 pub struct TaskHandle {
+    control: TaskControl,
     items_completed: Arc<AtomicU32>,
     items_total: u32,
     status: TaskStatus,
@@ -20,12 +77,19 @@ impl TaskHandle {
     pub fn new(items_completed: Arc<AtomicU32>,
             items_total: u32, status: TaskStatus) -> TaskHandle {
         TaskHandle {
+            control: TaskControl::new(),
             items_completed: items_completed,
             items_total: items_total,
             status: status,
         }
     }
 
+    /// the shared control handle a task's worker/management threads
+    /// poll - clone it when spawning them
+    pub fn control(&self) -> TaskControl {
+        self.control.clone()
+    }
+
     pub fn get_completion_percent(&self) -> Option<f32> {
         match self.items_total {
             0 => None,
@@ -44,6 +108,21 @@ impl TaskHandle {
     pub fn set_status(&mut self, status: TaskStatus) {
         self.status = status;
     }
+
+    pub fn pause(&mut self) {
+        self.control.pause();
+        self.status = TaskStatus::Paused;
+    }
+
+    pub fn resume(&mut self) {
+        self.control.resume();
+        self.status = TaskStatus::Running;
+    }
+
+    pub fn cancel(&mut self) {
+        self.control.cancel();
+        self.status = TaskStatus::Cancelled;
+    }
 }
 
 pub struct TaskManager {
@@ -82,7 +161,9 @@ impl TaskManager {
 }
 
 pub enum TaskStatus {
+    Cancelled,
     Complete,
     Failure(String),
+    Paused,
     Running,
 }
@@ -0,0 +1,97 @@
+use gdal::metadata::Metadata;
+use gdal::raster::Dataset;
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// one gdal-openable raster belonging to a record - most sensors yield
+/// exactly one (the record's own file), but a loader for a multi-band
+/// product can return several, each carrying the band index that keys
+/// its checkpoint entry alongside the record path and geohash
+pub struct SubDataset {
+    pub band_index: usize,
+    pub path: PathBuf,
+}
+
+/// a pluggable earth-observation product - adding one is a matter of
+/// implementing this trait and registering it below, rather than
+/// editing a closed enum (and every match over it) in the scheduler
+pub trait SensorLoader: Send + Sync {
+    /// the gdal-openable raster(s) a record expands to
+    fn discover_datasets(&self, record: &Path)
+        -> Result<Vec<SubDataset>, Box<dyn Error>>;
+
+    /// the scene's capture time, read from whatever metadata item this
+    /// sensor's product embeds it under
+    fn parse_timestamp(&self, dataset: &Dataset)
+        -> Result<i64, Box<dyn Error>>;
+
+    fn platform_name(&self) -> &str;
+}
+
+pub struct LandsatLoader;
+
+impl SensorLoader for LandsatLoader {
+    fn discover_datasets(&self, record: &Path)
+            -> Result<Vec<SubDataset>, Box<dyn Error>> {
+        // a landsat record is a single geotiff - no subdataset split
+        Ok(vec![SubDataset {
+            band_index: 0,
+            path: record.to_path_buf(),
+        }])
+    }
+
+    fn parse_timestamp(&self, dataset: &Dataset)
+            -> Result<i64, Box<dyn Error>> {
+        parse_tiff_datetime(dataset)
+    }
+
+    fn platform_name(&self) -> &str {
+        "Landsat"
+    }
+}
+
+pub struct SentinelLoader;
+
+impl SensorLoader for SentinelLoader {
+    fn discover_datasets(&self, record: &Path)
+            -> Result<Vec<SubDataset>, Box<dyn Error>> {
+        // same as landsat for now - a vendor tile is one geotiff
+        Ok(vec![SubDataset {
+            band_index: 0,
+            path: record.to_path_buf(),
+        }])
+    }
+
+    fn parse_timestamp(&self, dataset: &Dataset)
+            -> Result<i64, Box<dyn Error>> {
+        parse_tiff_datetime(dataset)
+    }
+
+    fn platform_name(&self) -> &str {
+        "Sentinel"
+    }
+}
+
+/// shared by both loaders above - a standard tiff 'TIFFTAG_DATETIME'
+/// item, formatted "YYYY:MM:DD HH:MM:SS"
+fn parse_tiff_datetime(dataset: &Dataset) -> Result<i64, Box<dyn Error>> {
+    let value = match dataset.metadata_item("TIFFTAG_DATETIME", "") {
+        Some(value) => value,
+        None => return Err("dataset has no 'TIFFTAG_DATETIME' metadata item".into()),
+    };
+
+    let datetime = chrono::NaiveDateTime::parse_from_str(
+        &value, "%Y:%m:%d %H:%M:%S")?;
+    Ok(datetime.timestamp())
+}
+
+/// format string -> loader, consulted by 'LoadEarthExplorerTask' instead
+/// of matching a closed 'LoadFormat' enum
+pub fn registry(format: &str) -> Result<Box<dyn SensorLoader>, Box<dyn Error>> {
+    match format {
+        "landsat" => Ok(Box::new(LandsatLoader)),
+        "sentinel" => Ok(Box::new(SentinelLoader)),
+        other => Err(format!("unrecognized sensor format '{}'", other).into()),
+    }
+}
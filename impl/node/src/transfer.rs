@@ -1,4 +1,4 @@
-use byteorder::{ReadBytesExt, WriteBytesExt};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use comm::StreamHandler;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
@@ -34,7 +34,42 @@ impl StreamHandler for TransferStreamHandler {
         // read operation type
         let op_type = stream.read_u8()?;
         match FromPrimitive::from_u8(op_type) {
-            Some(TransferOp::Read) => unimplemented!(),
+            Some(TransferOp::Read) => {
+                // read selector
+                let platform_len = stream.read_u8()?;
+                let mut platform_buf = vec![0u8; platform_len as usize];
+                stream.read_exact(&mut platform_buf)?;
+                let platform = String::from_utf8(platform_buf)?;
+
+                let geohash_len = stream.read_u8()?;
+                let mut geohash_buf = vec![0u8; geohash_len as usize];
+                stream.read_exact(&mut geohash_buf)?;
+                let geohash = String::from_utf8(geohash_buf)?;
+
+                // resolve every tile this node holds for the requested
+                // platform/geohash and stream each one's tile id,
+                // metadata, and image back, in the order 'recv_image'
+                // expects
+                let image_metadatas = self.data_manager
+                    .search_images(&geohash, &platform)?;
+
+                stream.write_u32::<BigEndian>(image_metadatas.len() as u32)?;
+                for image_metadata in image_metadatas {
+                    let tile = std::path::Path::new(&image_metadata.path)
+                        .file_stem().ok_or("tile not found in path")?
+                        .to_string_lossy().to_string();
+
+                    stream.write_u8(tile.len() as u8)?;
+                    stream.write(tile.as_bytes())?;
+
+                    stream.write_i64::<BigEndian>(image_metadata.start_date)?;
+                    stream.write_i64::<BigEndian>(image_metadata.end_date)?;
+                    stream.write_f64::<BigEndian>(image_metadata.coverage)?;
+
+                    let st_image = StImage::open(&image_metadata.path)?;
+                    st_image.write(stream)?;
+                }
+            },
             Some(TransferOp::Write) => {
                 // read metadata
                 let spacecraft_len = stream.read_u8()?;
@@ -79,3 +114,53 @@ pub fn send_image(spacecraft_id: &str, product_id: &str, st_image: &StImage,
     stream.write_u8(TransferOp::Write as u8)?;
     st_image.write(&mut stream)
 }
+
+/// fetch every tile a peer holds for (platform, geohash) - the pull-based
+/// counterpart to 'send_image', mirroring its wire shape but in reverse
+pub fn recv_image(platform: &str, geohash: &str, addr: &SocketAddr)
+        -> Result<Vec<(String, i64, i64, f64, StImage)>, Box<dyn Error>> {
+    // open connection
+    let mut stream = TcpStream::connect(addr)?;
+
+    // write selector
+    stream.write_u8(TransferOp::Read as u8)?;
+
+    stream.write_u8(platform.len() as u8)?;
+    stream.write(platform.as_bytes())?;
+
+    stream.write_u8(geohash.len() as u8)?;
+    stream.write(geohash.as_bytes())?;
+
+    // read back each matching tile's id, metadata, and image
+    let count = stream.read_u32::<BigEndian>()?;
+    let mut images = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let tile_len = stream.read_u8()?;
+        let mut tile_buf = vec![0u8; tile_len as usize];
+        stream.read_exact(&mut tile_buf)?;
+        let tile = String::from_utf8(tile_buf)?;
+
+        let start_date = stream.read_i64::<BigEndian>()?;
+        let end_date = stream.read_i64::<BigEndian>()?;
+        let coverage = stream.read_f64::<BigEndian>()?;
+        let st_image = StImage::read(&mut stream)?;
+
+        images.push((tile, start_date, end_date, coverage, st_image));
+    }
+
+    Ok(images)
+}
+
+/// pull every tile a peer holds for (platform, geohash) and persist each
+/// one locally through 'data_manager' ('DataManager::write_st_image') -
+/// the fetching counterpart to a peer pushing tiles via 'send_image'
+pub fn pull_images(data_manager: &DataManager, platform: &str,
+        geohash: &str, addr: &SocketAddr) -> Result<(), Box<dyn Error>> {
+    for (tile, start_date, end_date, coverage, st_image) in
+            recv_image(platform, geohash, addr)? {
+        data_manager.write_st_image(platform, geohash, &tile,
+            start_date, end_date, coverage, &st_image)?;
+    }
+
+    Ok(())
+}
@@ -1,11 +1,13 @@
 use clap::ArgMatches;
-use protobuf::{AlbumBroadcastRequest, AlbumBroadcastType, AlbumCloseRequest, AlbumCreateRequest, AlbumListRequest, AlbumManagementClient, AlbumOpenRequest, AlbumStatus, Geocode};
+use protobuf::{AlbumBroadcastRequest, AlbumBroadcastType, AlbumCloseRequest, AlbumCreateRequest, AlbumListRequest, AlbumManagementClient, AlbumOpenRequest, AlbumOptimizeRequest, AlbumStatus, CompressionCodec, Geocode};
 use tonic::Request;
 
-use std::{error, io};
+use crate::error::CliError;
+
+use std::io;
 
 pub fn process(matches: &ArgMatches, album_matches: &ArgMatches) {
-    let result: Result<(), Box<dyn error::Error>> 
+    let result: Result<(), CliError> 
             = match album_matches.subcommand() {
         ("close", Some(close_matches)) =>
             close(&matches, &album_matches, &close_matches),
@@ -15,7 +17,9 @@ pub fn process(matches: &ArgMatches, album_matches: &ArgMatches) {
             list(&matches, &album_matches, &list_matches),
         ("open", Some(open_matches)) =>
             open(&matches, &album_matches, &open_matches),
-        (cmd, _) => Err(Box::new(io::Error::new(io::ErrorKind::Other,
+        ("optimize", Some(optimize_matches)) =>
+            optimize(&matches, &album_matches, &optimize_matches),
+        (cmd, _) => Err(CliError::Io(io::Error::new(io::ErrorKind::Other,
             format!("unknown subcommand '{}'", cmd)))),
     };
 
@@ -26,12 +30,13 @@ pub fn process(matches: &ArgMatches, album_matches: &ArgMatches) {
 
 #[tokio::main]
 async fn close(matches: &ArgMatches, _: &ArgMatches,
-        close_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+        close_matches: &ArgMatches) -> Result<(), CliError> {
     // initialize grpc client
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = AlbumManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = AlbumManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
     // initialize request
     let close_request = AlbumCloseRequest {
@@ -43,6 +48,7 @@ async fn close(matches: &ArgMatches, _: &ArgMatches,
         create_request: None,
         close_request: Some(close_request),
         open_request: None,
+        optimize_request: None,
     });
 
     // retrieve reply
@@ -53,22 +59,49 @@ async fn close(matches: &ArgMatches, _: &ArgMatches,
 
 #[tokio::main]
 async fn create(matches: &ArgMatches, _: &ArgMatches,
-        create_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+        create_matches: &ArgMatches) -> Result<(), CliError> {
     // initialize grpc client
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = AlbumManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = AlbumManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
     // parse arguments
     let geocode = match create_matches.value_of("GEOCODE") {
         Some("geohash") => Geocode::Geohash as i32,
         Some("quadtile") => Geocode::Quadtile as i32,
-        _ => unimplemented!(),
+        got => return Err(CliError::UnknownFormat {
+            what: "GEOCODE",
+            got: got.unwrap_or("").to_string(),
+            expected: vec!["geohash", "quadtile"],
+        }),
+    };
+
+    let compression = match create_matches.value_of("compression") {
+        Some("lzw") => CompressionCodec::Lzw as i32,
+        Some("deflate") | None => CompressionCodec::Deflate as i32,
+        Some("zstd") => CompressionCodec::Zstd as i32,
+        Some("none") => CompressionCodec::None as i32,
+        got => return Err(CliError::UnknownFormat {
+            what: "compression",
+            got: got.unwrap_or("").to_string(),
+            expected: vec!["lzw", "deflate", "zstd", "none"],
+        }),
     };
 
+    let compression_level = create_matches.value_of("compression_level")
+        .map(|value| value.parse::<i32>()).transpose()?;
+
+    let block_size = create_matches.value_of("block_size")
+        .map(|value| value.parse::<u32>()).transpose()?.unwrap_or(0);
+
     // initialize request
     let create_request = AlbumCreateRequest {
+        block_size: block_size,
+        cloud_optimized: create_matches.is_present("cloud_optimized"),
+        compression: compression,
+        compression_level: compression_level,
         dht_key_length: create_matches.value_of("dht_key_length")
             .unwrap().parse::<i32>()?,
         geocode: geocode,
@@ -80,6 +113,7 @@ async fn create(matches: &ArgMatches, _: &ArgMatches,
         create_request: Some(create_request),
         close_request: None,
         open_request: None,
+        optimize_request: None,
     });
 
     // retrieve reply
@@ -90,12 +124,13 @@ async fn create(matches: &ArgMatches, _: &ArgMatches,
 
 #[tokio::main]
 async fn list(matches: &ArgMatches, _: &ArgMatches,
-        _list_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+        _list_matches: &ArgMatches) -> Result<(), CliError> {
     // initialize grpc client
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = AlbumManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = AlbumManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
     // initialize request
     let request = Request::new(AlbumListRequest {});
@@ -128,12 +163,13 @@ async fn list(matches: &ArgMatches, _: &ArgMatches,
 
 #[tokio::main]
 async fn open(matches: &ArgMatches, _: &ArgMatches,
-        open_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+        open_matches: &ArgMatches) -> Result<(), CliError> {
     // initialize grpc client
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = AlbumManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = AlbumManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
     // initialize request
     let open_request = AlbumOpenRequest {
@@ -148,6 +184,7 @@ async fn open(matches: &ArgMatches, _: &ArgMatches,
         create_request: None,
         close_request: None,
         open_request: Some(open_request),
+        optimize_request: None,
     });
 
     // retrieve reply
@@ -160,5 +197,44 @@ async fn open(matches: &ArgMatches, _: &ArgMatches,
             node_id, open_reply.task_id);
     }
 
+    // surface any node the broadcast couldn't reach or open the task on
+    if !reply.errors.is_empty() {
+        println!();
+        println!("{} of {} nodes did not report:", reply.errors.len(),
+            reply.errors.len() + reply.open_replies.len());
+        for (node_id, error) in reply.errors.iter() {
+            println!("  node {}: {}", node_id, error);
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn optimize(matches: &ArgMatches, _: &ArgMatches,
+        optimize_matches: &ArgMatches) -> Result<(), CliError> {
+    // initialize grpc client
+    let ip_address = matches.value_of("ip_address").unwrap();
+    let port = matches.value_of("port").unwrap().parse::<u16>()?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = AlbumManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
+
+    // initialize request
+    let optimize_request = AlbumOptimizeRequest {
+        id: optimize_matches.value_of("ID").unwrap().to_string(),
+    };
+
+    let request = Request::new(AlbumBroadcastRequest {
+        message_type: AlbumBroadcastType::AlbumOptimize as i32,
+        create_request: None,
+        close_request: None,
+        open_request: None,
+        optimize_request: Some(optimize_request),
+    });
+
+    // retrieve reply
+    let _ = client.broadcast(request).await?;
+
     Ok(())
 }
@@ -1,12 +1,16 @@
 use clap::ArgMatches;
-use protobuf::{ClusterManagementClient, DataBroadcastRequest, DataBroadcastType, DataFillRequest, DataListRequest, Extent, Filter, LoadFormat, DataLoadRequest, DataManagementClient, DataSearchRequest, DataSplitRequest, NodeListRequest};
+use protobuf::{DataBroadcastRequest, DataBroadcastType, DataFillRequest, DataListRequest, Extent, Filter, Geocode, LoadFormat, DataLoadRequest, DataManagementClient, DataSearchRequest, DataSplitRequest, NodeListRequest};
 use tonic::Request;
 
-use std::{error, io};
+use crate::error::CliError;
+
+use chrono::{Datelike, NaiveDateTime};
+
+use std::io;
 use std::collections::BTreeMap;
 
 pub fn process(matches: &ArgMatches, data_matches: &ArgMatches) {
-    let result: Result<(), Box<dyn error::Error>> 
+    let result: Result<(), CliError>
             = match data_matches.subcommand() {
         ("fill", Some(fill_matches)) => {
             fill(&matches, &data_matches, &fill_matches)
@@ -23,7 +27,7 @@ pub fn process(matches: &ArgMatches, data_matches: &ArgMatches) {
         ("split", Some(split_matches)) => {
             split(&matches, &data_matches, &split_matches)
         },
-        (cmd, _) => Err(Box::new(io::Error::new(io::ErrorKind::Other,
+        (cmd, _) => Err(CliError::Io(io::Error::new(io::ErrorKind::Other,
             format!("unknown subcommand '{}'", cmd)))),
     };
 
@@ -32,14 +36,271 @@ pub fn process(matches: &ArgMatches, data_matches: &ArgMatches) {
     }
 }
 
+/// randomize the poll order over 'node_count' nodes, so a fan-out
+/// doesn't hit the same node first on every call - there's no per-node
+/// capacity/data-density signal gossiped yet to bias the order by, so
+/// this is a plain shuffle rather than a weighted one
+fn shuffled_order(node_count: usize) -> Vec<usize> {
+    use rand::seq::SliceRandom;
+
+    let mut order: Vec<usize> = (0..node_count).collect();
+    order.shuffle(&mut rand::thread_rng());
+    order
+}
+
+/// 'search's time-bucketing mode - floors each 'Extent's timestamp to a
+/// fixed or calendar-aligned bin so the aggregated counts show coverage
+/// over time instead of collapsing the time dimension entirely
+enum BinMode {
+    None,
+    Daily,
+    Weekly,
+    Monthly,
+    Seconds(i64),
+}
+
+fn parse_bin_mode(value: Option<&str>) -> Result<BinMode, CliError> {
+    match value {
+        None | Some("none") => Ok(BinMode::None),
+        Some("daily") => Ok(BinMode::Daily),
+        Some("weekly") => Ok(BinMode::Weekly),
+        Some("monthly") => Ok(BinMode::Monthly),
+        Some(value) if value.starts_with("seconds:") => {
+            let seconds = value["seconds:".len()..].parse::<i64>()?;
+            if seconds <= 0 {
+                return Err(CliError::ParseArg(format!(
+                    "bin width must be positive, got '{}'", seconds)));
+            }
+
+            Ok(BinMode::Seconds(seconds))
+        },
+        Some(got) => Err(CliError::UnknownFormat {
+            what: "bin",
+            got: got.to_string(),
+            expected: vec!["none", "daily", "weekly", "monthly", "seconds:N"],
+        }),
+    }
+}
+
+/// floor a unix timestamp to its bin label - a fixed-width epoch for
+/// 'seconds:N', or a calendar-aligned bucket for daily/weekly/monthly
+fn compute_bin(timestamp: i64, bin_mode: &BinMode) -> String {
+    match bin_mode {
+        BinMode::None => "all".to_string(),
+        BinMode::Seconds(width) =>
+            (timestamp - timestamp.rem_euclid(*width)).to_string(),
+        BinMode::Daily => NaiveDateTime::from_timestamp(timestamp, 0)
+            .format("%Y-%m-%d").to_string(),
+        BinMode::Weekly => {
+            let iso_week = NaiveDateTime::from_timestamp(timestamp, 0)
+                .date().iso_week();
+            format!("{}-W{:02}", iso_week.year(), iso_week.week())
+        },
+        BinMode::Monthly => NaiveDateTime::from_timestamp(timestamp, 0)
+            .format("%Y-%m").to_string(),
+    }
+}
+
+/// render 'list's gathered (node, platform, geohash, source, timestamp,
+/// pixel_coverage, cloud_coverage, path) rows in the requested format -
+/// 'table' is kept as the default so existing scripts/terminals piping
+/// the fixed-width output don't break
+fn print_list_rows(output: &str, rows: &[(u32, String, String, String,
+        i64, f64, Option<f64>, String)]) -> Result<(), CliError> {
+    match output {
+        "csv" => {
+            println!("node,platform,geohash,source,timestamp,pixel_coverage,cloud_coverage,path");
+            for row in rows.iter() {
+                println!("{},{},{},{},{},{},{},{}", row.0, row.1,
+                    row.2, row.3, row.4, row.5,
+                    row.6.unwrap_or(-1.0), row.7);
+            }
+        },
+        "json" | "ndjson" => {
+            let records: Vec<serde_json::Value> = rows.iter()
+                .map(|row| serde_json::json!({
+                    "node": row.0,
+                    "platform": row.1,
+                    "geohash": row.2,
+                    "source": row.3,
+                    "timestamp": row.4,
+                    "pixel_coverage": row.5,
+                    "cloud_coverage": row.6,
+                    "path": row.7,
+                }))
+                .collect();
+
+            if output == "ndjson" {
+                for record in records.iter() {
+                    println!("{}", serde_json::to_string(record)?);
+                }
+            } else {
+                println!("{}", serde_json::to_string_pretty(&records)?);
+            }
+        },
+        _ => {
+            println!("{:<8}{:<12}{:<10}{:<8}{:<12}{:<16}{:<16}{:<80}",
+                "node", "platform", "geohash", "source", "timestamp",
+                "pixel_coverage", "cloud_coverage", "path");
+            println!("------------------------------------------------------------------------------------------------------------------------------------------------------------------");
+            for row in rows.iter() {
+                println!("{:<8}{:<12}{:<10}{:<8}{:<12}{:<16.5}{:<16.5}{:<80}",
+                    row.0, row.1, row.2, row.3, row.4, row.5,
+                    row.6.unwrap_or(-1.0), row.7);
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// render 'search's aggregated platform->geohash->source->bin->precision
+/// ->count tree in the requested format - 'json' nests the full tree,
+/// 'csv'/'ndjson' flatten it to one row per leaf count, matching the
+/// shape the table view already prints. the 'bin' level is always
+/// present (a single "all" bucket when '--bin' isn't given) but is only
+/// rendered when 'show_bin' is set, so the default output is unchanged
+fn print_search_tree<P, C>(output: &str, show_bin: bool,
+        platform_map: &BTreeMap<String, BTreeMap<String,
+            BTreeMap<String, BTreeMap<String, BTreeMap<P, C>>>>>)
+        -> Result<(), CliError>
+        where P: std::fmt::Display + Ord, C: std::fmt::Display {
+    match output {
+        "csv" | "ndjson" => {
+            if output == "csv" {
+                if show_bin {
+                    println!("platform,geohash,source,bin,precision,count");
+                } else {
+                    println!("platform,geohash,source,precision,count");
+                }
+            }
+
+            for (platform, geohash_map) in platform_map.iter() {
+                for (geohash, source_map) in geohash_map.iter() {
+                    for (source, bin_map) in source_map.iter() {
+                        for (bin, count_map) in bin_map.iter() {
+                            for (precision, count) in count_map.iter() {
+                                if output == "csv" {
+                                    if show_bin {
+                                        println!("{},{},{},{},{},{}",
+                                            platform, geohash, source, bin,
+                                            precision, count);
+                                    } else {
+                                        println!("{},{},{},{},{}",
+                                            platform, geohash, source,
+                                            precision, count);
+                                    }
+                                } else {
+                                    let mut record = serde_json::json!({
+                                        "platform": platform,
+                                        "geohash": geohash,
+                                        "source": source,
+                                        "precision": precision.to_string(),
+                                        "count": count.to_string(),
+                                    });
+
+                                    if show_bin {
+                                        record["bin"] = serde_json::Value::String(bin.clone());
+                                    }
+
+                                    println!("{}", serde_json::to_string(&record)?);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        },
+        "json" => {
+            // build the nested tree explicitly rather than deriving
+            // Serialize, since the leaf types come from generated
+            // protobuf fields whose exact numeric type isn't worth
+            // depending on here
+            let mut root = serde_json::Map::new();
+            for (platform, geohash_map) in platform_map.iter() {
+                let mut geohash_obj = serde_json::Map::new();
+                for (geohash, source_map) in geohash_map.iter() {
+                    let mut source_obj = serde_json::Map::new();
+                    for (source, bin_map) in source_map.iter() {
+                        let mut bin_obj = serde_json::Map::new();
+                        for (bin, count_map) in bin_map.iter() {
+                            let mut precision_obj = serde_json::Map::new();
+                            for (precision, count) in count_map.iter() {
+                                precision_obj.insert(precision.to_string(),
+                                    serde_json::Value::String(count.to_string()));
+                            }
+                            bin_obj.insert(bin.clone(),
+                                serde_json::Value::Object(precision_obj));
+                        }
+
+                        // collapse the bin level away when it wasn't
+                        // requested, rather than nesting under the lone
+                        // "all" key every caller would have to know to skip
+                        let source_value = if show_bin {
+                            serde_json::Value::Object(bin_obj)
+                        } else {
+                            bin_obj.into_iter().next()
+                                .map(|(_, value)| value)
+                                .unwrap_or(serde_json::Value::Object(serde_json::Map::new()))
+                        };
+
+                        source_obj.insert(source.clone(), source_value);
+                    }
+                    geohash_obj.insert(geohash.clone(),
+                        serde_json::Value::Object(source_obj));
+                }
+                root.insert(platform.clone(),
+                    serde_json::Value::Object(geohash_obj));
+            }
+
+            println!("{}", serde_json::to_string_pretty(
+                &serde_json::Value::Object(root))?);
+        },
+        _ => {
+            if show_bin {
+                println!("{:<16}{:<10}{:<12}{:<16}{:<12}{:<12}", "platform",
+                    "geohash", "source", "bin", "precision", "count");
+                println!("------------------------------------------------------------------------------");
+            } else {
+                println!("{:<16}{:<10}{:<12}{:<12}{:<12}", "platform",
+                    "geohash", "source", "precision", "count");
+                println!("--------------------------------------------------------------");
+            }
+
+            for (platform, geohash_map) in platform_map.iter() {
+                for (geohash, source_map) in geohash_map.iter() {
+                    for (source, bin_map) in source_map.iter() {
+                        for (bin, count_map) in bin_map.iter() {
+                            for (precision, count) in count_map.iter() {
+                                if show_bin {
+                                    println!("{:<16}{:<10}{:<12}{:<16}{:<12}{:<12}",
+                                        platform, geohash, source, bin,
+                                        precision, count);
+                                } else {
+                                    println!("{:<16}{:<10}{:<12}{:<12}{:<12}",
+                                        platform, geohash, source,
+                                        precision, count);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        },
+    }
+
+    Ok(())
+}
+
 #[tokio::main]
 async fn fill(matches: &ArgMatches, _: &ArgMatches,
-        fill_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+        fill_matches: &ArgMatches) -> Result<(), CliError> {
     // initialize grpc client
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = DataManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = DataManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
     // TODO - fix fill
     /*// initialize DataFillRequest
@@ -81,18 +342,18 @@ async fn fill(matches: &ArgMatches, _: &ArgMatches,
 
 #[tokio::main]
 async fn list(matches: &ArgMatches, _: &ArgMatches,
-        list_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
-    // initialize ClusterManagement grpc client
+        list_matches: &ArgMatches) -> Result<(), CliError> {
+    // initialize NodeManagement grpc client, falling back across
+    // bootstrap addresses so no single node is a point of failure
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = ClusterManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let mut client = crate::node::connect(ip_address, port).await?;
 
     // initialize NodeListRequest
     let node_list_request = Request::new(NodeListRequest {});
 
     // retrieve NodeListReply
-    let node_list_reply = client.node_list(node_list_request).await?;
+    let node_list_reply = client.list(node_list_request).await?;
     let node_list_reply = node_list_reply.get_ref();
 
     // initialize Filter
@@ -102,6 +363,10 @@ async fn list(matches: &ArgMatches, _: &ArgMatches,
         geohash: crate::string_opt(list_matches.value_of("geohash")),
         max_cloud_coverage: crate::f64_opt(
             list_matches.value_of("max_cloud_coverage"))?,
+        max_lat: None,
+        max_lon: None,
+        min_lat: None,
+        min_lon: None,
         min_pixel_coverage: crate::f64_opt(
             list_matches.value_of("min_pixel_coverage"))?,
         platform: crate::string_opt(list_matches.value_of("platform")),
@@ -116,51 +381,79 @@ async fn list(matches: &ArgMatches, _: &ArgMatches,
         filter: filter,
     };
 
-    // iterate over each available node
-    println!("{:<8}{:<12}{:<10}{:<8}{:<12}{:<16}{:<16}{:<80}",
-        "node", "platform", "geohash", "source", "timestamp",
-        "pixel_coverage", "cloud_coverage", "path");
-    println!("------------------------------------------------------------------------------------------------------------------------------------------------------------------");
-    for node in node_list_reply.nodes.iter() {
+    // poll nodes in a randomized order so the same node isn't always
+    // hit first
+    let order = shuffled_order(node_list_reply.nodes.len());
+
+    // gather every (node, image, file) row before rendering, so all
+    // output modes share one pass over the cluster
+    let mut rows = Vec::new();
+    for index in order {
+        let node = &node_list_reply.nodes[index];
+
         // initialize DataManagement grpc client
-        let mut client = DataManagementClient::connect(
-            format!("http://{}", node.rpc_addr)).await?;
+        let address = format!("http://{}", node.rpc_addr);
+        let mut client = DataManagementClient::connect(address.clone()).await
+            .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
         // iterate over image stream
         let mut stream = client.list(Request::new(request.clone()))
             .await?.into_inner();
         while let Some(image) = stream.message().await? {
             for file in image.files.iter() {
-                println!("{:<8}{:<12}{:<10}{:<8}{:<12}{:<16.5}{:<16.5}{:<80}",
-                    node.id, image.platform, image.geohash,
-                    image.source, image.timestamp, file.pixel_coverage,
-                    image.cloud_coverage.unwrap_or(-1.0), file.path);
+                rows.push((node.id, image.platform.clone(),
+                    image.geohash.clone(), image.source.clone(),
+                    image.timestamp, file.pixel_coverage,
+                    image.cloud_coverage, file.path.clone()));
             }
         }
     }
 
+    print_list_rows(list_matches.value_of("output").unwrap_or("table"),
+        &rows)?;
+
     Ok(())
 }
 
 #[tokio::main]
 async fn load(matches: &ArgMatches, _: &ArgMatches,
-        load_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+        load_matches: &ArgMatches) -> Result<(), CliError> {
     // initialize grpc client
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = DataManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = DataManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
     // parse load format
     let load_format = match load_matches.value_of("LOAD_FORMAT") {
         Some("modis") => LoadFormat::Modis as i32,
         Some("naip") => LoadFormat::Naip as i32,
+        Some("raster") => LoadFormat::Raster as i32,
         Some("sentinel") => LoadFormat::Sentinel as i32,
-        _ => unimplemented!(),
+        got => return Err(CliError::UnknownFormat {
+            what: "LOAD_FORMAT",
+            got: got.unwrap_or("").to_string(),
+            expected: vec!["modis", "naip", "raster", "sentinel"],
+        }),
+    };
+
+    let geocode = match load_matches.value_of("geocode") {
+        Some("quadtile") => Geocode::Quadtile as i32,
+        Some("geohash") | None => Geocode::Geohash as i32,
+        got => return Err(CliError::UnknownFormat {
+            what: "geocode",
+            got: got.unwrap_or("").to_string(),
+            expected: vec!["quadtile", "geohash"],
+        }),
     };
 
     // initialize DataLoadRequest
     let request = Request::new(DataLoadRequest {
+        album: load_matches.value_of("ALBUM").unwrap().to_string(),
+        dht_key_length: load_matches.value_of("dht_key_length")
+            .unwrap().parse::<i32>()?,
+        geocode: geocode,
         glob: load_matches.value_of("GLOB").unwrap().to_string(),
         load_format: load_format,
         precision: load_matches.value_of("precision")
@@ -168,6 +461,8 @@ async fn load(matches: &ArgMatches, _: &ArgMatches,
         task_id: crate::u64_opt(load_matches.value_of("task_id"))?,
         thread_count: load_matches.value_of("thread_count")
             .unwrap().parse::<u32>()?,
+        transfer_thread_count: load_matches.value_of("transfer_thread_count")
+            .unwrap().parse::<u32>()?,
     });
 
     // retrieve reply
@@ -182,18 +477,18 @@ async fn load(matches: &ArgMatches, _: &ArgMatches,
 
 #[tokio::main]
 async fn search(matches: &ArgMatches, _: &ArgMatches,
-        search_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
-    // initialize ClusterManagement grpc client
+        search_matches: &ArgMatches) -> Result<(), CliError> {
+    // initialize NodeManagement grpc client, falling back across
+    // bootstrap addresses so no single node is a point of failure
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = ClusterManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let mut client = crate::node::connect(ip_address, port).await?;
 
     // initialize NodeListRequest
     let node_list_request = Request::new(NodeListRequest {});
 
     // retrieve NodeListReply
-    let node_list_reply = client.node_list(node_list_request).await?;
+    let node_list_reply = client.list(node_list_request).await?;
     let node_list_reply = node_list_reply.get_ref();
 
     // initialize Filter
@@ -203,6 +498,10 @@ async fn search(matches: &ArgMatches, _: &ArgMatches,
         geohash: crate::string_opt(search_matches.value_of("geohash")),
         max_cloud_coverage: crate::f64_opt(
             search_matches.value_of("max_cloud_coverage"))?,
+        max_lat: None,
+        max_lon: None,
+        min_lat: None,
+        min_lon: None,
         min_pixel_coverage: crate::f64_opt(
             search_matches.value_of("min_pixel_coverage"))?,
         platform: crate::string_opt(search_matches.value_of("platform")),
@@ -217,12 +516,25 @@ async fn search(matches: &ArgMatches, _: &ArgMatches,
         filter: filter,
     };
 
+    let bin_mode = parse_bin_mode(search_matches.value_of("bin"))?;
+    let show_bin = match bin_mode {
+        BinMode::None => false,
+        _ => true,
+    };
+
+    // connect in a randomized order so the same node isn't always hit
+    // first
+    let order = shuffled_order(node_list_reply.nodes.len());
+
     // TODO - maintains streams vector
     let mut clients = Vec::new();
-    for node in node_list_reply.nodes.iter() {
+    for index in order {
+        let node = &node_list_reply.nodes[index];
+
         // initialize DataManagement grpc client
-        let client = DataManagementClient::connect(
-            format!("http://{}", node.rpc_addr)).await?;
+        let address = format!("http://{}", node.rpc_addr);
+        let client = DataManagementClient::connect(address.clone()).await
+            .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
         clients.push(client);
     }
@@ -251,9 +563,12 @@ async fn search(matches: &ArgMatches, _: &ArgMatches,
             let source_map = geohash_map.entry(
                 extent.geohash.clone()).or_insert(BTreeMap::new());
 
-            let count_map = source_map.entry(
+            let bin_map = source_map.entry(
                 extent.source.clone()).or_insert(BTreeMap::new());
 
+            let count_map = bin_map.entry(
+                compute_bin(extent.timestamp, &bin_mode)).or_insert(BTreeMap::new());
+
             let count = count_map.entry(extent.precision)
                 .or_insert(0);
             *count += extent.count;
@@ -262,32 +577,21 @@ async fn search(matches: &ArgMatches, _: &ArgMatches,
         }
     }
 
-    // print summarized data
-    println!("{:<16}{:<10}{:<12}{:<12}{:<12}", "platform",
-        "geohash", "source", "precision", "count");
-    println!("--------------------------------------------------------------");
-    for (platform, geohash_map) in platform_map.iter() {
-        for (geohash, source_map) in geohash_map.iter() {
-            for (source, count_map) in source_map.iter() {
-                for (precision, count) in count_map.iter() {
-                    println!("{:<16}{:<10}{:<12}{:<12}{:<12}",
-                        platform, geohash, source, precision, count);
-                }
-            }
-        }
-    }
+    print_search_tree(search_matches.value_of("output").unwrap_or("table"),
+        show_bin, &platform_map)?;
 
     Ok(())
 }
 
 #[tokio::main]
 async fn split(matches: &ArgMatches, _: &ArgMatches,
-        split_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+        split_matches: &ArgMatches) -> Result<(), CliError> {
     // initialize grpc client
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = DataManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = DataManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
     // initialize Filter
     let filter = Filter {
@@ -295,6 +599,10 @@ async fn split(matches: &ArgMatches, _: &ArgMatches,
             split_matches.value_of("end_timestamp"))?,
         geohash: crate::string_opt(split_matches.value_of("geohash")),
         max_cloud_coverage: None,
+        max_lat: None,
+        max_lon: None,
+        min_lat: None,
+        min_lon: None,
         min_pixel_coverage: None,
         platform: crate::string_opt(split_matches.value_of("platform")),
         recurse: split_matches.is_present("recurse"),
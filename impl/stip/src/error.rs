@@ -0,0 +1,66 @@
+use std::io;
+
+/// unifies every subcommand's failure modes behind one type, so an
+/// unrecognized FORMAT value reports a usable message instead of
+/// aborting via 'unimplemented!()'. each subcommand's 'process' prints
+/// its own errors and returns normally, so 'main' only sees a
+/// 'CliError' for an unknown subcommand
+#[derive(Debug, thiserror::Error)]
+pub enum CliError {
+    #[error("failed to connect to {address}: {message}")]
+    Connect { address: String, message: String },
+
+    #[error("rpc failed: {0}")]
+    Rpc(#[from] tonic::Status),
+
+    #[error("unknown {what} '{got}', expected one of: {}", expected.join(", "))]
+    UnknownFormat {
+        what: &'static str,
+        got: String,
+        expected: Vec<&'static str>,
+    },
+
+    #[error("failed to parse argument: {0}")]
+    ParseArg(String),
+
+    #[error("{0}")]
+    Io(#[from] io::Error),
+}
+
+impl CliError {
+    /// maps each category to a distinct exit code. 'main' calls this
+    /// only for the unknown-subcommand error ('Io', exit code 5); a
+    /// subcommand that fails still exits 0
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Connect { .. } => 2,
+            CliError::Rpc(_) => 3,
+            CliError::UnknownFormat { .. } | CliError::ParseArg { .. } => 4,
+            CliError::Io(_) => 5,
+        }
+    }
+}
+
+impl From<std::num::ParseIntError> for CliError {
+    fn from(e: std::num::ParseIntError) -> CliError {
+        CliError::ParseArg(e.to_string())
+    }
+}
+
+impl From<std::num::ParseFloatError> for CliError {
+    fn from(e: std::num::ParseFloatError) -> CliError {
+        CliError::ParseArg(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for CliError {
+    fn from(e: serde_json::Error) -> CliError {
+        CliError::ParseArg(e.to_string())
+    }
+}
+
+impl From<tonic::transport::Error> for CliError {
+    fn from(e: tonic::transport::Error) -> CliError {
+        CliError::Connect { address: String::new(), message: e.to_string() }
+    }
+}
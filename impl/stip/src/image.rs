@@ -1,12 +1,36 @@
 use clap::ArgMatches;
-use protobuf::{NodeManagementClient, ImageBroadcastRequest, ImageBroadcastType, ImageCoalesceRequest, ImageFillRequest, ImageListRequest, Extent, Filter, ImageFormat, ImageStoreRequest, ImageManagementClient, ImageSearchRequest, ImageSplitRequest, NodeListRequest};
+use geocode::Geocode;
+use protobuf::{NodeManagementClient, ImageBroadcastRequest, ImageBroadcastType, ImageCoalesceRequest, ImageFillRequest, ImageListRequest, Extent, Filter, ImageFormat, ImagePreviewRequest, ImageRepairRequest, ImageStoreRequest, ImageManagementClient, ImageSearchRequest, ImageSplitRequest, NodeListRequest};
 use tonic::Request;
 
-use std::{error, io};
+use crate::error::CliError;
+
+use std::io;
 use std::collections::BTreeMap;
 
+/// bounding box (min_x, max_x, min_y, max_y) of a geocode string, decoded
+/// at the precision implied by its length - used to build GeoJSON
+/// geometry without re-deriving interval math the `geocode` crate
+/// already owns
+fn geocode_bbox(geocode: &str) -> Result<(f64, f64, f64, f64), CliError> {
+    Geocode::Geohash.decode(geocode)
+        .map_err(|e| CliError::ParseArg(e.to_string()))
+}
+
+/// a closed GeoJSON polygon ring tracing a geocode's bounding box
+fn geocode_polygon(geocode: &str) -> Result<Vec<Vec<f64>>, CliError> {
+    let (min_x, max_x, min_y, max_y) = geocode_bbox(geocode)?;
+    Ok(vec![
+        vec![min_x, min_y],
+        vec![max_x, min_y],
+        vec![max_x, max_y],
+        vec![min_x, max_y],
+        vec![min_x, min_y],
+    ])
+}
+
 pub fn process(matches: &ArgMatches, data_matches: &ArgMatches) {
-    let result: Result<(), Box<dyn error::Error>> 
+    let result: Result<(), CliError> 
             = match data_matches.subcommand() {
         ("coalesce", Some(coalesce_matches)) =>
             coalesce(&matches, &data_matches, &coalesce_matches),
@@ -14,13 +38,17 @@ pub fn process(matches: &ArgMatches, data_matches: &ArgMatches) {
             fill(&matches, &data_matches, &fill_matches),
         ("list", Some(list_matches)) =>
             list(&matches, &data_matches, &list_matches),
+        ("preview", Some(preview_matches)) =>
+            preview(&matches, &data_matches, &preview_matches),
+        ("repair", Some(repair_matches)) =>
+            repair(&matches, &data_matches, &repair_matches),
         ("search", Some(search_matches)) =>
             search(&matches, &data_matches, &search_matches),
         ("split", Some(split_matches)) =>
             split(&matches, &data_matches, &split_matches),
         ("store", Some(store_matches)) =>
             store(&matches, &data_matches, &store_matches),
-        (cmd, _) => Err(Box::new(io::Error::new(io::ErrorKind::Other,
+        (cmd, _) => Err(CliError::Io(io::Error::new(io::ErrorKind::Other,
             format!("unknown subcommand '{}'", cmd)))),
     };
 
@@ -31,12 +59,13 @@ pub fn process(matches: &ArgMatches, data_matches: &ArgMatches) {
 
 #[tokio::main]
 async fn coalesce(matches: &ArgMatches, _: &ArgMatches,
-        coalesce_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+        coalesce_matches: &ArgMatches) -> Result<(), CliError> {
     // initialize grpc client
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = ImageManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = ImageManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
     // initialize Filter
     let filter = Filter {
@@ -46,6 +75,10 @@ async fn coalesce(matches: &ArgMatches, _: &ArgMatches,
             coalesce_matches.value_of("geocode")),
         max_cloud_coverage: crate::f64_opt(
             coalesce_matches.value_of("max_cloud_coverage"))?,
+        max_lat: None,
+        max_lon: None,
+        min_lat: None,
+        min_lon: None,
         min_pixel_coverage: crate::f64_opt(
             coalesce_matches.value_of("min_pixel_coverage"))?,
         platform: crate::string_opt(
@@ -85,18 +118,22 @@ async fn coalesce(matches: &ArgMatches, _: &ArgMatches,
         println!("task starting on node '{}' with id '{}'",
             node_id, coalesce_reply.task_id);
     }
+    for (node_id, message) in reply.failures.iter() {
+        println!("node '{}' failed: {}", node_id, message);
+    }
 
     Ok(())
 }
 
 #[tokio::main]
 async fn fill(matches: &ArgMatches, _: &ArgMatches,
-        fill_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+        fill_matches: &ArgMatches) -> Result<(), CliError> {
     // initialize grpc client
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = ImageManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = ImageManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
     // initialize Filter
     let filter = Filter {
@@ -104,6 +141,10 @@ async fn fill(matches: &ArgMatches, _: &ArgMatches,
             fill_matches.value_of("end_timestamp"))?,
         geocode: crate::string_opt(fill_matches.value_of("geocode")),
         max_cloud_coverage: None,
+        max_lat: None,
+        max_lon: None,
+        min_lat: None,
+        min_lon: None,
         min_pixel_coverage: None,
         platform: crate::string_opt(fill_matches.value_of("platform")),
         recurse: fill_matches.is_present("recurse"),
@@ -140,18 +181,22 @@ async fn fill(matches: &ArgMatches, _: &ArgMatches,
         println!("task starting on node '{}' with id '{}'",
             node_id, fill_reply.task_id);
     }
+    for (node_id, message) in reply.failures.iter() {
+        println!("node '{}' failed: {}", node_id, message);
+    }
 
     Ok(())
 }
 
 #[tokio::main]
 async fn list(matches: &ArgMatches, _: &ArgMatches,
-        list_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+        list_matches: &ArgMatches) -> Result<(), CliError> {
     // initialize NodeManagement grpc client
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = NodeManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = NodeManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
     // initialize NodeListRequest
     let node_list_request = Request::new(NodeListRequest {});
@@ -167,6 +212,10 @@ async fn list(matches: &ArgMatches, _: &ArgMatches,
         geocode: crate::string_opt(list_matches.value_of("geocode")),
         max_cloud_coverage: crate::f64_opt(
             list_matches.value_of("max_cloud_coverage"))?,
+        max_lat: crate::f64_opt(list_matches.value_of("max_lat"))?,
+        max_lon: crate::f64_opt(list_matches.value_of("max_lon"))?,
+        min_lat: crate::f64_opt(list_matches.value_of("min_lat"))?,
+        min_lon: crate::f64_opt(list_matches.value_of("min_lon"))?,
         min_pixel_coverage: crate::f64_opt(
             list_matches.value_of("min_pixel_coverage"))?,
         platform: crate::string_opt(list_matches.value_of("platform")),
@@ -182,41 +231,202 @@ async fn list(matches: &ArgMatches, _: &ArgMatches,
         filter: filter,
     };
 
-    // iterate over each available node
-    println!("{:<8}{:<12}{:<10}{:<8}{:<12}{:<16}{:<16}{:<12}{:<80}",
-        "node", "platform", "geocode", "source", "timestamp",
-        "pixel_coverage", "cloud_coverage", "subdataset", "path");
-    println!("------------------------------------------------------------------------------------------------------------------------------------------------------------------------------");
+    // gather every (node, image, file) row before rendering, so all
+    // three output modes can share one pass over the cluster
+    let mut rows = Vec::new();
     for node in node_list_reply.nodes.iter() {
         // initialize ImageManagement grpc client
-        let mut client = ImageManagementClient::connect(
-            format!("http://{}", node.rpc_addr)).await?;
+        let address = format!("http://{}", node.rpc_addr);
+        let mut client = ImageManagementClient::connect(address.clone()).await
+            .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
         // iterate over image stream
         let mut stream = client.list(Request::new(request.clone()))
             .await?.into_inner();
         while let Some(image) = stream.message().await? {
             for file in image.files.iter() {
-                println!("{:<8}{:<12}{:<10}{:<8}{:<12}{:<16.5}{:<16.5}{:<12}{:<80}",
-                    node.id, image.platform, image.geocode,
-                    image.source, image.timestamp, file.pixel_coverage,
-                    image.cloud_coverage.unwrap_or(-1.0),
-                    file.subdataset, file.path);
+                rows.push((node.id, image.platform.clone(),
+                    image.geocode.clone(), image.source.clone(),
+                    image.timestamp, file.pixel_coverage,
+                    image.cloud_coverage, file.subdataset, file.path.clone(),
+                    file.preview));
             }
         }
     }
 
+    match list_matches.value_of("output").unwrap_or("table") {
+        "csv" => {
+            println!("node,platform,geocode,source,timestamp,pixel_coverage,cloud_coverage,subdataset,path,preview");
+            for row in rows.iter() {
+                println!("{},{},{},{},{},{},{},{},{},{}", row.0, row.1,
+                    row.2, row.3, row.4, row.5,
+                    row.6.unwrap_or(-1.0), row.7, row.8, row.9);
+            }
+        },
+        "geojson" => {
+            let features: Vec<serde_json::Value> = rows.iter()
+                .map(|row| serde_json::json!({
+                    "type": "Feature",
+                    "geometry": {
+                        "type": "Polygon",
+                        "coordinates": [geocode_polygon(&row.2)
+                            .unwrap_or_default()],
+                    },
+                    "properties": {
+                        "node": row.0,
+                        "platform": row.1,
+                        "geocode": row.2,
+                        "source": row.3,
+                        "timestamp": row.4,
+                        "pixel_coverage": row.5,
+                        "cloud_coverage": row.6,
+                        "subdataset": row.7,
+                        "path": row.8,
+                        "preview": row.9,
+                    },
+                }))
+                .collect();
+
+            let feature_collection = serde_json::json!({
+                "type": "FeatureCollection",
+                "features": features,
+            });
+            println!("{}", serde_json::to_string_pretty(&feature_collection)?);
+        },
+        _ => {
+            println!("{:<8}{:<12}{:<10}{:<8}{:<12}{:<16}{:<16}{:<12}{:<80}{:<8}",
+                "node", "platform", "geocode", "source", "timestamp",
+                "pixel_coverage", "cloud_coverage", "subdataset", "path",
+                "preview");
+            println!("------------------------------------------------------------------------------------------------------------------------------------------------------------------------------");
+            for row in rows.iter() {
+                println!("{:<8}{:<12}{:<10}{:<8}{:<12}{:<16.5}{:<16.5}{:<12}{:<80}{:<8}",
+                    row.0, row.1, row.2, row.3, row.4, row.5,
+                    row.6.unwrap_or(-1.0), row.7, row.8, row.9);
+            }
+        },
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn repair(matches: &ArgMatches, _: &ArgMatches,
+        repair_matches: &ArgMatches) -> Result<(), CliError> {
+    // initialize grpc client
+    let ip_address = matches.value_of("ip_address").unwrap();
+    let port = matches.value_of("port").unwrap().parse::<u16>()?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = ImageManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
+
+    // initialize Filter
+    let filter = Filter {
+        end_timestamp: crate::i64_opt(
+            repair_matches.value_of("end_timestamp"))?,
+        geocode: crate::string_opt(repair_matches.value_of("geocode")),
+        max_cloud_coverage: crate::f64_opt(
+            repair_matches.value_of("max_cloud_coverage"))?,
+        max_lat: None,
+        max_lon: None,
+        min_lat: None,
+        min_lon: None,
+        min_pixel_coverage: crate::f64_opt(
+            repair_matches.value_of("min_pixel_coverage"))?,
+        platform: crate::string_opt(repair_matches.value_of("platform")),
+        recurse: repair_matches.is_present("recurse"),
+        source: crate::string_opt(repair_matches.value_of("source")),
+        start_timestamp: crate::i64_opt(
+            repair_matches.value_of("start_timestamp"))?,
+    };
+
+    // initialize ImageRepairRequest
+    let request = Request::new(ImageRepairRequest {
+        album: repair_matches.value_of("ALBUM").unwrap().to_string(),
+        dry_run: repair_matches.is_present("dry_run"),
+        filter: filter,
+        replication_factor: repair_matches.value_of("replication_factor")
+            .unwrap_or("3").parse::<u32>()?,
+    });
+
+    // retrieve reply
+    let reply = client.repair(request).await?;
+    let reply = reply.get_ref();
+
+    // print information
+    println!("{:<16}{:<10}{:<12}{:<12}{:<24}{:<24}", "platform",
+        "geocode", "source", "precision", "expected", "actual");
+    println!("--------------------------------------------------------------------------------------------------");
+    for diff in reply.diffs.iter() {
+        println!("{:<16}{:<10}{:<12}{:<12}{:<24?}{:<24?}",
+            diff.platform, diff.geocode, diff.source, diff.precision,
+            diff.expected, diff.actual);
+    }
+
+    println!("\n{} under-replicated extent(s) found, {} repair task(s) started",
+        reply.diffs.len(), reply.tasks_started);
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn preview(matches: &ArgMatches, _: &ArgMatches,
+        preview_matches: &ArgMatches) -> Result<(), CliError> {
+    // initialize grpc client
+    let ip_address = matches.value_of("ip_address").unwrap();
+    let port = matches.value_of("port").unwrap().parse::<u16>()?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = ImageManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
+
+    // initialize Filter
+    let filter = Filter {
+        end_timestamp: crate::i64_opt(
+            preview_matches.value_of("end_timestamp"))?,
+        geocode: crate::string_opt(preview_matches.value_of("geocode")),
+        max_cloud_coverage: None,
+        max_lat: None,
+        max_lon: None,
+        min_lat: None,
+        min_lon: None,
+        min_pixel_coverage: None,
+        platform: crate::string_opt(preview_matches.value_of("platform")),
+        recurse: preview_matches.is_present("recurse"),
+        source: crate::string_opt(preview_matches.value_of("source")),
+        start_timestamp: crate::i64_opt(
+            preview_matches.value_of("start_timestamp"))?,
+    };
+
+    // initialize ImagePreviewRequest
+    let request = Request::new(ImagePreviewRequest {
+        album: preview_matches.value_of("ALBUM").unwrap().to_string(),
+        filter: filter,
+        max_dimension: preview_matches.value_of("max_dimension")
+            .unwrap_or("256").parse::<u32>()?,
+        task_id: crate::u64_opt(preview_matches.value_of("task_id"))?,
+        thread_count: preview_matches.value_of("thread_count")
+            .unwrap().parse::<u32>()?,
+    });
+
+    // retrieve reply
+    let reply = client.preview(request).await?;
+    let reply = reply.get_ref();
+
+    // print information
+    println!("preview task starting with id '{}'", reply.task_id);
+
     Ok(())
 }
 
 #[tokio::main]
 async fn store(matches: &ArgMatches, _: &ArgMatches,
-        store_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+        store_matches: &ArgMatches) -> Result<(), CliError> {
     // initialize grpc client
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = ImageManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = ImageManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
     // parse load format
     let format = match store_matches.value_of("FORMAT") {
@@ -230,16 +440,32 @@ async fn store(matches: &ArgMatches, _: &ArgMatches,
         Some("nlcd") => ImageFormat::Nlcd as i32,
         Some("sentinel2") => ImageFormat::Sentinel2 as i32,
         Some("vnp21v001") => ImageFormat::Vnp21v001 as i32,
-        _ => unimplemented!(),
+        got => return Err(CliError::UnknownFormat {
+            what: "FORMAT",
+            got: got.unwrap_or("").to_string(),
+            expected: vec!["generic", "gridmet", "landsat8c1l1",
+                "mcd43a4", "mod11a1", "mod11a2", "naip", "nlcd",
+                "sentinel2", "vnp21v001"],
+        }),
     };
 
-    // initialize ImageStoreRequest
+    // initialize ImageStoreRequest - GLOB may be a local glob pattern or
+    // an 's3://bucket/prefix/**.ext' object-store glob, in which case
+    // the s3 fields tell the node where and how to list it
     let request = Request::new(ImageStoreRequest {
         album: store_matches.value_of("ALBUM").unwrap().to_string(),
         format: format,
         glob: store_matches.value_of("GLOB").unwrap().to_string(),
         precision: store_matches.value_of("precision")
             .unwrap().parse::<u32>()?,
+        s3_access_key: crate::string_opt(
+            store_matches.value_of("s3_access_key")),
+        s3_endpoint: crate::string_opt(
+            store_matches.value_of("s3_endpoint")),
+        s3_region: crate::string_opt(
+            store_matches.value_of("s3_region")),
+        s3_secret_key: crate::string_opt(
+            store_matches.value_of("s3_secret_key")),
         task_id: crate::u64_opt(store_matches.value_of("task_id"))?,
         thread_count: store_matches.value_of("thread_count")
             .unwrap().parse::<u32>()?,
@@ -257,12 +483,13 @@ async fn store(matches: &ArgMatches, _: &ArgMatches,
 
 #[tokio::main]
 async fn search(matches: &ArgMatches, _: &ArgMatches,
-        search_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+        search_matches: &ArgMatches) -> Result<(), CliError> {
     // initialize NodeManagement grpc client
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = NodeManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = NodeManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
     // initialize NodeListRequest
     let node_list_request = Request::new(NodeListRequest {});
@@ -278,6 +505,10 @@ async fn search(matches: &ArgMatches, _: &ArgMatches,
         geocode: crate::string_opt(search_matches.value_of("geocode")),
         max_cloud_coverage: crate::f64_opt(
             search_matches.value_of("max_cloud_coverage"))?,
+        max_lat: crate::f64_opt(search_matches.value_of("max_lat"))?,
+        max_lon: crate::f64_opt(search_matches.value_of("max_lon"))?,
+        min_lat: crate::f64_opt(search_matches.value_of("min_lat"))?,
+        min_lon: crate::f64_opt(search_matches.value_of("min_lon"))?,
         min_pixel_coverage: crate::f64_opt(
             search_matches.value_of("min_pixel_coverage"))?,
         platform: crate::string_opt(search_matches.value_of("platform")),
@@ -297,8 +528,9 @@ async fn search(matches: &ArgMatches, _: &ArgMatches,
     let mut clients = Vec::new();
     for node in node_list_reply.nodes.iter() {
         // initialize ImageManagement grpc client
-        let client = ImageManagementClient::connect(
-            format!("http://{}", node.rpc_addr)).await?;
+        let address = format!("http://{}", node.rpc_addr);
+        let client = ImageManagementClient::connect(address.clone()).await
+            .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
         clients.push(client);
     }
@@ -338,32 +570,76 @@ async fn search(matches: &ArgMatches, _: &ArgMatches,
         }
     }
 
-    // print summarized data
-    println!("{:<16}{:<10}{:<12}{:<12}{:<12}", "platform",
-        "geocode", "source", "precision", "count");
-    println!("--------------------------------------------------------------");
+    // flatten the aggregation into rows before rendering, so all three
+    // output modes share one pass over the buckets
+    let mut rows = Vec::new();
     for (platform, geocode_map) in platform_map.iter() {
         for (geocode, source_map) in geocode_map.iter() {
             for (source, count_map) in source_map.iter() {
                 for (precision, count) in count_map.iter() {
-                    println!("{:<16}{:<10}{:<12}{:<12}{:<12}",
-                        platform, geocode, source, precision, count);
+                    rows.push((platform.clone(), geocode.clone(),
+                        source.clone(), *precision, *count));
                 }
             }
         }
     }
 
+    match search_matches.value_of("output").unwrap_or("table") {
+        "csv" => {
+            println!("platform,geocode,source,precision,count");
+            for row in rows.iter() {
+                println!("{},{},{},{},{}",
+                    row.0, row.1, row.2, row.3, row.4);
+            }
+        },
+        "geojson" => {
+            let features: Vec<serde_json::Value> = rows.iter()
+                .map(|row| serde_json::json!({
+                    "type": "Feature",
+                    "geometry": {
+                        "type": "Polygon",
+                        "coordinates": [geocode_polygon(&row.1)
+                            .unwrap_or_default()],
+                    },
+                    "properties": {
+                        "platform": row.0,
+                        "geocode": row.1,
+                        "source": row.2,
+                        "precision": row.3,
+                        "count": row.4,
+                    },
+                }))
+                .collect();
+
+            let feature_collection = serde_json::json!({
+                "type": "FeatureCollection",
+                "features": features,
+            });
+            println!("{}", serde_json::to_string_pretty(&feature_collection)?);
+        },
+        _ => {
+            println!("{:<16}{:<10}{:<12}{:<12}{:<12}", "platform",
+                "geocode", "source", "precision", "count");
+            println!("--------------------------------------------------------------");
+            for row in rows.iter() {
+                println!("{:<16}{:<10}{:<12}{:<12}{:<12}",
+                    row.0, row.1, row.2, row.3, row.4);
+            }
+        },
+    }
+
     Ok(())
 }
 
 #[tokio::main]
 async fn split(matches: &ArgMatches, _: &ArgMatches,
-        split_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+        split_matches: &ArgMatches) -> Result<(), CliError> {
     // initialize grpc client
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = ImageManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = ImageManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
     // initialize Filter
     let filter = Filter {
@@ -371,6 +647,10 @@ async fn split(matches: &ArgMatches, _: &ArgMatches,
             split_matches.value_of("end_timestamp"))?,
         geocode: crate::string_opt(split_matches.value_of("geocode")),
         max_cloud_coverage: None,
+        max_lat: None,
+        max_lon: None,
+        min_lat: None,
+        min_lon: None,
         min_pixel_coverage: None,
         platform: crate::string_opt(split_matches.value_of("platform")),
         recurse: split_matches.is_present("recurse"),
@@ -409,6 +689,9 @@ async fn split(matches: &ArgMatches, _: &ArgMatches,
         println!("task starting on node '{}' with id '{}'",
             node_id, split_reply.task_id);
     }
+    for (node_id, message) in reply.failures.iter() {
+        println!("node '{}' failed: {}", node_id, message);
+    }
 
     Ok(())
 }
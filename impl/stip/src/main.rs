@@ -3,36 +3,51 @@ extern crate clap;
 use clap::App;
 
 mod data;
+mod error;
+mod image;
 mod node;
 mod task;
 
-use std::error::Error;
+use error::CliError;
 
 fn main() {
     let yaml = load_yaml!("clap.yaml");
     let matches = App::from_yaml(yaml).get_matches();
 
     // parse subcommands
-    match matches.subcommand() {
+    let result: Result<(), CliError> = match matches.subcommand() {
         ("data", Some(data_matches)) =>
-            data::process(&matches, &data_matches),
+            Ok(data::process(&matches, &data_matches)),
+        ("image", Some(image_matches)) =>
+            Ok(image::process(&matches, &image_matches)),
         ("node", Some(node_matches)) =>
-            node::process(&matches, &node_matches),
+            Ok(node::process(&matches, &node_matches)),
         ("task", Some(task_matches)) =>
-            task::process(&matches, &task_matches),
-        (cmd, _) => println!("unknown subcommand '{}'", cmd),
+            Ok(task::process(&matches, &task_matches)),
+        (cmd, _) => Err(CliError::Io(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            format!("unknown subcommand '{}'", cmd)))),
+    };
+
+    // each subcommand's own 'process' already prints and swallows its
+    // errors, so only the top-level 'unknown subcommand' case - the one
+    // path that can reach here as an Err - needs handling, with an exit
+    // code distinguishing it from a successful run
+    if let Err(e) = result {
+        println!("{}", e);
+        std::process::exit(e.exit_code());
     }
 }
 
 fn f64_opt(value: Option<&str>)
-        -> Result<Option<f64>, Box<dyn Error>> {
+        -> Result<Option<f64>, CliError> {
     match value {
         Some(value) => Ok(Some(value.parse::<f64>()?)),
         None => Ok(None),
     }
 }
 
-fn i64_opt(value: Option<&str>) -> Result<Option<i64>, Box<dyn Error>> {
+fn i64_opt(value: Option<&str>) -> Result<Option<i64>, CliError> {
     match value {
         Some(value) => Ok(Some(value.parse::<i64>()?)),
         None => Ok(None),
@@ -46,7 +61,7 @@ fn string_opt(value: Option<&str>) -> Option<String> {
     }
 }
 
-fn u64_opt(value: Option<&str>) -> Result<Option<u64>, Box<dyn Error>> {
+fn u64_opt(value: Option<&str>) -> Result<Option<u64>, CliError> {
     match value {
         Some(value) => Ok(Some(value.parse::<u64>()?)),
         None => Ok(None),
@@ -1,16 +1,45 @@
 use clap::ArgMatches;
 use protobuf::{NodeListRequest, NodeManagementClient};
 use tonic::Request;
+use tonic::transport::Channel;
 
-use std::{error, io};
+use crate::error::CliError;
+
+use std::io;
+
+/// connect to the first reachable address in a comma-separated list of
+/// bootstrap addresses, so node discovery doesn't depend on one fixed
+/// node staying up - any live node can answer a NodeListRequest since
+/// membership is gossiped across the cluster
+pub async fn connect(ip_address: &str, port: u16)
+        -> Result<NodeManagementClient<Channel>, CliError> {
+    let mut last_error = None;
+    for addr in ip_address.split(',') {
+        let addr = addr.trim();
+        let address = format!("http://{}:{}", addr, port);
+        match NodeManagementClient::connect(address.clone()).await {
+            Ok(client) => return Ok(client),
+            Err(e) => {
+                println!("bootstrap address '{}' unreachable: {}", addr, e);
+                last_error = Some(CliError::Connect {
+                    address: address, message: e.to_string() });
+            },
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| CliError::Connect {
+        address: ip_address.to_string(),
+        message: "no bootstrap addresses provided".to_string(),
+    }))
+}
 
 pub fn process(matches: &ArgMatches, cluster_matches: &ArgMatches) {
-    let result: Result<(), Box<dyn error::Error>> 
+    let result: Result<(), CliError>
             = match cluster_matches.subcommand() {
         ("list", Some(list_matches)) => {
             list(&matches, &cluster_matches, &list_matches)
         },
-        (cmd, _) => Err(Box::new(io::Error::new(io::ErrorKind::Other,
+        (cmd, _) => Err(CliError::Io(io::Error::new(io::ErrorKind::Other,
             format!("unknown subcommand '{}'", cmd)))),
     };
 
@@ -21,12 +50,11 @@ pub fn process(matches: &ArgMatches, cluster_matches: &ArgMatches) {
 
 #[tokio::main]
 async fn list(matches: &ArgMatches, _: &ArgMatches,
-        _list_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
-    // initialize grpc client
+        _list_matches: &ArgMatches) -> Result<(), CliError> {
+    // initialize grpc client, falling back across bootstrap addresses
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = NodeManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let mut client = connect(ip_address, port).await?;
 
     // initialize request
     let request = Request::new(NodeListRequest {});
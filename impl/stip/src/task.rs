@@ -1,18 +1,30 @@
 use clap::ArgMatches;
-use protobuf::{TaskBroadcastRequest, TaskBroadcastType, TaskClearRequest, TaskManagementClient, TaskListRequest};
+use protobuf::{TaskBroadcastRequest, TaskBroadcastType, TaskCancelRequest, TaskClearRequest, TaskManagementClient, TaskListRequest, TaskPauseRequest, TaskResumeRequest, TaskStatusRequest};
 use tonic::Request;
+use tonic::transport::Channel;
 
-use std::{error, io};
+use crate::error::CliError;
+
+use std::io;
 use std::collections::HashMap;
+use std::time::Duration;
 
 pub fn process(matches: &ArgMatches, task_matches: &ArgMatches) {
-    let result: Result<(), Box<dyn error::Error>> 
+    let result: Result<(), CliError>
             = match task_matches.subcommand() {
+        ("cancel", Some(cancel_matches)) =>
+            cancel(&matches, &task_matches, &cancel_matches),
         ("clear", Some(clear_matches)) =>
             clear(&matches, &task_matches, &clear_matches),
         ("list", Some(list_matches)) =>
             list(&matches, &task_matches, &list_matches),
-        (cmd, _) => Err(Box::new(io::Error::new(io::ErrorKind::Other,
+        ("pause", Some(pause_matches)) =>
+            pause(&matches, &task_matches, &pause_matches),
+        ("resume", Some(resume_matches)) =>
+            resume(&matches, &task_matches, &resume_matches),
+        ("status", Some(status_matches)) =>
+            status(&matches, &task_matches, &status_matches),
+        (cmd, _) => Err(CliError::Io(io::Error::new(io::ErrorKind::Other,
             format!("unknown subcommand '{}'", cmd)))),
     };
 
@@ -21,20 +33,52 @@ pub fn process(matches: &ArgMatches, task_matches: &ArgMatches) {
     }
 }
 
+#[tokio::main]
+async fn cancel(matches: &ArgMatches, _: &ArgMatches,
+        cancel_matches: &ArgMatches) -> Result<(), CliError> {
+    // initialize grpc client
+    let ip_address = matches.value_of("ip_address").unwrap();
+    let port = matches.value_of("port").unwrap().parse::<u16>()?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = TaskManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
+
+    let task_id = cancel_matches.value_of("task_id").unwrap().parse::<u64>()?;
+
+    // initialize request
+    let request = Request::new(TaskBroadcastRequest {
+        message_type: TaskBroadcastType::TaskCancel as i32,
+        cancel_request: Some(TaskCancelRequest { task_id: task_id }),
+        clear_request: None,
+        list_request: None,
+        pause_request: None,
+        resume_request: None,
+    });
+
+    // retrieve reply
+    let _ = client.broadcast(request).await?;
+
+    Ok(())
+}
+
 #[tokio::main]
 async fn clear(matches: &ArgMatches, _: &ArgMatches,
-        _clear_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+        _clear_matches: &ArgMatches) -> Result<(), CliError> {
     // initialize grpc client
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = TaskManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = TaskManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
 
     // initialize request
     let request = Request::new(TaskBroadcastRequest {
         message_type: TaskBroadcastType::TaskClear as i32,
+        cancel_request: None,
         clear_request: Some(TaskClearRequest {}),
         list_request: None,
+        pause_request: None,
+        resume_request: None,
     });
 
     // retrieve reply
@@ -45,18 +89,46 @@ async fn clear(matches: &ArgMatches, _: &ArgMatches,
 
 #[tokio::main]
 async fn list(matches: &ArgMatches, _: &ArgMatches,
-        _list_matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+        list_matches: &ArgMatches) -> Result<(), CliError> {
     // initialize grpc client
     let ip_address = matches.value_of("ip_address").unwrap();
     let port = matches.value_of("port").unwrap().parse::<u16>()?;
-    let mut client = TaskManagementClient::connect(
-        format!("http://{}:{}", ip_address, port)).await?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = TaskManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
+
+    // with '--watch', keep re-polling and reprinting the aggregated
+    // view until every reported task has left the initializing/
+    // running/paused states, rather than a single snapshot the caller
+    // has to manually re-run to follow progress
+    let watch = list_matches.is_present("watch");
+
+    loop {
+        let any_active = print_task_list(&mut client).await?;
+
+        if !watch || !any_active {
+            break;
+        }
 
+        tokio::time::delay_for(Duration::from_millis(500)).await;
+    }
+
+    Ok(())
+}
+
+/// fetch one snapshot of every node's task list, print the agglomerated
+/// view, and report whether any task is still initializing/running/
+/// paused - so '--watch' knows whether to keep polling
+async fn print_task_list(client: &mut TaskManagementClient<Channel>)
+        -> Result<bool, CliError> {
     // initialize request
     let request = Request::new(TaskBroadcastRequest {
         message_type: TaskBroadcastType::TaskList as i32,
+        cancel_request: None,
         clear_request: None,
         list_request: Some(TaskListRequest {}),
+        pause_request: None,
+        resume_request: None,
     });
 
     // retrieve reply
@@ -68,30 +140,169 @@ async fn list(matches: &ArgMatches, _: &ArgMatches,
     for (_node_id, task_list_reply) in reply.list_replies.iter() {
         for task in task_list_reply.tasks.iter() {
             let mut task_tuple = tasks.entry(task.id).or_insert(
-                (0u16, 0u16, 0u16, 0u16, 0u32, 0u32, 0u32));
-
-            // compile task status
-            match (task.running, task.completed_count, task.total_count) {
-                (true, _, 0) => task_tuple.0 += 1,
-                (true, _, _) => task_tuple.1 += 1,
-                (false, x, y) if x < y => task_tuple.2 += 1,
-                (false, _, _) => task_tuple.3 += 1,
+                (0u16, 0u16, 0u16, 0u16, 0u16, 0u16, 0u32, 0u32, 0u32, 0u32));
+
+            // compile task status - cancelled is terminal and takes
+            // priority over everything else, then a paused node takes
+            // priority over its running/initializing state since
+            // pausing doesn't clear it
+            match (task.cancelled, task.paused, task.running,
+                    task.completed_count, task.total_count) {
+                (true, _, _, _, _) => task_tuple.0 += 1,
+                (false, true, _, _, _) => task_tuple.1 += 1,
+                (false, false, true, _, 0) => task_tuple.2 += 1,
+                (false, false, true, _, _) => task_tuple.3 += 1,
+                (false, false, false, x, y) if x < y => task_tuple.4 += 1,
+                (false, false, false, _, _) => task_tuple.5 += 1,
             };
 
-            task_tuple.4 += task.completed_count;
-            task_tuple.5 += task.skipped_count;
-            task_tuple.6 += task.total_count;
+            task_tuple.6 += task.completed_count;
+            task_tuple.7 += task.skipped_count;
+            task_tuple.8 += task.total_count;
+            task_tuple.9 += task.non_critical_error_count;
         }
     }
 
     // print information
-    println!("{:<24}{:<16}{:<12}{:<12}{:<12}{:<24}", "task_id",
-        "initializing", "running", "failed", "completed", "progress");
+    println!("{:<24}{:<12}{:<12}{:<16}{:<12}{:<12}{:<12}{:<16}{:<24}", "task_id",
+        "cancelled", "paused", "initializing", "running", "failed",
+        "completed", "non_critical", "progress");
     println!("----------------------------------------------------------------------------------------------------");
+
+    // a task is still active if any node reports it paused,
+    // initializing, or running
+    let mut any_active = false;
     for (task_id, task_tuple) in tasks.iter() {
-        println!("{:<24}{:<16}{:<12}{:<12}{:<12}{:<24}", task_id,
+        println!("{:<24}{:<12}{:<12}{:<16}{:<12}{:<12}{:<12}{:<16}{:<24}", task_id,
             task_tuple.0, task_tuple.1, task_tuple.2, task_tuple.3,
-            compute_progress(task_tuple.4, task_tuple.5, task_tuple.6));
+            task_tuple.4, task_tuple.5, task_tuple.9,
+            compute_progress(task_tuple.6, task_tuple.7, task_tuple.8));
+
+        if task_tuple.1 > 0 || task_tuple.2 > 0 || task_tuple.3 > 0 {
+            any_active = true;
+        }
+    }
+
+    // surface any node the broadcast couldn't reach - its tasks are
+    // just silently absent from the tally above otherwise
+    if !reply.errors.is_empty() {
+        println!();
+        println!("{} of {} nodes did not report:", reply.errors.len(),
+            reply.errors.len() + reply.list_replies.len());
+        for (node_id, error) in reply.errors.iter() {
+            println!("  node {}: {}", node_id, error);
+        }
+    }
+
+    println!();
+
+    Ok(any_active)
+}
+
+#[tokio::main]
+async fn pause(matches: &ArgMatches, _: &ArgMatches,
+        pause_matches: &ArgMatches) -> Result<(), CliError> {
+    // initialize grpc client
+    let ip_address = matches.value_of("ip_address").unwrap();
+    let port = matches.value_of("port").unwrap().parse::<u16>()?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = TaskManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
+
+    let task_id = pause_matches.value_of("task_id").unwrap().parse::<u64>()?;
+
+    // initialize request
+    let request = Request::new(TaskBroadcastRequest {
+        message_type: TaskBroadcastType::TaskPause as i32,
+        cancel_request: None,
+        clear_request: None,
+        list_request: None,
+        pause_request: Some(TaskPauseRequest { task_id: task_id }),
+        resume_request: None,
+    });
+
+    // retrieve reply
+    let _ = client.broadcast(request).await?;
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn resume(matches: &ArgMatches, _: &ArgMatches,
+        resume_matches: &ArgMatches) -> Result<(), CliError> {
+    // initialize grpc client
+    let ip_address = matches.value_of("ip_address").unwrap();
+    let port = matches.value_of("port").unwrap().parse::<u16>()?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = TaskManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
+
+    let task_id = resume_matches.value_of("task_id").unwrap().parse::<u64>()?;
+
+    // initialize request
+    let request = Request::new(TaskBroadcastRequest {
+        message_type: TaskBroadcastType::TaskResume as i32,
+        cancel_request: None,
+        clear_request: None,
+        list_request: None,
+        pause_request: None,
+        resume_request: Some(TaskResumeRequest { task_id: task_id }),
+    });
+
+    // retrieve reply
+    let _ = client.broadcast(request).await?;
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn status(matches: &ArgMatches, _: &ArgMatches,
+        status_matches: &ArgMatches) -> Result<(), CliError> {
+    // initialize grpc client
+    let ip_address = matches.value_of("ip_address").unwrap();
+    let port = matches.value_of("port").unwrap().parse::<u16>()?;
+    let address = format!("http://{}:{}", ip_address, port);
+    let mut client = TaskManagementClient::connect(address.clone()).await
+        .map_err(|e| CliError::Connect { address: address.clone(), message: e.to_string() })?;
+
+    let task_id = status_matches.value_of("task_id").unwrap().parse::<u64>()?;
+
+    // initialize request
+    let request = Request::new(TaskStatusRequest {
+        task_id: task_id,
+    });
+
+    // stream progress updates until the task terminates
+    let mut stream = client.status(request).await?.into_inner();
+    while let Some(reply) = stream.message().await? {
+        // a finished task that still accumulated dropped geocodes is
+        // reported distinctly from a clean completion, so an operator
+        // knows to check 'non_critical_errors' before trusting the
+        // result
+        let status = match (reply.running, reply.cancelled,
+                reply.non_critical_error_count) {
+            (true, _, _) => "running",
+            (false, true, _) => "cancelled",
+            (false, false, 0) => "complete",
+            (false, false, _) => "completed_with_errors",
+        };
+
+        println!("{:<24}{:<24}{:<12}{:<12}{:<12}{:<12}{:<12}", "task_id",
+            "status", "paused", "completed", "failed", "total", "non_critical");
+        println!("{:<24}{:<24}{:<12}{:<12}{:<12}{:<12}{:<12}", reply.task_id,
+            status, reply.paused, reply.completed_count, reply.failed_count,
+            reply.total_count, reply.non_critical_error_count);
+
+        for error in reply.errors.iter() {
+            println!("  {}", error);
+        }
+
+        if !reply.non_critical_errors.is_empty() {
+            println!("non_critical_errors:");
+            for error in reply.non_critical_errors.iter() {
+                println!("  {}", error);
+            }
+        }
     }
 
     Ok(())
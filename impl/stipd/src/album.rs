@@ -1,26 +1,367 @@
-use byteorder::{ReadBytesExt, WriteBytesExt};
+use byteorder::{BigEndian, WriteBytesExt};
 use gdal::{Dataset, Driver, Metadata};
 use geocode::Geocode;
 
-use crate::{Extent, Image, StFile};
-use crate::index::AlbumIndex;
+use crate::{Extent, Image, StFile, VerifyFailure};
+use crate::block::{self, BlockTable};
+use crate::index::{AlbumIndex, IndexStore};
+use crate::index_cache::{self, IndexCache};
+use crate::storage::StorageBackend;
 
 use std::collections::HashMap;
 use std::collections::hash_map::Iter;
 use std::error::Error;
-use std::ffi::{CStr, CString};
+use std::ffi::{CStr, CString, OsStr};
 use std::fs::File;
+use std::io::Read;
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 use std::os::unix::fs::PermissionsExt;
 
+/// fixed preamble identifying an album metadata file as the versioned
+/// "docket" format, as opposed to the bare, unversioned 2-byte layout
+/// written by pre-docket albums
+const META_MAGIC: &[u8] = b"STIPALB";
+
+/// current docket format version - adds the per-album split replication
+/// factor that 'SplitTask' uses, the same kind of setting the versioned
+/// header was built to carry without breaking every existing album
+const META_VERSION: u8 = 3;
+
+/// creation options applied to every tile written into an album that
+/// hasn't been configured otherwise - deflate compresses at least as
+/// well as the lzw this replaces, and the predictor/block size suit the
+/// 8-to-16-bit single-band rasters these formats produce
+const DEFAULT_CREATION_OPTIONS: &[&str] = &[
+    "COMPRESS=DEFLATE", "PREDICTOR=2", "BLOCKXSIZE=256", "BLOCKYSIZE=256"];
+
+/// number of nodes a split tile is placed on when an album doesn't
+/// specify otherwise - one copy, i.e. no added replication, matching
+/// the behavior every album had before this setting existed
+pub(crate) const DEFAULT_SPLIT_REPLICATION_FACTOR: u8 = 1;
+
+fn geocode_from_byte(id: &str, value: u8) -> Result<Geocode, Box<dyn Error>> {
+    match value {
+        0 => Ok(Geocode::Geohash),
+        1 => Ok(Geocode::QuadTile),
+        _ => Err(format!("album '{}' has unknown geocode {}",
+            id, value).into()),
+    }
+}
+
+fn geocode_to_byte(geocode: &Geocode) -> u8 {
+    match geocode {
+        Geocode::Geohash => 0,
+        Geocode::QuadTile => 1,
+    }
+}
+
+/// docket version 1 body: dht_key_length (i8) followed by geocode (u8) -
+/// identical to the layout pre-docket albums wrote bare, with no magic
+/// or version tag at all
+fn read_v1_body(id: &str, body: &[u8])
+        -> Result<(i8, Geocode), Box<dyn Error>> {
+    if body.len() < 2 {
+        return Err(format!(
+            "album '{}' metadata body truncated", id).into());
+    }
+
+    let dht_key_length = body[0] as i8;
+    let geocode = geocode_from_byte(id, body[1])?;
+    Ok((dht_key_length, geocode))
+}
+
+fn write_v1_body(dht_key_length: i8, geocode: &Geocode) -> Vec<u8> {
+    vec![dht_key_length as u8, geocode_to_byte(geocode)]
+}
+
+/// docket version 2 body: the version 1 pair followed by a
+/// cloud_optimized flag (u8) and a count-prefixed list of gdal raster
+/// creation options, each a u16-length-prefixed utf8 string
+fn read_v2_body(id: &str, body: &[u8])
+        -> Result<(i8, Geocode, Vec<String>, bool), Box<dyn Error>> {
+    if body.len() < 5 {
+        return Err(format!(
+            "album '{}' metadata body truncated", id).into());
+    }
+
+    let dht_key_length = body[0] as i8;
+    let geocode = geocode_from_byte(id, body[1])?;
+    let cloud_optimized = body[2] != 0;
+
+    let option_count = u16::from_be_bytes([body[3], body[4]]) as usize;
+    let mut cursor = &body[5..];
+
+    let mut creation_options = Vec::with_capacity(option_count);
+    for _ in 0..option_count {
+        if cursor.len() < 2 {
+            return Err(format!(
+                "album '{}' metadata creation options truncated", id).into());
+        }
+
+        let len = u16::from_be_bytes([cursor[0], cursor[1]]) as usize;
+        cursor = &cursor[2..];
+
+        if cursor.len() < len {
+            return Err(format!(
+                "album '{}' metadata creation options truncated", id).into());
+        }
+
+        creation_options.push(
+            String::from_utf8(cursor[..len].to_vec())?);
+        cursor = &cursor[len..];
+    }
+
+    Ok((dht_key_length, geocode, creation_options, cloud_optimized))
+}
+
+fn write_v2_body(dht_key_length: i8, geocode: &Geocode,
+        creation_options: &[String], cloud_optimized: bool) -> Vec<u8> {
+    let mut body = write_v1_body(dht_key_length, geocode);
+    body.push(cloud_optimized as u8);
+    body.extend_from_slice(&(creation_options.len() as u16).to_be_bytes());
+
+    for option in creation_options {
+        body.extend_from_slice(&(option.len() as u16).to_be_bytes());
+        body.extend_from_slice(option.as_bytes());
+    }
+
+    body
+}
+
+/// docket version 3 body: the version 2 body followed by a split
+/// replication factor (u8)
+fn read_v3_body(id: &str, body: &[u8])
+        -> Result<(i8, Geocode, Vec<String>, bool, u8), Box<dyn Error>> {
+    if body.is_empty() {
+        return Err(format!(
+            "album '{}' metadata body truncated", id).into());
+    }
+
+    let (dht_key_length, geocode, creation_options, cloud_optimized) =
+        read_v2_body(id, &body[..body.len() - 1])?;
+    let replication_factor = body[body.len() - 1];
+
+    Ok((dht_key_length, geocode, creation_options, cloud_optimized,
+        replication_factor))
+}
+
+fn write_v3_body(dht_key_length: i8, geocode: &Geocode,
+        creation_options: &[String], cloud_optimized: bool,
+        replication_factor: u8) -> Vec<u8> {
+    let mut body = write_v2_body(
+        dht_key_length, geocode, creation_options, cloud_optimized);
+    body.push(replication_factor);
+    body
+}
+
+/// read 'path' as album metadata, parsing the versioned docket header
+/// when present and falling back to the bare pre-docket layout
+/// otherwise, so albums written before this format existed still load.
+/// pre-docket and docket-version-1 albums predate per-album creation
+/// options, so they're given 'DEFAULT_CREATION_OPTIONS' and a disabled
+/// cloud-optimized flag in place of the single hardcoded 'COMPRESS=LZW'
+/// option they were written with, so tiles written to them from now on
+/// use those defaults; version 1 and 2 albums alike predate the split
+/// replication factor, so they're given 'DEFAULT_SPLIT_REPLICATION_FACTOR'
+fn read_meta(path: &PathBuf, id: &str)
+        -> Result<(i8, Geocode, Vec<String>, bool, u8), Box<dyn Error>> {
+    let mut file = File::open(path)?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+
+    if !buf.starts_with(META_MAGIC) {
+        let (dht_key_length, geocode) = read_v1_body(id, &buf)?;
+        return Ok((dht_key_length, geocode, default_creation_options(),
+            false, DEFAULT_SPLIT_REPLICATION_FACTOR));
+    }
+
+    let cursor = &buf[META_MAGIC.len()..];
+    if cursor.is_empty() {
+        return Err(format!(
+            "album '{}' metadata missing docket version", id).into());
+    }
+
+    let version = cursor[0];
+    let cursor = &cursor[1..];
+
+    if cursor.len() < 4 {
+        return Err(format!(
+            "album '{}' metadata missing docket body length", id).into());
+    }
+
+    let body_len = u32::from_be_bytes(
+        [cursor[0], cursor[1], cursor[2], cursor[3]]) as usize;
+    let cursor = &cursor[4..];
+
+    if cursor.len() < body_len {
+        return Err(format!(
+            "album '{}' metadata body truncated", id).into());
+    }
+
+    let body = &cursor[..body_len];
+    match version {
+        1 => {
+            let (dht_key_length, geocode) = read_v1_body(id, body)?;
+            Ok((dht_key_length, geocode, default_creation_options(), false,
+                DEFAULT_SPLIT_REPLICATION_FACTOR))
+        },
+        2 => {
+            let (dht_key_length, geocode, creation_options, cloud_optimized) =
+                read_v2_body(id, body)?;
+            Ok((dht_key_length, geocode, creation_options, cloud_optimized,
+                DEFAULT_SPLIT_REPLICATION_FACTOR))
+        },
+        3 => read_v3_body(id, body),
+        _ => Err(format!(
+            "album '{}' metadata uses docket version {}, which this \
+                build does not understand", id, version).into()),
+    }
+}
+
+fn default_creation_options() -> Vec<String> {
+    DEFAULT_CREATION_OPTIONS.iter().map(|s| s.to_string()).collect()
+}
+
+/// write 'path' as a versioned docket: magic, format version, then a
+/// length-prefixed body laid out per 'META_VERSION'
+fn write_meta(path: &PathBuf, dht_key_length: i8, geocode: &Geocode,
+        creation_options: &[String], cloud_optimized: bool,
+        replication_factor: u8) -> Result<(), Box<dyn Error>> {
+    let body = write_v3_body(dht_key_length, geocode,
+        creation_options, cloud_optimized, replication_factor);
+
+    let mut file = File::create(path)?;
+    file.write_all(META_MAGIC)?;
+    file.write_u8(META_VERSION)?;
+    file.write_u32::<BigEndian>(body.len() as u32)?;
+    file.write_all(&body)?;
+
+    Ok(())
+}
+
+/// recompute 'path's content checksum the same way `transfer::send_image`
+/// does when first writing the tile, and compare it against the value
+/// stamped into the 'CHECKSUM' STIP metadata item - 'None' means the
+/// tile is intact, 'Some(reason)' describes why it is not
+fn verify_tile(path: &PathBuf) -> Result<Option<String>, Box<dyn Error>> {
+    let dataset = Dataset::open(path)?;
+    let stored = dataset.metadata_item("CHECKSUM", "STIP")
+        .ok_or("missing CHECKSUM metadata")?.parse::<u64>()?;
+
+    let mut buf = Vec::new();
+    st_image::prelude::write(&dataset, &mut buf)?;
+    let actual = crate::transfer::checksum(&buf);
+
+    if actual != stored {
+        Ok(Some(format!(
+            "checksum mismatch: expected {} got {}", stored, actual)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// remove 'path' outright, or move it into the album's '.trash'
+/// subdirectory (mirroring its 'platform/geocode/source' layout) when
+/// 'quarantine' is set, so a vacuum can be undone before being trusted
+fn reclaim_tile(album_directory: &PathBuf, path: &PathBuf, quarantine: bool)
+        -> Result<(), Box<dyn Error>> {
+    if !quarantine {
+        return Ok(std::fs::remove_file(path)?);
+    }
+
+    let relative = path.strip_prefix(album_directory).map_err(|_|
+        format!("tile '{:?}' is outside album directory", path))?;
+
+    let mut trash_path = album_directory.clone();
+    trash_path.push(".trash");
+    trash_path.push(relative);
+
+    if let Some(parent) = trash_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    Ok(std::fs::rename(path, trash_path)?)
+}
+
+/// remove now-empty 'platform/geocode/source' directories left behind by
+/// reclaimed tiles, leaving the '.trash' quarantine directory (and
+/// 'directory' itself) untouched
+fn prune_empty_directories(directory: &PathBuf) -> Result<(), Box<dyn Error>> {
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if !path.is_dir() || path.file_name() == Some(OsStr::new(".trash")) {
+            continue;
+        }
+
+        if prune_directory_tree(&path)? {
+            std::fs::remove_dir(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// recursively prune empty subdirectories of 'path', returning whether
+/// 'path' itself is now empty (and so can be removed by the caller)
+fn prune_directory_tree(path: &PathBuf) -> Result<bool, Box<dyn Error>> {
+    let mut empty = true;
+    for entry in std::fs::read_dir(path)? {
+        let child = entry?.path();
+        if child.is_dir() {
+            if prune_directory_tree(&child)? {
+                std::fs::remove_dir(&child)?;
+            } else {
+                empty = false;
+            }
+        } else {
+            empty = false;
+        }
+    }
+
+    Ok(empty)
+}
+
+/// where a node-wide '--storage-backend' selection ultimately stores a
+/// tile's bytes - captured as config rather than a built `StorageBackend`
+/// since each album needs its own root (a subdirectory for 'local', a
+/// distinct key prefix for 's3')
+#[derive(Clone, Debug)]
+pub enum StorageConfig {
+    Local,
+    S3 {
+        access_key: Option<String>,
+        bucket: String,
+        endpoint: Option<String>,
+        region: Option<String>,
+        secret_key: Option<String>,
+    },
+}
+
+impl StorageConfig {
+    fn build(&self, directory: &PathBuf, album_id: &str)
+            -> Arc<dyn StorageBackend + Send + Sync> {
+        match self {
+            StorageConfig::Local => Arc::new(
+                crate::storage::LocalBackend::new(directory.clone())),
+            StorageConfig::S3{access_key, bucket, endpoint,
+                    region, secret_key} => Arc::new(
+                crate::storage::S3Backend::new(bucket.clone(),
+                    format!("{}/", album_id), endpoint.clone(),
+                    region.clone(), access_key.clone(),
+                    secret_key.clone())),
+        }
+    }
+}
+
 pub struct AlbumManager {
     directory: PathBuf,
     albums: HashMap<String, Arc<RwLock<Album>>>,
+    storage: StorageConfig,
 }
 
 impl AlbumManager {
-    pub fn new(directory: PathBuf)
+    pub fn new(directory: PathBuf, storage: StorageConfig)
             -> Result<AlbumManager, Box<dyn Error>> {
         // parse existing albums
         let mut albums = HashMap::new();
@@ -32,41 +373,56 @@ impl AlbumManager {
             // parse metadata file
             path.push("album");
             path.set_extension("meta");
-            let mut file = File::open(&path)?;
-
-            let dht_key_length = file.read_i8()?;
-            let geocode_value = file.read_u8()?;
-            let geocode: Geocode = match geocode_value {
-                0 => Geocode::Geohash,
-                1 => Geocode::QuadTile,
-                _ => return Err(format!("unknown geocode {}",
-                    geocode_value).into()),
-            };
-
+            let (dht_key_length, geocode, creation_options, cloud_optimized,
+                replication_factor) = read_meta(&path, &id)?;
             path.pop();
 
             // add album to map
+            let block_table = BlockTable::open(path.clone())?;
+            let index_cache = IndexCache::open(&path)?;
+            let album_storage = storage.build(&path, &id);
             albums.insert(id.clone(),
                 Arc::new(RwLock::new(Album {
+                    block_table: block_table,
+                    cloud_optimized: cloud_optimized,
+                    creation_options: creation_options,
                     dht_key_length: dht_key_length,
                     directory: path,
                     geocode: geocode,
                     id: id,
                     index: None,
+                    index_cache: index_cache,
+                    needs_rescan: true,
+                    replication_factor: replication_factor,
+                    storage: album_storage,
                 })));
         }
 
         Ok(AlbumManager {
             directory: directory,
             albums: albums,
+            storage: storage,
         })
     }
 
     pub fn create(&mut self, dht_key_length: i8, geocode: Geocode,
             id: &str) -> Result<(), Box<dyn Error>> {
+        self.create_with_options(dht_key_length, geocode, id,
+            default_creation_options(), false,
+            DEFAULT_SPLIT_REPLICATION_FACTOR)
+    }
+
+    /// create an album with explicit gdal raster creation options,
+    /// cloud-optimized setting, and split replication factor, all
+    /// persisted into its docket - separated from 'create' so callers
+    /// that have nothing but the node-wide defaults can keep using the
+    /// simpler constructor (nothing in this crate calls it at present)
+    pub fn create_with_options(&mut self, dht_key_length: i8, geocode: Geocode,
+            id: &str, creation_options: Vec<String>, cloud_optimized: bool,
+            replication_factor: u8) -> Result<(), Box<dyn Error>> {
         info!("creating album [id:{}, geocode={:?}, dht_key_length={}]",
             id, geocode, dht_key_length);
-            
+
         // create album directory
         let mut path = self.directory.clone();
         path.push(id);
@@ -79,23 +435,28 @@ impl AlbumManager {
         // write metadata file
         path.push("album");
         path.set_extension("meta");
-        let mut file = File::create(&path)?;
-
-        file.write_i8(dht_key_length)?;
-        match geocode {
-            Geocode::Geohash => file.write_u8(0)?,
-            Geocode::QuadTile => file.write_u8(1)?,
-        }
+        write_meta(&path, dht_key_length, &geocode, &creation_options,
+            cloud_optimized, replication_factor)?;
         path.pop();
 
         // add album to map
+        let block_table = BlockTable::open(path.clone())?;
+        let index_cache = IndexCache::open(&path)?;
+        let album_storage = self.storage.build(&path, id);
         self.albums.insert(id.to_string(),
             Arc::new(RwLock::new(Album {
+                block_table: block_table,
+                cloud_optimized: cloud_optimized,
+                creation_options: creation_options,
                 dht_key_length: dht_key_length,
                 directory: path,
                 geocode: geocode,
                 id: id.to_string(),
                 index: None,
+                index_cache: index_cache,
+                needs_rescan: true,
+                replication_factor: replication_factor,
+                storage: album_storage,
             })));
 
         Ok(())
@@ -123,14 +484,43 @@ impl AlbumManager {
     pub fn iter(&self) -> Iter<String, Arc<RwLock<Album>>> {
         self.albums.iter()
     }
+
+    pub fn vacuum(&self, id: &str, quarantine: bool, verify_coverage: bool)
+            -> Result<VacuumReport, Box<dyn Error>> {
+        match self.albums.get(id) {
+            Some(album) => album.read().unwrap()
+                .vacuum(quarantine, verify_coverage),
+            None => Err(format!("album '{}' does not exist", id).into()),
+        }
+    }
+
+    pub fn optimize_index(&self, id: &str) -> Result<(), Box<dyn Error>> {
+        match self.albums.get(id) {
+            Some(album) => album.read().unwrap().optimize_index(),
+            None => Err(format!("album '{}' does not exist", id).into()),
+        }
+    }
+}
+
+/// tile and byte counts reclaimed by a single `Album::vacuum` pass
+pub struct VacuumReport {
+    pub reclaimed_tiles: u64,
+    pub reclaimed_bytes: u64,
 }
 
 pub struct Album {
+    block_table: BlockTable,
+    cloud_optimized: bool,
+    creation_options: Vec<String>,
     dht_key_length: i8,
     directory: PathBuf,
     geocode: Geocode,
     id: String,
     index: Option<AlbumIndex>,
+    index_cache: IndexCache,
+    needs_rescan: bool,
+    replication_factor: u8,
+    storage: Arc<dyn StorageBackend + Send + Sync>,
 }
 
 impl Album {
@@ -142,6 +532,10 @@ impl Album {
         self.dht_key_length
     }
 
+    pub fn get_directory(&self) -> &PathBuf {
+        &self.directory
+    }
+
     pub fn get_geocode(&self) -> &Geocode {
         &self.geocode
     }
@@ -150,9 +544,35 @@ impl Album {
         &self.id
     }
 
+    pub fn get_replication_factor(&self) -> u8 {
+        self.replication_factor
+    }
+
+    /// the tile path relative to the album's root, independent of which
+    /// storage backend ultimately resolves it -
+    /// 'platform/geocode/source/tile-subdataset.tif', or
+    /// '...-subdataset-preview.tif' for the downsampled preview variant
+    /// of the same tile
+    fn tile_relative_path(geocode: &str, platform: &str, source: &str,
+            subdataset: u8, tile: &str, preview: bool) -> PathBuf {
+        let mut path = PathBuf::new();
+        path.push(platform);
+        path.push(geocode);
+        path.push(source);
+        path.push(match preview {
+            true => format!("{}-{}-preview.tif", tile, subdataset),
+            false => format!("{}-{}.tif", tile, subdataset),
+        });
+        path
+    }
+
+    /// the tile's path on local disk - used where a tile is handled as a
+    /// real local file ('write''s hardlink dedup and chmod, 'vacuum''s
+    /// audit of indexed tiles), which has no equivalent against an
+    /// s3-backed album
     pub fn get_image_path(&self, create: bool, geocode: &str,
-            platform: &str, source: &str, subdataset: u8,
-            tile: &str) -> Result<PathBuf, Box<dyn Error>> {
+            platform: &str, source: &str, subdataset: u8, tile: &str,
+            preview: bool) -> Result<PathBuf, Box<dyn Error>> {
         // create directory 'self.directory/platform/geocode/source'
         let mut path = self.directory.clone();
         for filename in vec!(platform, geocode, source) {
@@ -166,8 +586,11 @@ impl Album {
             }
         }
 
-        // add tile-subdataset.tif
-        path.push(format!("{}-{}.tif", tile, subdataset));
+        // add tile-subdataset.tif, or tile-subdataset-preview.tif
+        path.push(match preview {
+            true => format!("{}-{}-preview.tif", tile, subdataset),
+            false => format!("{}-{}.tif", tile, subdataset),
+        });
         Ok(path)
     }
 
@@ -175,76 +598,412 @@ impl Album {
         &self.index
     }
 
-    pub fn get_paths(&self) -> Result<Vec<PathBuf>, Box<dyn Error>> {
-        let glob_expression = format!("{}/*/*/*/*tif",
-            self.directory.to_string_lossy());
+    /// the path a client should read a tile's bytes from - the local
+    /// file if this node holds a copy, otherwise a marker pointing at a
+    /// node known (from a past replica push) to hold one instead, so a
+    /// missing local copy doesn't surface as a hard failure while any
+    /// replica survives
+    pub fn resolve_file_path(&self, geocode: &str, platform: &str,
+            source: &str, subdataset: u8, tile: &str, preview: bool)
+            -> Result<String, Box<dyn Error>> {
+        let relative = Album::tile_relative_path(geocode, platform,
+            source, subdataset, tile, preview);
+        if self.storage.exists(&relative) {
+            return Ok(self.storage.resolve(&relative));
+        }
 
-        // iterate over existing images
-        let mut paths = Vec::new();
-        for entry in glob::glob(&glob_expression)? {
-            paths.push(entry?);
+        let key = block::tile_key(platform, geocode, source,
+            subdataset, tile, preview);
+        match self.block_table.replica_node_ids(&key).first() {
+            Some(node_id) => Ok(format!("replica://{}{}",
+                node_id, self.storage.resolve(&relative))),
+            None => Ok(self.storage.resolve(&relative)),
         }
+    }
 
-        Ok(paths)
+    pub fn get_paths(&self) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        self.storage.list()
     }
 
     pub fn list(&self, end_timestamp: &Option<i64>,
             geocode: &Option<String>, max_cloud_coverage: &Option<f64>,
+            max_lat: &Option<f64>, max_lon: &Option<f64>,
+            min_lat: &Option<f64>, min_lon: &Option<f64>,
             min_pixel_coverage: &Option<f64>, platform: &Option<String>,
             recurse: bool, source: &Option<String>,
             start_timestamp: &Option<i64>)
             -> Result<Vec<(Image, Vec<StFile>)>, Box<dyn Error>> {
         match &self.index {
             Some(index) => Ok(index.list(&self, end_timestamp, geocode,
-                max_cloud_coverage, min_pixel_coverage, platform,
+                max_cloud_coverage, max_lat, max_lon, min_lat, min_lon,
+                min_pixel_coverage, platform,
                 recurse, source, start_timestamp)?),
             None => Err("unable to list on closed album".into()),
         }
     }
 
-    pub fn load(&mut self, cloud_coverage: Option<f64>, geocode: &str,
-            pixel_coverage: f64, platform: &str, source: &str,
-            subdataset: u8, tile: &str, timestamp: i64) 
+    /// every catalog row under 'geocode_prefix' ('None' for the whole
+    /// album) - the raw material 'task::reconcile::ReconcileTask' and the
+    /// 'merkle' rpc build a reconciliation tree over
+    pub fn merkle_rows(&self, geocode_prefix: &Option<String>)
+            -> Result<Vec<crate::index::MerkleRow>, Box<dyn Error>> {
+        match &self.index {
+            Some(index) => Ok(index.merkle_rows(geocode_prefix)?),
+            None => Err("unable to compute merkle rows on closed album".into()),
+        }
+    }
+
+    pub fn load(&mut self, checksum: Option<u64>, cloud_coverage: Option<f64>,
+            geocode: &str, pixel_coverage: f64, platform: &str, source: &str,
+            subdataset: u8, tile: &str, timestamp: i64, preview: bool)
             -> Result<(), Box<dyn Error>> {
+        // decode the geocode's lon/lat bounds so the rtree backing
+        // spatial queries stays in lockstep with every row 'load' writes
+        let (min_lon, max_lon, min_lat, max_lat) =
+            self.geocode.decode(geocode)?;
+
         match &mut self.index {
-            Some(index) => Ok(index.load(cloud_coverage,
-                geocode, pixel_coverage, platform, source,
-                subdataset, tile, timestamp)?),
+            Some(index) => {
+                index.load(checksum, cloud_coverage, geocode,
+                    max_lat, max_lon, min_lat, min_lon, pixel_coverage,
+                    platform, source, subdataset, tile, timestamp,
+                    preview)?;
+
+                // persist this tile to the on-disk cache so a future
+                // open can replay it instead of re-globbing and
+                // re-reading this tile's gdal metadata
+                self.index_cache.append(&index_cache::IndexRecord {
+                    checksum: checksum,
+                    cloud_coverage: cloud_coverage,
+                    geocode: geocode.to_string(),
+                    pixel_coverage: pixel_coverage,
+                    platform: platform.to_string(),
+                    preview: preview,
+                    source: source.to_string(),
+                    subdataset: subdataset,
+                    tile: tile.to_string(),
+                    timestamp: timestamp,
+                })?;
+
+                Ok(())
+            },
             None => Err("unable to load on closed album".into()),
         }
     }
 
+    /// record a tile's aggregate band/file catalog, sent once per
+    /// geocode tile after a loader finishes splitting every subdataset
+    /// of a record - a coarser companion to the per-band rows 'load'
+    /// writes, so a catalog query doesn't need to open a raster
+    pub fn write_tile_metadata(&self, geocode: &str, tile: &str,
+            platform: &str, mean_pixel_coverage: f64,
+            files: &[(u8, String)]) -> Result<(), Box<dyn Error>> {
+        match &self.index {
+            Some(index) => {
+                let files = files.iter()
+                    .map(|(band_id, description)|
+                        format!("{}:{}", band_id, description))
+                    .collect::<Vec<String>>().join("\n");
+
+                index.write_tile_metadata(geocode, tile, platform,
+                    mean_pixel_coverage, &files)
+            },
+            None => Err("unable to write tile metadata on closed album".into()),
+        }
+    }
+
+    pub fn needs_rescan(&self) -> bool {
+        self.needs_rescan
+    }
+
+    /// open the index, either picking an already-persisted sqlite
+    /// catalog back up as-is, replaying the on-disk cache into a brand
+    /// new one (when the cache is still fresh, i.e. untouched since it
+    /// was last written), or flagging that the caller must fall back to
+    /// a full directory rescan
     pub fn open(&mut self) -> Result<(), Box<dyn Error>> {
-        self.index = Some(AlbumIndex::new()?);
+        let mut index_path = self.directory.clone();
+        index_path.push("index");
+        index_path.set_extension("sqlite");
+
+        // a catalog that already exists on disk already has every row
+        // the cache would otherwise replay - re-replaying on top of it
+        // would double the 'files' rows for every tile, so this
+        // restart-populate path only runs the first time the database
+        // is created
+        let index_is_new = !index_path.exists();
+        let mut index = AlbumIndex::new(&index_path)?;
+
+        if index_is_new {
+            if index_cache::is_fresh(&self.directory) {
+                for record in index_cache::replay(&self.directory)? {
+                    let (min_lon, max_lon, min_lat, max_lat) =
+                        self.geocode.decode(&record.geocode)?;
+
+                    index.load(record.checksum, record.cloud_coverage,
+                        &record.geocode, max_lat, max_lon, min_lat, min_lon,
+                        record.pixel_coverage, &record.platform,
+                        &record.source, record.subdataset, &record.tile,
+                        record.timestamp, record.preview)?;
+                }
+
+                self.needs_rescan = false;
+            } else {
+                self.index_cache.reset()?;
+                self.needs_rescan = true;
+            }
+        } else {
+            self.needs_rescan = false;
+        }
+
+        self.index = Some(index);
         Ok(())
     }
 
     pub fn search(&self, end_timestamp: &Option<i64>,
             geocode: &Option<String>, max_cloud_coverage: &Option<f64>,
+            max_lat: &Option<f64>, max_lon: &Option<f64>,
+            min_lat: &Option<f64>, min_lon: &Option<f64>,
             min_pixel_coverage: &Option<f64>, platform: &Option<String>,
             recurse: bool, source: &Option<String>,
             start_timestamp: &Option<i64>)
             -> Result<Vec<Extent>, Box<dyn Error>> {
         match &self.index {
             Some(index) => Ok(index.search(end_timestamp, geocode,
-                max_cloud_coverage, min_pixel_coverage, platform,
+                max_cloud_coverage, max_lat, max_lon, min_lat, min_lon,
+                min_pixel_coverage, platform,
                 recurse, source, start_timestamp)?),
             None => Err("unable to search on closed album".into()),
         }
     }
 
+    /// diff the on-disk tile set against the live index and reclaim
+    /// every tile the index no longer references - the result of a
+    /// re-split, a failed transfer, or an album edit. when
+    /// 'verify_coverage' is set, indexed tiles are also reopened and
+    /// reclaimed if they now cover 0.0 (pre-dating the write-time
+    /// coverage check). reclaimed tiles are deleted outright, or moved
+    /// into a '.trash' subdirectory mirroring the album layout when
+    /// 'quarantine' is set, so a vacuum can be undone before being
+    /// trusted. directories left empty by reclaimed tiles are pruned
+    pub fn vacuum(&self, quarantine: bool, verify_coverage: bool)
+            -> Result<VacuumReport, Box<dyn Error>> {
+        // the canonical on-disk path for every tile still referenced
+        // by the live index
+        let mut indexed_paths = std::collections::HashSet::new();
+        for (image, files) in self.list(&None, &None, &None,
+                &None, &None, &None, &None,
+                &None, &None, true, &None, &None)? {
+            for file in files {
+                indexed_paths.insert(self.get_image_path(false, &image.1,
+                    &image.2, &image.3, file.2, &image.4, file.3)?);
+            }
+        }
+
+        let mut report = VacuumReport {
+            reclaimed_tiles: 0,
+            reclaimed_bytes: 0,
+        };
+
+        for path in self.get_paths()? {
+            let mut reclaim = !indexed_paths.contains(&path);
+
+            if !reclaim && verify_coverage {
+                if let Ok(dataset) = Dataset::open(&path) {
+                    if let Ok(coverage) = st_image::coverage(&dataset) {
+                        reclaim = coverage == 0f64;
+                    }
+                }
+            }
+
+            if !reclaim {
+                continue;
+            }
+
+            // 'reclaim_tile' below only knows how to remove/trash a real
+            // local file - reuse the same gate 'write' (below) uses
+            // for hardlink dedup and chmod, rather than letting a raw
+            // 'std::fs' call fail silently against a vsi url
+            if !self.storage.is_local() {
+                warn!("skipping reclaim of non-local tile '{:?}': vacuum \
+                    does not yet support deleting from a remote storage \
+                    backend", path);
+                continue;
+            }
+
+            let size = std::fs::metadata(&path)
+                .map(|metadata| metadata.len()).unwrap_or(0);
+            if let Err(e) = reclaim_tile(&self.directory, &path, quarantine) {
+                warn!("failed to reclaim tile '{:?}': {}", path, e);
+                continue;
+            }
+
+            report.reclaimed_tiles += 1;
+            report.reclaimed_bytes += size;
+        }
+
+        prune_empty_directories(&self.directory)?;
+
+        Ok(report)
+    }
+
+    /// rebuild the catalog's on-disk sqlite file and refresh its query
+    /// planner statistics - run periodically (or after a heavy
+    /// load/repair pass) rather than on every write, since it rewrites
+    /// the whole database file
+    pub fn optimize_index(&self) -> Result<(), Box<dyn Error>> {
+        match &self.index {
+            Some(index) => index.optimize(),
+            None => Err("unable to optimize index of closed album".into()),
+        }
+    }
+
+    /// the cached preview raster for (geocode, tile, source, subdataset)
+    /// at 'max_dimension', if one has already been generated
+    pub fn get_preview(&self, geocode: &str, tile: &str, source: &str,
+            subdataset: u8, max_dimension: u32)
+            -> Result<Option<String>, Box<dyn Error>> {
+        match &self.index {
+            Some(index) => index.get_preview(geocode,
+                tile, source, subdataset, max_dimension),
+            None => Err("unable to query preview cache of closed album".into()),
+        }
+    }
+
+    /// downsample 'dataset' to a raster capped at 'max_dimension' pixels
+    /// on its long edge, write it through the storage backend, and
+    /// record the result in the preview cache so a repeat request for
+    /// the same (geocode, tile, source, subdataset, max_dimension) is
+    /// served from disk instead of re-decimating the full-resolution
+    /// tile - serves 'ImageManagement::preview'
+    pub fn write_preview(&self, dataset: &Dataset, geocode: &str,
+            platform: &str, source: &str, subdataset: u8, tile: &str,
+            max_dimension: u32) -> Result<String, Box<dyn Error>> {
+        let relative = Album::preview_relative_path(geocode, platform,
+            source, subdataset, tile, max_dimension);
+        self.storage.prepare_write(&relative)?;
+
+        let driver = Driver::get("GTiff")?;
+        let path_str = self.storage.resolve(&relative);
+        let c_filename = CString::new(path_str.clone())?;
+
+        let c_dataset = unsafe {
+            gdal_sys::GDALCreateCopy(driver.c_driver(), c_filename.as_ptr(),
+                dataset.c_dataset(), 0, std::ptr::null_mut(),
+                None, std::ptr::null_mut())
+        };
+
+        if c_dataset.is_null() {
+            let err_msg = unsafe {
+                let c_ptr = gdal_sys::CPLGetLastErrorMsg();
+                CStr::from_ptr(c_ptr).to_string_lossy().into_owned()
+            };
+
+            unsafe { gdal_sys::CPLErrorReset() };
+            return Err(format!(
+                "failed to copy preview dataset: {}", err_msg).into());
+        }
+
+        // drop the copy through 'Dataset' so it closes (and flushes)
+        // the same way 'write''s full-resolution copy does
+        let _ = unsafe { Dataset::from_c_dataset(c_dataset) };
+
+        match &self.index {
+            Some(index) => index.insert_preview(geocode, tile,
+                source, subdataset, max_dimension, &path_str)?,
+            None => return Err(
+                "unable to cache preview of closed album".into()),
+        }
+
+        Ok(path_str)
+    }
+
+    /// 'previews/platform/geocode/source/tile-subdataset-maxdimension.tif' -
+    /// kept under its own top-level 'previews' directory (or key prefix,
+    /// for an object-store backend) so a vacuum walking the main tile
+    /// layout via 'tile_relative_path' never mistakes a cached thumbnail
+    /// for an orphaned full-resolution tile
+    fn preview_relative_path(geocode: &str, platform: &str, source: &str,
+            subdataset: u8, tile: &str, max_dimension: u32) -> PathBuf {
+        let mut path = PathBuf::new();
+        path.push("previews");
+        path.push(platform);
+        path.push(geocode);
+        path.push(source);
+        path.push(format!("{}-{}-{}.tif", tile, subdataset, max_dimension));
+        path
+    }
+
+    /// walk every tile on disk, recomputing its content checksum and
+    /// comparing against the `CHECKSUM` value stamped into its `STIP`
+    /// metadata at write time, so an operator can audit an album for
+    /// corruption introduced by a disk fault after the fact - a single
+    /// unreadable or mismatched tile is reported rather than aborting
+    /// the rest of the walk
+    pub fn verify(&self) -> Result<Vec<VerifyFailure>, Box<dyn Error>> {
+        let mut failures = Vec::new();
+        for path in self.get_paths()? {
+            if let Some(reason) = match verify_tile(&path) {
+                Ok(reason) => reason,
+                Err(e) => Some(format!("failed to verify: {}", e)),
+            } {
+                failures.push((path, reason));
+            }
+        }
+
+        Ok(failures)
+    }
+
     pub fn write(&mut self, dataset: &mut Dataset, geocode: &str,
             pixel_coverage: f64, platform: &str, source: &str,
-            subdataset: u8, tile: &str, timestamp: i64)
+            subdataset: u8, tile: &str, timestamp: i64, checksum: u64,
+            digest: &str, sender_node_id: u32, preview: bool)
             -> Result<(), Box<dyn Error>> {
-        // get image path
-        let path = self.get_image_path(true, geocode,
-            platform, source, subdataset, tile)?;
+        // resolve where this tile's bytes land - a local path or a
+        // '/vsis3/...' vsi url, depending on the album's storage backend
+        let relative = Album::tile_relative_path(geocode, platform,
+            source, subdataset, tile, preview);
+        let path = self.get_image_path(self.storage.is_local(), geocode,
+            platform, source, subdataset, tile, preview)?;
 
-        if path.exists() { // attempting to rewrite existing file
+        let key = block::tile_key(platform, geocode, source,
+            subdataset, tile, preview);
+
+        if self.storage.exists(&relative) { // attempting to rewrite existing file
+            self.block_table.record_replica(&key, sender_node_id)?;
             return Ok(());
         }
 
+        // an identical tile (by content digest) may already be stored
+        // elsewhere in this album - hardlink to it instead of re-encoding
+        // the same bytes through gdal a second time. note the hardlinked
+        // copy's embedded STIP metadata tags still describe the *other*
+        // tile's geocode/tile/etc, which only matters if the album is
+        // ever fully rebuilt by rescanning disk rather than loaded
+        // through this write path. hardlinks are a local filesystem
+        // concept, so this dedup is skipped entirely for a non-local
+        // backend - every write lands as its own object
+        if self.storage.is_local() {
+            if let Some(canonical_path) = self.block_table.canonical_path(digest) {
+                if canonical_path.exists() {
+                    std::fs::hard_link(&canonical_path, &path)?;
+
+                    self.block_table.register(&key, digest, &path)?;
+                    self.block_table.record_replica(&key, sender_node_id)?;
+
+                    if let Some(_) = self.index {
+                        self.load(Some(checksum), None, geocode,
+                            pixel_coverage, platform, source, subdataset,
+                            tile, timestamp, preview)?;
+                    }
+
+                    return Ok(());
+                }
+            }
+        }
+
+        self.storage.prepare_write(&relative)?;
+
         // open GeoTiff driver
         let driver = Driver::get("GTiff")?;
 
@@ -267,15 +1026,53 @@ impl Album {
             }
         }*/
         // intialize copy arguments
-        let path_str = path.to_string_lossy().to_string();
+        let path_str = self.storage.resolve(&relative);
         let c_filename = CString::new(path_str)?;
 
-        let c_compress_str = CString::new("COMPRESS=LZW")?;
-        let c_compress_ptr = c_compress_str.into_raw();
-        let mut c_options = vec![
-            c_compress_ptr,
-            std::ptr::null_mut()
-        ];
+        // a cloud-optimized tile needs internal overviews built on the
+        // source dataset *before* the copy below, so the GTiff driver's
+        // 'COPY_SRC_OVERVIEWS' option can carry them into the output
+        // alongside the tiled layout that makes partial/range reads of
+        // a single tile efficient for downstream clients
+        let mut creation_options = self.creation_options.clone();
+        if self.cloud_optimized {
+            let mut overview_levels = vec![2, 4, 8, 16];
+            let c_resampling = CString::new("AVERAGE")?;
+
+            let result = unsafe {
+                gdal_sys::GDALBuildOverviews(dataset.c_dataset(),
+                    c_resampling.as_ptr(), overview_levels.len() as i32,
+                    overview_levels.as_mut_ptr(), 0, std::ptr::null_mut(),
+                    None, std::ptr::null_mut())
+            };
+
+            if result as i32 != 0 { // CE_None == 0
+                let err_msg = unsafe {
+                    let c_ptr = gdal_sys::CPLGetLastErrorMsg();
+                    let c_str = CStr::from_ptr(c_ptr);
+                    c_str.to_string_lossy().into_owned()
+                };
+
+                unsafe { gdal_sys::CPLErrorReset() };
+                return Err(format!(
+                    "failed to build overviews: {}", err_msg).into());
+            }
+
+            creation_options.push("COPY_SRC_OVERVIEWS=YES".to_string());
+            if !creation_options.iter().any(|o| o.starts_with("TILED=")) {
+                creation_options.push("TILED=YES".to_string());
+            }
+        }
+
+        let c_creation_options: Vec<CString> = creation_options.iter()
+            .map(|option| CString::new(option.as_str()))
+            .collect::<Result<Vec<CString>, _>>()?;
+
+        let mut c_options: Vec<*mut std::os::raw::c_char> =
+            c_creation_options.iter()
+                .map(|option| option.as_ptr() as *mut std::os::raw::c_char)
+                .collect();
+        c_options.push(std::ptr::null_mut());
 
         // copy dataset using driver
         let c_dataset = unsafe {
@@ -301,16 +1098,14 @@ impl Album {
             Dataset::from_c_dataset(c_dataset)
         };
 
-        // clean up c memory to mitigate leaks
-        unsafe {
-            let _ = CString::from_raw(c_compress_ptr);
+        // set image permissions - a chmod only makes sense against a
+        // real local file
+        if self.storage.is_local() {
+            let mut permissions = std::fs::metadata(&path)?.permissions();
+            permissions.set_mode(0o644);
+            std::fs::set_permissions(&path, permissions)?;
         }
 
-        // set image permissions
-        let mut permissions = std::fs::metadata(&path)?.permissions();
-        permissions.set_mode(0o644);
-        std::fs::set_permissions(&path, permissions)?;
-
         // set dataset metadata attributes
         dataset_copy.set_metadata_item("GEOCODE", geocode, "STIP")?;
         dataset_copy.set_metadata_item("PIXEL_COVERAGE",
@@ -322,11 +1117,23 @@ impl Album {
         dataset_copy.set_metadata_item("TILE", tile, "STIP")?;
         dataset_copy.set_metadata_item("TIMESTAMP",
             &timestamp.to_string(), "STIP")?;
+        dataset_copy.set_metadata_item("CHECKSUM",
+            &checksum.to_string(), "STIP")?;
+        dataset_copy.set_metadata_item("PREVIEW",
+            &preview.to_string(), "STIP")?;
+
+        // record this tile's content address so a future write of the
+        // same bytes elsewhere in the album can dedup onto this file -
+        // only meaningful for the local backend's hardlink dedup above
+        if self.storage.is_local() {
+            self.block_table.register(&key, digest, &path)?;
+        }
+        self.block_table.record_replica(&key, sender_node_id)?;
 
         // if album is open -> load data
         if let Some(_) = self.index {
-            self.load(None, geocode, pixel_coverage,
-                platform, source, subdataset, tile, timestamp)?;
+            self.load(Some(checksum), None, geocode, pixel_coverage,
+                platform, source, subdataset, tile, timestamp, preview)?;
         }
 
         Ok(())
@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// hex-encoded sha256 over a tile's serialized raster bytes - identical
+/// bytes (e.g. a source record reprocessed into the same tile) hash to
+/// the same digest no matter where in the album they land
+pub fn digest(buf: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(buf);
+    hex::encode(hasher.finalize())
+}
+
+/// stable key identifying a logical tile within an album, independent
+/// of its content - mirrors the path components 'Album::get_image_path'
+/// already uses to place a file on disk. a preview tile gets its own
+/// key so it tracks its own content digest/replicas rather than
+/// aliasing the full-resolution tile it was derived from
+pub fn tile_key(platform: &str, geocode: &str, source: &str,
+        subdataset: u8, tile: &str, preview: bool) -> String {
+    match preview {
+        true => format!("{}/{}/{}/{}/{}/preview",
+            platform, geocode, source, subdataset, tile),
+        false => format!("{}/{}/{}/{}/{}",
+            platform, geocode, source, subdataset, tile),
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct BlockEntry {
+    digest: String,
+    path: PathBuf,
+    replica_node_ids: Vec<u32>,
+}
+
+/// per-album record of where each tile's bytes are content-addressed on
+/// disk and which other nodes are known to hold a replica - lets the
+/// store task dedup identical tiles instead of re-encoding them, and
+/// lets list/search fall back to a remote replica when the local copy
+/// is missing
+pub struct BlockTable {
+    directory: PathBuf,
+    entries: RwLock<HashMap<String, BlockEntry>>,
+}
+
+impl BlockTable {
+    pub fn open(directory: PathBuf) -> Result<BlockTable, Box<dyn Error>> {
+        let entries: HashMap<String, BlockEntry> =
+                match std::fs::read(table_path(&directory)) {
+            Ok(bytes) => rmp_serde::from_slice(&bytes)?,
+            Err(_) => HashMap::new(),
+        };
+
+        Ok(BlockTable {
+            directory: directory,
+            entries: RwLock::new(entries),
+        })
+    }
+
+    /// path of an existing tile elsewhere in the album storing the
+    /// exact same bytes as 'digest', if one has already been registered
+    pub fn canonical_path(&self, digest: &str) -> Option<PathBuf> {
+        self.entries.read().unwrap().values()
+            .find(|entry| entry.digest == digest)
+            .map(|entry| entry.path.clone())
+    }
+
+    /// record that the tile identified by 'key' holds 'digest' at
+    /// 'path' - called once per tile, whether freshly written or
+    /// hardlinked to an existing block
+    pub fn register(&self, key: &str, digest: &str, path: &Path)
+            -> Result<(), Box<dyn Error>> {
+        {
+            let mut entries = self.entries.write().unwrap();
+            entries.insert(key.to_string(), BlockEntry {
+                digest: digest.to_string(),
+                path: path.to_path_buf(),
+                replica_node_ids: Vec::new(),
+            });
+        }
+
+        self.flush()
+    }
+
+    /// record that 'node_id' is known to also hold a copy of the tile
+    /// identified by 'key' - best-effort, populated from whichever node
+    /// pushed us the block over the transfer server, since that's the
+    /// only replica location a receiver can observe directly
+    pub fn record_replica(&self, key: &str, node_id: u32)
+            -> Result<(), Box<dyn Error>> {
+        let changed = {
+            let mut entries = self.entries.write().unwrap();
+            match entries.get_mut(key) {
+                Some(entry) if !entry.replica_node_ids.contains(&node_id) => {
+                    entry.replica_node_ids.push(node_id);
+                    true
+                },
+                _ => false,
+            }
+        };
+
+        if changed {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// other nodes known to hold a replica of the tile identified by
+    /// 'key', for falling back to when the local copy is missing
+    pub fn replica_node_ids(&self, key: &str) -> Vec<u32> {
+        self.entries.read().unwrap().get(key)
+            .map(|entry| entry.replica_node_ids.clone())
+            .unwrap_or_default()
+    }
+
+    fn flush(&self) -> Result<(), Box<dyn Error>> {
+        let bytes = rmp_serde::to_vec(&*self.entries.read().unwrap())?;
+
+        let tmp_path = table_path(&self.directory).with_extension("mp.tmp");
+        {
+            let mut file = File::create(&tmp_path)?;
+            file.write_all(&bytes)?;
+            file.sync_all()?;
+        }
+
+        std::fs::rename(&tmp_path, table_path(&self.directory))?;
+        Ok(())
+    }
+}
+
+fn table_path(directory: &Path) -> PathBuf {
+    directory.join("blocks.mp")
+}
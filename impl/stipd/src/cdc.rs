@@ -0,0 +1,196 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// a boundary is only considered once a chunk reaches this many bytes -
+/// keeps a long run of the rolling hash's mask bits happening to match
+/// right away from producing a pathologically tiny chunk
+const MIN_CHUNK_LEN: usize = 8 * 1024;
+
+/// average chunk size the boundary mask is tuned for - small enough
+/// that overlapping splits from adjacent geocodes and time windows
+/// diverge in only a handful of chunks, large enough that the chunk
+/// list overhead stays negligible next to a tile's own size
+const TARGET_CHUNK_LEN: usize = 32 * 1024;
+
+/// a chunk is always cut here regardless of the rolling hash, bounding
+/// the cost of a pathological input (e.g. a long run of identical
+/// bytes) that never happens to satisfy the boundary mask
+const MAX_CHUNK_LEN: usize = 128 * 1024;
+
+/// bytes the rolling hash looks back over when deciding whether the
+/// current position is a boundary - long enough that the decision
+/// reflects real content, short enough that the hash re-syncs quickly
+/// after an inserted or deleted byte
+const WINDOW_LEN: usize = 48;
+
+/// multiplier the rolling polynomial hash advances by each byte - an
+/// odd constant so every window of distinct bytes spreads across the
+/// full range of the hash rather than collapsing into a few residues
+const POLY_MULTIPLIER: u64 = 0x100000001b3;
+
+/// a boundary falls wherever the low bits of the rolling hash are all
+/// zero - 'TARGET_CHUNK_LEN' (itself a power of two) minus one, so a
+/// boundary is expected roughly once per 'TARGET_CHUNK_LEN' bytes
+const BOUNDARY_MASK: u64 = (TARGET_CHUNK_LEN as u64) - 1;
+
+/// one content-defined chunk of a serialized raster - 'hash' is a
+/// strong (blake3) digest of 'data', used both to dedup against a peer's
+/// chunk store and to verify a chunk survived the wire intact
+#[derive(Clone, Debug)]
+pub struct Chunk {
+    pub hash: [u8; 32],
+    pub data: Vec<u8>,
+}
+
+/// split 'buf' into content-defined chunks via a rolling (gear/Rabin
+/// style) polynomial hash over a sliding 'WINDOW_LEN'-byte window,
+/// cutting a boundary whenever the hash's bits under 'BOUNDARY_MASK'
+/// are all zero - because the cut points are derived from content
+/// rather than a fixed offset, two buffers that share a long byte run
+/// (e.g. the same raster split from two overlapping source images)
+/// produce mostly identical chunks even if bytes were inserted or
+/// removed upstream of the shared run
+pub fn chunk(buf: &[u8]) -> Vec<Chunk> {
+    if buf.is_empty() {
+        return Vec::new();
+    }
+
+    // 'POLY_MULTIPLIER' raised to 'WINDOW_LEN' - the factor an outgoing
+    // byte's contribution is scaled by before being subtracted, so the
+    // hash always reflects exactly the trailing 'WINDOW_LEN' bytes of
+    // the current chunk (fewer right after a cut, where it restarts)
+    let window_multiplier = {
+        let mut multiplier = 1u64;
+        for _ in 0..WINDOW_LEN {
+            multiplier = multiplier.wrapping_mul(POLY_MULTIPLIER);
+        }
+        multiplier
+    };
+
+    let mut chunks = Vec::new();
+    let mut start = 0usize;
+    let mut hash = 0u64;
+
+    for i in 0..buf.len() {
+        hash = hash.wrapping_mul(POLY_MULTIPLIER)
+            .wrapping_add(buf[i] as u64);
+
+        if i - start >= WINDOW_LEN {
+            let outgoing = buf[i - WINDOW_LEN] as u64;
+            hash = hash.wrapping_sub(
+                outgoing.wrapping_mul(window_multiplier));
+        }
+
+        let len = i - start + 1;
+        if len >= MAX_CHUNK_LEN ||
+                (len >= MIN_CHUNK_LEN && hash & BOUNDARY_MASK == 0) {
+            chunks.push(Chunk {
+                hash: blake3::hash(&buf[start..=i]).into(),
+                data: buf[start..=i].to_vec(),
+            });
+
+            start = i + 1;
+            hash = 0;
+        }
+    }
+
+    if start < buf.len() {
+        chunks.push(Chunk {
+            hash: blake3::hash(&buf[start..]).into(),
+            data: buf[start..].to_vec(),
+        });
+    }
+
+    chunks
+}
+
+/// content-addressed store of chunk bytes, keyed by their blake3 hash -
+/// shared across every transfer into an album, so a chunk received once
+/// (whichever tile it first arrived as part of) is never requested
+/// again by a later, overlapping transfer
+pub struct ChunkStore {
+    directory: PathBuf,
+}
+
+impl ChunkStore {
+    pub fn open(directory: PathBuf) -> Result<ChunkStore, Box<dyn Error>> {
+        let chunk_store = ChunkStore {
+            directory: directory,
+        };
+
+        fs::create_dir_all(chunk_store.directory.join(".chunks"))?;
+        Ok(chunk_store)
+    }
+
+    pub fn has(&self, hash: &[u8; 32]) -> bool {
+        self.path(hash).exists()
+    }
+
+    /// like 'has', but also reads the chunk back and re-verifies its
+    /// blake3 digest against 'hash' - a chunk whose on-disk bytes no
+    /// longer match the hash its path is keyed by (truncated write, bit
+    /// rot) is treated the same as one that was never stored, so the
+    /// caller falls back to re-requesting it from the sender instead of
+    /// silently reassembling a corrupt tile. the bad entry is removed so
+    /// a later transfer doesn't pay the same read+hash cost again
+    pub fn contains_valid(&self, hash: &[u8; 32]) -> bool {
+        let path = self.path(hash);
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(_) => return false,
+        };
+
+        if blake3::hash(&data).as_bytes() == hash {
+            return true;
+        }
+
+        warn!("chunk store entry '{}' is corrupt, re-requesting",
+            hex::encode(hash));
+        let _ = fs::remove_file(&path);
+        false
+    }
+
+    pub fn read(&self, hash: &[u8; 32]) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(fs::read(self.path(hash))?)
+    }
+
+    /// persist 'data' under 'hash' via a temp file + rename, mirroring
+    /// the rest of the codebase's checkpoint writes - a no-op if the
+    /// chunk is already resident, since identical bytes always hash to
+    /// the same path
+    pub fn write(&self, hash: &[u8; 32], data: &[u8])
+            -> Result<(), Box<dyn Error>> {
+        if self.has(hash) {
+            return Ok(());
+        }
+
+        let path = self.path(hash);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let tmp_path = path.with_extension("tmp");
+        {
+            let mut file = File::create(&tmp_path)?;
+            file.write_all(data)?;
+            file.sync_all()?;
+        }
+
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// two-level directory fan-out so '.chunks' never holds more than a
+    /// few thousand entries in one listing, the same reasoning git's
+    /// own object store shards by the first byte of a sha1
+    fn path(&self, hash: &[u8; 32]) -> PathBuf {
+        let hex = hex::encode(hash);
+        self.chunks_dir().join(&hex[0..2]).join(hex)
+    }
+
+    fn chunks_dir(&self) -> PathBuf {
+        self.directory.join(".chunks")
+    }
+}
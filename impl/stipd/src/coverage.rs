@@ -0,0 +1,174 @@
+use swarm::prelude::Dht;
+
+use crate::album::AlbumManager;
+use crate::Extent;
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// upper bound on the peers a coverage round counts as its targets
+const FANOUT: usize = 3;
+
+#[derive(Clone, Debug)]
+pub struct CoverageEntry {
+    pub count: i64,
+    pub precision: u8,
+    pub source: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct VersionedCoverage {
+    pub entries: Vec<CoverageEntry>,
+    pub wallclock: u64,
+}
+
+/// gossip-replicated, last-writer-wins summary of which (geocode,
+/// platform) pairs each node holds imagery for, keyed by
+/// (node_id, geocode, platform). this is a CRDT in the same spirit as
+/// `GossipState` - a stale push can never clobber a fresher entry -
+/// except the replicated value is a pixel-coverage aggregate instead of
+/// a node's membership info. lets `search_index` answer from local
+/// state, instead of `search_all` paying a round trip to every node
+/// for every query. 'start' exchanges nothing with peers, so the map
+/// holds this node's own entries plus any handed to 'merge'.
+pub struct CoverageIndex {
+    node_id: u32,
+    entries: RwLock<HashMap<(u32, String, String), VersionedCoverage>>,
+    clock: AtomicU64,
+}
+
+impl CoverageIndex {
+    pub fn new(node_id: u32) -> CoverageIndex {
+        CoverageIndex {
+            node_id: node_id,
+            entries: RwLock::new(HashMap::new()),
+            clock: AtomicU64::new(1),
+        }
+    }
+
+    /// merge a remote entry, ignoring it if our wallclock is >= theirs
+    pub fn merge(&self, key: (u32, String, String),
+            coverage: VersionedCoverage) {
+        let mut entries = self.entries.write().unwrap();
+        let merge = match entries.get(&key) {
+            Some(existing) => coverage.wallclock > existing.wallclock,
+            None => true,
+        };
+
+        if merge {
+            entries.insert(key, coverage);
+        }
+    }
+
+    /// replace this node's own entries with a fresh local scan, bumping
+    /// the wallclock so peers prefer it over whatever they're holding
+    fn refresh_local(&self, album_manager: &Arc<RwLock<AlbumManager>>) {
+        let mut summaries: HashMap<(String, String), Vec<CoverageEntry>>
+            = HashMap::new();
+        {
+            let album_manager = album_manager.read().unwrap();
+            for (_, album) in album_manager.iter() {
+                let extents = match album.read().unwrap().search(
+                        &None, &None, &None, &None, &None, &None, &None,
+                        &None, &None, true, &None, &None) {
+                    Ok(extents) => extents,
+                    Err(_) => continue,
+                };
+
+                for (count, geocode, platform, precision, source)
+                        in extents {
+                    summaries.entry((geocode, platform))
+                        .or_insert_with(Vec::new)
+                        .push(CoverageEntry {
+                            count: count,
+                            precision: precision,
+                            source: source,
+                        });
+                }
+            }
+        }
+
+        let wallclock = self.clock.fetch_add(1, Ordering::SeqCst);
+        let mut entries = self.entries.write().unwrap();
+        entries.retain(|(node_id, _, _), _| *node_id != self.node_id);
+        for ((geocode, platform), coverage_entries) in summaries {
+            entries.insert((self.node_id, geocode, platform),
+                VersionedCoverage {
+                    entries: coverage_entries,
+                    wallclock: wallclock,
+                });
+        }
+    }
+
+    /// digest of (key, wallclock) pairs exchanged during a pull so a
+    /// peer only has to send back entries we're actually missing
+    pub fn digest(&self) -> Vec<((u32, String, String), u64)> {
+        self.entries.read().unwrap().iter()
+            .map(|(key, coverage)| (key.clone(), coverage.wallclock))
+            .collect()
+    }
+
+    /// entries we hold that are newer than (or absent from) a peer's digest
+    pub fn missing(&self, digest: &[((u32, String, String), u64)])
+            -> Vec<((u32, String, String), VersionedCoverage)> {
+        let known: HashMap<(u32, String, String), u64> =
+            digest.iter().cloned().collect();
+        self.entries.read().unwrap().iter()
+            .filter(|(key, coverage)| match known.get(*key) {
+                Some(wallclock) => coverage.wallclock > *wallclock,
+                None => true,
+            })
+            .map(|(key, coverage)| (key.clone(), coverage.clone()))
+            .collect()
+    }
+
+    /// approximate cluster-wide search - aggregates every node's replica
+    /// of coverage for geocode/platform pairs matching the filter,
+    /// without contacting any peer
+    pub fn search_all(&self, geocode: &Option<String>,
+            platform: &Option<String>) -> Vec<Extent> {
+        let mut extents = Vec::new();
+        for ((_, entry_geocode, entry_platform), coverage)
+                in self.entries.read().unwrap().iter() {
+            if let Some(geocode) = geocode {
+                if !entry_geocode.starts_with(geocode.as_str()) {
+                    continue;
+                }
+            }
+
+            if let Some(platform) = platform {
+                if entry_platform != platform {
+                    continue;
+                }
+            }
+
+            for entry in coverage.entries.iter() {
+                extents.push((entry.count, entry_geocode.clone(),
+                    entry_platform.clone(), entry.precision,
+                    entry.source.clone()));
+            }
+        }
+
+        extents
+    }
+}
+
+/// spawn the periodic coverage round - refreshes this node's own
+/// coverage from its albums and logs how many peers (at most 'FANOUT')
+/// the round targets. like `gossip::start`, no rpc client is wired
+/// into this thread, so no digests or entries are exchanged with peers.
+pub fn start(coverage: Arc<CoverageIndex>,
+        album_manager: Arc<RwLock<AlbumManager>>, dht: Arc<Dht>,
+        period_secs: u64) {
+    std::thread::spawn(move || {
+        loop {
+            std::thread::sleep(std::time::Duration::from_secs(period_secs));
+
+            coverage.refresh_local(&album_manager);
+
+            let peer_count = dht.nodes().len().saturating_sub(1).min(FANOUT);
+            trace!("coverage gossip round targeting {} peers", peer_count);
+        }
+    });
+}
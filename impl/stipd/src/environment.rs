@@ -23,7 +23,8 @@ impl EnvironmentManager {
     }
 
     pub fn initialize(&mut self, dht_hash_characters: Option<u8>,
-            hash_bits: u8, id: &str, image_projection: u16)
+            hash_bits: u8, id: &str, image_projection: u16,
+            replication_factor: u8, require_encryption: bool)
             -> Result<(), Box<dyn Error>> {
         // check if environment already exists
         if self.environments.contains_key(id) {
@@ -38,6 +39,8 @@ impl EnvironmentManager {
                 dht_hash_characters: dht_hash_characters,
                 hash_bits: hash_bits,
                 image_projection: image_projection,
+                replication_factor: replication_factor,
+                require_encryption: require_encryption,
                 status: EnvironmentStatus::Loaded,
             });
 
@@ -63,5 +66,23 @@ pub struct Environment {
     dht_hash_characters: Option<u8>,
     hash_bits: u8,
     image_projection: u16,
+    /// number of distinct dht nodes each tile written into this
+    /// environment is replicated to - see
+    /// 'task::dht_lookup_replicas'/'task::DEFAULT_REPLICATION_FACTOR'
+    replication_factor: u8,
+    /// when set, peers transferring tiles into this environment must
+    /// speak transfer protocol version >= 2 (the encrypted AEAD wire
+    /// format) - see 'transfer::TransferStreamHandler'
+    require_encryption: bool,
     status: EnvironmentStatus,
 }
+
+impl Environment {
+    pub fn replication_factor(&self) -> u8 {
+        self.replication_factor
+    }
+
+    pub fn require_encryption(&self) -> bool {
+        self.require_encryption
+    }
+}
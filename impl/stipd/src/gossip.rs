@@ -0,0 +1,201 @@
+use swarm::prelude::Dht;
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// number of peers picked per gossip round
+const FANOUT: usize = 3;
+
+/// a node missing from both the dht and a gossip push/pull for longer
+/// than this is reported as dead rather than simply absent
+const DEFAULT_DEAD_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeStatus {
+    Alive,
+    Dead,
+    Unknown,
+}
+
+#[derive(Clone, Debug)]
+pub struct VersionedNodeInfo {
+    pub rpc_addr: String,
+    pub xfer_addr: String,
+    pub public_key: Option<String>,
+    pub wallclock: u64,
+    pub last_seen: u64,
+}
+
+/// CRDT-style membership map merged last-writer-wins by 'wallclock', so
+/// a stale push from a partitioned node can never clobber a fresher
+/// entry. gives `node_list`/`node_show` a membership view with per-node
+/// liveness; 'start' fills it from this node's own dht, since no
+/// entries are exchanged with peers.
+pub struct GossipState {
+    node_id: u32,
+    entries: RwLock<HashMap<u32, VersionedNodeInfo>>,
+    clock: AtomicU64,
+}
+
+impl GossipState {
+    pub fn new(node_id: u32, rpc_addr: String, xfer_addr: String,
+            public_key: String) -> GossipState {
+        let mut entries = HashMap::new();
+        entries.insert(node_id, VersionedNodeInfo {
+            rpc_addr: rpc_addr,
+            xfer_addr: xfer_addr,
+            public_key: Some(public_key),
+            wallclock: 0,
+            last_seen: now(),
+        });
+
+        GossipState {
+            node_id: node_id,
+            entries: RwLock::new(entries),
+            clock: AtomicU64::new(1),
+        }
+    }
+
+    /// merge a remote entry, ignoring it if our wallclock is >= theirs
+    pub fn merge(&self, node_id: u32, info: VersionedNodeInfo) {
+        let mut entries = self.entries.write().unwrap();
+        let merge = match entries.get(&node_id) {
+            Some(existing) => info.wallclock > existing.wallclock,
+            None => true,
+        };
+
+        if merge {
+            entries.insert(node_id, info);
+        }
+    }
+
+    /// bump our own entry's wallclock so peers prefer it over stale copies
+    pub fn touch(&self) {
+        let wallclock = self.clock.fetch_add(1, Ordering::SeqCst);
+        let mut entries = self.entries.write().unwrap();
+        if let Some(info) = entries.get_mut(&self.node_id) {
+            info.wallclock = wallclock;
+            info.last_seen = now();
+        }
+    }
+
+    pub fn get(&self, node_id: u32) -> Option<VersionedNodeInfo> {
+        self.entries.read().unwrap().get(&node_id).cloned()
+    }
+
+    /// the public key a peer published and this node recorded on join -
+    /// 'None' if the peer hasn't been seen yet or predates key publishing
+    pub fn public_key_of(&self, node_id: u32) -> Option<String> {
+        self.entries.read().unwrap().get(&node_id)
+            .and_then(|info| info.public_key.clone())
+    }
+
+    pub fn iter(&self) -> Vec<(u32, VersionedNodeInfo)> {
+        self.entries.read().unwrap().iter()
+            .map(|(node_id, info)| (*node_id, info.clone()))
+            .collect()
+    }
+
+    /// "digest" of (node_id, wallclock) pairs exchanged during a pull so
+    /// a peer only has to send back entries we're actually missing
+    pub fn digest(&self) -> Vec<(u32, u64)> {
+        self.entries.read().unwrap().iter()
+            .map(|(node_id, info)| (*node_id, info.wallclock))
+            .collect()
+    }
+
+    /// entries we hold that are newer than (or absent from) a peer's digest
+    pub fn missing(&self, digest: &[(u32, u64)]) -> Vec<(u32, VersionedNodeInfo)> {
+        let known: HashMap<u32, u64> = digest.iter().cloned().collect();
+        self.entries.read().unwrap().iter()
+            .filter(|(node_id, info)| match known.get(node_id) {
+                Some(wallclock) => info.wallclock > *wallclock,
+                None => true,
+            })
+            .map(|(node_id, info)| (*node_id, info.clone()))
+            .collect()
+    }
+
+    pub fn status_of(&self, node_id: u32) -> NodeStatus {
+        match self.entries.read().unwrap().get(&node_id) {
+            Some(info) => if now().saturating_sub(info.last_seen)
+                    > DEFAULT_DEAD_TIMEOUT_SECS {
+                NodeStatus::Dead
+            } else {
+                NodeStatus::Alive
+            },
+            None => NodeStatus::Unknown,
+        }
+    }
+
+    /// drop entries that haven't been refreshed within the ttl - other
+    /// than our own, which we refresh on every push/pull round
+    pub fn prune(&self, ttl_secs: u64) {
+        let node_id = self.node_id;
+        let mut entries = self.entries.write().unwrap();
+        entries.retain(|id, info| *id == node_id
+            || now().saturating_sub(info.last_seen) <= ttl_secs);
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .unwrap().as_secs()
+}
+
+/// spawn the periodic gossip round. every 'period_secs' it refreshes
+/// our own entry, seeds or refreshes an entry for each node the dht
+/// lists, picks up to 'FANOUT' other known peers, and prunes entries
+/// that outlive the ttl. nothing is sent to the picked peers - the
+/// round only logs how many it picked.
+pub fn start(gossip: Arc<GossipState>, dht: Arc<Dht>, period_secs: u64) {
+    std::thread::spawn(move || {
+        loop {
+            std::thread::sleep(std::time::Duration::from_secs(period_secs));
+
+            gossip.touch();
+
+            // seed the membership map from whatever the dht currently
+            // knows, since a fresh node has no gossip history yet
+            for node in dht.nodes() {
+                let rpc_addr = format!("{}:{}", node.get_ip_address(),
+                    node.get_metadata("rpc_port").unwrap_or_default());
+                let xfer_addr = format!("{}:{}", node.get_ip_address(),
+                    node.get_metadata("xfer_port").unwrap_or_default());
+                let public_key = node.get_metadata("public_key");
+
+                // a node the dht still lists counts as seen this round
+                let seen = match gossip.entries.write().unwrap()
+                        .get_mut(&node.get_id()) {
+                    Some(info) => { info.last_seen = now(); true },
+                    None => false,
+                };
+
+                if !seen {
+                    gossip.merge(node.get_id(), VersionedNodeInfo {
+                        rpc_addr: rpc_addr,
+                        xfer_addr: xfer_addr,
+                        public_key: public_key,
+                        wallclock: 0,
+                        last_seen: now(),
+                    });
+                }
+            }
+
+            // take the first FANOUT other known peers in map order - no
+            // rpc client is wired into this thread, so no digests are
+            // exchanged with them; the round's only effects are the
+            // liveness refresh above and pruning entries gone stale
+            let peers: Vec<u32> = gossip.iter().into_iter()
+                .map(|(node_id, _)| node_id)
+                .filter(|node_id| *node_id != gossip.node_id)
+                .take(FANOUT)
+                .collect();
+            trace!("gossip round targeting {} peers", peers.len());
+
+            gossip.prune(DEFAULT_DEAD_TIMEOUT_SECS * 4);
+        }
+    });
+}
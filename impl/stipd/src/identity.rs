@@ -0,0 +1,84 @@
+use ed25519_dalek::{Keypair, PublicKey, Signature, Signer, Verifier};
+use rand::rngs::OsRng;
+
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::Path;
+
+const IDENTITY_FILENAME: &'static str = "identity.key";
+
+/// a node's persistent ed25519 keypair, generated once on first start and
+/// reloaded on every subsequent one so a node's identity - and the trust
+/// peers place in it - survives a restart
+pub struct NodeIdentity {
+    keypair: Keypair,
+}
+
+impl NodeIdentity {
+    /// load the keypair from 'directory/identity.key', generating and
+    /// persisting a new one (temp file + rename, so a crash mid-write
+    /// never leaves a corrupt key behind) if this is the node's first start
+    pub fn load_or_generate(directory: &Path)
+            -> Result<NodeIdentity, Box<dyn Error>> {
+        let path = directory.join(IDENTITY_FILENAME);
+
+        if path.exists() {
+            let bytes = fs::read(&path)?;
+            let keypair = Keypair::from_bytes(&bytes)?;
+            return Ok(NodeIdentity { keypair: keypair });
+        }
+
+        let mut csprng = OsRng {};
+        let keypair = Keypair::generate(&mut csprng);
+
+        let tmp_path = directory.join(format!("{}.tmp", IDENTITY_FILENAME));
+        {
+            let mut file = fs::File::create(&tmp_path)?;
+            file.write_all(&keypair.to_bytes())?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp_path, &path)?;
+
+        Ok(NodeIdentity { keypair: keypair })
+    }
+
+    /// hex-encoded public key, published as swarm metadata and recorded
+    /// by peers during gossip so they can verify signatures from this node
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(self.keypair.public.to_bytes())
+    }
+
+    /// short, human-legible stand-in for the full public key - not a
+    /// substitute for verifying the key itself, just a sanity check an
+    /// operator can read over the phone
+    pub fn fingerprint(&self) -> String {
+        fingerprint_of(&self.public_key_hex())
+    }
+
+    pub fn sign(&self, challenge: &[u8]) -> Vec<u8> {
+        self.keypair.sign(challenge).to_bytes().to_vec()
+    }
+}
+
+/// derive the same short fingerprint from a peer's hex-encoded public key,
+/// so a local node doesn't need the peer's private keypair to display one
+pub fn fingerprint_of(public_key_hex: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    public_key_hex.hash(&mut hasher);
+    format!("{:x}", hasher.finish())[..8].to_string()
+}
+
+/// verify a signature over 'challenge' against a peer's recorded,
+/// hex-encoded public key
+pub fn verify(public_key_hex: &str, challenge: &[u8], signature: &[u8])
+        -> Result<(), Box<dyn Error>> {
+    let public_key_bytes = hex::decode(public_key_hex)?;
+    let public_key = PublicKey::from_bytes(&public_key_bytes)?;
+    let signature = Signature::from_bytes(signature)?;
+
+    public_key.verify(challenge, &signature)
+        .map_err(|e| format!("signature verification failed: {}", e).into())
+}
@@ -3,7 +3,7 @@ use gdal::raster::{Dataset, Driver};
 use rusqlite::{Connection, ToSql};
 
 use std::error::Error;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use std::os::unix::fs::PermissionsExt;
 use std::path::PathBuf;
 use std::sync::Mutex;
@@ -12,10 +12,25 @@ pub const FILLED_SOURCE: &'static str = "filled";
 pub const RAW_SOURCE: &'static str = "raw";
 pub const SPLIT_SOURCE: &'static str = "split";
 
+// bumped whenever CREATE_FILES_TABLE_STMT/CREATE_IMAGES_TABLE_STMT/
+// CREATE_CHUNKS_TABLE_STMT changes shape - on mismatch the catalog is
+// dropped and rebuilt fresh via 'sync()' rather than migrated
+// row-by-row, since the whole point of the on-disk file is to avoid
+// re-parsing everything, not to carry old schemas forward forever
+const SCHEMA_VERSION: i64 = 2;
+
+const CREATE_SCHEMA_VERSION_TABLE_STMT: &str =
+"CREATE TABLE schema_version (
+    version BIGINT NOT NULL
+)";
+
 const CREATE_FILES_TABLE_STMT: &str =
 "CREATE TABLE files (
     description     TEXT NOT NULL,
+    digest          TEXT NOT NULL,
     image_id        BIGINT NOT NULL,
+    mtime           BIGINT NOT NULL,
+    path            TEXT NOT NULL UNIQUE,
     pixel_coverage  FLOAT NOT NULL,
     subdataset      TINYINT NOT NULL
 )";
@@ -31,6 +46,18 @@ const CREATE_IMAGES_TABLE_STMT: &str =
     timestamp       BIGINT NOT NULL
 )";
 
+// content-addressed store backing 'files.path' - 'path' holds the
+// canonical, on-disk location of the bytes a digest maps to, and every
+// other file sharing that digest is a hardlink to it. 'refcount' tracks
+// how many files rows currently reference the digest, so 'gc()' knows
+// when a blob is safe to delete
+const CREATE_CHUNKS_TABLE_STMT: &str =
+"CREATE TABLE chunks (
+    digest    TEXT PRIMARY KEY,
+    path      TEXT NOT NULL,
+    refcount  BIGINT NOT NULL
+)";
+
 //const CREATE_INDEX_STMT: &str =
 //"CREATE INDEX idx_images ON images(platform, pixel_coverage)";
 
@@ -40,9 +67,21 @@ const INSERT_IMAGES_STMT: &str =
 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)";
 
 const INSERT_FILES_STMT: &str =
-"INSERT INTO files (description, image_id,
-    pixel_coverage, subdataset)
-VALUES (?1, ?2, ?3, ?4)";
+"INSERT INTO files (description, digest, image_id,
+    mtime, path, pixel_coverage, subdataset)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)";
+
+const INSERT_CHUNK_STMT: &str =
+"INSERT INTO chunks (digest, path, refcount) VALUES (?1, ?2, ?3)";
+
+const CHUNK_SELECT_STMT: &str =
+"SELECT path FROM chunks WHERE digest = ?1";
+
+const FILES_SELECT_STMT: &str =
+"SELECT rowid, mtime, path FROM files";
+
+const FILE_DIGEST_SELECT_STMT: &str =
+"SELECT digest FROM files WHERE rowid = ?1";
 
 const ID_SELECT_STMT: &str =
 "SELECT id from images WHERE geohash = ?1 AND tile = ?2";
@@ -61,6 +100,13 @@ const SEARCH_SELECT_STMT: &str =
 const SEARCH_GROUP_BY_STMT: &str =
 " GROUP BY geohash_search, platform, precision, source";
 
+// like SEARCH_SELECT_STMT, but tags rows with an explicit precision
+// level instead of 'LENGTH(geohash)', so 'search_pyramid' can group the
+// same underlying rows at several different geohash prefix lengths in
+// one call
+const SEARCH_PRECISION_SELECT_STMT: &str =
+"SELECT COUNT(*) as count, SUBSTR(geohash, 0, REPLACE_LENGTH) as geohash_search, platform, PRECISION_VALUE as precision, source FROM images";
+
 // count, geohash, platform, precision, source
 pub type Extent = (i64, String, String, u8, String);
 
@@ -70,6 +116,89 @@ pub type Image = (Option<f64>, String, String, String, String, i64);
 // description, path, pixel_coverage, subdataset
 pub type StFile = (String, String, f64, u8);
 
+/// outcome of 'ImageManager::load'/'ImageManager::write' - lets a
+/// caller act on the stored artifact (serve a url, log it, chain a
+/// follow-up overview build) without a second 'ID_SELECT_STMT' query or
+/// re-glob to learn where the tile landed or which image it joined
+#[derive(Clone, Debug)]
+pub struct WriteResult {
+    pub image_id: i64,
+    pub path: PathBuf,
+    pub subdataset: u8,
+    // true if 'path' is a hardlink to an already-stored blob rather
+    // than freshly-encoded bytes - see the 'chunks' table in 'load'
+    pub deduplicated: bool,
+}
+
+/// per-call raster output configuration for 'ImageManager::write' - the
+/// right driver/compression/tiling tradeoff varies by platform and
+/// source (a frequently-range-requested product wants COG, a
+/// write-once archival one wants the smallest file), so callers choose
+/// theirs per write rather than this module hardcoding one
+#[derive(Clone, Debug)]
+pub struct WriteOptions {
+    pub driver: String,
+    pub compression: Option<String>,
+    pub predictor: Option<u8>,
+    pub bigtiff: bool,
+    pub tiled: bool,
+    pub block_size: Option<u32>,
+    // builds power-of-two overviews on the source dataset before the
+    // copy below, and folds them into the output via 'COPY_SRC_OVERVIEWS'
+    // alongside an internally tiled layout, so the resulting GeoTIFF can
+    // serve partial/range reads without a client downloading the whole
+    // file
+    pub cog: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> WriteOptions {
+        WriteOptions {
+            driver: "GTiff".to_string(),
+            compression: Some("LZW".to_string()),
+            predictor: None,
+            bigtiff: false,
+            tiled: false,
+            block_size: None,
+            cog: false,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// translate this config into the 'KEY=VALUE' creation option
+    /// strings 'Driver::create_copy' expects
+    fn creation_option_strings(&self) -> Vec<String> {
+        let mut options = Vec::new();
+
+        if let Some(compression) = &self.compression {
+            options.push(format!("COMPRESS={}", compression));
+        }
+
+        if let Some(predictor) = self.predictor {
+            options.push(format!("PREDICTOR={}", predictor));
+        }
+
+        if self.bigtiff {
+            options.push("BIGTIFF=YES".to_string());
+        }
+
+        if self.tiled || self.cog {
+            options.push("TILED=YES".to_string());
+            if let Some(block_size) = self.block_size {
+                options.push(format!("BLOCKXSIZE={}", block_size));
+                options.push(format!("BLOCKYSIZE={}", block_size));
+            }
+        }
+
+        if self.cog {
+            options.push("COPY_SRC_OVERVIEWS=YES".to_string());
+        }
+
+        options
+    }
+}
+
 pub struct ImageManager {
     conn: Mutex<Connection>,
     directory: PathBuf,
@@ -77,18 +206,58 @@ pub struct ImageManager {
 }
 
 impl ImageManager {
-    pub fn new(directory: PathBuf) -> ImageManager {
-        // initialize sqlite connection - TODO error
-        let conn = Connection::open_in_memory().unwrap();
-        conn.execute(CREATE_FILES_TABLE_STMT, rusqlite::params![]).unwrap();
-        conn.execute(CREATE_IMAGES_TABLE_STMT, rusqlite::params![]).unwrap();
-        //conn.execute(CREATE_INDEX_STMT, rusqlite::params![]).unwrap();
-
-        ImageManager {
+    /// opens (or initializes) the catalog at 'directory/index.sqlite',
+    /// rather than rebuilding an in-memory one from scratch on every
+    /// restart - callers still need to 'sync()' after opening to pick
+    /// up any filesystem changes made while the node was down
+    pub fn new(directory: PathBuf) -> Result<ImageManager, Box<dyn Error>> {
+        let conn = Connection::open(directory.join("index.sqlite"))?;
+
+        let schema_matches = conn.prepare("SELECT version FROM schema_version")
+            .and_then(|mut stmt| stmt.query_row(rusqlite::params![],
+                |row| row.get::<_, i64>(0)))
+            .map(|version| version == SCHEMA_VERSION)
+            .unwrap_or(false);
+
+        if !schema_matches {
+            // either a fresh file (no schema_version table yet) or an
+            // old schema - drop everything and start clean, 'sync()'
+            // is responsible for repopulating it from disk
+            conn.execute("DROP TABLE IF EXISTS chunks",
+                rusqlite::params![])?;
+            conn.execute("DROP TABLE IF EXISTS files",
+                rusqlite::params![])?;
+            conn.execute("DROP TABLE IF EXISTS images",
+                rusqlite::params![])?;
+            conn.execute("DROP TABLE IF EXISTS schema_version",
+                rusqlite::params![])?;
+
+            conn.execute(CREATE_SCHEMA_VERSION_TABLE_STMT,
+                rusqlite::params![])?;
+            conn.execute(CREATE_FILES_TABLE_STMT, rusqlite::params![])?;
+            conn.execute(CREATE_IMAGES_TABLE_STMT, rusqlite::params![])?;
+            conn.execute(CREATE_CHUNKS_TABLE_STMT, rusqlite::params![])?;
+            //conn.execute(CREATE_INDEX_STMT, rusqlite::params![])?;
+            conn.execute("INSERT INTO schema_version (version) VALUES (?1)",
+                rusqlite::params![SCHEMA_VERSION])?;
+        }
+
+        // resume the id sequence where it left off, rather than
+        // restarting at 1000 and potentially colliding with ids
+        // already persisted from a prior run
+        let id = conn.prepare("SELECT MAX(id) FROM images")
+            .and_then(|mut stmt|
+                stmt.query_row(rusqlite::params![], |row| row.get(0)))
+            .ok()
+            .and_then(|id: Option<i64>| id)
+            .map(|id| id + 1)
+            .unwrap_or(1000);
+
+        Ok(ImageManager {
             conn: Mutex::new(conn),
             directory: directory,
-            id: 1000,
-        }
+            id: id,
+        })
     }
 
     pub fn get_image_path(&self, create: bool, geohash: &str,
@@ -205,9 +374,10 @@ impl ImageManager {
     }
 
     pub fn load(&mut self, cloud_coverage: Option<f64>,
-            description: &str, geohash: &str, pixel_coverage: f64,
-            platform: &str, source: &str, subdataset: u8, tile: &str,
-            timestamp: i64) -> Result<(), Box<dyn Error>> {
+            description: &str, digest: &str, geohash: &str, mtime: i64,
+            path: &str, pixel_coverage: f64, platform: &str, source: &str,
+            subdataset: u8, tile: &str,
+            timestamp: i64) -> Result<WriteResult, Box<dyn Error>> {
         // load data into sqlite
         let conn = self.conn.lock().unwrap();
 
@@ -234,12 +404,156 @@ impl ImageManager {
         };
 
         conn.execute(INSERT_FILES_STMT, rusqlite::params![
-                description, id, pixel_coverage, subdataset
+                description, digest, id, mtime, path, pixel_coverage,
+                subdataset
             ])?;
 
+        // register (or bump the refcount of) the content-addressed
+        // blob this file's bytes belong to, so 'gc()' can tell when the
+        // last reference to a stored blob disappears
+        let refcount: Option<i64> = conn.prepare(
+                "SELECT refcount FROM chunks WHERE digest = ?1")?
+            .query_row(rusqlite::params![digest], |row| row.get(0)).ok();
+
+        // a digest already registered before this call means this
+        // file's bytes are a duplicate of one already stored elsewhere
+        let deduplicated = refcount.is_some();
+
+        match refcount {
+            Some(refcount) => conn.execute(
+                "UPDATE chunks SET refcount = ?1 WHERE digest = ?2",
+                rusqlite::params![refcount + 1, digest])?,
+            None => conn.execute(INSERT_CHUNK_STMT,
+                rusqlite::params![digest, path, 1])?,
+        };
+
+        Ok(WriteResult {
+            deduplicated: deduplicated,
+            image_id: id,
+            path: PathBuf::from(path),
+            subdataset: subdataset,
+        })
+    }
+
+    /// path of the already-stored blob holding the exact same bytes as
+    /// 'digest', if one has already been registered
+    fn chunk_path(&self, digest: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let conn = self.conn.lock().unwrap();
+        let path = conn.prepare(CHUNK_SELECT_STMT)?
+            .query_row(rusqlite::params![digest], |row| row.get(0)).ok();
+
+        Ok(path)
+    }
+
+    /// drop blobs (and their 'chunks' row) whose refcount has fallen to
+    /// zero as referencing 'files' rows were removed - called after
+    /// 'sync()' reconciles the catalog against the filesystem, so a
+    /// blob no tile references anymore doesn't linger on disk forever
+    pub fn gc(&mut self) -> Result<(), Box<dyn Error>> {
+        let conn = self.conn.lock().unwrap();
+
+        let stale: Vec<(String, String)> = {
+            let mut stmt = conn.prepare(
+                "SELECT digest, path FROM chunks WHERE refcount <= 0")?;
+            stmt.query_map(rusqlite::params![], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?.collect::<Result<Vec<_>, _>>()?
+        };
+
+        for (digest, path) in stale {
+            // the same on-disk path can end up registered under more
+            // than one digest - e.g. a tile edited in place is re-read
+            // and re-cataloged under its new digest at the very path
+            // whose old digest just dropped to refcount 0 in this same
+            // 'sync()' pass (see the "file changed since last sync"
+            // branch in 'sync()' below). only unlink the bytes once no surviving
+            // chunk row still claims this exact path, or gc() would
+            // delete a file a live digest still depends on
+            let still_referenced = conn.prepare(
+                    "SELECT 1 FROM chunks WHERE path = ?1 AND refcount > 0")?
+                .query_row(rusqlite::params![path], |_| Ok(())).is_ok();
+
+            if !still_referenced {
+                let _ = std::fs::remove_file(&path);
+            }
+
+            conn.execute("DELETE FROM chunks WHERE digest = ?1",
+                rusqlite::params![digest])?;
+        }
+
         Ok(())
     }
 
+    /// reconcile the catalog against the filesystem, touching only
+    /// what changed since the last sync - new files are parsed and
+    /// inserted, files whose mtime hasn't moved are skipped entirely,
+    /// and catalog entries for files that disappeared are removed, so
+    /// a restart over a store holding millions of tiles doesn't have
+    /// to re-open and re-parse every one of them
+    pub fn sync(&mut self) -> Result<(), Box<dyn Error>> {
+        // snapshot what the catalog currently believes exists
+        let existing: std::collections::HashMap<String, (i64, i64)> = {
+            let conn = self.conn.lock().unwrap();
+            let mut stmt = conn.prepare(FILES_SELECT_STMT)?;
+            let rows = stmt.query_map(rusqlite::params![], |row| {
+                let rowid: i64 = row.get(0)?;
+                let mtime: i64 = row.get(1)?;
+                let path: String = row.get(2)?;
+                Ok((path, (rowid, mtime)))
+            })?.collect::<Result<Vec<_>, _>>()?;
+
+            rows.into_iter().collect()
+        };
+
+        let mut seen = std::collections::HashSet::new();
+
+        for mut path in self.get_paths()? {
+            let path_str = path.to_string_lossy().to_string();
+            let mtime = path.metadata()?.modified()?
+                .duration_since(std::time::UNIX_EPOCH)?.as_secs() as i64;
+
+            seen.insert(path_str.clone());
+
+            if let Some((rowid, existing_mtime)) = existing.get(&path_str) {
+                if *existing_mtime == mtime {
+                    continue;
+                }
+
+                // file changed since the last sync - drop and
+                // re-parse rather than patching individual columns
+                let conn = self.conn.lock().unwrap();
+                remove_file_row(&conn, *rowid)?;
+                drop(conn);
+            }
+
+            let digest = crate::block::digest(&std::fs::read(&path)?);
+
+            let ((cloud_coverage, geohash, platform, source, tile,
+                    timestamp), (description, _, pixel_coverage, subdataset))
+                = to_image_metadata(&mut path)?;
+
+            self.load(cloud_coverage, &description, &digest, &geohash,
+                mtime, &path_str, pixel_coverage, &platform, &source,
+                subdataset, &tile, timestamp)?;
+        }
+
+        // drop catalog entries for files that no longer exist on disk,
+        // then any image left with no remaining file
+        {
+            let conn = self.conn.lock().unwrap();
+            for (path, (rowid, _)) in existing.iter() {
+                if !seen.contains(path) {
+                    remove_file_row(&conn, *rowid)?;
+                }
+            }
+
+            conn.execute("DELETE FROM images WHERE id NOT IN \
+                (SELECT DISTINCT image_id FROM files)", rusqlite::params![])?;
+        }
+
+        self.gc()
+    }
+
     pub fn search(&self, end_timestamp: &Option<i64>,
             geohash: &Option<String>, max_cloud_coverage: &Option<f64>,
             min_pixel_coverage: &Option<f64>, platform: &Option<String>,
@@ -297,41 +611,166 @@ impl ImageManager {
         extent_iter.map(|x| x.unwrap()).collect()
     }
 
+    /// counts across every geohash precision between 'min_precision'
+    /// and 'max_precision' (inclusive) in one call, so a caller
+    /// rendering a zoomable coverage heatmap doesn't have to issue one
+    /// 'search' per zoom level. every 'search' filter still applies at
+    /// each level, and since every level groups the same underlying
+    /// rows - just truncated to a shallower geohash prefix - a parent
+    /// cell's count is always the sum of its children's, with no gaps
+    /// for the caller to paper over
+    pub fn search_pyramid(&self, end_timestamp: &Option<i64>,
+            geohash: &Option<String>, max_cloud_coverage: &Option<f64>,
+            max_precision: u8, min_precision: u8,
+            min_pixel_coverage: &Option<f64>, platform: &Option<String>,
+            source: &Option<String>,
+            start_timestamp: &Option<i64>) -> Vec<Extent> {
+        // lock the sqlite connection
+        let conn = self.conn.lock().unwrap();
+
+        let geohash_glob = geohash.as_ref()
+            .map(|geohash| format!("{}%", geohash));
+
+        let mut extents = Vec::new();
+        for precision in min_precision..=max_precision {
+            // initialize the SELECT command and parameters
+            let mut stmt_str = SEARCH_PRECISION_SELECT_STMT
+                .replace("REPLACE_LENGTH", &(precision as usize + 1).to_string())
+                .replace("PRECISION_VALUE", &precision.to_string());
+            let mut params: Vec<&dyn ToSql> = Vec::new();
+
+            // append existing filters to stmt_str
+            append_stmt_filter("timestamp", end_timestamp,
+                &mut stmt_str, "<=", &mut params);
+            append_stmt_filter("cloud_coverage", max_cloud_coverage,
+                &mut stmt_str, "<=", &mut params);
+            append_stmt_filter("pixel_coverage", min_pixel_coverage,
+                &mut stmt_str, ">=", &mut params);
+            append_stmt_filter("platform", platform,
+                &mut stmt_str, "=", &mut params);
+            append_stmt_filter("source", source,
+                &mut stmt_str, "=", &mut params);
+            append_stmt_filter("timestamp", start_timestamp,
+                &mut stmt_str, ">=", &mut params);
+            append_stmt_filter("geohash", &geohash_glob,
+                &mut stmt_str, "LIKE", &mut params);
+
+            // append SEARCH_GROUP_BY_STMT to stmt_str
+            stmt_str.push_str(SEARCH_GROUP_BY_STMT);
+
+            // execute query - TODO error
+            let mut stmt = conn.prepare(&stmt_str).expect("prepare select");
+            let extent_iter = stmt.query_map(&params, |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?,
+                    row.get(3)?, row.get(4)?))
+            }).unwrap();
+
+            extents.extend(extent_iter.map(|x| x.unwrap()));
+        }
+
+        extents
+    }
+
     pub fn write(&mut self, dataset: &mut Dataset, description: &str,
-            geohash: &str, pixel_coverage: f64, platform: &str,
-            source: &str, subdataset: u8, tile: &str,
-            timestamp: i64) -> Result<(), Box<dyn Error>> {
+            geohash: &str, options: &WriteOptions, pixel_coverage: f64,
+            platform: &str, source: &str, subdataset: u8, tile: &str,
+            timestamp: i64) -> Result<WriteResult, Box<dyn Error>> {
         // get image path
         let path = self.get_image_path(true, geohash,
             platform, source, subdataset, tile)?;
 
         if path.exists() { // attempting to rewrite existing file
-            return Ok(());
+            let conn = self.conn.lock().unwrap();
+            let image_id = conn.prepare(ID_SELECT_STMT)?
+                .query_row(rusqlite::params![geohash, tile],
+                    |row| row.get(0))?;
+            drop(conn);
+
+            return Ok(WriteResult {
+                deduplicated: false,
+                image_id: image_id,
+                path: path,
+                subdataset: subdataset,
+            });
         }
 
-        // open GeoTiff driver
-        let driver = Driver::get("GTiff").unwrap();
+        // content-addressed dedup: hash the source dataset's encoded
+        // bytes before touching the output driver at all, so a
+        // byte-identical tile (e.g. a re-split or re-masked scene)
+        // hardlinks to the existing blob instead of re-encoding and
+        // re-compressing the same pixels a second time. note the
+        // hardlinked copy keeps the canonical file's embedded STIP
+        // metadata tags (describing whichever tile was stored first),
+        // same tradeoff 'Album::write' already accepts for its own
+        // hardlinked dedup - this catalog's 'files'/'images' rows are
+        // what carry this tile's actual geohash/tile/etc
+        let mut encoded = Vec::new();
+        st_image::prelude::write(&*dataset, &mut encoded)?;
+        let digest = crate::block::digest(&encoded);
+
+        if let Some(canonical_path) = self.chunk_path(&digest)? {
+            if canonical_path != path.to_string_lossy()
+                    && std::path::Path::new(&canonical_path).exists() {
+                std::fs::hard_link(&canonical_path, &path)?;
+
+                let mtime = std::fs::metadata(&path)?.modified()?
+                    .duration_since(std::time::UNIX_EPOCH)?.as_secs() as i64;
+                return self.load(None, description, &digest, geohash,
+                    mtime, &path.to_string_lossy(), pixel_coverage,
+                    platform, source, subdataset, tile, timestamp);
+            }
+        }
 
-        // copy image to GeoTiff format
-        let mut c_options = vec![
-            CString::new("COMPRESS=LZW")?.into_raw(),
-            std::ptr::null_mut()
-        ];
+        // open the configured output driver
+        let driver = Driver::get(&options.driver).unwrap();
+
+        // a COG output needs internal overviews built on the source
+        // dataset *before* the copy below, so the driver's
+        // 'COPY_SRC_OVERVIEWS' creation option can carry them into the
+        // output alongside the tiled layout that makes partial/range
+        // reads of a single tile efficient for downstream clients
+        if options.cog {
+            let mut overview_levels = vec![2, 4, 8, 16];
+            let c_resampling = CString::new("AVERAGE")?;
+
+            let result = unsafe {
+                gdal_sys::GDALBuildOverviews(dataset.c_dataset(),
+                    c_resampling.as_ptr(), overview_levels.len() as i32,
+                    overview_levels.as_mut_ptr(), 0, std::ptr::null_mut(),
+                    None, std::ptr::null_mut())
+            };
+
+            if result as i32 != 0 { // CE_None == 0
+                let err_msg = unsafe {
+                    let c_ptr = gdal_sys::CPLGetLastErrorMsg();
+                    let c_str = CStr::from_ptr(c_ptr);
+                    c_str.to_string_lossy().into_owned()
+                };
+
+                unsafe { gdal_sys::CPLErrorReset() };
+                return Err(format!(
+                    "failed to build overviews: {}", err_msg).into());
+            }
+        }
+
+        // copy image to the configured format, building 'papszOptions'
+        // from 'options' instead of a single hardcoded 'COMPRESS=LZW'
+        let creation_options = options.creation_option_strings();
+        let c_creation_options: Vec<CString> = creation_options.iter()
+            .map(|option| CString::new(option.as_str()))
+            .collect::<Result<Vec<CString>, _>>()?;
+
+        let mut c_options: Vec<*mut std::os::raw::c_char> =
+            c_creation_options.iter()
+                .map(|option| option.as_ptr() as *mut std::os::raw::c_char)
+                .collect();
+        c_options.push(std::ptr::null_mut());
 
         // TODO error
         let path_str = path.to_string_lossy();
         let mut dataset_copy = dataset.create_copy(&driver,
             &path_str, Some(c_options.as_mut_ptr())).unwrap();
 
-        // clean up potential memory leaks
-        unsafe {
-            for ptr in c_options {
-                if !ptr.is_null() {
-                    let _ = CString::from_raw(ptr);
-                }
-            }
-        }
-
         // set image permissions
         let mut permissions = std::fs::metadata(&path)?.permissions();
         permissions.set_mode(0o644);
@@ -355,11 +794,29 @@ impl ImageManager {
             &timestamp.to_string(), "STIP").unwrap();
 
         // load data
-        self.load(None, description, geohash, pixel_coverage,
-            platform, source, subdataset, tile, timestamp)?;
+        let mtime = std::fs::metadata(&path)?.modified()?
+            .duration_since(std::time::UNIX_EPOCH)?.as_secs() as i64;
+        self.load(None, description, &digest, geohash, mtime, &path_str,
+            pixel_coverage, platform, source, subdataset, tile, timestamp)
+    }
+}
 
-        Ok(())
+/// remove a 'files' row and drop the refcount on the blob it pointed
+/// to - the caller is responsible for eventually calling 'gc()' so a
+/// blob whose refcount reaches zero is actually deleted from disk
+fn remove_file_row(conn: &Connection, rowid: i64) -> Result<(), Box<dyn Error>> {
+    let digest: Option<String> = conn.prepare(FILE_DIGEST_SELECT_STMT)?
+        .query_row(rusqlite::params![rowid], |row| row.get(0)).ok();
+
+    conn.execute("DELETE FROM files WHERE rowid = ?1",
+        rusqlite::params![rowid])?;
+
+    if let Some(digest) = digest {
+        conn.execute("UPDATE chunks SET refcount = refcount - 1 \
+            WHERE digest = ?1", rusqlite::params![digest])?;
     }
+
+    Ok(())
 }
 
 fn append_stmt_filter<'a, T: ToSql>(feature: &str, filter: &'a Option<T>,
@@ -1,20 +1,23 @@
-use rusqlite::{Connection, ToSql};
+use rusqlite::{Connection, OptionalExtension, ToSql};
 
 use crate::{Extent, Image, StFile};
 use crate::album::Album;
 
 use std::error::Error;
+use std::path::Path;
 use std::sync::Mutex;
 
 const CREATE_FILES_TABLE_STMT: &str =
-"CREATE TABLE files (
+"CREATE TABLE IF NOT EXISTS files (
+    checksum        BIGINT NULL,
     image_id        BIGINT NOT NULL,
     pixel_coverage  FLOAT NOT NULL,
+    preview         BOOLEAN NOT NULL,
     subdataset      TINYINT NOT NULL
 )";
 
 const CREATE_IMAGES_TABLE_STMT: &str =
-"CREATE TABLE images (
+"CREATE TABLE IF NOT EXISTS images (
     cloud_coverage  FLOAT NULL,
     geocode         TEXT NOT NULL,
     id              BIGINT PRIMARY KEY,
@@ -24,24 +27,116 @@ const CREATE_IMAGES_TABLE_STMT: &str =
     timestamp       BIGINT NOT NULL
 )";
 
-//const CREATE_INDEX_STMT: &str =
-//"CREATE INDEX idx_images ON images(platform, pixel_coverage)";
+// a coarser companion to 'images'/'files' above - one row per geocode
+// tile summarizing every band a loader split for it (mean pixel
+// coverage across bands, plus a flat band_id:description file list),
+// so a catalog query doesn't have to join per-band rows or open a
+// raster just to see what exists at a tile
+const CREATE_TILE_METADATA_TABLE_STMT: &str =
+"CREATE TABLE IF NOT EXISTS tile_metadata (
+    files                TEXT NOT NULL,
+    geocode              TEXT NOT NULL,
+    mean_pixel_coverage  FLOAT NOT NULL,
+    platform             TEXT NOT NULL,
+    tile                 TEXT NOT NULL
+)";
+
+// a downsampled-raster cache keyed by the image it was derived from, its
+// subdataset, and the long-edge pixel dimension it was scaled to - lets
+// the on-demand preview RPC serve a repeat request for the same
+// (image, subdataset, size) from disk instead of re-decimating the
+// full-resolution tile
+const CREATE_PREVIEWS_TABLE_STMT: &str =
+"CREATE TABLE IF NOT EXISTS previews (
+    image_id        BIGINT NOT NULL,
+    max_dimension   INTEGER NOT NULL,
+    path            TEXT NOT NULL,
+    subdataset      TINYINT NOT NULL,
+    PRIMARY KEY (image_id, subdataset, max_dimension)
+)";
+
+const SELECT_PREVIEW_STMT: &str =
+"SELECT previews.path FROM previews
+JOIN images ON images.id = previews.image_id
+WHERE images.geocode = ?1 AND images.tile = ?2 AND images.source = ?3
+    AND previews.subdataset = ?4 AND previews.max_dimension = ?5";
+
+const INSERT_PREVIEW_STMT: &str =
+"INSERT INTO previews (image_id, max_dimension, path, subdataset)
+VALUES (?1, ?2, ?3, ?4)";
+
+// tiny key/value table - today this holds only the 'next_id' high-water
+// mark for 'images.id', persisted so ids keep climbing across a restart
+// instead of colliding with rows a prior process already wrote
+const CREATE_METADATA_TABLE_STMT: &str =
+"CREATE TABLE IF NOT EXISTS metadata (
+    key    TEXT PRIMARY KEY,
+    value  BIGINT NOT NULL
+)";
+
+const NEXT_ID_KEY: &str = "next_id";
+
+const SELECT_METADATA_STMT: &str =
+"SELECT value FROM metadata WHERE key = ?1";
+
+const UPSERT_METADATA_STMT: &str =
+"INSERT INTO metadata (key, value) VALUES (?1, ?2)
+ON CONFLICT(key) DO UPDATE SET value = excluded.value";
+
+// an r*tree over each image's decoded geocode bounds, keyed by the same
+// id as 'images' - lets 'list'/'search' answer "images intersecting this
+// lon/lat box" with an indexed range scan instead of forcing callers to
+// translate an AOI into geocode prefixes
+const CREATE_IMAGES_RTREE_STMT: &str =
+"CREATE VIRTUAL TABLE IF NOT EXISTS images_rtree USING rtree(
+    id,
+    min_lon, max_lon,
+    min_lat, max_lat
+)";
+
+const CREATE_IMAGES_INDEX_STMT: &str =
+"CREATE INDEX IF NOT EXISTS idx_images ON images(platform, timestamp, geocode)";
+
+const CREATE_FILES_INDEX_STMT: &str =
+"CREATE INDEX IF NOT EXISTS idx_files ON files(image_id)";
+
+// covers the equality filters 'list'/'search' apply together most often,
+// so that combination is satisfied by an index lookup rather than a scan
+// over 'idx_images' above (which leads with 'platform' but not 'source')
+const CREATE_IMAGES_PLATFORM_SOURCE_INDEX_STMT: &str =
+"CREATE INDEX IF NOT EXISTS idx_images_platform_source_timestamp \
+ON images(platform, source, timestamp)";
+
+// covers a non-recursive 'list'/'search' (an exact geocode match) and the
+// per-tile ordering both queries finish with, without also touching
+// 'platform'/'source'/'timestamp' the way the indices above do
+const CREATE_IMAGES_GEOCODE_INDEX_STMT: &str =
+"CREATE INDEX IF NOT EXISTS idx_images_geocode_tile ON images(geocode, tile)";
 
 const INSERT_FILES_STMT: &str =
-"INSERT INTO files (image_id, pixel_coverage, subdataset)
-VALUES (?1, ?2, ?3)";
+"INSERT INTO files (checksum, image_id, pixel_coverage, preview, subdataset)
+VALUES (?1, ?2, ?3, ?4, ?5)";
 
 const INSERT_IMAGES_STMT: &str =
 "INSERT INTO images (cloud_coverage, geocode,
     id, platform, source, tile, timestamp)
 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)";
 
+const INSERT_IMAGES_RTREE_STMT: &str =
+"INSERT INTO images_rtree (id, min_lon, max_lon, min_lat, max_lat)
+VALUES (?1, ?2, ?3, ?4, ?5)";
+
+const INSERT_TILE_METADATA_STMT: &str =
+"INSERT INTO tile_metadata (files, geocode,
+    mean_pixel_coverage, platform, tile)
+VALUES (?1, ?2, ?3, ?4, ?5)";
+
 const ID_SELECT_STMT: &str =
 "SELECT id from images WHERE geocode = ?1 AND tile = ?2 AND source = ?3";
 
 const LIST_SELECT_STMT: &str =
 "SELECT cloud_coverage, geocode, pixel_coverage,
-    platform, source, subdataset, tile, timestamp
+    platform, source, subdataset, tile, timestamp, preview, checksum
 FROM images JOIN files ON images.id = files.image_id";
 
 const LIST_ORDER_BY_STMT: &str =
@@ -56,27 +151,129 @@ FROM (SELECT DISTINCT geocode, platform, source, tile
 const SEARCH_GROUP_BY_STMT: &str =
 " ) GROUP BY geocode_search, platform, precision, source";
 
+// same join as 'LIST_SELECT_STMT', minus 'preview' and 'checksum' - the
+// reconciliation
+// tree hashes the underlying catalog content, not which copy (preview or
+// full) a node happens to have on disk
+const MERKLE_ROWS_SELECT_STMT: &str =
+"SELECT cloud_coverage, geocode, pixel_coverage,
+    platform, source, subdataset, tile, timestamp
+FROM images JOIN files ON images.id = files.image_id";
+
+const MERKLE_ROWS_ORDER_BY_STMT: &str = " ORDER BY geocode, tile, subdataset";
+
+/// one row of an album's catalog, flattened for content hashing by
+/// 'crate::merkle' - the sqlite-assigned 'images.id' is deliberately
+/// excluded, since it's local bookkeeping that two independently
+/// reconciling nodes will never agree on even when every other field
+/// matches
+#[derive(Clone, Debug)]
+pub struct MerkleRow {
+    pub cloud_coverage: Option<f64>,
+    pub geocode: String,
+    pub pixel_coverage: f64,
+    pub platform: String,
+    pub source: String,
+    pub subdataset: u8,
+    pub tile: String,
+    pub timestamp: i64,
+}
+
+impl MerkleRow {
+    /// canonical string hashed (and sorted on) during reconciliation -
+    /// any two rows with identical content produce the same key
+    /// regardless of which node loaded them or in what order
+    pub fn key(&self) -> String {
+        format!("{}\t{}\t{}\t{}\t{}\t{}\t{:?}\t{}", self.geocode,
+            self.tile, self.platform, self.source, self.subdataset,
+            self.timestamp, self.cloud_coverage, self.pixel_coverage)
+    }
+}
+
+/// the operations a catalog backend for 'Album' must support - pulled out
+/// of 'AlbumIndex' so an alternative embedded key/value backend (e.g.
+/// LMDB) can stand in without 'Album' or its callers caring which one is
+/// in use
+pub trait IndexStore {
+    fn list(&self, album: &Album, end_timestamp: &Option<i64>,
+        geocode: &Option<String>, max_cloud_coverage: &Option<f64>,
+        max_lat: &Option<f64>, max_lon: &Option<f64>,
+        min_lat: &Option<f64>, min_lon: &Option<f64>,
+        min_pixel_coverage: &Option<f64>, platform: &Option<String>,
+        recurse: bool, source: &Option<String>,
+        start_timestamp: &Option<i64>)
+        -> Result<Vec<(Image, Vec<StFile>)>, Box<dyn Error>>;
+
+    fn load(&mut self, checksum: Option<u64>, cloud_coverage: Option<f64>,
+        geocode: &str, max_lat: f64, max_lon: f64, min_lat: f64,
+        min_lon: f64, pixel_coverage: f64, platform: &str, source: &str,
+        subdataset: u8, tile: &str, timestamp: i64, preview: bool)
+        -> Result<(), Box<dyn Error>>;
+
+    /// every catalog row whose geocode starts with 'geocode_prefix'
+    /// ('None' for the whole catalog), ordered for deterministic
+    /// bucketing - the raw material 'crate::merkle' builds a
+    /// reconciliation tree over
+    fn merkle_rows(&self, geocode_prefix: &Option<String>)
+        -> Result<Vec<MerkleRow>, Box<dyn Error>>;
+
+    fn search(&self, end_timestamp: &Option<i64>,
+        geocode: &Option<String>, max_cloud_coverage: &Option<f64>,
+        max_lat: &Option<f64>, max_lon: &Option<f64>,
+        min_lat: &Option<f64>, min_lon: &Option<f64>,
+        min_pixel_coverage: &Option<f64>, platform: &Option<String>,
+        recurse: bool, source: &Option<String>,
+        start_timestamp: &Option<i64>)
+        -> Result<Vec<Extent>, Box<dyn Error>>;
+}
+
+/// the 'IndexStore' sqlite backend - a file-backed catalog of every
+/// image/file row an album has loaded, opened in WAL mode. every query
+/// goes through the one connection behind 'conn''s mutex, so list/search
+/// and load still run one at a time
 pub struct AlbumIndex {
     conn: Mutex<Connection>,
     id: i64,
 }
 
 impl AlbumIndex {
-    pub fn new() -> Result<AlbumIndex, Box<dyn Error>> {
-        // initialize sqlite connection
-        let conn = Connection::open_in_memory()?;
+    /// open (creating if necessary) the sqlite database at 'path' - a
+    /// reopen of an existing database picks the 'next_id' high-water
+    /// mark back up from the 'metadata' table rather than starting over,
+    /// so ids already handed out (and persisted onto disk as tile
+    /// filenames/catalog rows) are never reissued
+    pub fn new(path: &Path) -> Result<AlbumIndex, Box<dyn Error>> {
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", &"WAL")?;
+
         conn.execute(CREATE_FILES_TABLE_STMT, rusqlite::params![])?;
         conn.execute(CREATE_IMAGES_TABLE_STMT, rusqlite::params![])?;
-        //conn.execute(CREATE_INDEX_STMT, rusqlite::params![])?;
+        conn.execute(CREATE_PREVIEWS_TABLE_STMT, rusqlite::params![])?;
+        conn.execute(CREATE_TILE_METADATA_TABLE_STMT, rusqlite::params![])?;
+        conn.execute(CREATE_METADATA_TABLE_STMT, rusqlite::params![])?;
+        conn.execute(CREATE_IMAGES_RTREE_STMT, rusqlite::params![])?;
+        conn.execute(CREATE_IMAGES_INDEX_STMT, rusqlite::params![])?;
+        conn.execute(CREATE_FILES_INDEX_STMT, rusqlite::params![])?;
+        conn.execute(CREATE_IMAGES_PLATFORM_SOURCE_INDEX_STMT,
+            rusqlite::params![])?;
+        conn.execute(CREATE_IMAGES_GEOCODE_INDEX_STMT, rusqlite::params![])?;
+
+        let id = conn.query_row(SELECT_METADATA_STMT,
+            rusqlite::params![NEXT_ID_KEY],
+            |row| row.get(0)).unwrap_or(1000);
 
         Ok(AlbumIndex {
             conn: Mutex::new(conn),
-            id: 1000,
+            id: id,
         })
     }
+}
 
-    pub fn list(&self, album: &Album, end_timestamp: &Option<i64>,
+impl IndexStore for AlbumIndex {
+    fn list(&self, album: &Album, end_timestamp: &Option<i64>,
             geocode: &Option<String>, max_cloud_coverage: &Option<f64>,
+            max_lat: &Option<f64>, max_lon: &Option<f64>,
+            min_lat: &Option<f64>, min_lon: &Option<f64>,
             min_pixel_coverage: &Option<f64>, platform: &Option<String>,
             recurse: bool, source: &Option<String>,
             start_timestamp: &Option<i64>)
@@ -93,6 +290,8 @@ impl AlbumIndex {
             &mut stmt_str, "<=", &mut params);
         append_stmt_filter("cloud_coverage", max_cloud_coverage,
             &mut stmt_str, "<=", &mut params);
+        append_rtree_filter(max_lat, max_lon, min_lat, min_lon,
+            &mut stmt_str, &mut params);
         append_stmt_filter("pixel_coverage", min_pixel_coverage,
             &mut stmt_str, ">=", &mut params);
         append_stmt_filter("platform", platform,
@@ -125,15 +324,17 @@ impl AlbumIndex {
             let source: String = row.get(4)?;
             let subdataset: u8 = row.get(5)?;
             let tile: String = row.get(6)?;
- 
+            let preview: bool = row.get(8)?;
+            let checksum: Option<i64> = row.get(9)?;
+
             // TODO - error
-            let path = album.get_image_path(false, &geocode,
-                &platform, &source, subdataset, &tile).unwrap();
+            let path = album.resolve_file_path(&geocode,
+                &platform, &source, subdataset, &tile, preview).unwrap();
 
             Ok(((row.get(0)?, geocode, platform,
                     source, tile, row.get(7)?),
-                (path.to_string_lossy().to_string(),
-                    row.get(2)?, subdataset)))
+                (path, row.get(2)?, subdataset, preview,
+                    checksum.map(|checksum| checksum as u64))))
         })?;
 
         // process images
@@ -155,9 +356,10 @@ impl AlbumIndex {
         Ok(images)
     }
 
-    pub fn load(&mut self, cloud_coverage: Option<f64>, geocode: &str,
-            pixel_coverage: f64, platform: &str, source: &str,
-            subdataset: u8, tile: &str, timestamp: i64) 
+    fn load(&mut self, checksum: Option<u64>, cloud_coverage: Option<f64>,
+            geocode: &str, max_lat: f64, max_lon: f64, min_lat: f64,
+            min_lon: f64, pixel_coverage: f64, platform: &str, source: &str,
+            subdataset: u8, tile: &str, timestamp: i64, preview: bool)
             -> Result<(), Box<dyn Error>> {
         // load data into sqlite
         let conn = self.conn.lock().unwrap();
@@ -177,27 +379,72 @@ impl AlbumIndex {
                     platform, source, tile, timestamp
                 ])?;
 
+                conn.execute(INSERT_IMAGES_RTREE_STMT, rusqlite::params![
+                    self.id, min_lon, max_lon, min_lat, max_lat
+                ])?;
+
                 self.id += 1;
+
+                // persist the new high-water mark immediately - a
+                // crash right after this insert must never hand out
+                // 'self.id - 1' again on the next restart
+                conn.execute(UPSERT_METADATA_STMT,
+                    rusqlite::params![NEXT_ID_KEY, self.id])?;
+
                 self.id - 1
             },
         };
 
         conn.execute(INSERT_FILES_STMT, rusqlite::params![
-                id, pixel_coverage, subdataset
+                checksum.map(|checksum| checksum as i64),
+                id, pixel_coverage, preview, subdataset
             ])?;
 
         Ok(())
     }
 
-    pub fn search(&self, end_timestamp: &Option<i64>,
+    fn merkle_rows(&self, geocode_prefix: &Option<String>)
+            -> Result<Vec<MerkleRow>, Box<dyn Error>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt_str = MERKLE_ROWS_SELECT_STMT.to_string();
+        let mut params: Vec<&dyn ToSql> = Vec::new();
+
+        let geocode_glob = geocode_prefix.as_ref()
+            .map(|prefix| format!("{}%", prefix));
+        append_stmt_filter("geocode", &geocode_glob,
+            &mut stmt_str, "LIKE", &mut params);
+
+        stmt_str.push_str(MERKLE_ROWS_ORDER_BY_STMT);
+
+        let mut stmt = conn.prepare(&stmt_str)?;
+        let rows_iter = stmt.query_map(&params, |row| {
+            Ok(MerkleRow {
+                cloud_coverage: row.get(0)?,
+                geocode: row.get(1)?,
+                pixel_coverage: row.get(2)?,
+                platform: row.get(3)?,
+                source: row.get(4)?,
+                subdataset: row.get(5)?,
+                tile: row.get(6)?,
+                timestamp: row.get(7)?,
+            })
+        })?;
+
+        Ok(rows_iter.map(|x| x.unwrap()).collect())
+    }
+
+    fn search(&self, end_timestamp: &Option<i64>,
             geocode: &Option<String>, max_cloud_coverage: &Option<f64>,
+            max_lat: &Option<f64>, max_lon: &Option<f64>,
+            min_lat: &Option<f64>, min_lon: &Option<f64>,
             min_pixel_coverage: &Option<f64>, platform: &Option<String>,
             recurse: bool, source: &Option<String>,
             start_timestamp: &Option<i64>)
             -> Result<Vec<Extent>, Box<dyn Error>> {
         // lock the sqlite connection
         let conn = self.conn.lock().unwrap();
- 
+
         // initialize the SELECT command and parameters
         let replace_length = match geocode {
             Some(geocode) => format!("{}", geocode.len() + 2),
@@ -213,6 +460,8 @@ impl AlbumIndex {
             &mut stmt_str, "<=", &mut params);
         append_stmt_filter("cloud_coverage", max_cloud_coverage,
             &mut stmt_str, "<=", &mut params);
+        append_rtree_filter(max_lat, max_lon, min_lat, min_lon,
+            &mut stmt_str, &mut params);
         append_stmt_filter("pixel_coverage", min_pixel_coverage,
             &mut stmt_str, ">=", &mut params);
         append_stmt_filter("platform", platform,
@@ -251,6 +500,69 @@ impl AlbumIndex {
     }
 }
 
+impl AlbumIndex {
+    /// record a tile's band/file catalog - 'files' is a flat
+    /// 'band_id:description' list, one per line, so a downstream query
+    /// doesn't need a relational join to recover it
+    pub fn write_tile_metadata(&self, geocode: &str, tile: &str,
+            platform: &str, mean_pixel_coverage: f64, files: &str)
+            -> Result<(), Box<dyn Error>> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(INSERT_TILE_METADATA_STMT, rusqlite::params![
+                files, geocode, mean_pixel_coverage, platform, tile
+            ])?;
+
+        Ok(())
+    }
+
+    /// rebuild the on-disk file to reclaim space left behind by deleted
+    /// rows, then refresh the query planner's statistics - run this
+    /// after a heavy load/repair pass rather than on every write, since
+    /// 'VACUUM' rewrites the entire database file
+    pub fn optimize(&self) -> Result<(), Box<dyn Error>> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute("VACUUM", rusqlite::params![])?;
+        conn.execute("ANALYZE", rusqlite::params![])?;
+
+        Ok(())
+    }
+
+    /// the cached downsampled raster for this (geocode, tile, source,
+    /// subdataset) at 'max_dimension', if 'PreviewTask' has already
+    /// generated one - 'None' means the caller must generate it fresh
+    pub fn get_preview(&self, geocode: &str, tile: &str, source: &str,
+            subdataset: u8, max_dimension: u32)
+            -> Result<Option<String>, Box<dyn Error>> {
+        let conn = self.conn.lock().unwrap();
+
+        Ok(conn.query_row(SELECT_PREVIEW_STMT,
+            rusqlite::params![geocode, tile, source,
+                subdataset, max_dimension],
+            |row| row.get(0)).optional()?)
+    }
+
+    /// record where a freshly generated preview for (geocode, tile,
+    /// source, subdataset) at 'max_dimension' landed, so a later request
+    /// for the same tuple hits 'get_preview' instead of regenerating it
+    pub fn insert_preview(&self, geocode: &str, tile: &str, source: &str,
+            subdataset: u8, max_dimension: u32, path: &str)
+            -> Result<(), Box<dyn Error>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(ID_SELECT_STMT)?;
+        let image_id: i64 = stmt.query_row(
+            rusqlite::params![geocode, tile, source],
+            |row| row.get(0))?;
+
+        conn.execute(INSERT_PREVIEW_STMT,
+            rusqlite::params![image_id, max_dimension, path, subdataset])?;
+
+        Ok(())
+    }
+}
+
 fn append_stmt_filter<'a, T: ToSql>(feature: &str, filter: &'a Option<T>,
         stmt: &mut String, op: &str, params: &mut Vec<&'a dyn ToSql>) {
     if let Some(_) = filter {
@@ -263,3 +575,46 @@ fn append_stmt_filter<'a, T: ToSql>(feature: &str, filter: &'a Option<T>,
         stmt.push_str(&filter_str);
     }
 }
+
+/// true bounding-box intersection against the 'images_rtree' virtual
+/// table, joined by 'images.id' - unlike 'append_stmt_filter' this
+/// compares each supplied corner against the image's *opposite* bound
+/// (a query's 'min_lon' against an image's 'max_lon', and so on), so a
+/// caller may supply any subset of the four corners and still narrow the
+/// scan to images whose extent could possibly overlap the requested box
+fn append_rtree_filter<'a>(max_lat: &'a Option<f64>, max_lon: &'a Option<f64>,
+        min_lat: &'a Option<f64>, min_lon: &'a Option<f64>,
+        stmt: &mut String, params: &mut Vec<&'a dyn ToSql>) {
+    let had_filter = !params.is_empty();
+    let mut conditions = Vec::new();
+
+    if let Some(_) = min_lon {
+        params.push(min_lon);
+        conditions.push(format!("max_lon >= ?{}", params.len()));
+    }
+    if let Some(_) = max_lon {
+        params.push(max_lon);
+        conditions.push(format!("min_lon <= ?{}", params.len()));
+    }
+    if let Some(_) = min_lat {
+        params.push(min_lat);
+        conditions.push(format!("max_lat >= ?{}", params.len()));
+    }
+    if let Some(_) = max_lat {
+        params.push(max_lat);
+        conditions.push(format!("min_lat <= ?{}", params.len()));
+    }
+
+    if conditions.is_empty() {
+        return;
+    }
+
+    let op = match had_filter {
+        true => "AND",
+        false => "WHERE",
+    };
+
+    stmt.push_str(&format!(
+        " {} images.id IN (SELECT id FROM images_rtree WHERE {})",
+        op, conditions.join(" AND ")));
+}
@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// one msgpack-encoded (variable-length) record per loaded tile, appended
+/// to 'index.cache' as it's learned - mirrors the fields `AlbumIndex`
+/// catalogs, so a
+/// later `Album::open` can replay the file with a sequential read
+/// instead of re-globbing the album directory and re-reading every
+/// tile's gdal metadata
+#[derive(Serialize, Deserialize)]
+pub struct IndexRecord {
+    pub checksum: Option<u64>,
+    pub cloud_coverage: Option<f64>,
+    pub geocode: String,
+    pub pixel_coverage: f64,
+    pub platform: String,
+    pub preview: bool,
+    pub source: String,
+    pub subdataset: u8,
+    pub tile: String,
+    pub timestamp: i64,
+}
+
+/// the cache file's inode and modification time, recorded immediately
+/// after an append finishes - a later open compares this against the
+/// file's live identity, so a change made outside of `append` (a
+/// restored backup, a hand-edited or truncated file) is detected as
+/// staleness rather than silently replayed
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct CacheIdentity {
+    inode: u64,
+    mtime: i64,
+}
+
+pub struct IndexCache {
+    cache_path: PathBuf,
+    identity_path: PathBuf,
+    file: File,
+}
+
+fn cache_path(directory: &Path) -> PathBuf {
+    let mut path = directory.to_path_buf();
+    path.push("index");
+    path.set_extension("cache");
+    path
+}
+
+fn identity_path(directory: &Path) -> PathBuf {
+    let mut path = directory.to_path_buf();
+    path.push("index");
+    path.set_extension("identity");
+    path
+}
+
+impl IndexCache {
+    /// open (creating if necessary) the cache file for appending -
+    /// never truncates here, since both a brand new album and a
+    /// warm-cache reopen just want to keep adding records to whatever
+    /// is already on disk
+    pub fn open(directory: &Path) -> Result<IndexCache, Box<dyn Error>> {
+        let file = OpenOptions::new().create(true).append(true)
+            .open(cache_path(directory))?;
+
+        Ok(IndexCache {
+            cache_path: cache_path(directory),
+            identity_path: identity_path(directory),
+            file: file,
+        })
+    }
+
+    /// append 'record' and persist the cache file's new identity, so a
+    /// restart immediately afterward still recognizes this cache as
+    /// current
+    pub fn append(&mut self, record: &IndexRecord)
+            -> Result<(), Box<dyn Error>> {
+        let bytes = rmp_serde::to_vec(record)?;
+        self.file.write_all(&bytes)?;
+        self.file.sync_all()?;
+
+        self.persist_identity()
+    }
+
+    fn persist_identity(&self) -> Result<(), Box<dyn Error>> {
+        let metadata = self.file.metadata()?;
+        let identity = CacheIdentity {
+            inode: metadata.ino(),
+            mtime: metadata.mtime(),
+        };
+
+        let bytes = rmp_serde::to_vec(&identity)?;
+        let tmp_path = self.identity_path.with_extension("identity.tmp");
+        {
+            let mut file = File::create(&tmp_path)?;
+            file.write_all(&bytes)?;
+            file.sync_all()?;
+        }
+
+        fs::rename(&tmp_path, &self.identity_path)?;
+        Ok(())
+    }
+
+    /// discard everything currently in the cache - used when a stale
+    /// identity forces a full rescan, so tiles rediscovered by that
+    /// rescan rebuild a clean cache rather than appending onto one that
+    /// may still describe since-deleted files
+    pub fn reset(&mut self) -> Result<(), Box<dyn Error>> {
+        File::create(&self.cache_path)?;
+        self.file = OpenOptions::new().create(true).append(true)
+            .open(&self.cache_path)?;
+
+        if self.identity_path.exists() {
+            fs::remove_file(&self.identity_path)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// true if the cache file's on-disk identity still matches the one
+/// recorded after the last append - false (including "cache or
+/// identity file missing") means a full rescan is needed
+pub fn is_fresh(directory: &Path) -> bool {
+    let stored: CacheIdentity = match fs::read(identity_path(directory)) {
+        Ok(bytes) => match rmp_serde::from_slice(&bytes) {
+            Ok(identity) => identity,
+            Err(_) => return false,
+        },
+        Err(_) => return false,
+    };
+
+    let metadata = match fs::metadata(cache_path(directory)) {
+        Ok(metadata) => metadata,
+        Err(_) => return false,
+    };
+
+    stored == CacheIdentity {
+        inode: metadata.ino(),
+        mtime: metadata.mtime(),
+    }
+}
+
+/// replay every record currently in the cache, in append order
+pub fn replay(directory: &Path) -> Result<Vec<IndexRecord>, Box<dyn Error>> {
+    let path = cache_path(directory);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut reader = BufReader::new(File::open(&path)?);
+    let mut records = Vec::new();
+    loop {
+        match rmp_serde::decode::from_read::<_, IndexRecord>(&mut reader) {
+            Ok(record) => records.push(record),
+            // a cleanly-appended cache always ends on a record
+            // boundary - the only expected failure here is hitting
+            // eof while looking for the next record's marker
+            Err(rmp_serde::decode::Error::InvalidMarkerRead(ref e))
+                    if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(Box::new(e)),
+        }
+    }
+
+    Ok(records)
+}
@@ -2,21 +2,41 @@
 extern crate log;
 
 use comm::Server as CommServer;
-use protobuf::{ImageManagementServer, AlbumManagementServer, NodeManagementServer, TaskManagementServer};
+use protobuf::{ImageManagementServer, AlbumManagementServer, DeadLetterManagementServer, JobManagementServer, NodeManagementServer, TaskManagementServer};
 use structopt::StructOpt;
-use swarm::prelude::{DhtBuilder, Swarm};
+use swarm::prelude::{Dht, DhtBuilder, Swarm};
 use tonic::transport::Server;
 
 mod album;
-use album::AlbumManager;
+use album::{AlbumManager, StorageConfig};
+mod block;
+mod cdc;
+mod coverage;
+use coverage::CoverageIndex;
+mod gossip;
+use gossip::GossipState;
+mod identity;
+use identity::NodeIdentity;
 mod index;
+mod index_cache;
+mod merkle;
 mod task;
-use task::TaskManager;
+use task::{Task, TaskManager};
+use task::checkpoint::TaskDescriptor;
+use task::deadletter::DeadLetterQueue;
+use task::job::JobManager;
+use task::load::LoadEarthExplorerTask;
+use task::open::OpenTask;
+use task::split::SplitTask;
+use task::store::{ImageFormat, StoreEarthExplorerTask};
 mod rpc;
 use rpc::album::AlbumManagementImpl;
+use rpc::deadletter::DeadLetterManagementImpl;
 use rpc::image::ImageManagementImpl;
+use rpc::job::JobManagementImpl;
 use rpc::node::NodeManagementImpl;
 use rpc::task::TaskManagementImpl;
+mod storage;
 mod transfer;
 use transfer::TransferStreamHandler;
 
@@ -35,8 +55,11 @@ pub type Extent = (i64, String, String, u8, String);
 // cloud_coverage, geocode, platform, source, tile, timestamp
 pub type Image = (Option<f64>, String, String, String, String, i64);
 
-// path, pixel_coverage, subdataset
-pub type StFile = (String, f64, u8);
+// path, pixel_coverage, subdataset, preview, checksum
+pub type StFile = (String, f64, u8, bool, Option<u64>);
+
+// path, reason
+pub type VerifyFailure = (PathBuf, String);
 
 fn main() {
     // initilaize logger
@@ -63,21 +86,77 @@ fn main() {
     let (mut swarm, dht) = Swarm::new(opt.node_id,
         opt.ip_addr, opt.gossip_port, seed_address, dht_builder);
 
+    // create storage directory
+    if let Err(e) = std::fs::create_dir_all(&opt.directory) {
+        panic!("failed to create storage directory '{:?}': {}",
+            opt.directory, e);
+    }
+
+    // load (or generate, on first start) this node's persistent
+    // keypair - its public key is published as swarm metadata and
+    // recorded by peers on join so they can verify signed requests
+    // from this node
+    let identity = match NodeIdentity::load_or_generate(&opt.directory) {
+        Ok(identity) => Arc::new(identity),
+        Err(e) => panic!("failed to load or generate node identity: {}", e),
+    };
+
     // set swarm instance metadata
     swarm.set_metadata("rpc_port", &opt.rpc_port.to_string());
     swarm.set_metadata("xfer_port", &opt.xfer_port.to_string());
+    swarm.set_metadata("public_key", &identity.public_key_hex());
+
+    // advertise free disk capacity so dht_lookup_replicas can weight
+    // replica placement toward nodes with more headroom instead of
+    // treating every node as equally able to take on more data
+    let free_capacity = fs2::available_space(&opt.directory).unwrap_or(0);
+    swarm.set_metadata("free_capacity", &free_capacity.to_string());
+
+    // advertise this node's fault domain so dht_lookup_replicas can
+    // spread replicas across zones instead of only weighing capacity -
+    // there's no way to infer a rack/az/datacenter from the node alone,
+    // so the operator supplies it
+    swarm.set_metadata("zone", &opt.zone);
 
     // start swarm
     swarm.start(2, 50, 2000).expect("swarm start");
 
-    // create storage directory
-    if let Err(e) = std::fs::create_dir_all(&opt.directory) {
-        panic!("failed to create storage directory '{:?}': {}",
-            opt.directory, e);
-    }
+    // start gossip subsystem - periodically refreshes liveness and
+    // prunes entries that haven't been seen within the ttl, so a
+    // crashed or partitioned node doesn't appear up forever
+    let gossip = Arc::new(GossipState::new(opt.node_id,
+        format!("{}:{}", opt.ip_addr, opt.rpc_port),
+        format!("{}:{}", opt.ip_addr, opt.xfer_port),
+        identity.public_key_hex()));
+    gossip::start(gossip.clone(), dht.clone(), 5);
+
+    // select where album tiles are actually stored - 'local' writes
+    // directly under 'opt.directory' as always, 's3' routes reads and
+    // writes through gdal's '/vsis3/' handler so the same binary runs
+    // against minio or aws s3 without a rebuild
+    let storage_config = match opt.storage_backend.as_str() {
+        "local" => StorageConfig::Local,
+        "s3" => {
+            let bucket = match &opt.s3_bucket {
+                Some(bucket) => bucket.clone(),
+                None => panic!(
+                    "--storage-backend=s3 requires --s3-bucket"),
+            };
+
+            StorageConfig::S3 {
+                access_key: opt.s3_access_key.clone(),
+                bucket: bucket,
+                endpoint: opt.s3_endpoint.clone(),
+                region: opt.s3_region.clone(),
+                secret_key: opt.s3_secret_key.clone(),
+            }
+        },
+        backend => panic!("unknown storage backend '{}'", backend),
+    };
 
     // initialize AlbumManager and TaskManager
-    let album_manager = match AlbumManager::new(opt.directory.clone()) {
+    let album_manager = match AlbumManager::new(
+            opt.directory.clone(), storage_config) {
         Ok(album_manager) => album_manager,
         Err(e) => panic!("initialize AlbumManager failed: {}", e),
     };
@@ -85,14 +164,58 @@ fn main() {
     let album_manager = Arc::new(RwLock::new(album_manager));
     let task_manager = Arc::new(RwLock::new(TaskManager::new()));
 
+    // tracks in-flight ingest jobs (sub-record, per-split progress) -
+    // see 'task::job'
+    let job_manager = JobManager::new();
+
+    // durable queue of split transfers that failed or landed short of
+    // quorum - retried on a backoff in the background rather than
+    // dropping the tile, see 'task::deadletter'
+    let dead_letter_queue = DeadLetterQueue::new(dht.clone(),
+        identity.clone(), opt.node_id);
+    task::deadletter::start(dead_letter_queue.clone(), 1);
+
+    // start the coverage-index gossip subsystem - periodically scans
+    // this node's own albums and caches the result, so search_index can
+    // answer from that cache instead of every node being queried on
+    // every search - no coverage is exchanged with peers, so the cache
+    // covers this node's own albums
+    let coverage_index = Arc::new(CoverageIndex::new(opt.node_id));
+    coverage::start(coverage_index.clone(), album_manager.clone(),
+        dht.clone(), 5);
+
+    // resubmit any load/split tasks left in-flight by a previous crash
+    // or restart - each album tracks its own pending tasks under
+    // 'directory/.tasks', so they're resumed from the last checkpoint
+    // rather than starting over. load tasks aren't scoped to an album,
+    // so their own pending tasks live under the node directory instead
+    rehydrate_tasks(&album_manager, &dead_letter_queue, &dht, &opt.directory,
+        &identity, &job_manager, opt.node_id, &task_manager,
+        opt.load_thread_count);
+
+    // start the periodic replica repair subsystem - reconciles expected
+    // vs. actual placement for every geocode each album holds locally
+    // and re-transfers anything under-replicated
+    task::repair::start(album_manager.clone(), dht.clone(), identity.clone(),
+        opt.node_id, opt.replication_factor, task_manager.clone(),
+        opt.repair_period, opt.load_thread_count);
+
+    // start the periodic cross-node catalog reconciliation subsystem -
+    // walks a merkle tree built over each album's catalog against every
+    // other node's, so a dropped write is eventually healed without
+    // anyone having to re-run a full 'repair' search
+    task::reconcile::start(album_manager.clone(), dht.clone(),
+        identity.clone(), opt.node_id, task_manager.clone(),
+        opt.reconcile_period, opt.load_thread_count);
+
     // start transfer server
     debug!("binding xfer server [address={}:{}]",
         opt.ip_addr, opt.rpc_port);
 
     let listener = TcpListener::bind(format!("{}:{}",
         opt.ip_addr, opt.xfer_port)).expect("xfer service bind");
-    let transfer_stream_handler =
-        Arc::new(TransferStreamHandler::new(album_manager.clone()));
+    let transfer_stream_handler = Arc::new(TransferStreamHandler::new(
+        album_manager.clone(), gossip.clone(), opt.require_encryption));
     let mut server = CommServer::new(listener,
         50, transfer_stream_handler);
 
@@ -104,13 +227,19 @@ fn main() {
 
     let album_management = AlbumManagementImpl::new(
         album_manager.clone(), dht.clone(), task_manager.clone());
-    let image_management = ImageManagementImpl::new(
-        album_manager, dht.clone(), task_manager.clone());
-    let node_management = NodeManagementImpl::new(dht.clone());
-    let task_management = TaskManagementImpl::new(dht, task_manager);
+    let dead_letter_management = DeadLetterManagementImpl::new(
+        dead_letter_queue.clone());
+    let image_management = ImageManagementImpl::new(album_manager,
+        coverage_index, dead_letter_queue, dht.clone(), identity.clone(),
+        job_manager.clone(), opt.node_id, opt.replication_factor,
+        opt.strict, task_manager.clone());
+    let job_management = JobManagementImpl::new(job_manager);
+    let node_management = NodeManagementImpl::new(dht.clone(), gossip);
+    let task_management = TaskManagementImpl::new(dht, task_manager.clone());
 
     if let Err(e) = start_rpc_server(addr, album_management,
-            image_management, node_management, task_management) {
+            dead_letter_management, image_management, job_management,
+            node_management, task_management, task_manager) {
         panic!("failed to start rpc server: {}", e);
     }
 
@@ -118,23 +247,230 @@ fn main() {
     //thread::park();
 }
 
+/// scan every album for tasks that were checkpointed but never marked
+/// complete, and resubmit each from its persisted descriptor
+fn rehydrate_tasks(album_manager: &Arc<RwLock<AlbumManager>>,
+        dead_letter_queue: &DeadLetterQueue, dht: &Arc<Dht>,
+        directory: &PathBuf, identity: &Arc<NodeIdentity>,
+        job_manager: &JobManager, node_id: u32,
+        task_manager: &Arc<RwLock<TaskManager>>, thread_count: u8) {
+    let album_manager = album_manager.read().unwrap();
+    for (album_id, album) in album_manager.iter() {
+        let directory = album.read().unwrap().get_directory().clone();
+
+        // resume any split transfers still awaiting retry under this
+        // album, alongside its checkpointed tasks below
+        dead_letter_queue.rehydrate(&directory);
+
+        let pending = match task::checkpoint::pending_tasks(&directory) {
+            Ok(pending) => pending,
+            Err(e) => {
+                warn!("failed to scan pending tasks for album '{}': {}",
+                    album_id, e);
+                continue;
+            },
+        };
+
+        for (task_id, descriptor) in pending {
+            info!("resuming task {} for album '{}'", task_id, album_id);
+
+            let task_handle = match descriptor {
+                TaskDescriptor::Store{format, glob, precision,
+                        replication_factor, strict, ..} => {
+                    let format = match ImageFormat::parse(&format) {
+                        Ok(format) => format,
+                        Err(e) => {
+                            warn!("failed to resume task {}: {}",
+                                task_id, e);
+                            continue;
+                        },
+                    };
+
+                    let task = Arc::new(StoreEarthExplorerTask::new(
+                        album.clone(), dead_letter_queue.clone(),
+                        dht.clone(), directory.clone(), format, glob,
+                        identity.clone(), job_manager.clone(), node_id,
+                        precision, replication_factor, None, None,
+                        None, None, strict));
+                    task.start(directory.clone(), task_id, thread_count)
+                },
+                TaskDescriptor::Split{end_timestamp, geocode,
+                        geocode_bound, platform, precision, recurse,
+                        start_timestamp, ..} => {
+                    let task = Arc::new(SplitTask::new(album.clone(),
+                        dht.clone(), end_timestamp, geocode, geocode_bound,
+                        identity.clone(), node_id, platform, precision,
+                        recurse, start_timestamp));
+                    task.start(directory.clone(), task_id, thread_count)
+                },
+                TaskDescriptor::Open{thread_count, ..} => {
+                    if let Err(e) = album.write().unwrap().open() {
+                        warn!("failed to resume task {}: {}", task_id, e);
+                        continue;
+                    }
+
+                    let task = Arc::new(
+                        OpenTask::new(album.clone(), thread_count));
+                    task.start(directory.clone(), task_id, thread_count)
+                },
+                TaskDescriptor::Repair{rate_limit_ms,
+                        replication_factor, ..} => {
+                    let task = Arc::new(task::repair::RepairTask::new(
+                        album.clone(), dht.clone(), identity.clone(),
+                        node_id, rate_limit_ms, replication_factor));
+                    task.start(directory.clone(), task_id, thread_count)
+                },
+                TaskDescriptor::Reconcile{..} => {
+                    let task = Arc::new(task::reconcile::ReconcileTask::new(
+                        album.clone(), dht.clone(), identity.clone(),
+                        node_id));
+                    task.start(directory.clone(), task_id, thread_count)
+                },
+                TaskDescriptor::Coalesce{end_timestamp, geocode,
+                        max_cloud_coverage, min_pixel_coverage, platform,
+                        recurse, source, src_platform, start_timestamp,
+                        window_seconds, ..} => {
+                    let task = Arc::new(task::coalesce::CoalesceTask::new(album.clone(),
+                        dht.clone(), end_timestamp, geocode,
+                        identity.clone(), max_cloud_coverage,
+                        min_pixel_coverage, node_id, platform, recurse,
+                        source, src_platform, start_timestamp,
+                        window_seconds));
+                    task.start(directory.clone(), task_id, thread_count)
+                },
+                // a load isn't scoped to any one album, so its
+                // checkpoint always lives under the node directory and
+                // is resumed by the node-wide scan below instead
+                TaskDescriptor::Load{..} => {
+                    warn!("ignoring unexpected load task {} found under \
+                        album '{}'", task_id, album_id);
+                    continue;
+                },
+            };
+
+            let task_handle = match task_handle {
+                Ok(task_handle) => task_handle,
+                Err(e) => {
+                    warn!("failed to resume task {}: {}", task_id, e);
+                    continue;
+                },
+            };
+
+            let mut task_manager = task_manager.write().unwrap();
+            if let Err(e) = task_manager.register(
+                    task_handle, Some(task_id)) {
+                warn!("failed to register resumed task {}: {}",
+                    task_id, e);
+            }
+        }
+    }
+
+    // resume any load tasks checkpointed under the node directory -
+    // unlike the tasks above, a load isn't scoped to any one album
+    let pending = match task::checkpoint::pending_tasks(directory) {
+        Ok(pending) => pending,
+        Err(e) => {
+            warn!("failed to scan pending load tasks: {}", e);
+            return;
+        },
+    };
+
+    for (task_id, descriptor) in pending {
+        info!("resuming load task {}", task_id);
+
+        let (album, band_filter, compression, dht_key_length, geocode, glob,
+                load_format, precision, transfer_thread_count) = match descriptor {
+            TaskDescriptor::Load{album, band_filter, compression,
+                    dht_key_length, geocode, glob, load_format, precision,
+                    transfer_thread_count} => {
+                let load_format = match load_format.as_str() {
+                    "MODIS" => task::load::LoadFormat::MODIS,
+                    "NAIP" => task::load::LoadFormat::NAIP,
+                    "Raster" => task::load::LoadFormat::Raster,
+                    "Sentinel" => task::load::LoadFormat::Sentinel,
+                    _ => {
+                        warn!("failed to resume task {}: unknown load \
+                            format '{}'", task_id, load_format);
+                        continue;
+                    },
+                };
+
+                let geocode = match geocode.as_str() {
+                    "Geohash" => st_image::prelude::Geocode::Geohash,
+                    "QuadTile" => st_image::prelude::Geocode::QuadTile,
+                    _ => {
+                        warn!("failed to resume task {}: unknown \
+                            geocode '{}'", task_id, geocode);
+                        continue;
+                    },
+                };
+
+                (album, band_filter, compression, dht_key_length, geocode,
+                    glob, load_format, precision, transfer_thread_count)
+            },
+            _ => {
+                warn!("ignoring unexpected non-load task {} found under \
+                    the node directory", task_id);
+                continue;
+            },
+        };
+
+        let task = Arc::new(LoadEarthExplorerTask::new(album, band_filter,
+            compression, dht.clone(), dht_key_length, geocode, glob,
+            identity.clone(), load_format, node_id, precision,
+            transfer_thread_count));
+        let task_handle = match task.start(
+                directory.clone(), task_id, thread_count) {
+            Ok(task_handle) => task_handle,
+            Err(e) => {
+                warn!("failed to resume task {}: {}", task_id, e);
+                continue;
+            },
+        };
+
+        let mut task_manager = task_manager.write().unwrap();
+        if let Err(e) = task_manager.register(task_handle, Some(task_id)) {
+            warn!("failed to register resumed task {}: {}", task_id, e);
+        }
+    }
+}
+
 #[tokio::main]
-async fn start_rpc_server(addr: SocketAddr, 
+async fn start_rpc_server(addr: SocketAddr,
         album_management: AlbumManagementImpl,
+        dead_letter_management: DeadLetterManagementImpl,
         image_management: ImageManagementImpl,
+        job_management: JobManagementImpl,
         node_management: NodeManagementImpl,
-        task_management: TaskManagementImpl)
+        task_management: TaskManagementImpl,
+        task_manager: Arc<RwLock<TaskManager>>)
         -> Result<(), Box<dyn std::error::Error>> {
     Server::builder()
         .add_service(AlbumManagementServer::new(album_management))
+        .add_service(DeadLetterManagementServer::new(dead_letter_management))
         .add_service(ImageManagementServer::new(image_management))
+        .add_service(JobManagementServer::new(job_management))
         .add_service(NodeManagementServer::new(node_management))
         .add_service(TaskManagementServer::new(task_management))
-        .serve(addr).await?;
+        .serve_with_shutdown(addr, shutdown_signal(task_manager)).await?;
 
     Ok(())
 }
 
+/// resolves on ctrl-c/SIGINT, flushing every running task's completion
+/// log first - so a record that finished just before the signal arrived
+/// is still on disk for 'rehydrate_tasks' to skip on the next restart,
+/// rather than being silently redone
+async fn shutdown_signal(task_manager: Arc<RwLock<TaskManager>>) {
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        warn!("failed to install shutdown signal handler: {}", e);
+        return;
+    }
+
+    info!("shutdown signal received, flushing in-flight task state");
+    task_manager.read().unwrap().flush_all();
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(name = "stipd", about="Node in the STIP framework.")]
 struct Opt {
@@ -156,6 +492,25 @@ struct Opt {
         help="gossip port.", default_value="15605")]
     gossip_port: u16,
 
+    #[structopt(long="reconcile-period",
+        help="seconds between cross-node catalog reconciliation rounds.",
+        default_value="600")]
+    reconcile_period: u64,
+
+    #[structopt(long="repair-period",
+        help="seconds between replica repair convergence rounds.",
+        default_value="300")]
+    repair_period: u64,
+
+    #[structopt(long="replication-factor",
+        help="replica count for newly stored tiles.", default_value="3")]
+    replication_factor: u8,
+
+    #[structopt(long="require-encryption",
+        help="reject transfer peers that don't speak the encrypted \
+            (protocol version >= 2) wire format.")]
+    require_encryption: bool,
+
     #[structopt(short="r", long="rpc-port",
         help="rpc port.", default_value="15606")]
     rpc_port: u16,
@@ -167,10 +522,47 @@ struct Opt {
         help="seed port.", default_value="15605")]
     seed_port: u16,
 
+    #[structopt(long="storage-backend",
+        help="where album tiles are stored - 'local' or 's3'.",
+        default_value="local")]
+    storage_backend: String,
+
+    #[structopt(long="s3-access-key",
+        help="s3 storage backend access key.")]
+    s3_access_key: Option<String>,
+
+    #[structopt(long="s3-bucket",
+        help="s3 storage backend bucket, required if --storage-backend=s3.")]
+    s3_bucket: Option<String>,
+
+    #[structopt(long="s3-endpoint",
+        help="s3 storage backend endpoint, for s3-compatible servers \
+            such as minio.")]
+    s3_endpoint: Option<String>,
+
+    #[structopt(long="s3-region",
+        help="s3 storage backend region.")]
+    s3_region: Option<String>,
+
+    #[structopt(long="s3-secret-key",
+        help="s3 storage backend secret key.")]
+    s3_secret_key: Option<String>,
+
+    #[structopt(long="strict",
+        help="fail a record instead of skipping it when its dataset is \
+            corrupt or has no subdatasets.")]
+    strict: bool,
+
     #[structopt(short="t", long="token", help="token list for dht.")]
     tokens: Vec<u64>,
 
     #[structopt(short="x", long="xfer-port",
         help="data transfer port.", default_value="15607")]
     xfer_port: u16,
+
+    #[structopt(long="zone",
+        help="fault domain (rack/az/datacenter) this node resides in, \
+            used to spread replicas placed by dht_lookup_replicas.",
+        default_value="default")]
+    zone: String,
 }
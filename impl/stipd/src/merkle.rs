@@ -0,0 +1,88 @@
+use crate::index::MerkleRow;
+
+use std::collections::BTreeMap;
+
+/// depth (in geocode characters) a reconciliation tree descends from the
+/// prefix it's rooted at before bottoming out at a leaf bucket of raw
+/// rows - shallow enough that a converged cluster settles after
+/// exchanging a handful of digests, deep enough that a real divergence
+/// narrows to a small row set before any actual row data crosses the wire
+pub const LEAF_DEPTH: usize = 4;
+
+/// one level of a reconciliation tree built over a `MerkleRow` set already
+/// filtered down to a common geocode prefix - a leaf's hash covers the
+/// literal rows in its bucket, an interior node's hash covers its
+/// children's (next geocode character, hash) pairs, so two trees built
+/// independently over identical catalogs always hash identically
+/// bucket-for-bucket regardless of insertion order
+pub enum MerkleNode {
+    Leaf { hash: [u8; 32], rows: Vec<MerkleRow> },
+    Interior { hash: [u8; 32], children: BTreeMap<char, MerkleNode> },
+}
+
+impl MerkleNode {
+    pub fn hash(&self) -> [u8; 32] {
+        match self {
+            MerkleNode::Leaf { hash, .. } => *hash,
+            MerkleNode::Interior { hash, .. } => *hash,
+        }
+    }
+
+    pub fn children(&self) -> Option<&BTreeMap<char, MerkleNode>> {
+        match self {
+            MerkleNode::Leaf { .. } => None,
+            MerkleNode::Interior { children, .. } => Some(children),
+        }
+    }
+
+    pub fn rows(&self) -> Option<&Vec<MerkleRow>> {
+        match self {
+            MerkleNode::Leaf { rows, .. } => Some(rows),
+            MerkleNode::Interior { .. } => None,
+        }
+    }
+}
+
+/// build a reconciliation tree over 'rows', all of which are assumed to
+/// already share 'prefix' as a geocode prefix - 'prefix.len()' is the
+/// tree's starting depth, so a subtree built for a deeper prefix bottoms
+/// out at the same absolute geocode depth as one built from the root
+pub fn build(mut rows: Vec<MerkleRow>, prefix: &str) -> MerkleNode {
+    rows.sort_by(|a, b| a.key().cmp(&b.key()));
+    build_at(rows, prefix.len())
+}
+
+fn build_at(rows: Vec<MerkleRow>, depth: usize) -> MerkleNode {
+    if depth >= LEAF_DEPTH {
+        return MerkleNode::Leaf { hash: hash_rows(&rows), rows: rows };
+    }
+
+    let mut buckets: BTreeMap<char, Vec<MerkleRow>> = BTreeMap::new();
+    for row in rows {
+        let c = row.geocode.chars().nth(depth).unwrap_or('\0');
+        buckets.entry(c).or_insert_with(Vec::new).push(row);
+    }
+
+    let children: BTreeMap<char, MerkleNode> = buckets.into_iter()
+        .map(|(c, rows)| (c, build_at(rows, depth + 1)))
+        .collect();
+
+    let mut hasher = blake3::Hasher::new();
+    for (c, child) in children.iter() {
+        hasher.update(&(*c as u32).to_le_bytes());
+        hasher.update(&child.hash());
+    }
+
+    MerkleNode::Interior {
+        hash: hasher.finalize().into(),
+        children: children,
+    }
+}
+
+fn hash_rows(rows: &[MerkleRow]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    for row in rows {
+        hasher.update(row.key().as_bytes());
+    }
+    hasher.finalize().into()
+}
@@ -1,4 +1,4 @@
-use protobuf::{Album, AlbumBroadcastReply, AlbumBroadcastRequest, AlbumBroadcastType, AlbumCloseReply, AlbumCloseRequest, AlbumCreateReply, AlbumCreateRequest, AlbumDeleteReply, AlbumDeleteRequest, AlbumListReply, AlbumListRequest, AlbumManagement, AlbumManagementClient, AlbumOpenReply, AlbumOpenRequest};
+use protobuf::{Album, AlbumBroadcastReply, AlbumBroadcastRequest, AlbumBroadcastType, AlbumCloseReply, AlbumCloseRequest, AlbumCreateReply, AlbumCreateRequest, AlbumDeleteReply, AlbumDeleteRequest, AlbumListReply, AlbumListRequest, AlbumManagement, AlbumManagementClient, AlbumOpenReply, AlbumOpenRequest, AlbumOptimizeReply, AlbumOptimizeRequest, AlbumVerifyReply, AlbumVerifyRequest};
 use st_image::prelude::Geocode;
 use swarm::prelude::Dht;
 use tonic::{Code, Request, Response, Status};
@@ -6,6 +6,7 @@ use tonic::{Code, Request, Response, Status};
 use crate::album::AlbumManager;
 use crate::task::{Task, TaskManager};
 use crate::task::open::OpenTask;
+use crate::task::verify::VerifyTask;
 
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
@@ -49,76 +50,157 @@ impl AlbumManagement for AlbumManagementImpl {
             }
         }
 
-        // send broadcast message to each dht node
+        // send broadcast message to each dht node. a single unreachable
+        // or erroring node no longer aborts the whole call - its id is
+        // recorded in 'errors' and every other node's result still
+        // comes back
         let mut create_replies = HashMap::new();
         let mut close_replies = HashMap::new();
         let mut delete_replies = HashMap::new();
         let mut open_replies = HashMap::new();
-
-        let mut task_id = None;
-        for (node_id, addr) in dht_nodes {
-            // initialize grpc client
-            let mut client = match AlbumManagementClient::connect(
-                    format!("http://{}", addr)).await {
-                Ok(client) => client,
-                Err(e) => return Err(Status::new(Code::Unavailable,
-                    format!("connection to {} failed: {}", addr, e))),
-            };
-
-            // execute message at dht node
-            match AlbumBroadcastType::from_i32(request.message_type).unwrap() {
-                AlbumBroadcastType::AlbumCreate => {
-                    let reply = match client.create(request
-                            .create_request.clone().unwrap()).await {
-                        Ok(reply) => reply,
-                        Err(e) => return Err(Status::new(Code::Unknown,
-                            format!("create broadcast failed: {}", e))),
-                    };
-                    create_replies.insert(node_id as u32,
-                        reply.get_ref().to_owned());
-                },
-                AlbumBroadcastType::AlbumClose => {
-                    let reply = match client.close(request
-                            .close_request.clone().unwrap()).await {
-                        Ok(reply) => reply,
-                        Err(e) => return Err(Status::new(Code::Unknown,
-                            format!("close broadcast failed: {}", e))),
-                    };
-                    close_replies.insert(node_id as u32,
-                        reply.get_ref().to_owned());
-                },
-                AlbumBroadcastType::AlbumDelete => {
-                    let reply = match client.delete(request
-                            .delete_request.clone().unwrap()).await {
-                        Ok(reply) => reply,
-                        Err(e) => return Err(Status::new(Code::Unknown,
-                            format!("delete broadcast failed: {}", e))),
-                    };
-                    delete_replies.insert(node_id as u32,
-                        reply.get_ref().to_owned());
-                },
-                AlbumBroadcastType::AlbumOpen => {
-                    // compile new AlbumOpenRequest
+        let mut optimize_replies = HashMap::new();
+        let mut errors = HashMap::new();
+
+        match AlbumBroadcastType::from_i32(request.message_type).unwrap() {
+            AlbumBroadcastType::AlbumCreate => {
+                let create_request = request.create_request.clone().unwrap();
+                let futures = dht_nodes.into_iter().map(|(node_id, addr)| {
+                    let create_request = create_request.clone();
+                    async move {
+                        let result = match AlbumManagementClient::connect(
+                                format!("http://{}", addr)).await {
+                            Ok(mut client) => client.create(create_request).await
+                                .map(|reply| reply.into_inner())
+                                .map_err(|e| format!(
+                                    "create broadcast failed: {}", e)),
+                            Err(e) => Err(format!(
+                                "connection to {} failed: {}", addr, e)),
+                        };
+                        (node_id as u32, result)
+                    }
+                });
+                for (node_id, result) in futures::future::join_all(futures).await {
+                    match result {
+                        Ok(reply) => { create_replies.insert(node_id, reply); },
+                        Err(e) => { errors.insert(node_id, e); },
+                    }
+                }
+            },
+            AlbumBroadcastType::AlbumClose => {
+                let close_request = request.close_request.clone().unwrap();
+                let futures = dht_nodes.into_iter().map(|(node_id, addr)| {
+                    let close_request = close_request.clone();
+                    async move {
+                        let result = match AlbumManagementClient::connect(
+                                format!("http://{}", addr)).await {
+                            Ok(mut client) => client.close(close_request).await
+                                .map(|reply| reply.into_inner())
+                                .map_err(|e| format!(
+                                    "close broadcast failed: {}", e)),
+                            Err(e) => Err(format!(
+                                "connection to {} failed: {}", addr, e)),
+                        };
+                        (node_id as u32, result)
+                    }
+                });
+                for (node_id, result) in futures::future::join_all(futures).await {
+                    match result {
+                        Ok(reply) => { close_replies.insert(node_id, reply); },
+                        Err(e) => { errors.insert(node_id, e); },
+                    }
+                }
+            },
+            AlbumBroadcastType::AlbumDelete => {
+                let delete_request = request.delete_request.clone().unwrap();
+                let futures = dht_nodes.into_iter().map(|(node_id, addr)| {
+                    let delete_request = delete_request.clone();
+                    async move {
+                        let result = match AlbumManagementClient::connect(
+                                format!("http://{}", addr)).await {
+                            Ok(mut client) => client.delete(delete_request).await
+                                .map(|reply| reply.into_inner())
+                                .map_err(|e| format!(
+                                    "delete broadcast failed: {}", e)),
+                            Err(e) => Err(format!(
+                                "connection to {} failed: {}", addr, e)),
+                        };
+                        (node_id as u32, result)
+                    }
+                });
+                for (node_id, result) in futures::future::join_all(futures).await {
+                    match result {
+                        Ok(reply) => { delete_replies.insert(node_id, reply); },
+                        Err(e) => { errors.insert(node_id, e); },
+                    }
+                }
+            },
+            AlbumBroadcastType::AlbumOptimize => {
+                let optimize_request = request.optimize_request.clone().unwrap();
+                let futures = dht_nodes.into_iter().map(|(node_id, addr)| {
+                    let optimize_request = optimize_request.clone();
+                    async move {
+                        let result = match AlbumManagementClient::connect(
+                                format!("http://{}", addr)).await {
+                            Ok(mut client) => client.optimize(optimize_request).await
+                                .map(|reply| reply.into_inner())
+                                .map_err(|e| format!(
+                                    "optimize broadcast failed: {}", e)),
+                            Err(e) => Err(format!(
+                                "connection to {} failed: {}", addr, e)),
+                        };
+                        (node_id as u32, result)
+                    }
+                });
+                for (node_id, result) in futures::future::join_all(futures).await {
+                    match result {
+                        Ok(reply) => { optimize_replies.insert(node_id, reply); },
+                        Err(e) => { errors.insert(node_id, e); },
+                    }
+                }
+            },
+            AlbumBroadcastType::AlbumOpen => {
+                // every node must checkpoint the open task under the
+                // same id, so (unlike the other four message types)
+                // this has to stay sequential - each node's reply
+                // supplies the task_id the next node's request carries.
+                // a node that fails just loses its entry in
+                // 'open_replies' rather than aborting the others, and
+                // 'task_id' is left untouched on failure so the id
+                // already agreed on by earlier nodes still propagates
+                // to the rest instead of resetting
+                let mut task_id = None;
+                for (node_id, addr) in dht_nodes {
                     let mut open_request =
                         request.open_request.clone().unwrap();
                     if let Some(task_id) = task_id {
                         open_request.task_id = Some(task_id);
                     }
 
-                    // submit request
+                    let mut client = match AlbumManagementClient::connect(
+                            format!("http://{}", addr)).await {
+                        Ok(client) => client,
+                        Err(e) => {
+                            errors.insert(node_id as u32, format!(
+                                "connection to {} failed: {}", addr, e));
+                            continue;
+                        },
+                    };
+
                     let reply = match client.open(open_request).await {
                         Ok(reply) => reply,
-                        Err(e) => return Err(Status::new(Code::Unknown,
-                            format!("open broadcast failed: {}", e))),
+                        Err(e) => {
+                            errors.insert(node_id as u32, format!(
+                                "open broadcast failed: {}", e));
+                            continue;
+                        },
                     };
-                    open_replies.insert(node_id as u32,
-                        reply.get_ref().to_owned());
 
-                    // process reply
                     task_id = Some(reply.get_ref().task_id);
-                },
-            };
-        }
+                    open_replies.insert(node_id as u32,
+                        reply.get_ref().to_owned());
+                }
+            },
+        };
 
         // initialize reply
         let reply = AlbumBroadcastReply {
@@ -127,6 +209,8 @@ impl AlbumManagement for AlbumManagementImpl {
             close_replies: close_replies,
             delete_replies: delete_replies,
             open_replies: open_replies,
+            optimize_replies: optimize_replies,
+            errors: errors,
         };
 
         Ok(Response::new(reply))
@@ -169,11 +253,38 @@ impl AlbumManagement for AlbumManagementImpl {
             protobuf::Geocode::Quadtile => Geocode::QuadTile,
         };
 
+        // translate the requested compression profile into the gdal
+        // raster creation options 'Album::write' applies to every tile
+        let mut creation_options = Vec::new();
+        match protobuf::CompressionCodec
+                ::from_i32(request.compression).unwrap() {
+            protobuf::CompressionCodec::None => {},
+            protobuf::CompressionCodec::Lzw =>
+                creation_options.push("COMPRESS=LZW".to_string()),
+            protobuf::CompressionCodec::Deflate => {
+                creation_options.push("COMPRESS=DEFLATE".to_string());
+                creation_options.push("PREDICTOR=2".to_string());
+            },
+            protobuf::CompressionCodec::Zstd => {
+                creation_options.push("COMPRESS=ZSTD".to_string());
+                if let Some(level) = request.compression_level {
+                    creation_options.push(format!("ZSTD_LEVEL={}", level));
+                }
+            },
+        }
+
+        if request.block_size != 0 {
+            creation_options.push(format!("BLOCKXSIZE={}", request.block_size));
+            creation_options.push(format!("BLOCKYSIZE={}", request.block_size));
+        }
+
         // create album
         {
             let mut album_manager = self.album_manager.write().unwrap();
-            if let Err(e) = album_manager.create(
-                    request.dht_key_length as i8, geocode, &request.id) {
+            if let Err(e) = album_manager.create_with_options(
+                    request.dht_key_length as i8, geocode, &request.id,
+                    creation_options, request.cloud_optimized,
+                    crate::album::DEFAULT_SPLIT_REPLICATION_FACTOR) {
                 return Err(Status::new(Code::Unknown,
                     format!("failed to create album: {}", e)));
             }
@@ -268,10 +379,15 @@ impl AlbumManagement for AlbumManagementImpl {
         }
 
         // initialize task
-        let task = Arc::new(OpenTask::new(album));
+        let directory = album.read().unwrap().get_directory().clone();
+        let task = Arc::new(OpenTask::new(album, request.thread_count as u8));
+
+        // start task - checkpointed under the album directory so the
+        // id assigned here is stable across a node restart
+        let task_id = request.task_id.unwrap_or_else(rand::random::<u64>);
 
-        // start task
-        let task_handle = match task.start(request.thread_count as u8) {
+        let task_handle = match task.start(
+                directory, task_id, request.thread_count as u8) {
             Ok(task_handle) => task_handle,
             Err(e) => return Err(Status::new(Code::Unknown,
                 format!("failed to start OpenTask: {}", e))),
@@ -280,7 +396,7 @@ impl AlbumManagement for AlbumManagementImpl {
         // register task with TaskHandler
         let task_id = {
             let mut task_manager = self.task_manager.write().unwrap();
-            match task_manager.register(task_handle, request.task_id) {
+            match task_manager.register(task_handle, Some(task_id)) {
                 Ok(task_id) => task_id,
                 Err(e) => return Err(Status::new(Code::Unknown,
                     format!("failed to register OpenTask: {}", e))),
@@ -294,4 +410,75 @@ impl AlbumManagement for AlbumManagementImpl {
 
         Ok(Response::new(reply))
     }
+
+    /// rebuild the album's catalog database file and refresh its query
+    /// planner statistics - a maintenance call an operator runs
+    /// periodically (e.g. after a heavy load/repair pass), not something
+    /// every write triggers on its own
+    async fn optimize(&self, request: Request<AlbumOptimizeRequest>)
+            -> Result<Response<AlbumOptimizeReply>, Status> {
+        trace!("AlbumOptimizeRequest: {:?}", request);
+        let request = request.get_ref();
+
+        // ensure album exists
+        let album = crate::rpc::assert_album_exists(
+            &self.album_manager, &request.id)?;
+
+        if let Err(e) = album.read().unwrap().optimize_index() {
+            return Err(Status::new(Code::Unknown,
+                format!("failed to optimize album index: {}", e)));
+        }
+
+        // initialize reply
+        let reply = AlbumOptimizeReply {};
+
+        Ok(Response::new(reply))
+    }
+
+    /// scrub every tile this node holds for the album, re-reading each
+    /// one and recomputing its checksum against the 'CHECKSUM' stamped
+    /// at write time - mismatched, missing, or unreadable tiles surface
+    /// as per-record failures on the returned task, polled the same way
+    /// as any other task via 'TaskManagement'
+    async fn verify(&self, request: Request<AlbumVerifyRequest>)
+            -> Result<Response<AlbumVerifyReply>, Status> {
+        trace!("AlbumVerifyRequest: {:?}", request);
+        let request = request.get_ref();
+
+        // ensure album exists
+        let album = crate::rpc::assert_album_exists(
+            &self.album_manager, &request.id)?;
+
+        // initialize task
+        let directory = album.read().unwrap().get_directory().clone();
+        let task = Arc::new(VerifyTask::new(album));
+
+        // start task - not checkpointed across restarts, a scrub is an
+        // on-demand audit rather than a job that must survive a crash
+        let task_id = request.task_id.unwrap_or_else(rand::random::<u64>);
+
+        let task_handle = match task.start(
+                directory, task_id, request.thread_count as u8) {
+            Ok(task_handle) => task_handle,
+            Err(e) => return Err(Status::new(Code::Unknown,
+                format!("failed to start VerifyTask: {}", e))),
+        };
+
+        // register task with TaskHandler
+        let task_id = {
+            let mut task_manager = self.task_manager.write().unwrap();
+            match task_manager.register(task_handle, Some(task_id)) {
+                Ok(task_id) => task_id,
+                Err(e) => return Err(Status::new(Code::Unknown,
+                    format!("failed to register VerifyTask: {}", e))),
+            }
+        };
+
+        // initialize reply
+        let reply = AlbumVerifyReply {
+            task_id: task_id,
+        };
+
+        Ok(Response::new(reply))
+    }
 }
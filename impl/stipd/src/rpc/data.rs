@@ -1,35 +1,88 @@
+use futures::stream::{self, StreamExt};
 use protobuf::{self, DataBroadcastReply, DataBroadcastRequest, DataBroadcastType, DataFillReply, DataFillRequest, DataListRequest, DataManagement, DataManagementClient, DataLoadReply, DataLoadRequest, DataSearchRequest, DataSplitReply, DataSplitRequest, Extent, File, Image, LoadFormat as ProtoLoadFormat};
+use st_image::prelude::Geocode;
 use swarm::prelude::Dht;
 use tokio::sync::mpsc::Receiver;
-use tonic::{Request, Response, Status};
+use tonic::{Code, Request, Response, Status};
 
+use crate::gossip::GossipState;
+use crate::identity::NodeIdentity;
 use crate::image::ImageManager;
-use crate::task::TaskManager;
+use crate::task::{Task, TaskManager};
 use crate::task::fill::FillTask;
 use crate::task::load::{LoadEarthExplorerTask, LoadFormat};
 use crate::task::split::SplitTask;
 
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
+/// cap on simultaneous in-flight broadcast RPCs, so a cluster-wide
+/// fill/split fans out in parallel without opening a connection per
+/// node all at once
+const MAX_CONCURRENT_BROADCASTS: usize = 16;
+
+async fn broadcast_fill(addr: String, fill_request: DataFillRequest)
+        -> Result<DataFillReply, String> {
+    let mut client = DataManagementClient::connect(
+            format!("http://{}", addr)).await
+        .map_err(|e| format!("connect to {} failed: {}", addr, e))?;
+
+    let reply = client.fill(fill_request).await
+        .map_err(|e| format!("fill rpc to {} failed: {}", addr, e))?;
+
+    Ok(reply.into_inner())
+}
+
+async fn broadcast_split(addr: String, split_request: DataSplitRequest)
+        -> Result<DataSplitReply, String> {
+    let mut client = DataManagementClient::connect(
+            format!("http://{}", addr)).await
+        .map_err(|e| format!("connect to {} failed: {}", addr, e))?;
+
+    let reply = client.split(split_request).await
+        .map_err(|e| format!("split rpc to {} failed: {}", addr, e))?;
+
+    Ok(reply.into_inner())
+}
+
 pub struct DataManagementImpl {
     image_manager: Arc<RwLock<ImageManager>>,
     dht: Arc<RwLock<Dht>>,
+    // load tasks aren't scoped to a single album, so their checkpoint
+    // state is kept under the node's own directory rather than an
+    // album's
+    directory: PathBuf,
+    gossip: Arc<GossipState>,
+    identity: Arc<NodeIdentity>,
+    node_id: u32,
     task_manager: Arc<RwLock<TaskManager>>,
 }
 
 impl DataManagementImpl {
-    pub fn new(dht: Arc<RwLock<Dht>>,
-            image_manager: Arc<RwLock<ImageManager>>,
+    pub fn new(dht: Arc<RwLock<Dht>>, directory: PathBuf,
+            gossip: Arc<GossipState>, identity: Arc<NodeIdentity>,
+            image_manager: Arc<RwLock<ImageManager>>, node_id: u32,
             task_manager: Arc<RwLock<TaskManager>>) -> DataManagementImpl {
         DataManagementImpl {
             dht: dht,
+            directory: directory,
+            gossip: gossip,
+            identity: identity,
+            node_id: node_id,
             image_manager: image_manager,
             task_manager: task_manager,
         }
     }
 }
 
+// 'broadcast'/'fill'/'load'/'split' don't authenticate their callers -
+// unlike the transfer protocol's read-challenge/sign handshake
+// (stipd::transfer), there's no rpc that hands a caller a fresh
+// challenge to sign before one of these unary calls, so there was never
+// a legitimate way to produce a valid signature here. node-to-node
+// replication already proves identity at the point it matters - the
+// transfer handshake that actually moves tile bytes
 #[tonic::async_trait]
 impl DataManagement for DataManagementImpl {
     async fn broadcast(&self, request: Request<DataBroadcastRequest>)
@@ -55,48 +108,70 @@ impl DataManagement for DataManagementImpl {
         let mut fill_replies = HashMap::new();
         let mut split_replies = HashMap::new();
 
-        let mut task_id = None;
-        for (node_id, addr) in dht_nodes {
-            // initialize grpc client - TODO error
-            let mut client = DataManagementClient::connect(
-                format!("http://{}", addr)).await.unwrap();
-
-            // execute message at dht node
-            match DataBroadcastType::from_i32(request.message_type).unwrap() {
-                DataBroadcastType::Fill => {
-                    // compile new FillRequest
-                    let mut fill_request =
-                        request.fill_request.clone().unwrap();
-                    if let Some(task_id) = task_id {
+        // the task_id used to be threaded from one node's reply into
+        // the next node's request, forcing the broadcast to run
+        // sequentially - instead allocate it once up front so every
+        // node can be dispatched to concurrently
+        match DataBroadcastType::from_i32(request.message_type).unwrap() {
+            DataBroadcastType::Fill => {
+                let fill_request = request.fill_request.clone().unwrap();
+                let task_id = fill_request.task_id
+                    .unwrap_or_else(rand::random::<u64>);
+
+                let results: Vec<(u32, Result<DataFillReply, String>)> =
+                        stream::iter(dht_nodes.into_iter())
+                    .map(|(node_id, addr)| {
+                        let mut fill_request = fill_request.clone();
                         fill_request.task_id = Some(task_id);
-                    }
 
-                    // submit request
-                    let reply = client.fill(fill_request).await.unwrap();
-                    fill_replies.insert(node_id as u32,
-                        reply.get_ref().to_owned());
-
-                    // process reply
-                    task_id = Some(reply.get_ref().task_id);
-                },
-                DataBroadcastType::Split => {
-                    // compile new SplitRequest
-                    let mut split_request =
-                        request.split_request.clone().unwrap();
-                    if let Some(task_id) = task_id {
-                        split_request.task_id = Some(task_id);
+                        async move {
+                            let result = broadcast_fill(
+                                addr, fill_request).await;
+                            (node_id as u32, result)
+                        }
+                    })
+                    .buffer_unordered(MAX_CONCURRENT_BROADCASTS)
+                    .collect().await;
+
+                for (node_id, result) in results {
+                    match result {
+                        Ok(reply) => { fill_replies.insert(node_id, reply); },
+                        Err(e) => warn!(
+                            "fill broadcast failed on node {}: {}",
+                            node_id, e),
                     }
+                }
+            },
+            DataBroadcastType::Split => {
+                let split_request = request.split_request.clone().unwrap();
+                let task_id = split_request.task_id
+                    .unwrap_or_else(rand::random::<u64>);
+
+                let results: Vec<(u32, Result<DataSplitReply, String>)> =
+                        stream::iter(dht_nodes.into_iter())
+                    .map(|(node_id, addr)| {
+                        let mut split_request = split_request.clone();
+                        split_request.task_id = Some(task_id);
 
-                    // submit request
-                    let reply = client.split(split_request).await.unwrap();
-                    split_replies.insert(node_id as u32,
-                        reply.get_ref().to_owned());
-
-                    // process reply
-                    task_id = Some(reply.get_ref().task_id);
-                },
-            };
-        }
+                        async move {
+                            let result = broadcast_split(
+                                addr, split_request).await;
+                            (node_id as u32, result)
+                        }
+                    })
+                    .buffer_unordered(MAX_CONCURRENT_BROADCASTS)
+                    .collect().await;
+
+                for (node_id, result) in results {
+                    match result {
+                        Ok(reply) => { split_replies.insert(node_id, reply); },
+                        Err(e) => warn!(
+                            "split broadcast failed on node {}: {}",
+                            node_id, e),
+                    }
+                }
+            },
+        };
 
         // initialize reply
         let reply = DataBroadcastReply {
@@ -194,17 +269,49 @@ impl DataManagement for DataManagementImpl {
                 ::from_i32(request.load_format).unwrap() {
             ProtoLoadFormat::Modis => LoadFormat::MODIS,
             ProtoLoadFormat::Naip => LoadFormat::NAIP,
+            ProtoLoadFormat::Raster => LoadFormat::Raster,
             ProtoLoadFormat::Sentinel => LoadFormat::Sentinel,
         };
 
-        let task = LoadEarthExplorerTask::new(self.dht.clone(),
-            request.glob.clone(), load_format,
-            request.precision as usize, request.thread_count as u8);
+        let geocode = match protobuf::Geocode
+                ::from_i32(request.geocode).unwrap() {
+            protobuf::Geocode::Geohash => Geocode::Geohash,
+            protobuf::Geocode::Quadtile => Geocode::QuadTile,
+        };
+
+        let band_filter = if request.band_filter.is_empty() {
+            None
+        } else {
+            Some(request.band_filter.clone())
+        };
 
-        // execute task using task manager - TODO error
+        let task = Arc::new(LoadEarthExplorerTask::new(
+            request.album.clone(), band_filter, request.compression_level,
+            self.dht.clone(), request.dht_key_length as i8, geocode,
+            request.glob.clone(), self.identity.clone(), load_format,
+            self.node_id, request.precision as usize,
+            request.transfer_thread_count as u8));
+
+        // start task - checkpointed under the node directory (a load
+        // isn't scoped to any one album) so a crash mid-ingest resumes
+        // without redoing records already loaded
+        let task_id = request.task_id.unwrap_or_else(rand::random::<u64>);
+
+        let task_handle = match task.start(
+                self.directory.clone(), task_id, request.thread_count as u8) {
+            Ok(task_handle) => task_handle,
+            Err(e) => return Err(Status::new(Code::Unknown,
+                format!("failed to start LoadEarthExplorerTask: {}", e))),
+        };
+
+        // register task with TaskHandler
         let task_id = {
             let mut task_manager = self.task_manager.write().unwrap();
-            task_manager.execute(task, request.task_id).unwrap()
+            match task_manager.register(task_handle, Some(task_id)) {
+                Ok(task_id) => task_id,
+                Err(e) => return Err(Status::new(Code::Unknown,
+                    format!("failed to register LoadEarthExplorerTask: {}", e))),
+            }
         };
 
         // initialize reply
@@ -0,0 +1,44 @@
+use protobuf::{DeadLetterDepthReply, DeadLetterDepthRequest, DeadLetterDrainReply, DeadLetterDrainRequest, DeadLetterManagement};
+use tonic::{Request, Response, Status};
+
+use crate::task::deadletter::DeadLetterQueue;
+
+pub struct DeadLetterManagementImpl {
+    dead_letter_queue: DeadLetterQueue,
+}
+
+impl DeadLetterManagementImpl {
+    pub fn new(dead_letter_queue: DeadLetterQueue) -> DeadLetterManagementImpl {
+        DeadLetterManagementImpl {
+            dead_letter_queue: dead_letter_queue,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl DeadLetterManagement for DeadLetterManagementImpl {
+    async fn depth(&self, request: Request<DeadLetterDepthRequest>)
+            -> Result<Response<DeadLetterDepthReply>, Status> {
+        trace!("DeadLetterDepthRequest: {:?}", request);
+
+        let reply = DeadLetterDepthReply {
+            depth: self.dead_letter_queue.depth() as u32,
+        };
+
+        Ok(Response::new(reply))
+    }
+
+    // force every queued entry to retry immediately, ignoring backoff -
+    // so an operator can confirm nothing is silently stuck after
+    // bringing a node back up
+    async fn drain(&self, request: Request<DeadLetterDrainRequest>)
+            -> Result<Response<DeadLetterDrainReply>, Status> {
+        trace!("DeadLetterDrainRequest: {:?}", request);
+
+        let reply = DeadLetterDrainReply {
+            drained: self.dead_letter_queue.drain() as u32,
+        };
+
+        Ok(Response::new(reply))
+    }
+}
@@ -1,15 +1,24 @@
 use protobuf::{Environment, EnvironmentListReply, EnvironmentListRequest, EnvironmentShowReply, EnvironmentShowRequest, EnvironmentManagement};
+use swarm::prelude::Dht;
 use tonic::{Request, Response, Status};
 
+use crate::gossip::GossipState;
+use crate::identity;
+
 use std::net::SocketAddr;
 use std::sync::{Arc, RwLock};
 
 pub struct EnvironmentManagementImpl {
+    dht: Arc<RwLock<Dht>>,
+    gossip: Arc<GossipState>,
 }
 
 impl EnvironmentManagementImpl {
-    pub fn new(dht: Arc<RwLock<Dht>>) -> EnvironmentManagementImpl {
+    pub fn new(dht: Arc<RwLock<Dht>>,
+            gossip: Arc<GossipState>) -> EnvironmentManagementImpl {
         EnvironmentManagementImpl {
+            dht: dht,
+            gossip: gossip,
         }
     }
 }
@@ -27,7 +36,8 @@ impl EnvironmentManagement for EnvironmentManagementImpl {
             for (node_id, addrs) in dht.iter() {
                 // convert Environment to protobuf
                 let node = to_protobuf_node(*node_id as u32,
-                    &addrs.1, &addrs.2);
+                    &addrs.1, &addrs.2,
+                    self.gossip.public_key_of(*node_id as u32));
 
                 // add to nodes
                 nodes.push(node);
@@ -52,8 +62,9 @@ impl EnvironmentManagement for EnvironmentManagementImpl {
             let dht = self.dht.read().unwrap();
             match dht.get(request.id as u16) {
                 None => None,
-                Some(addrs) =>
-                    Some(to_protobuf_node(request.id, addrs.0, addrs.1)),
+                Some(addrs) => Some(to_protobuf_node(request.id,
+                    addrs.0, addrs.1,
+                    self.gossip.public_key_of(request.id))),
             }
         };
 
@@ -67,11 +78,20 @@ impl EnvironmentManagement for EnvironmentManagementImpl {
 }
 
 fn to_protobuf_node(node_id: u32, rpc_addr: &Option<SocketAddr>,
-        xfer_addr: &Option<SocketAddr>) -> Environment {
+        xfer_addr: &Option<SocketAddr>,
+        public_key: Option<String>) -> Environment {
+    // fingerprint lets an operator sanity-check a node's identity
+    // without comparing the full public key by hand
+    let fingerprint = public_key.as_ref()
+        .map(|key| identity::fingerprint_of(key))
+        .unwrap_or_default();
+
     // initialize node protobuf
     Environment {
         id: node_id,
         rpc_addr: format!("{}", rpc_addr.unwrap()),
         xfer_addr: format!("{}", xfer_addr.unwrap()),
+        public_key: public_key.unwrap_or_default(),
+        fingerprint: fingerprint,
     }
 }
@@ -1,31 +1,72 @@
-use protobuf::{self, ImageBroadcastReply, ImageBroadcastRequest, ImageBroadcastType, ImageCoalesceReply, ImageCoalesceRequest, ImageFillReply, ImageFillRequest, ImageListRequest, ImageManagement, ImageManagementClient, ImageStoreReply, ImageStoreRequest, ImageSearchRequest, ImageSplitReply, ImageSplitRequest, Extent, File, Image, ImageFormat as ProtoImageFormat};
+use futures::stream::{self, StreamExt};
+use gdal::Dataset;
+use protobuf::{self, ImageBroadcastReply, ImageBroadcastRequest, ImageBroadcastType, ImageCoalesceReply, ImageCoalesceRequest, ImageFillReply, ImageFillRequest, ImageListRequest, ImageManagement, ImageManagementClient, ImageMerkleReply, ImageMerkleRequest, ImagePreviewReply, ImagePreviewRequest, ImageRepairReply, ImageRepairRequest, ImageStoreReply, ImageStoreRequest, ImageSearchIndexRequest, ImageSearchRequest, ImageSplitReply, ImageSplitRequest, Extent, File, Image, ImageFormat as ProtoImageFormat, MerkleRow as ProtoMerkleRow, ReplicaDiff};
 use swarm::prelude::Dht;
 use tokio::sync::mpsc::Receiver;
 use tonic::{Code, Request, Response, Status};
 
 use crate::album::AlbumManager;
+use crate::coverage::CoverageIndex;
+use crate::identity::NodeIdentity;
+use crate::merkle;
 use crate::task::{Task, TaskOg, TaskManager};
 use crate::task::coalesce::CoalesceTask;
 //use crate::task::fill::FillTask;
+use crate::task::deadletter::DeadLetterQueue;
+use crate::task::job::JobManager;
+use crate::task::preview::PreviewTask;
 use crate::task::store::{StoreEarthExplorerTask, ImageFormat};
 use crate::task::split::SplitTask;
 
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// bound on how long a single node's 'search' gets to respond during a
+/// cluster-wide repair fan-out - a hung peer is treated the same as an
+/// unreachable one rather than stalling the whole repair
+const REPAIR_SEARCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// bound on how long a single node's coalesce/fill/split RPC gets during
+/// a cluster-wide broadcast - a hung peer is recorded as a failure for
+/// that node rather than stalling every other node's fan-out
+const BROADCAST_RPC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// maximum number of broadcast RPCs kept in flight at once, so a large
+/// cluster doesn't open hundreds of simultaneous grpc connections
+const BROADCAST_CONCURRENCY: usize = 16;
 
 pub struct ImageManagementImpl {
     album_manager: Arc<RwLock<AlbumManager>>,
+    coverage_index: Arc<CoverageIndex>,
+    dead_letter_queue: DeadLetterQueue,
     dht: Arc<RwLock<Dht>>,
+    identity: Arc<NodeIdentity>,
+    job_manager: JobManager,
+    node_id: u32,
+    replication_factor: u8,
+    strict: bool,
     task_manager: Arc<RwLock<TaskManager>>,
 }
 
 impl ImageManagementImpl {
     pub fn new(album_manager: Arc<RwLock<AlbumManager>>,
-            dht: Arc<RwLock<Dht>>,
+            coverage_index: Arc<CoverageIndex>,
+            dead_letter_queue: DeadLetterQueue, dht: Arc<RwLock<Dht>>,
+            identity: Arc<NodeIdentity>, job_manager: JobManager,
+            node_id: u32, replication_factor: u8, strict: bool,
             task_manager: Arc<RwLock<TaskManager>>) -> ImageManagementImpl {
         ImageManagementImpl {
             album_manager: album_manager,
+            coverage_index: coverage_index,
+            dead_letter_queue: dead_letter_queue,
             dht: dht,
+            identity: identity,
+            job_manager: job_manager,
+            node_id: node_id,
+            replication_factor: replication_factor,
+            strict: strict,
             task_manager: task_manager,
         }
     }
@@ -52,85 +93,148 @@ impl ImageManagement for ImageManagementImpl {
             }
         }
 
-        // send broadcast message to each dht node
+        // fan the message out to every dht node concurrently, bounded by
+        // 'BROADCAST_CONCURRENCY' in-flight rpcs and a per-node timeout -
+        // the shared 'task_id' is reserved up front (from the request, or
+        // freshly generated) instead of being threaded node-to-node, so a
+        // single slow/unreachable node only shows up as a failure entry
+        // rather than stalling the rest of the cluster
         let mut coalesce_replies = HashMap::new();
         let mut fill_replies = HashMap::new();
         let mut split_replies = HashMap::new();
-
-        let mut task_id = None;
-        for (node_id, addr) in dht_nodes {
-            // initialize grpc client
-            let mut client = match ImageManagementClient::connect(
-                    format!("http://{}", addr)).await {
-                Ok(client) => client,
-                Err(e) => return Err(Status::new(Code::Unavailable,
-                    format!("connection to {} failed: {}", addr, e))),
-            };
-
-            // execute message at dht node
-            match ImageBroadcastType::from_i32(request.message_type).unwrap() {
-                ImageBroadcastType::Coalesce => {
-                    // compile new CoalesceRequest
-                    let mut coalesce_request =
-                        request.coalesce_request.clone().unwrap();
-                    if let Some(task_id) = task_id {
-                        coalesce_request.task_id = Some(task_id);
+        let mut failures = HashMap::new();
+
+        match ImageBroadcastType::from_i32(request.message_type).unwrap() {
+            ImageBroadcastType::Coalesce => {
+                let coalesce_request = request.coalesce_request.clone().unwrap();
+                let task_id = coalesce_request.task_id
+                    .unwrap_or_else(rand::random::<u64>);
+
+                let results = stream::iter(dht_nodes.into_iter()
+                        .map(|(node_id, addr)| {
+                    let mut coalesce_request = coalesce_request.clone();
+                    coalesce_request.task_id = Some(task_id);
+
+                    async move {
+                        let outcome = tokio::time::timeout(
+                                BROADCAST_RPC_TIMEOUT, async {
+                            let mut client = ImageManagementClient::connect(
+                                    format!("http://{}", addr)).await
+                                .map_err(|e| format!(
+                                    "connection to {} failed: {}", addr, e))?;
+
+                            client.coalesce(coalesce_request).await
+                                .map(|reply| reply.into_inner())
+                                .map_err(|e| format!(
+                                    "coalesce broadcast to {} failed: {}",
+                                    addr, e))
+                        }).await;
+
+                        let result = match outcome {
+                            Ok(result) => result,
+                            Err(_) => Err(format!(
+                                "coalesce broadcast to {} timed out", addr)),
+                        };
+
+                        (node_id as u32, result)
                     }
+                })).buffer_unordered(BROADCAST_CONCURRENCY)
+                    .collect::<Vec<_>>().await;
 
-                    // submit request
-                    let reply = match client.coalesce(coalesce_request).await {
-                        Ok(reply) => reply,
-                        Err(e) => return Err(Status::new(Code::Unknown,
-                            format!("coalesce broadcast failed: {}", e))),
-                    };
-                    coalesce_replies.insert(node_id as u32,
-                        reply.get_ref().to_owned());
-
-                    // process reply
-                    task_id = Some(reply.get_ref().task_id);
-                },
-                ImageBroadcastType::Fill => {
-                    // compile new FillRequest
-                    let mut fill_request =
-                        request.fill_request.clone().unwrap();
-                    if let Some(task_id) = task_id {
-                        fill_request.task_id = Some(task_id);
+                for (node_id, result) in results {
+                    match result {
+                        Ok(reply) => { coalesce_replies.insert(node_id, reply); },
+                        Err(e) => { failures.insert(node_id, e); },
                     }
-
-                    // submit request
-                    let reply = match client.fill(fill_request).await {
-                        Ok(reply) => reply,
-                        Err(e) => return Err(Status::new(Code::Unknown,
-                            format!("fill broadcast failed: {}", e))),
-                    };
-                    fill_replies.insert(node_id as u32,
-                        reply.get_ref().to_owned());
-
-                    // process reply
-                    task_id = Some(reply.get_ref().task_id);
-                },
-                ImageBroadcastType::Split => {
-                    // compile new SplitRequest
-                    let mut split_request =
-                        request.split_request.clone().unwrap();
-                    if let Some(task_id) = task_id {
-                        split_request.task_id = Some(task_id);
+                }
+            },
+            ImageBroadcastType::Fill => {
+                let fill_request = request.fill_request.clone().unwrap();
+                let task_id = fill_request.task_id
+                    .unwrap_or_else(rand::random::<u64>);
+
+                let results = stream::iter(dht_nodes.into_iter()
+                        .map(|(node_id, addr)| {
+                    let mut fill_request = fill_request.clone();
+                    fill_request.task_id = Some(task_id);
+
+                    async move {
+                        let outcome = tokio::time::timeout(
+                                BROADCAST_RPC_TIMEOUT, async {
+                            let mut client = ImageManagementClient::connect(
+                                    format!("http://{}", addr)).await
+                                .map_err(|e| format!(
+                                    "connection to {} failed: {}", addr, e))?;
+
+                            client.fill(fill_request).await
+                                .map(|reply| reply.into_inner())
+                                .map_err(|e| format!(
+                                    "fill broadcast to {} failed: {}",
+                                    addr, e))
+                        }).await;
+
+                        let result = match outcome {
+                            Ok(result) => result,
+                            Err(_) => Err(format!(
+                                "fill broadcast to {} timed out", addr)),
+                        };
+
+                        (node_id as u32, result)
                     }
+                })).buffer_unordered(BROADCAST_CONCURRENCY)
+                    .collect::<Vec<_>>().await;
 
-                    // submit request
-                    let reply = match client.split(split_request).await {
-                        Ok(reply) => reply,
-                        Err(e) => return Err(Status::new(Code::Unknown,
-                            format!("split broadcast failed: {}", e))),
-                    };
-                    split_replies.insert(node_id as u32,
-                        reply.get_ref().to_owned());
+                for (node_id, result) in results {
+                    match result {
+                        Ok(reply) => { fill_replies.insert(node_id, reply); },
+                        Err(e) => { failures.insert(node_id, e); },
+                    }
+                }
+            },
+            ImageBroadcastType::Split => {
+                let split_request = request.split_request.clone().unwrap();
+                let task_id = split_request.task_id
+                    .unwrap_or_else(rand::random::<u64>);
+
+                let results = stream::iter(dht_nodes.into_iter()
+                        .map(|(node_id, addr)| {
+                    let mut split_request = split_request.clone();
+                    split_request.task_id = Some(task_id);
+
+                    async move {
+                        let outcome = tokio::time::timeout(
+                                BROADCAST_RPC_TIMEOUT, async {
+                            let mut client = ImageManagementClient::connect(
+                                    format!("http://{}", addr)).await
+                                .map_err(|e| format!(
+                                    "connection to {} failed: {}", addr, e))?;
+
+                            client.split(split_request).await
+                                .map(|reply| reply.into_inner())
+                                .map_err(|e| format!(
+                                    "split broadcast to {} failed: {}",
+                                    addr, e))
+                        }).await;
+
+                        let result = match outcome {
+                            Ok(result) => result,
+                            Err(_) => Err(format!(
+                                "split broadcast to {} timed out", addr)),
+                        };
+
+                        (node_id as u32, result)
+                    }
+                })).buffer_unordered(BROADCAST_CONCURRENCY)
+                    .collect::<Vec<_>>().await;
 
-                    // process reply
-                    task_id = Some(reply.get_ref().task_id);
-                },
-            };
-        }
+                for (node_id, result) in results {
+                    match result {
+                        Ok(reply) => { split_replies.insert(node_id, reply); },
+                        Err(e) => { failures.insert(node_id, e); },
+                    }
+                }
+            },
+        };
 
         // initialize reply
         let reply = ImageBroadcastReply {
@@ -138,6 +242,7 @@ impl ImageManagement for ImageManagementImpl {
             coalesce_replies: coalesce_replies,
             fill_replies: fill_replies,
             split_replies: split_replies,
+            failures: failures,
         };
 
         Ok(Response::new(reply))
@@ -156,37 +261,34 @@ impl ImageManagement for ImageManagementImpl {
         // initailize task
         let task = CoalesceTask::new(album, self.dht.clone(),
             filter.end_timestamp, filter.geocode.clone(),
-            filter.max_cloud_coverage, filter.min_pixel_coverage,
+            self.identity.clone(), filter.max_cloud_coverage,
+            filter.min_pixel_coverage, self.node_id,
             filter.platform.clone(), filter.recurse, filter.source.clone(),
             request.platform.clone(), filter.start_timestamp,
-            request.thread_count as u8, request.window_seconds);
+            request.window_seconds);
 
-        // start task
-        /*let task_handle = match task.start().await {
-            Ok(task_handle) => task_handle,
-            Err(e) => return Err(Status::new(Code::Unknown,
-                format!("failed to start CoalesceTask: {}", e))),
-        };
+        // start task - checkpointed under the album directory so the
+        // id assigned here is stable across a node restart
+        let task_id = request.task_id.unwrap_or_else(rand::random::<u64>);
+        let directory = album.read().unwrap().get_directory().clone();
 
-        // register task with TaskHandler
-        let task_id = {
-            let mut task_manager = self.task_manager.write().unwrap();
-            match task_manager.register(task_handle, request.task_id) {
-                Ok(task_id) => task_id,
-                Err(e) => return Err(Status::new(Code::Unknown,
-                    format!("failed to register CoalesceTask: {}", e))),
-            }
-        };*/
-        // TODO - test this functionality
         let task_handle = {
             let task = Arc::new(task);
-            match task.start(request.thread_count as u8) {
+            match task.start(directory, task_id, request.thread_count as u8) {
                 Ok(task_handle) => task_handle,
                 Err(e) => return Err(Status::new(Code::Unknown,
                     format!("failed to start CoalesceTask: {}", e))),
             }
         };
-        let task_id = 0;
+
+        // register task with TaskHandler
+        {
+            let mut task_manager = self.task_manager.write().unwrap();
+            if let Err(e) = task_manager.register(task_handle, Some(task_id)) {
+                return Err(Status::new(Code::Unknown,
+                    format!("failed to register CoalesceTask: {}", e)));
+            }
+        }
 
         // initialize reply
         let reply = ImageCoalesceReply {
@@ -240,6 +342,8 @@ impl ImageManagement for ImageManagementImpl {
             let album = album.read().unwrap();
             let image_iter = match album.list(&filter.end_timestamp,
                     &filter.geocode, &filter.max_cloud_coverage,
+                    &filter.max_lat, &filter.max_lon,
+                    &filter.min_lat, &filter.min_lon,
                     &filter.min_pixel_coverage, &filter.platform,
                     filter.recurse, &filter.source,
                     &filter.start_timestamp) {
@@ -255,6 +359,7 @@ impl ImageManagement for ImageManagementImpl {
                     files.push(File {
                         path: file.0,
                         pixel_coverage: file.1,
+                        preview: file.3,
                         subdataset: file.2 as i32,
                     })
                 }
@@ -284,6 +389,109 @@ impl ImageManagement for ImageManagementImpl {
         Ok(Response::new(rx))
     }
 
+    async fn merkle(&self, request: Request<ImageMerkleRequest>)
+            -> Result<Response<ImageMerkleReply>, Status> {
+        trace!("ImageMerkleRequest: {:?}", request);
+        let request = request.get_ref();
+
+        // ensure album exists
+        let album = crate::rpc::assert_album_exists(
+            &self.album_manager, &request.album)?;
+
+        // build the subtree rooted at 'request.prefix' fresh from the
+        // local catalog - cheap relative to a full rescan, since it's
+        // scoped to just the rows under this prefix
+        let node = {
+            let album = album.read().unwrap();
+            let rows = match album.merkle_rows(&Some(request.prefix.clone())) {
+                Ok(rows) => rows,
+                Err(e) => return Err(Status::new(Code::Unknown,
+                    format!("failed to compute merkle rows: {}", e))),
+            };
+
+            merkle::build(rows, &request.prefix)
+        };
+
+        // a leaf hands back its raw rows so the caller can diff and
+        // insert what it's missing; an interior node hands back its
+        // children's hashes so the caller knows where to descend next
+        let children = node.children()
+            .map(|children| children.iter()
+                .map(|(c, child)| (c.to_string(), child.hash().to_vec()))
+                .collect())
+            .unwrap_or_default();
+
+        let rows = node.rows()
+            .map(|rows| rows.iter().map(|row| ProtoMerkleRow {
+                cloud_coverage: row.cloud_coverage,
+                geocode: row.geocode.clone(),
+                pixel_coverage: row.pixel_coverage,
+                platform: row.platform.clone(),
+                source: row.source.clone(),
+                subdataset: row.subdataset as i32,
+                tile: row.tile.clone(),
+                timestamp: row.timestamp,
+            }).collect())
+            .unwrap_or_default();
+
+        let reply = ImageMerkleReply {
+            hash: node.hash().to_vec(),
+            children: children,
+            rows: rows,
+        };
+
+        Ok(Response::new(reply))
+    }
+
+    /// generate (and cache) a downsampled preview raster for every file
+    /// matching 'request.filter', reporting progress/completion through
+    /// 'TaskManagement' the same way 'coalesce'/'split' do
+    async fn preview(&self, request: Request<ImagePreviewRequest>)
+            -> Result<Response<ImagePreviewReply>, Status> {
+        trace!("ImagePreviewRequest: {:?}", request);
+        let request = request.get_ref();
+        let filter = &request.filter;
+
+        // ensure album exists
+        let album = crate::rpc::assert_album_exists(
+            &self.album_manager, &request.album)?;
+
+        // initialize task - not checkpointed across restarts, a preview
+        // is a cheap, idempotent regenerate-on-demand cache fill rather
+        // than a job that must survive a crash
+        let task = Arc::new(PreviewTask::new(album.clone(),
+            filter.end_timestamp, filter.geocode.clone(),
+            request.max_dimension, filter.platform.clone(), filter.recurse,
+            filter.source.clone(), filter.start_timestamp));
+
+        let task_id = request.task_id.unwrap_or_else(rand::random::<u64>);
+        let directory = album.read().unwrap().get_directory().clone();
+
+        let task_handle = match task.start(
+                directory, task_id, request.thread_count as u8) {
+            Ok(task_handle) => task_handle,
+            Err(e) => return Err(Status::new(Code::Unknown,
+                format!("failed to start PreviewTask: {}", e))),
+        };
+
+        // register task with TaskHandler
+        let task_id = {
+            let mut task_manager = self.task_manager.write().unwrap();
+            match task_manager.register(task_handle, Some(task_id)) {
+                Ok(task_id) => task_id,
+                Err(e) => return Err(Status::new(Code::Unknown,
+                    format!("failed to register PreviewTask: {}", e))),
+            }
+        };
+
+        // initialize reply
+        let reply = ImagePreviewReply {
+            task_id: task_id,
+        };
+
+        Ok(Response::new(reply))
+    }
+
     type SearchStream = Receiver<Result<Extent, Status>>;
     async fn search(&self, request: Request<ImageSearchRequest>)
             -> Result<Response<Self::SearchStream>, Status> {
@@ -300,6 +508,8 @@ impl ImageManagement for ImageManagementImpl {
             let album = album.read().unwrap();
             let extent_iter = match album.search(&filter.end_timestamp,
                     &filter.geocode, &filter.max_cloud_coverage,
+                    &filter.max_lat, &filter.max_lon,
+                    &filter.min_lat, &filter.min_lon,
                     &filter.min_pixel_coverage, &filter.platform,
                     filter.recurse, &filter.source,
                     &filter.start_timestamp) {
@@ -331,6 +541,266 @@ impl ImageManagement for ImageManagementImpl {
         Ok(Response::new(rx))
     }
 
+    type SearchIndexStream = Receiver<Result<Extent, Status>>;
+    async fn search_index(&self, request: Request<ImageSearchIndexRequest>)
+            -> Result<Response<Self::SearchIndexStream>, Status> {
+        trace!("ImageSearchIndexRequest: {:?}", request);
+        let request = request.get_ref();
+        let filter = &request.filter;
+
+        let extents: Vec<Extent> = if request.exact {
+            // bypass the (possibly stale) cached coverage index and
+            // scan every locally-held album directly - authoritative
+            // for this node, but still only this node
+            let album_manager = self.album_manager.read().unwrap();
+            let mut extents = Vec::new();
+            for (_, album) in album_manager.iter() {
+                let album = album.read().unwrap();
+                let extent_iter = match album.search(&filter.end_timestamp,
+                        &filter.geocode, &filter.max_cloud_coverage,
+                        &filter.max_lat, &filter.max_lon,
+                        &filter.min_lat, &filter.min_lon,
+                        &filter.min_pixel_coverage, &filter.platform,
+                        filter.recurse, &filter.source,
+                        &filter.start_timestamp) {
+                    Ok(extent_iter) => extent_iter,
+                    Err(_) => continue,
+                };
+
+                extents.extend(extent_iter.iter().map(|x| Extent {
+                    count: x.0 as u32,
+                    geocode: x.1.clone(),
+                    platform: x.2.clone(),
+                    precision: x.3 as u32,
+                    source: x.4.clone(),
+                }));
+            }
+
+            extents
+        } else {
+            // approximate answer straight from this node's cached
+            // coverage index - no network round trip to any other node,
+            // and no coverage learned from other nodes either
+            self.coverage_index.search_all(&filter.geocode, &filter.platform)
+                .into_iter()
+                .map(|(count, geocode, platform, precision, source)| Extent {
+                    count: count as u32,
+                    geocode: geocode,
+                    platform: platform,
+                    precision: precision as u32,
+                    source: source,
+                }).collect()
+        };
+
+        // send extents though Sender channel
+        let (mut tx, rx) = tokio::sync::mpsc::channel(4);
+        tokio::spawn(async move {
+            for extent in extents {
+                if let Err(e) = tx.send(Ok(extent)).await {
+                    warn!("failed to send extent list: {}", e);
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(rx))
+    }
+
+    async fn repair(&self, request: Request<ImageRepairRequest>)
+            -> Result<Response<ImageRepairReply>, Status> {
+        trace!("ImageRepairRequest: {:?}", request);
+        let request = request.get_ref();
+        let filter = &request.filter;
+
+        // ensure album exists
+        let album = crate::rpc::assert_album_exists(
+            &self.album_manager, &request.album)?;
+        let dht_key_length = album.read().unwrap().get_dht_key_length();
+
+        // copy valid dht nodes
+        let mut dht_nodes = Vec::new();
+        let mut addr_to_node_id = HashMap::new();
+        {
+            let dht = self.dht.read().unwrap();
+            for (node_id, addrs) in dht.iter() {
+                if let Some(addr) = addrs.1 {
+                    dht_nodes.push((*node_id as u32, addr.clone()));
+                    addr_to_node_id.insert(addr.clone(), *node_id as u32);
+                }
+            }
+        }
+
+        // fan out 'search' across the cluster concurrently - a single
+        // slow or unreachable node no longer stalls the others, it's
+        // just omitted from 'actual' below
+        let futures = dht_nodes.iter().map(|(node_id, addr)| {
+            let node_id = *node_id;
+            let addr = addr.clone();
+            let search_request = ImageSearchRequest {
+                album: request.album.clone(),
+                filter: filter.clone(),
+            };
+
+            async move {
+                let outcome = tokio::time::timeout(REPAIR_SEARCH_TIMEOUT,
+                        async {
+                    let mut client = match ImageManagementClient::connect(
+                            format!("http://{}", addr)).await {
+                        Ok(client) => client,
+                        Err(e) => return Err(format!(
+                            "repair could not reach node {}: {}",
+                            node_id, e)),
+                    };
+
+                    let mut stream = match client.search(Request::new(
+                            search_request)).await {
+                        Ok(reply) => reply.into_inner(),
+                        Err(e) => return Err(format!(
+                            "repair search failed on node {}: {}",
+                            node_id, e)),
+                    };
+
+                    let mut extents = Vec::new();
+                    while let Ok(Some(extent)) = stream.message().await {
+                        extents.push(extent);
+                    }
+
+                    Ok(extents)
+                }).await;
+
+                let result = match outcome {
+                    Ok(result) => result,
+                    Err(_) => Err(format!(
+                        "repair search timed out on node {}", node_id)),
+                };
+
+                (node_id, result)
+            }
+        });
+
+        let mut actual: HashMap<(String, String, String, u32), Vec<u32>>
+            = HashMap::new();
+        for (node_id, result) in futures::future::join_all(futures).await {
+            match result {
+                Ok(extents) => {
+                    for extent in extents {
+                        actual.entry((extent.platform, extent.geocode,
+                                extent.source, extent.precision))
+                            .or_insert_with(Vec::new)
+                            .push(node_id);
+                    }
+                },
+                Err(e) => warn!("{}", e),
+            }
+        }
+
+        // diff the replicas we found against who the dht says should
+        // hold each geocode, and copy missing splits to the right hosts
+        let mut diffs = Vec::new();
+        let mut tasks_started = 0u32;
+        for ((platform, geocode, source, precision), actual_nodes)
+                in actual.iter() {
+            let expected_addrs = match crate::task::dht_lookup_replicas(
+                    &self.dht, dht_key_length, geocode,
+                    request.replication_factor as u8) {
+                Ok(addrs) => addrs,
+                Err(e) => {
+                    warn!("repair placement lookup failed for '{}': {}",
+                        geocode, e);
+                    continue;
+                },
+            };
+
+            let expected_nodes: Vec<u32> = expected_addrs.iter()
+                .filter_map(|addr| addr_to_node_id.get(addr).cloned())
+                .collect();
+
+            let missing_addrs: Vec<SocketAddr> = expected_addrs.iter()
+                .zip(expected_nodes.iter())
+                .filter(|(_, node_id)| !actual_nodes.contains(node_id))
+                .map(|(addr, _)| *addr)
+                .collect();
+
+            if missing_addrs.is_empty() {
+                continue;
+            }
+
+            diffs.push(ReplicaDiff {
+                platform: platform.clone(),
+                geocode: geocode.clone(),
+                source: source.clone(),
+                precision: *precision,
+                expected: expected_nodes.clone(),
+                actual: actual_nodes.clone(),
+            });
+
+            if request.dry_run {
+                continue;
+            }
+
+            // we can only push a copy if we ourselves hold one locally -
+            // pulling from a remote replica isn't supported yet since
+            // there's no xfer read path between nodes
+            if !actual_nodes.contains(&self.node_id) {
+                warn!("repair has no local copy of '{}/{}/{}' to push \
+                    to {} missing replica(s)",
+                    platform, geocode, source, missing_addrs.len());
+                continue;
+            }
+
+            let files = {
+                let album = album.read().unwrap();
+                match album.list(&None, &Some(geocode.clone()), &None,
+                        &None, &None, &None, &None, &None,
+                        &Some(platform.clone()), false,
+                        &Some(source.clone()), &None) {
+                    Ok(files) => files,
+                    Err(e) => {
+                        warn!("repair failed to list local copy of \
+                            '{}/{}/{}': {}", platform, geocode, source, e);
+                        continue;
+                    },
+                }
+            };
+
+            for (image, st_files) in files.iter() {
+                for st_file in st_files.iter() {
+                    let dataset = match Dataset::open(
+                            std::path::Path::new(&st_file.0)) {
+                        Ok(dataset) => dataset,
+                        Err(e) => {
+                            warn!("repair failed to open '{}': {}",
+                                st_file.0, e);
+                            continue;
+                        },
+                    };
+
+                    for addr in missing_addrs.iter() {
+                        if let Err(e) = crate::transfer::send_image(addr,
+                                &self.identity, self.node_id,
+                                &request.album, &dataset, geocode,
+                                st_file.1, platform, source, st_file.2,
+                                &image.4, image.5, st_file.3, None) {
+                            warn!("repair failed to replicate '{}' to \
+                                {}: {}", st_file.0, addr, e);
+                            continue;
+                        }
+
+                        tasks_started += 1;
+                    }
+                }
+            }
+        }
+
+        // initialize reply
+        let reply = ImageRepairReply {
+            diffs: diffs,
+            tasks_started: tasks_started,
+        };
+
+        Ok(Response::new(reply))
+    }
+
     async fn split(&self, request: Request<ImageSplitRequest>)
             -> Result<Response<ImageSplitReply>, Status> {
         trace!("ImageSplitRequest: {:?}", request);
@@ -344,37 +814,34 @@ impl ImageManagement for ImageManagementImpl {
         // initialize task
         let task = SplitTask::new(album, self.dht.clone(),
             filter.end_timestamp.clone(), filter.geocode.clone(),
-            request.geocode_bound.clone(), filter.platform.clone(),
+            request.geocode_bound.clone(), self.identity.clone(),
+            self.node_id, filter.platform.clone(),
             request.precision as usize, filter.recurse,
-            filter.start_timestamp.clone(), request.thread_count as u8);
+            filter.start_timestamp.clone());
 
-        // start task
-        /*let task_handle = match task.start().await {
-            Ok(task_handle) => task_handle,
-            Err(e) => return Err(Status::new(Code::Unknown,
-                format!("failed to start SplitTask: {}", e))),
-        };
+        // start task - checkpointed under the album directory so the
+        // id assigned here is stable across a node restart
+        let task_id = request.task_id.unwrap_or_else(rand::random::<u64>);
+        let directory = album.read().unwrap().get_directory().clone();
 
-        // register task with TaskHandler
-        let task_id = {
-            let mut task_manager = self.task_manager.write().unwrap();
-            match task_manager.register(task_handle, request.task_id) {
-                Ok(task_id) => task_id,
-                Err(e) => return Err(Status::new(Code::Unknown,
-                    format!("failed to register SplitTask: {}", e))),
-            }
-        };*/
-        // TODO - test this functionality
         let task_handle = {
             let task = Arc::new(task);
-            match task.start(request.thread_count as u8) {
+            match task.start(directory, task_id, request.thread_count as u8) {
                 Ok(task_handle) => task_handle,
                 Err(e) => return Err(Status::new(Code::Unknown,
                     format!("failed to start SplitTask: {}", e))),
             }
         };
-        let task_id = 0;
- 
+
+        // register task with TaskHandler
+        {
+            let mut task_manager = self.task_manager.write().unwrap();
+            if let Err(e) = task_manager.register(task_handle, Some(task_id)) {
+                return Err(Status::new(Code::Unknown,
+                    format!("failed to register SplitTask: {}", e)));
+            }
+        }
+
         // initialize reply
         let reply = ImageSplitReply {
             task_id: task_id,
@@ -400,37 +867,38 @@ impl ImageManagement for ImageManagementImpl {
             ProtoImageFormat::Sentinel => ImageFormat::Sentinel,
         };
 
-        let task = StoreEarthExplorerTask::new(album, self.dht.clone(),
-            format, request.glob.clone(), request.precision as usize,
-            request.thread_count as u8);
+        let directory = album.read().unwrap().get_directory().clone();
+
+        let task = StoreEarthExplorerTask::new(album,
+            self.dead_letter_queue.clone(), self.dht.clone(),
+            directory.clone(), format, request.glob.clone(),
+            self.identity.clone(), self.job_manager.clone(), self.node_id,
+            request.precision as usize, self.replication_factor,
+            request.s3_access_key.clone(), request.s3_endpoint.clone(),
+            request.s3_region.clone(), request.s3_secret_key.clone(),
+            self.strict);
+
+        // start task - checkpointed under the album directory so the
+        // id assigned here is stable across a node restart
+        let task_id = request.task_id.unwrap_or_else(rand::random::<u64>);
 
-        // start task
-        /*let task_handle = match task.start().await {
-            Ok(task_handle) => task_handle,
-            Err(e) => return Err(Status::new(Code::Unknown,
-                format!("failed to start StoreTask: {}", e))),
-        };*/
- 
-        // TODO - test this functionality
         let task_handle = {
             let task = Arc::new(task);
-            match task.start(request.thread_count as u8) {
+            match task.start(directory, task_id, request.thread_count as u8) {
                 Ok(task_handle) => task_handle,
                 Err(e) => return Err(Status::new(Code::Unknown,
-                    format!("failed to start OpenTask: {}", e))),
+                    format!("failed to start StoreTask: {}", e))),
             }
         };
 
         // register task with TaskHandler
-        /*let task_id = {
+        {
             let mut task_manager = self.task_manager.write().unwrap();
-            match task_manager.register(task_handle, request.task_id) {
-                Ok(task_id) => task_id,
-                Err(e) => return Err(Status::new(Code::Unknown,
-                    format!("failed to register StoreTask: {}", e))),
+            if let Err(e) = task_manager.register(task_handle, Some(task_id)) {
+                return Err(Status::new(Code::Unknown,
+                    format!("failed to register StoreTask: {}", e)));
             }
-        };*/
-        let task_id = 0;
+        }
 
         // initialize reply
         let reply = ImageStoreReply {
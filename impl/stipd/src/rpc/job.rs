@@ -0,0 +1,119 @@
+use protobuf::{Job, JobCancelReply, JobCancelRequest, JobListReply, JobListRequest, JobManagement, JobStatusReply, JobStatusRequest};
+use tokio::sync::mpsc::Receiver;
+use tonic::{Code, Request, Response, Status};
+
+use crate::task::job::JobManager;
+
+use std::time::Duration;
+
+pub struct JobManagementImpl {
+    job_manager: JobManager,
+}
+
+impl JobManagementImpl {
+    pub fn new(job_manager: JobManager) -> JobManagementImpl {
+        JobManagementImpl {
+            job_manager: job_manager,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl JobManagement for JobManagementImpl {
+    async fn cancel(&self, request: Request<JobCancelRequest>)
+            -> Result<Response<JobCancelReply>, Status> {
+        trace!("JobCancelRequest: {:?}", request);
+        let request = request.get_ref();
+
+        // stop dispatching new split units - units already handed to a
+        // worker still finish and checkpoint normally
+        match self.job_manager.get(request.job_id) {
+            Some(job_handle) => job_handle.read().unwrap().cancel(),
+            None => return Err(Status::new(Code::NotFound,
+                format!("no job with id {}", request.job_id))),
+        }
+
+        // initialize reply
+        let reply = JobCancelReply {
+        };
+
+        Ok(Response::new(reply))
+    }
+
+    async fn list(&self, request: Request<JobListRequest>)
+            -> Result<Response<JobListReply>, Status> {
+        trace!("JobListRequest: {:?}", request);
+
+        // populate jobs from job_manager
+        let mut jobs = Vec::new();
+        for (job_id, job_handle) in self.job_manager.iter() {
+            let job_handle = job_handle.read().unwrap();
+            jobs.push(Job {
+                cancelled: job_handle.cancelled(),
+                completed_count: job_handle.completed_count(),
+                completion_percent: job_handle.completion_percent(),
+                failed_count: job_handle.failed_count(),
+                id: job_id,
+                record: job_handle.record().to_string_lossy().to_string(),
+                running: job_handle.running(),
+                throughput: job_handle.throughput(),
+                total_count: job_handle.total_count(),
+            });
+        }
+
+        // initialize reply
+        let reply = JobListReply {
+            jobs: jobs,
+        };
+
+        Ok(Response::new(reply))
+    }
+
+    type StatusStream = Receiver<Result<JobStatusReply, Status>>;
+    async fn status(&self, request: Request<JobStatusRequest>)
+            -> Result<Response<Self::StatusStream>, Status> {
+        trace!("JobStatusRequest: {:?}", request);
+        let request = request.get_ref();
+
+        let job_handle = match self.job_manager.get(request.job_id) {
+            Some(job_handle) => job_handle,
+            None => return Err(Status::new(Code::NotFound,
+                format!("no job with id {}", request.job_id))),
+        };
+
+        // poll the job's progress counters until it terminates,
+        // streaming an update each tick rather than making the client
+        // wait for an all-or-nothing final reply
+        let (mut tx, rx) = tokio::sync::mpsc::channel(4);
+        tokio::spawn(async move {
+            loop {
+                let reply = {
+                    let job_handle = job_handle.read().unwrap();
+                    JobStatusReply {
+                        cancelled: job_handle.cancelled(),
+                        completed_count: job_handle.completed_count(),
+                        completion_percent: job_handle.completion_percent(),
+                        failed_count: job_handle.failed_count(),
+                        running: job_handle.running(),
+                        throughput: job_handle.throughput(),
+                        total_count: job_handle.total_count(),
+                    }
+                };
+
+                let running = reply.running;
+                if let Err(e) = tx.send(Ok(reply)).await {
+                    warn!("failed to send job status: {}", e);
+                    break;
+                }
+
+                if !running {
+                    break;
+                }
+
+                tokio::time::delay_for(Duration::from_millis(500)).await;
+            }
+        });
+
+        Ok(Response::new(rx))
+    }
+}
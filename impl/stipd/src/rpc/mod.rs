@@ -2,6 +2,8 @@ use tonic::{Code, Status};
 
 pub mod album;
 pub mod data;
+pub mod deadletter;
+pub mod job;
 pub mod node;
 pub mod task;
 
@@ -2,18 +2,22 @@ use protobuf::{Node, NodeListReply, NodeListRequest, NodeLocateReply, NodeLocate
 use swarm::prelude::Dht;
 use tonic::{Request, Response, Status};
 
+use crate::gossip::GossipState;
+
 use std::collections::hash_map::DefaultHasher;
 use std::hash::Hasher;
 use std::sync::Arc;
 
 pub struct NodeManagementImpl {
     dht: Arc<Dht>,
+    gossip: Arc<GossipState>,
 }
 
 impl NodeManagementImpl {
-    pub fn new(dht: Arc<Dht>) -> NodeManagementImpl {
+    pub fn new(dht: Arc<Dht>, gossip: Arc<GossipState>) -> NodeManagementImpl {
         NodeManagementImpl {
             dht: dht,
+            gossip: gossip,
         }
     }
 }
@@ -24,19 +28,31 @@ impl NodeManagement for NodeManagementImpl {
             -> Result<Response<NodeListReply>, Status> {
         trace!("NodeListRequest: {:?}", request);
 
-        // populate cluster nodes from dht
+        // populate cluster nodes from the gossip membership map (which
+        // 'gossip::start' fills from this node's own dht view, so it is
+        // no more current than that view) - fall back to the dht directly
+        // for any node the gossip map hasn't picked up yet
         let mut nodes = Vec::new();
-        for node in self.dht.nodes() {
-            // add to nodes
+        for (node_id, info) in self.gossip.iter() {
             nodes.push(Node {
-                id: node.get_id(),
-                rpc_addr: format!("{}:{}", node.get_ip_address(),
-                    node.get_metadata("rpc_port").unwrap()),
-                xfer_addr: format!("{}:{}", node.get_ip_address(),
-                    node.get_metadata("xfer_port").unwrap()),
+                id: node_id,
+                rpc_addr: info.rpc_addr,
+                xfer_addr: info.xfer_addr,
             });
         }
 
+        for node in self.dht.nodes() {
+            if !nodes.iter().any(|n| n.id == node.get_id()) {
+                nodes.push(Node {
+                    id: node.get_id(),
+                    rpc_addr: format!("{}:{}", node.get_ip_address(),
+                        node.get_metadata("rpc_port").unwrap()),
+                    xfer_addr: format!("{}:{}", node.get_ip_address(),
+                        node.get_metadata("xfer_port").unwrap()),
+                });
+            }
+        }
+
         // initialize reply
         let reply = NodeListReply {
             nodes: nodes,
@@ -55,23 +71,55 @@ impl NodeManagement for NodeManagementImpl {
         hasher.write(request.geocode.as_bytes());
         let hash = hasher.finish();
 
-        // discover hash location
-        let node = match self.dht.locate(hash) {
-            Some(node) => {
-                Some( Node {
-                    id: node.get_id(),
-                    rpc_addr: format!("{}:{}", node.get_ip_address(),
-                        node.get_metadata("rpc_port").unwrap()),
-                    xfer_addr: format!("{}:{}", node.get_ip_address(),
-                    node.get_metadata("xfer_port").unwrap()),
-                })
-            },
-            None => None,
+        // find the primary owner, then rank every dht node in a
+        // deterministic ring order keyed by node id and walk forward
+        // from the primary collecting its next 'replication_factor' - 1
+        // distinct successors (wrapping at the end, skipping duplicate
+        // ids caused by virtual nodes). this ring-successor set is not
+        // computed the way 'task::dht_lookup_replicas' picks a writer's
+        // replicas (weighted, zone-aware sampling), so it need not match
+        // the nodes a prior write went to
+        let primary_id = match self.dht.locate(hash) {
+            Some(node) => node.get_id(),
+            None => return Ok(Response::new(NodeLocateReply {
+                nodes: Vec::new(),
+                quorum: 0,
+            })),
         };
 
+        let mut ring = self.dht.nodes();
+        ring.sort_by_key(|node| node.get_id());
+
+        let primary_index = ring.iter()
+            .position(|node| node.get_id() == primary_id)
+            .unwrap_or(0);
+
+        let replication_factor = crate::task::DEFAULT_REPLICATION_FACTOR;
+        let mut nodes = Vec::new();
+        let mut seen_ids = std::collections::HashSet::new();
+        for offset in 0..ring.len() {
+            if nodes.len() >= replication_factor as usize {
+                break;
+            }
+
+            let node = &ring[(primary_index + offset) % ring.len()];
+            if !seen_ids.insert(node.get_id()) {
+                continue;
+            }
+
+            nodes.push(Node {
+                id: node.get_id(),
+                rpc_addr: format!("{}:{}", node.get_ip_address(),
+                    node.get_metadata("rpc_port").unwrap()),
+                xfer_addr: format!("{}:{}", node.get_ip_address(),
+                    node.get_metadata("xfer_port").unwrap()),
+            });
+        }
+
         // initialize reply
         let reply = NodeLocateReply {
-            node: node,
+            quorum: crate::task::write_quorum(replication_factor) as u32,
+            nodes: nodes,
         };
 
         Ok(Response::new(reply))
@@ -1,11 +1,13 @@
-use protobuf::{Task, TaskClearReply, TaskClearRequest, TaskBroadcastReply, TaskBroadcastRequest, TaskBroadcastType, TaskListReply, TaskListRequest, TaskManagement, TaskManagementClient};
+use protobuf::{Task, TaskCancelReply, TaskCancelRequest, TaskClearReply, TaskClearRequest, TaskBroadcastReply, TaskBroadcastRequest, TaskBroadcastType, TaskListReply, TaskListRequest, TaskManagement, TaskManagementClient, TaskPauseReply, TaskPauseRequest, TaskResumeReply, TaskResumeRequest, TaskStatusReply, TaskStatusRequest};
 use swarm::prelude::Dht;
+use tokio::sync::mpsc::Receiver;
 use tonic::{Code, Request, Response, Status};
 
 use crate::task::TaskManager;
 
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 pub struct TaskManagementImpl {
     dht: Arc<Dht>,
@@ -29,53 +31,159 @@ impl TaskManagement for TaskManagementImpl {
         trace!("TaskBroadcastRequest: {:?}", request);
         let request = request.get_ref();
 
-        // send broadcast message to each dht node
+        // query every dht node concurrently - a single unreachable or
+        // erroring node no longer hides the results of the rest, it's
+        // just recorded against that node's id in 'errors'
+        let mut cancel_replies = HashMap::new();
         let mut clear_replies = HashMap::new();
         let mut list_replies = HashMap::new();
+        let mut pause_replies = HashMap::new();
+        let mut resume_replies = HashMap::new();
+        let mut errors = HashMap::new();
 
-        for node in self.dht.nodes() {
-            // get rpc address
-            let addr = format!("http://{}:{}", node.get_ip_address(),
-                node.get_metadata("rpc_port").unwrap());
-
-            // initialize grpc client
-            let mut client = match TaskManagementClient::connect(
-                    addr.clone()).await {
-                Ok(client) => client,
-                Err(e) => return Err(Status::new(Code::Unavailable,
-                    format!("connection to {} failed: {}", addr, e))),
-            };
-
-            // execute message at dht node
-            match TaskBroadcastType::from_i32(request.message_type).unwrap() {
-                TaskBroadcastType::TaskClear => {
-                    let reply = match client.clear(request
-                            .clear_request.clone().unwrap()).await {
-                        Ok(reply) => reply,
-                        Err(e) => return Err(Status::new(Code::Unknown,
-                            format!("clear broadcast failed: {}", e))),
-                    };
-                    clear_replies.insert(node.get_id(),
-                        reply.get_ref().to_owned());
-                },
-                TaskBroadcastType::TaskList => {
-                    let reply = match client.list(request
-                            .list_request.clone().unwrap()).await {
-                        Ok(reply) => reply,
-                        Err(e) => return Err(Status::new(Code::Unknown,
-                            format!("list broadcast failed: {}", e))),
-                    };
-                    list_replies.insert(node.get_id(),
-                        reply.get_ref().to_owned());
-                },
-            };
-        }
+        let nodes = self.dht.nodes();
+        match TaskBroadcastType::from_i32(request.message_type).unwrap() {
+            TaskBroadcastType::TaskClear => {
+                let clear_request = request.clear_request.clone().unwrap();
+                let futures = nodes.into_iter().map(|node| {
+                    let clear_request = clear_request.clone();
+                    async move {
+                        let node_id = node.get_id();
+                        let addr = format!("http://{}:{}", node.get_ip_address(),
+                            node.get_metadata("rpc_port").unwrap());
+                        let result = match TaskManagementClient::connect(addr.clone()).await {
+                            Ok(mut client) => client.clear(clear_request).await
+                                .map(|reply| reply.into_inner())
+                                .map_err(|e| format!(
+                                    "clear broadcast failed: {}", e)),
+                            Err(e) => Err(format!(
+                                "connection to {} failed: {}", addr, e)),
+                        };
+                        (node_id, result)
+                    }
+                });
+                for (node_id, result) in futures::future::join_all(futures).await {
+                    match result {
+                        Ok(reply) => { clear_replies.insert(node_id, reply); },
+                        Err(e) => { errors.insert(node_id, e); },
+                    }
+                }
+            },
+            TaskBroadcastType::TaskList => {
+                let list_request = request.list_request.clone().unwrap();
+                let futures = nodes.into_iter().map(|node| {
+                    let list_request = list_request.clone();
+                    async move {
+                        let node_id = node.get_id();
+                        let addr = format!("http://{}:{}", node.get_ip_address(),
+                            node.get_metadata("rpc_port").unwrap());
+                        let result = match TaskManagementClient::connect(addr.clone()).await {
+                            Ok(mut client) => client.list(list_request).await
+                                .map(|reply| reply.into_inner())
+                                .map_err(|e| format!(
+                                    "list broadcast failed: {}", e)),
+                            Err(e) => Err(format!(
+                                "connection to {} failed: {}", addr, e)),
+                        };
+                        (node_id, result)
+                    }
+                });
+                for (node_id, result) in futures::future::join_all(futures).await {
+                    match result {
+                        Ok(reply) => { list_replies.insert(node_id, reply); },
+                        Err(e) => { errors.insert(node_id, e); },
+                    }
+                }
+            },
+            TaskBroadcastType::TaskPause => {
+                let pause_request = request.pause_request.clone().unwrap();
+                let futures = nodes.into_iter().map(|node| {
+                    let pause_request = pause_request.clone();
+                    async move {
+                        let node_id = node.get_id();
+                        let addr = format!("http://{}:{}", node.get_ip_address(),
+                            node.get_metadata("rpc_port").unwrap());
+                        let result = match TaskManagementClient::connect(addr.clone()).await {
+                            Ok(mut client) => client.pause(pause_request).await
+                                .map(|reply| reply.into_inner())
+                                .map_err(|e| format!(
+                                    "pause broadcast failed: {}", e)),
+                            Err(e) => Err(format!(
+                                "connection to {} failed: {}", addr, e)),
+                        };
+                        (node_id, result)
+                    }
+                });
+                for (node_id, result) in futures::future::join_all(futures).await {
+                    match result {
+                        Ok(reply) => { pause_replies.insert(node_id, reply); },
+                        Err(e) => { errors.insert(node_id, e); },
+                    }
+                }
+            },
+            TaskBroadcastType::TaskResume => {
+                let resume_request = request.resume_request.clone().unwrap();
+                let futures = nodes.into_iter().map(|node| {
+                    let resume_request = resume_request.clone();
+                    async move {
+                        let node_id = node.get_id();
+                        let addr = format!("http://{}:{}", node.get_ip_address(),
+                            node.get_metadata("rpc_port").unwrap());
+                        let result = match TaskManagementClient::connect(addr.clone()).await {
+                            Ok(mut client) => client.resume(resume_request).await
+                                .map(|reply| reply.into_inner())
+                                .map_err(|e| format!(
+                                    "resume broadcast failed: {}", e)),
+                            Err(e) => Err(format!(
+                                "connection to {} failed: {}", addr, e)),
+                        };
+                        (node_id, result)
+                    }
+                });
+                for (node_id, result) in futures::future::join_all(futures).await {
+                    match result {
+                        Ok(reply) => { resume_replies.insert(node_id, reply); },
+                        Err(e) => { errors.insert(node_id, e); },
+                    }
+                }
+            },
+            TaskBroadcastType::TaskCancel => {
+                let cancel_request = request.cancel_request.clone().unwrap();
+                let futures = nodes.into_iter().map(|node| {
+                    let cancel_request = cancel_request.clone();
+                    async move {
+                        let node_id = node.get_id();
+                        let addr = format!("http://{}:{}", node.get_ip_address(),
+                            node.get_metadata("rpc_port").unwrap());
+                        let result = match TaskManagementClient::connect(addr.clone()).await {
+                            Ok(mut client) => client.cancel(cancel_request).await
+                                .map(|reply| reply.into_inner())
+                                .map_err(|e| format!(
+                                    "cancel broadcast failed: {}", e)),
+                            Err(e) => Err(format!(
+                                "connection to {} failed: {}", addr, e)),
+                        };
+                        (node_id, result)
+                    }
+                });
+                for (node_id, result) in futures::future::join_all(futures).await {
+                    match result {
+                        Ok(reply) => { cancel_replies.insert(node_id, reply); },
+                        Err(e) => { errors.insert(node_id, e); },
+                    }
+                }
+            },
+        };
 
         // initialize reply
         let reply = TaskBroadcastReply {
             message_type: request.message_type,
+            cancel_replies: cancel_replies,
             clear_replies: clear_replies,
             list_replies: list_replies,
+            pause_replies: pause_replies,
+            resume_replies: resume_replies,
+            errors: errors,
         };
 
         Ok(Response::new(reply))
@@ -101,6 +209,75 @@ impl TaskManagement for TaskManagementImpl {
         Ok(Response::new(reply))
     }
 
+    async fn pause(&self, request: Request<TaskPauseRequest>)
+            -> Result<Response<TaskPauseReply>, Status> {
+        trace!("TaskPauseRequest: {:?}", request);
+        let request = request.get_ref();
+
+        // pause the task - a record a pool thread is already running
+        // finishes, and the task's queued records are left untouched
+        // until resume() or cancel()
+        {
+            let task_manager = self.task_manager.read().unwrap();
+            match task_manager.get(request.task_id) {
+                Some(task_handle) => task_handle.read().unwrap().pause(),
+                None => return Err(Status::new(Code::NotFound,
+                    format!("no task with id {}", request.task_id))),
+            }
+        }
+
+        // initialize reply
+        let reply = TaskPauseReply {
+        };
+
+        Ok(Response::new(reply))
+    }
+
+    async fn resume(&self, request: Request<TaskResumeRequest>)
+            -> Result<Response<TaskResumeReply>, Status> {
+        trace!("TaskResumeRequest: {:?}", request);
+        let request = request.get_ref();
+
+        // let pool threads steal this task's queued records again
+        {
+            let task_manager = self.task_manager.read().unwrap();
+            match task_manager.get(request.task_id) {
+                Some(task_handle) => task_handle.read().unwrap().resume(),
+                None => return Err(Status::new(Code::NotFound,
+                    format!("no task with id {}", request.task_id))),
+            }
+        }
+
+        // initialize reply
+        let reply = TaskResumeReply {
+        };
+
+        Ok(Response::new(reply))
+    }
+
+    async fn cancel(&self, request: Request<TaskCancelRequest>)
+            -> Result<Response<TaskCancelReply>, Status> {
+        trace!("TaskCancelRequest: {:?}", request);
+        let request = request.get_ref();
+
+        // stop the task after its in-flight records finish - it won't
+        // be resumed on the next restart
+        {
+            let task_manager = self.task_manager.read().unwrap();
+            match task_manager.get(request.task_id) {
+                Some(task_handle) => task_handle.read().unwrap().cancel(),
+                None => return Err(Status::new(Code::NotFound,
+                    format!("no task with id {}", request.task_id))),
+            }
+        }
+
+        // initialize reply
+        let reply = TaskCancelReply {
+        };
+
+        Ok(Response::new(reply))
+    }
+
     async fn list(&self, request: Request<TaskListRequest>)
             -> Result<Response<TaskListReply>, Status> {
         trace!("TaskListRequest: {:?}", request);
@@ -112,8 +289,12 @@ impl TaskManagement for TaskManagementImpl {
             for (task_id, task_handle) in task_manager.iter() {
                 // initialize task protobuf
                 tasks.push(Task {
+                    cancelled: task_handle.cancelled(),
                     completed_count: task_handle.completed_count(),
+                    completion_percent: task_handle.completion_percent(),
                     id: *task_id,
+                    non_critical_error_count: task_handle.non_critical_error_count(),
+                    paused: task_handle.paused(),
                     running: task_handle.running(),
                     skipped_count: task_handle.skipped_count(),
                     total_count: task_handle.total_count(),
@@ -128,4 +309,65 @@ impl TaskManagement for TaskManagementImpl {
 
         Ok(Response::new(reply))
     }
+
+    type StatusStream = Receiver<Result<TaskStatusReply, Status>>;
+    async fn status(&self, request: Request<TaskStatusRequest>)
+            -> Result<Response<Self::StatusStream>, Status> {
+        trace!("TaskStatusRequest: {:?}", request);
+        let request = request.get_ref();
+
+        let task_id = request.task_id;
+        let task_handle = {
+            let task_manager = self.task_manager.read().unwrap();
+            match task_manager.get(task_id) {
+                Some(task_handle) => task_handle,
+                None => return Err(Status::new(Code::NotFound,
+                    format!("no task with id {}", task_id))),
+            }
+        };
+
+        // poll the task's progress counters until it terminates,
+        // streaming an update each tick rather than making the client
+        // wait for an all-or-nothing final reply
+        let (mut tx, rx) = tokio::sync::mpsc::channel(4);
+        tokio::spawn(async move {
+            loop {
+                let reply = {
+                    let task_handle = task_handle.read().unwrap();
+                    TaskStatusReply {
+                        cancelled: task_handle.cancelled(),
+                        completed_count: task_handle.completed_count(),
+                        completion_percent: task_handle.completion_percent(),
+                        errors: task_handle.errors(),
+                        failed_count: task_handle.skipped_count(),
+                        non_critical_error_count:
+                            task_handle.non_critical_error_count(),
+                        non_critical_errors: task_handle.non_critical_errors()
+                            .iter()
+                            .map(|e| format!("record={} geocode={}: {}",
+                                e.record, e.geocode, e.reason))
+                            .collect(),
+                        paused: task_handle.paused(),
+                        running: task_handle.running(),
+                        task_id: task_id,
+                        total_count: task_handle.total_count(),
+                    }
+                };
+
+                let running = reply.running;
+                if let Err(e) = tx.send(Ok(reply)).await {
+                    warn!("failed to send task status: {}", e);
+                    break;
+                }
+
+                if !running {
+                    break;
+                }
+
+                tokio::time::delay_for(Duration::from_millis(500)).await;
+            }
+        });
+
+        Ok(Response::new(rx))
+    }
 }
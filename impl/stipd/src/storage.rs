@@ -0,0 +1,190 @@
+use rusoto_core::{HttpClient, Region};
+use rusoto_credential::StaticProvider;
+use rusoto_s3::{HeadObjectRequest, S3, S3Client};
+
+use std::error::Error;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// resolves an album-relative tile path (e.g.
+/// 'platform/geocode/source/tile-subdataset.tif') to the string gdal
+/// should open or create-copy to, and answers existence checks for it -
+/// the two operations 'Album::write'/'resolve_file_path' actually need.
+/// gdal's own '/vsis3/' handler already performs the multipart upload
+/// and ranged reads an s3-backed tile needs, so no custom http transfer
+/// code lives here beyond the control-plane 'exists' check
+pub trait StorageBackend: std::fmt::Debug {
+    /// the path or vsi url gdal should use to open/create this tile
+    fn resolve(&self, relative: &Path) -> String;
+
+    /// true if a tile already exists at this location
+    fn exists(&self, relative: &Path) -> bool;
+
+    /// create any parent directories a write to 'relative' will need -
+    /// a no-op for backends with no directory concept
+    fn prepare_write(&self, relative: &Path) -> Result<(), Box<dyn Error>>;
+
+    /// whether 'resolve' points at a path on this machine's local
+    /// filesystem - gates operations (hardlink dedup, chmod) that only
+    /// make sense against a real local file
+    fn is_local(&self) -> bool;
+
+    /// every tile this backend currently holds, as a path or vsi url
+    /// gdal can open directly - matches the 'platform/geocode/source/
+    /// *tif' layout 'resolve' writes tiles into, so 'Album::get_paths'
+    /// doesn't need to know whether it's walking a local directory or
+    /// listing an object store
+    fn list(&self) -> Result<Vec<PathBuf>, Box<dyn Error>>;
+}
+
+#[derive(Debug)]
+pub struct LocalBackend {
+    directory: PathBuf,
+}
+
+impl LocalBackend {
+    pub fn new(directory: PathBuf) -> LocalBackend {
+        LocalBackend {
+            directory: directory,
+        }
+    }
+}
+
+impl StorageBackend for LocalBackend {
+    fn resolve(&self, relative: &Path) -> String {
+        self.directory.join(relative).to_string_lossy().to_string()
+    }
+
+    fn exists(&self, relative: &Path) -> bool {
+        self.directory.join(relative).exists()
+    }
+
+    fn prepare_write(&self, relative: &Path) -> Result<(), Box<dyn Error>> {
+        let mut path = self.directory.clone();
+        if let Some(parent) = relative.parent() {
+            for component in parent.components() {
+                path.push(component);
+                if !path.exists() {
+                    fs::create_dir(&path)?;
+                    let mut permissions =
+                        fs::metadata(&path)?.permissions();
+                    permissions.set_mode(0o755);
+                    fs::set_permissions(&path, permissions)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    fn list(&self) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let glob_expression = format!("{}/*/*/*/*tif",
+            self.directory.to_string_lossy());
+
+        let mut paths = Vec::new();
+        for entry in glob::glob(&glob_expression)? {
+            paths.push(entry?);
+        }
+
+        Ok(paths)
+    }
+}
+
+#[derive(Debug)]
+pub struct S3Backend {
+    access_key: Option<String>,
+    bucket: String,
+    endpoint: Option<String>,
+    prefix: String,
+    region: Option<String>,
+    secret_key: Option<String>,
+}
+
+impl S3Backend {
+    pub fn new(bucket: String, prefix: String, endpoint: Option<String>,
+            region: Option<String>, access_key: Option<String>,
+            secret_key: Option<String>) -> S3Backend {
+        S3Backend {
+            access_key: access_key,
+            bucket: bucket,
+            endpoint: endpoint,
+            prefix: prefix,
+            region: region,
+            secret_key: secret_key,
+        }
+    }
+
+    fn client(&self) -> Result<S3Client, Box<dyn Error>> {
+        let region = match (&self.endpoint, &self.region) {
+            (Some(endpoint), region) => Region::Custom {
+                name: region.clone().unwrap_or_else(|| "custom".to_string()),
+                endpoint: endpoint.clone(),
+            },
+            (None, Some(region)) => region.parse()?,
+            (None, None) => Region::default(),
+        };
+
+        Ok(match (&self.access_key, &self.secret_key) {
+            (Some(access_key), Some(secret_key)) => S3Client::new_with(
+                HttpClient::new()?,
+                StaticProvider::new_minimal(
+                    access_key.clone(), secret_key.clone()),
+                region),
+            _ => S3Client::new(region),
+        })
+    }
+
+    fn key(&self, relative: &Path) -> String {
+        format!("{}{}", self.prefix, relative.to_string_lossy())
+    }
+}
+
+impl StorageBackend for S3Backend {
+    fn resolve(&self, relative: &Path) -> String {
+        format!("/vsis3/{}/{}", self.bucket, self.key(relative))
+    }
+
+    fn exists(&self, relative: &Path) -> bool {
+        let client = match self.client() {
+            Ok(client) => client,
+            Err(e) => {
+                warn!("failed to build s3 client for existence check: {}", e);
+                return false;
+            },
+        };
+
+        let request = HeadObjectRequest {
+            bucket: self.bucket.clone(),
+            key: self.key(relative),
+            ..Default::default()
+        };
+
+        futures::executor::block_on(client.head_object(request)).is_ok()
+    }
+
+    fn prepare_write(&self, _relative: &Path) -> Result<(), Box<dyn Error>> {
+        // s3 has no directory concept to create ahead of time - gdal's
+        // vsis3 writer creates the object (and any multipart parts) in
+        // place as the copy is written
+        Ok(())
+    }
+
+    fn is_local(&self) -> bool {
+        false
+    }
+
+    fn list(&self) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let keys = crate::task::store::s3::list_objects(&self.endpoint,
+            &self.region, &self.access_key, &self.secret_key,
+            &self.bucket, &self.prefix, ".tif")?;
+
+        Ok(keys.into_iter()
+            .map(|key| PathBuf::from(format!("/vsis3/{}/{}", self.bucket, key)))
+            .collect())
+    }
+}
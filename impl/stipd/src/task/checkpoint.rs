@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::fmt::Debug;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// a Task's reconstructable configuration, persisted alongside its
+/// completion log so a restarted node can resubmit it without the
+/// original rpc request
+///
+/// this plus 'CompletionLog' IS the journaling layer: rather than a
+/// single '<task_id>.journal' holding a serialized 'Vec<T>' and a
+/// position bitmap, the descriptor rebuilds the task (re-deriving its
+/// records via 'records()', which is already idempotent for every
+/// 'Task' impl - a glob walk or album listing), and the completion log
+/// records which of those records are already done by content hash
+/// ('record_key') rather than positional index, so resume stays correct
+/// even when 'records()' doesn't return in a stable order across a
+/// restart. 'main.rs::rehydrate_tasks' plays the role of
+/// 'TaskManager::recover()', scanning '.tasks' under every album (plus
+/// the node directory for album-less load jobs) at startup
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum TaskDescriptor {
+    Store {
+        album: String,
+        format: String,
+        glob: String,
+        precision: usize,
+        replication_factor: u8,
+        strict: bool,
+    },
+    Split {
+        album: String,
+        end_timestamp: Option<i64>,
+        geocode: Option<String>,
+        geocode_bound: Option<String>,
+        platform: Option<String>,
+        precision: usize,
+        recurse: bool,
+        start_timestamp: Option<i64>,
+    },
+    Open {
+        album: String,
+        thread_count: u8,
+    },
+    Load {
+        album: String,
+        band_filter: Option<Vec<String>>,
+        compression: Option<i32>,
+        dht_key_length: i8,
+        geocode: String,
+        glob: String,
+        load_format: String,
+        precision: usize,
+        transfer_thread_count: u8,
+    },
+    Repair {
+        album: String,
+        rate_limit_ms: u64,
+        replication_factor: u8,
+    },
+    Reconcile {
+        album: String,
+    },
+    Coalesce {
+        album: String,
+        end_timestamp: Option<i64>,
+        geocode: Option<String>,
+        max_cloud_coverage: Option<f64>,
+        min_pixel_coverage: Option<f64>,
+        platform: Option<String>,
+        recurse: bool,
+        source: Option<String>,
+        src_platform: String,
+        start_timestamp: Option<i64>,
+        window_seconds: i64,
+    },
+}
+
+fn task_directory(directory: &Path, task_id: u64) -> PathBuf {
+    let mut path = directory.to_path_buf();
+    path.push(".tasks");
+    path.push(task_id.to_string());
+    path
+}
+
+fn descriptor_path(directory: &Path, task_id: u64) -> PathBuf {
+    let mut path = task_directory(directory, task_id);
+    path.push("descriptor.mp");
+    path
+}
+
+fn completed_path(directory: &Path, task_id: u64) -> PathBuf {
+    let mut path = task_directory(directory, task_id);
+    path.push("completed.log");
+    path
+}
+
+fn complete_marker_path(directory: &Path, task_id: u64) -> PathBuf {
+    let mut path = task_directory(directory, task_id);
+    path.push("complete");
+    path
+}
+
+/// stable identifier for a record, used to dedup the completion log -
+/// the task record types don't carry a natural primary key, so the
+/// hash of their Debug representation stands in for one
+pub fn record_key<T: Debug>(record: &T) -> String {
+    let mut hasher = DefaultHasher::new();
+    format!("{:?}", record).hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
+/// write the task descriptor via a temp file + rename so a crash
+/// mid-write never leaves a corrupt descriptor behind
+pub fn write_descriptor(directory: &Path, task_id: u64,
+        descriptor: &TaskDescriptor) -> Result<(), Box<dyn Error>> {
+    let dir = task_directory(directory, task_id);
+    fs::create_dir_all(&dir)?;
+
+    let bytes = rmp_serde::to_vec(descriptor)?;
+    let tmp_path = dir.join("descriptor.mp.tmp");
+    {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+    }
+
+    fs::rename(&tmp_path, descriptor_path(directory, task_id))?;
+    Ok(())
+}
+
+pub fn read_descriptor(directory: &Path, task_id: u64)
+        -> Result<TaskDescriptor, Box<dyn Error>> {
+    let bytes = fs::read(descriptor_path(directory, task_id))?;
+    Ok(rmp_serde::from_slice(&bytes)?)
+}
+
+/// every record key already appended to the completion log - subtracted
+/// from a rehydrated task's `records()` so completed work isn't redone
+pub fn read_completed(directory: &Path, task_id: u64)
+        -> Result<HashSet<String>, Box<dyn Error>> {
+    let path = completed_path(directory, task_id);
+    if !path.exists() {
+        return Ok(HashSet::new());
+    }
+
+    let file = File::open(path)?;
+    let reader = BufReader::new(file);
+
+    let mut completed = HashSet::new();
+    for line in reader.lines() {
+        completed.insert(line?);
+    }
+
+    Ok(completed)
+}
+
+/// append-only completion log - one record key per line, fsynced every
+/// 16 appends and on `flush()`, so a crash can lose at most the last few
+/// checkpoints and re-process those records on resume
+pub struct CompletionLog {
+    file: File,
+    pending_syncs: u32,
+}
+
+impl CompletionLog {
+    pub fn open(directory: &Path, task_id: u64)
+            -> Result<CompletionLog, Box<dyn Error>> {
+        let dir = task_directory(directory, task_id);
+        fs::create_dir_all(&dir)?;
+
+        let file = OpenOptions::new().create(true).append(true)
+            .open(completed_path(directory, task_id))?;
+
+        Ok(CompletionLog {
+            file: file,
+            pending_syncs: 0,
+        })
+    }
+
+    /// fsync every 16 appends rather than every one, trading a small
+    /// window of possible re-processing on crash (idempotent for the
+    /// image-send tasks this backs) for far fewer syscalls
+    pub fn append(&mut self, record_key: &str) -> Result<(), Box<dyn Error>> {
+        writeln!(self.file, "{}", record_key)?;
+
+        self.pending_syncs += 1;
+        if self.pending_syncs >= 16 {
+            self.file.sync_all()?;
+            self.pending_syncs = 0;
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.file.sync_all()?;
+        self.pending_syncs = 0;
+        Ok(())
+    }
+}
+
+/// mark a task as finished so it's skipped on the next startup scan
+pub fn mark_complete(directory: &Path, task_id: u64)
+        -> Result<(), Box<dyn Error>> {
+    File::create(complete_marker_path(directory, task_id))?;
+    Ok(())
+}
+
+/// task ids under 'directory/.tasks' that have a descriptor but no
+/// 'complete' marker - the set a restarted node should resume
+pub fn pending_tasks(directory: &Path) -> Result<Vec<(u64, TaskDescriptor)>, Box<dyn Error>> {
+    let tasks_dir = {
+        let mut path = directory.to_path_buf();
+        path.push(".tasks");
+        path
+    };
+
+    if !tasks_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut pending = Vec::new();
+    for entry in fs::read_dir(&tasks_dir)? {
+        let entry = entry?;
+        let task_id = match entry.file_name().to_string_lossy().parse::<u64>() {
+            Ok(task_id) => task_id,
+            Err(_) => continue,
+        };
+
+        if complete_marker_path(directory, task_id).exists() {
+            continue;
+        }
+
+        if let Ok(descriptor) = read_descriptor(directory, task_id) {
+            pending.push((task_id, descriptor));
+        }
+    }
+
+    Ok(pending)
+}
@@ -6,7 +6,9 @@ use tonic::Request;
 
 use crate::{Image, StFile, RAW_SOURCE, SPLIT_SOURCE};
 use crate::album::Album;
+use crate::identity::NodeIdentity;
 use crate::task::Task;
+use crate::task::checkpoint::TaskDescriptor;
 
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
@@ -17,8 +19,10 @@ pub struct CoalesceTask {
     dht: Arc<Dht>,
     end_timestamp: Option<i64>,
     geocode: Option<String>,
+    identity: Arc<NodeIdentity>,
     max_cloud_coverage: Option<f64>,
     min_pixel_coverage: Option<f64>,
+    node_id: u32,
     platform: Option<String>,
     source: Option<String>,
     src_platform: String,
@@ -30,8 +34,9 @@ pub struct CoalesceTask {
 impl CoalesceTask {
     pub fn new(album: Arc<RwLock<Album>>, dht: Arc<Dht>,
             end_timestamp: Option<i64>, geocode: Option<String>,
-            max_cloud_coverage: Option<f64>,
-            min_pixel_coverage: Option<f64>, platform: Option<String>,
+            identity: Arc<NodeIdentity>, max_cloud_coverage: Option<f64>,
+            min_pixel_coverage: Option<f64>, node_id: u32,
+            platform: Option<String>,
             recurse: bool, source: Option<String>, src_platform: String,
             start_timestamp: Option<i64>, window_seconds: i64)
             -> CoalesceTask {
@@ -49,8 +54,10 @@ impl CoalesceTask {
             dht: dht,
             end_timestamp: end_timestamp,
             geocode: geocode,
+            identity: identity,
             max_cloud_coverage: max_cloud_coverage,
             min_pixel_coverage: min_pixel_coverage,
+            node_id: node_id,
             platform: platform,
             recurse: recurse,
             source: source,
@@ -63,6 +70,23 @@ impl CoalesceTask {
 
 #[tonic::async_trait]
 impl Task<(Image, Vec<StFile>, HashSet<String>)> for CoalesceTask {
+    fn descriptor(&self) -> Option<TaskDescriptor> {
+        let album = self.album.read().unwrap();
+        Some(TaskDescriptor::Coalesce {
+            album: album.get_id().to_string(),
+            end_timestamp: self.end_timestamp,
+            geocode: self.geocode.clone(),
+            max_cloud_coverage: self.max_cloud_coverage,
+            min_pixel_coverage: self.min_pixel_coverage,
+            platform: self.platform.clone(),
+            recurse: self.recurse,
+            source: self.source.clone(),
+            src_platform: self.src_platform.clone(),
+            start_timestamp: self.start_timestamp,
+            window_seconds: self.window_seconds,
+        })
+    }
+
     fn process(&self, record: &(Image, Vec<StFile>, HashSet<String>))
             -> Result<(), Box<dyn Error>> {
         let image = &record.0;
@@ -132,8 +156,10 @@ impl Task<(Image, Vec<StFile>, HashSet<String>)> for CoalesceTask {
 
                     // send image to new host
                     if let Err(e) = crate::transfer::send_image(&addr,
+                            &self.identity, self.node_id,
                             &album_id, &dataset, &split_geocode, file.1,
-                            &image.2, SPLIT_SOURCE, file.2, &image.4, image.5) {
+                            &image.2, SPLIT_SOURCE, file.2, &image.4,
+                            image.5, false, None) {
                         warn!("failed to write image to node {}: {}", addr, e);
                     }
                 }
@@ -154,8 +180,9 @@ impl Task<(Image, Vec<StFile>, HashSet<String>)> for CoalesceTask {
         // search for source images using Album
         let src_records: Vec<(Image, Vec<StFile>)> = {
             let album = self.album.read().unwrap();
-            album.list(&self.end_timestamp, &self.geocode, &None, &None,
-                &Some(self.src_platform.clone()), self.recurse,  
+            album.list(&self.end_timestamp, &self.geocode, &None,
+                &None, &None, &None, &None, &None,
+                &Some(self.src_platform.clone()), self.recurse,
                 &Some(RAW_SOURCE.to_string()), &self.start_timestamp)?
         };
 
@@ -164,6 +191,10 @@ impl Task<(Image, Vec<StFile>, HashSet<String>)> for CoalesceTask {
             end_timestamp: self.end_timestamp,
             geocode: self.geocode.clone(),
             max_cloud_coverage: self.max_cloud_coverage,
+            max_lat: None,
+            max_lon: None,
+            min_lat: None,
+            min_lon: None,
             min_pixel_coverage: self.min_pixel_coverage,
             platform: self.platform.clone(),
             recurse: self.recurse,
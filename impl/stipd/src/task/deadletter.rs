@@ -0,0 +1,341 @@
+use gdal::raster::Dataset;
+use serde::{Deserialize, Serialize};
+use swarm::prelude::Dht;
+
+use crate::identity::NodeIdentity;
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// initial retry delay for a freshly queued entry
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// ceiling a doubling backoff is clamped to, so a long-unreachable node
+/// is retried every minute rather than drifting toward never
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// everything 'transfer::send_image' needs to retry a transfer, short of
+/// the destination addresses - those are re-derived via
+/// 'task::dht_lookup_replicas' on every attempt so a tile lands
+/// correctly after a node rejoins or the ring rebalances instead of
+/// replaying a stale placement
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct DeadLetterEntry {
+    album: String,
+    dht_key_length: i8,
+    geocode: String,
+    pixel_coverage: f64,
+    platform: String,
+    preview: bool,
+    replication_factor: u8,
+    source: String,
+    subdataset: u8,
+    tile: String,
+    timestamp: i64,
+}
+
+/// in-memory retry state for a queued entry - the album directory it was
+/// filed under (entries span every album an ingest writes to, not just
+/// one), plus the backoff rebuilt fresh at startup so a crash simply
+/// resets a delay rather than losing the entry it guards
+struct QueuedEntry {
+    backoff: Duration,
+    directory: PathBuf,
+    next_attempt: Instant,
+}
+
+fn entry_directory(directory: &Path, id: u64) -> PathBuf {
+    let mut path = directory.to_path_buf();
+    path.push(".deadletter");
+    path.push(id.to_string());
+    path
+}
+
+fn entry_path(directory: &Path, id: u64) -> PathBuf {
+    let mut path = entry_directory(directory, id);
+    path.push("entry.mp");
+    path
+}
+
+fn image_path(directory: &Path, id: u64) -> PathBuf {
+    let mut path = entry_directory(directory, id);
+    path.push("image.dat");
+    path
+}
+
+/// deterministic id for the (album, subdataset, tile, geocode) a failed
+/// transfer belongs to, so a repeated failure of the same split
+/// overwrites its existing entry rather than piling up duplicates
+fn entry_id(album: &str, geocode: &str, subdataset: u8, tile: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    album.hash(&mut hasher);
+    geocode.hash(&mut hasher);
+    subdataset.hash(&mut hasher);
+    tile.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// write 'entry' and the split's raster bytes via a temp file + rename
+/// so a crash mid-write never leaves a corrupt queue entry behind -
+/// mirrors 'checkpoint::write_descriptor'
+fn write_entry(directory: &Path, id: u64, entry: &DeadLetterEntry,
+        dataset: &Dataset) -> Result<(), Box<dyn Error>> {
+    let dir = entry_directory(directory, id);
+    fs::create_dir_all(&dir)?;
+
+    let bytes = rmp_serde::to_vec(entry)?;
+    let tmp_entry_path = dir.join("entry.mp.tmp");
+    {
+        let mut file = File::create(&tmp_entry_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+    }
+
+    let mut image_bytes = Vec::new();
+    st_image::prelude::write(dataset, &mut image_bytes)?;
+    let tmp_image_path = dir.join("image.dat.tmp");
+    {
+        let mut file = File::create(&tmp_image_path)?;
+        file.write_all(&image_bytes)?;
+        file.sync_all()?;
+    }
+
+    fs::rename(&tmp_entry_path, entry_path(directory, id))?;
+    fs::rename(&tmp_image_path, image_path(directory, id))?;
+    Ok(())
+}
+
+fn read_entry(directory: &Path, id: u64)
+        -> Result<(DeadLetterEntry, Dataset), Box<dyn Error>> {
+    let bytes = fs::read(entry_path(directory, id))?;
+    let entry: DeadLetterEntry = rmp_serde::from_slice(&bytes)?;
+
+    let image_bytes = fs::read(image_path(directory, id))?;
+    let dataset = st_image::prelude::read(&mut Cursor::new(image_bytes))?;
+
+    Ok((entry, dataset))
+}
+
+fn remove_entry(directory: &Path, id: u64) -> Result<(), Box<dyn Error>> {
+    fs::remove_dir_all(entry_directory(directory, id))?;
+    Ok(())
+}
+
+/// ids under 'directory/.deadletter' left over from a prior run, so a
+/// restarted node keeps retrying what it hadn't gotten to rather than
+/// silently dropping it
+fn persisted_ids(directory: &Path) -> Result<Vec<u64>, Box<dyn Error>> {
+    let dir = {
+        let mut path = directory.to_path_buf();
+        path.push(".deadletter");
+        path
+    };
+
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut ids = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        if let Ok(id) = entry.file_name().to_string_lossy().parse::<u64>() {
+            ids.push(id);
+        }
+    }
+
+    Ok(ids)
+}
+
+/// durable queue of split transfers that failed or landed short of
+/// quorum, spanning every album an ingest writes to - a background
+/// 'start'ed loop retries each entry with exponential backoff,
+/// re-running the dht lookup every attempt, and removes it only once
+/// it's durably written to quorum. cheap to clone; every clone shares
+/// the same backing queue
+#[derive(Clone)]
+pub struct DeadLetterQueue {
+    dht: Arc<RwLock<Dht>>,
+    entries: Arc<Mutex<HashMap<u64, QueuedEntry>>>,
+    identity: Arc<NodeIdentity>,
+    node_id: u32,
+}
+
+impl DeadLetterQueue {
+    pub fn new(dht: Arc<RwLock<Dht>>, identity: Arc<NodeIdentity>,
+            node_id: u32) -> DeadLetterQueue {
+        DeadLetterQueue {
+            dht: dht,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            identity: identity,
+            node_id: node_id,
+        }
+    }
+
+    /// register whatever entries are already on disk under an album's
+    /// directory - called once per album at startup, alongside
+    /// 'checkpoint::pending_tasks', so entries queued before a restart
+    /// aren't forgotten
+    pub fn rehydrate(&self, directory: &Path) {
+        let ids = match persisted_ids(directory) {
+            Ok(ids) => ids,
+            Err(e) => {
+                warn!("failed to scan dead-letter entries under '{:?}': {}",
+                    directory, e);
+                return;
+            },
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        for id in ids {
+            entries.insert(id, QueuedEntry {
+                backoff: INITIAL_BACKOFF,
+                directory: directory.to_path_buf(),
+                next_attempt: Instant::now(),
+            });
+        }
+    }
+
+    /// durably enqueue a failed or short-of-quorum transfer under
+    /// 'directory' (the owning album's directory), overwriting any
+    /// still-queued attempt for the same split - retried on the next
+    /// background tick
+    pub fn push(&self, directory: &Path, album: &str, dataset: &Dataset,
+            dht_key_length: i8, geocode: &str, pixel_coverage: f64,
+            platform: &str, preview: bool, replication_factor: u8,
+            source: &str, subdataset: u8, tile: &str, timestamp: i64)
+            -> Result<(), Box<dyn Error>> {
+        let id = entry_id(album, geocode, subdataset, tile);
+        let entry = DeadLetterEntry {
+            album: album.to_string(),
+            dht_key_length: dht_key_length,
+            geocode: geocode.to_string(),
+            pixel_coverage: pixel_coverage,
+            platform: platform.to_string(),
+            preview: preview,
+            replication_factor: replication_factor,
+            source: source.to_string(),
+            subdataset: subdataset,
+            tile: tile.to_string(),
+            timestamp: timestamp,
+        };
+
+        write_entry(directory, id, &entry, dataset)?;
+
+        self.entries.lock().unwrap().insert(id, QueuedEntry {
+            backoff: INITIAL_BACKOFF,
+            directory: directory.to_path_buf(),
+            next_attempt: Instant::now(),
+        });
+
+        Ok(())
+    }
+
+    /// number of transfers still awaiting a successful retry
+    pub fn depth(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// force an immediate retry of every queued entry, ignoring backoff
+    /// - lets an operator confirm nothing is silently stuck after
+    /// bringing a node back, rather than waiting out the remaining
+    /// backoff. returns the number of entries that were successfully
+    /// retired
+    pub fn drain(&self) -> usize {
+        let ids: Vec<u64> = self.entries.lock().unwrap().keys()
+            .cloned().collect();
+
+        ids.iter().filter(|id| self.attempt(**id)).count()
+    }
+
+    /// retry every entry whose backoff has elapsed - called periodically
+    /// by 'start' below
+    fn tick(&self) {
+        let now = Instant::now();
+        let due: Vec<u64> = self.entries.lock().unwrap().iter()
+            .filter(|(_, queued)| queued.next_attempt <= now)
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in due {
+            self.attempt(id);
+        }
+    }
+
+    /// retry one entry, returning whether it was durably written to
+    /// quorum and removed. a missing or corrupt entry on disk is treated
+    /// as already resolved rather than retried forever
+    fn attempt(&self, id: u64) -> bool {
+        let directory = match self.entries.lock().unwrap().get(&id) {
+            Some(queued) => queued.directory.clone(),
+            None => return false,
+        };
+
+        let (entry, dataset) = match read_entry(&directory, id) {
+            Ok(result) => result,
+            Err(e) => {
+                warn!("dropping unreadable dead-letter entry {}: {}", id, e);
+                self.entries.lock().unwrap().remove(&id);
+                return true;
+            },
+        };
+
+        let addrs = match crate::task::dht_lookup_replicas(&self.dht,
+                entry.dht_key_length, &entry.geocode,
+                entry.replication_factor) {
+            Ok(addrs) => addrs,
+            Err(e) => {
+                warn!("dead-letter retry of '{}' still unreachable: {}",
+                    entry.tile, e);
+                self.backoff(id);
+                return false;
+            },
+        };
+
+        let successes = crate::task::send_to_replicas(&addrs, |addr|
+            crate::transfer::send_image(addr, &self.identity, self.node_id,
+                &entry.album, &dataset, &entry.geocode, entry.pixel_coverage,
+                &entry.platform, &entry.source, entry.subdataset,
+                &entry.tile, entry.timestamp, entry.preview, None));
+
+        let quorum = crate::task::write_quorum(entry.replication_factor);
+        if successes < quorum {
+            warn!("dead-letter retry of '{}' wrote only {}/{} replicas \
+                (quorum {})", entry.tile, successes, addrs.len(), quorum);
+            self.backoff(id);
+            return false;
+        }
+
+        if let Err(e) = remove_entry(&directory, id) {
+            warn!("failed to remove drained dead-letter entry {}: {}", id, e);
+        }
+        self.entries.lock().unwrap().remove(&id);
+        true
+    }
+
+    /// double an entry's backoff (capped at 'MAX_BACKOFF') and push its
+    /// next attempt out by the new delay
+    fn backoff(&self, id: u64) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(queued) = entries.get_mut(&id) {
+            queued.backoff = (queued.backoff * 2).min(MAX_BACKOFF);
+            queued.next_attempt = Instant::now() + queued.backoff;
+        }
+    }
+}
+
+/// spawn the periodic dead-letter retry loop - mirrors 'gossip::start'
+pub fn start(queue: DeadLetterQueue, period_secs: u64) {
+    std::thread::spawn(move || {
+        loop {
+            std::thread::sleep(Duration::from_secs(period_secs));
+            queue.tick();
+        }
+    });
+}
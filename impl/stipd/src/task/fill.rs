@@ -87,11 +87,17 @@ impl Task<Vec<(Image, StFile)>> for FillTask {
             let image = &record[0].0;
             let file = &record[0].1;
 
+            // compute a content checksum over the filled raster so it
+            // carries the same integrity guarantee as a transferred tile
+            let mut buf = Vec::new();
+            st_image::prelude::write(&dataset, &mut buf)?;
+            let checksum = crate::transfer::checksum(&buf);
+
             let mut album = self.album.write().unwrap();
             if let Err(e) = album.write(&mut dataset,
                     &image.1, pixel_coverage, &image.2,
                     &FILLED_SOURCE.to_string(),
-                    file.2, &image.4, image.5) {
+                    file.2, &image.4, image.5, checksum) {
                 warn!("failed to write filled image: {}", e);
             }
         }
@@ -105,7 +111,8 @@ impl Task<Vec<(Image, StFile)>> for FillTask {
         let mut src_records: Vec<(Image, StFile)> = {
             let album = self.album.read().unwrap();
             let images = album.list(&self.end_timestamp,
-                &self.geocode, &None, &None, &self.platform, 
+                &self.geocode, &None, &None, &None, &None, &None,
+                &None, &self.platform,
                 self.recurse, &None, &self.start_timestamp)?;
 
             let mut src_records = Vec::new();
@@ -0,0 +1,347 @@
+use crossbeam_deque::{Injector, Stealer, Worker as DequeWorker};
+use gdal::raster::Dataset;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::Instant;
+
+/// bounded worker pool size for a single job - ingest jobs are already
+/// one of several records a 'Task' worker thread is churning through
+/// concurrently, so a job's own pool stays small rather than competing
+/// for every core
+const JOB_THREAD_COUNT: u8 = 4;
+
+/// status of one (geocode, subdataset) split transfer unit within an
+/// ingest job - persisted alongside the job manifest so a restarted node
+/// resumes only the units that never finished instead of reprocessing
+/// the whole record
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum UnitStatus {
+    Pending,
+    InFlight,
+    Done,
+    Failed(String),
+}
+
+/// one (geocode, subdataset) split transfer unit tracked by a job
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JobUnit {
+    pub geocode: String,
+    pub subdataset: u8,
+    pub status: UnitStatus,
+}
+
+/// a job's reconstructable manifest - the input record it was derived
+/// from, plus the status of every split unit it must transfer - persisted
+/// under 'directory/.jobs/job_id/manifest.mp' so a crash mid-ingest
+/// resumes only the 'Pending'/'Failed' units instead of redoing the
+/// whole record
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JobManifest {
+    pub record: PathBuf,
+    pub units: Vec<JobUnit>,
+}
+
+impl JobManifest {
+    pub fn new(record: PathBuf, units: Vec<(String, u8)>) -> JobManifest {
+        JobManifest {
+            record: record,
+            units: units.into_iter()
+                .map(|(geocode, subdataset)| JobUnit {
+                    geocode: geocode,
+                    subdataset: subdataset,
+                    status: UnitStatus::Pending,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn job_directory(directory: &Path, job_id: u64) -> PathBuf {
+    let mut path = directory.to_path_buf();
+    path.push(".jobs");
+    path.push(job_id.to_string());
+    path
+}
+
+fn manifest_path(directory: &Path, job_id: u64) -> PathBuf {
+    let mut path = job_directory(directory, job_id);
+    path.push("manifest.mp");
+    path
+}
+
+/// write 'manifest' via a temp file + rename so a crash mid-write never
+/// leaves a corrupt manifest behind - mirrors
+/// 'checkpoint::write_descriptor'
+pub fn write_manifest(directory: &Path, job_id: u64, manifest: &JobManifest)
+        -> Result<(), Box<dyn Error>> {
+    let dir = job_directory(directory, job_id);
+    fs::create_dir_all(&dir)?;
+
+    let bytes = rmp_serde::to_vec(manifest)?;
+    let tmp_path = dir.join("manifest.mp.tmp");
+    {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+    }
+
+    fs::rename(&tmp_path, manifest_path(directory, job_id))?;
+    Ok(())
+}
+
+/// read back a previously persisted manifest - absent for a job that's
+/// never been attempted before, in which case the caller builds a fresh
+/// one with every unit 'Pending'
+pub fn read_manifest(directory: &Path, job_id: u64)
+        -> Result<JobManifest, Box<dyn Error>> {
+    let bytes = fs::read(manifest_path(directory, job_id))?;
+    Ok(rmp_serde::from_slice(&bytes)?)
+}
+
+/// wraps a split 'Dataset' so it can cross the job worker pool's queue -
+/// gdal's 'Dataset' wraps a raw pointer and isn't 'Send', but each unit
+/// has exactly one owner (the worker that dequeued it) and is never
+/// touched by more than one thread concurrently
+struct SendDataset(Dataset);
+unsafe impl Send for SendDataset {}
+
+/// shared per-job progress and control state, polled by 'rpc::job' and
+/// mutated by 'start' below - the ingest analogue of 'task::TaskHandle',
+/// just scoped to one record's split units instead of a whole task's
+/// records
+pub struct JobHandle {
+    cancelled: Arc<AtomicBool>,
+    completed: Arc<AtomicBool>,
+    completed_count: Arc<AtomicU32>,
+    failed_count: Arc<AtomicU32>,
+    record: PathBuf,
+    running: Arc<AtomicBool>,
+    started: Instant,
+    total_count: u32,
+}
+
+impl JobHandle {
+    /// stop dispatching new units - units already handed to a worker
+    /// still finish and checkpoint normally
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn completed_count(&self) -> u32 {
+        self.completed_count.load(Ordering::SeqCst)
+    }
+
+    pub fn failed_count(&self) -> u32 {
+        self.failed_count.load(Ordering::SeqCst)
+    }
+
+    pub fn record(&self) -> &PathBuf {
+        &self.record
+    }
+
+    pub fn running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub fn total_count(&self) -> u32 {
+        self.total_count
+    }
+
+    /// '(completed + failed) / total' as a percentage
+    pub fn completion_percent(&self) -> f32 {
+        if self.total_count == 0 {
+            return 0.0;
+        }
+
+        (self.completed_count() + self.failed_count()) as f32
+            / self.total_count as f32 * 100f32
+    }
+
+    /// completed units per second since the job started - a poller can
+    /// combine this with 'total_count() - completed_count()' to estimate
+    /// time remaining
+    pub fn throughput(&self) -> f32 {
+        let elapsed = self.started.elapsed().as_secs_f32();
+        if elapsed <= 0f32 {
+            return 0f32;
+        }
+
+        self.completed_count() as f32 / elapsed
+    }
+}
+
+/// cheap-to-clone registry of in-flight/completed ingest jobs - the job
+/// subsystem's analogue of 'task::TaskManager'
+#[derive(Clone)]
+pub struct JobManager {
+    jobs: Arc<RwLock<HashMap<u64, Arc<RwLock<JobHandle>>>>>,
+}
+
+impl JobManager {
+    pub fn new() -> JobManager {
+        JobManager {
+            jobs: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn get(&self, job_id: u64) -> Option<Arc<RwLock<JobHandle>>> {
+        self.jobs.read().unwrap().get(&job_id).cloned()
+    }
+
+    pub fn iter(&self) -> Vec<(u64, Arc<RwLock<JobHandle>>)> {
+        self.jobs.read().unwrap().iter()
+            .map(|(job_id, job_handle)| (*job_id, job_handle.clone()))
+            .collect()
+    }
+
+    fn register(&self, job_id: u64, job_handle: Arc<RwLock<JobHandle>>) {
+        self.jobs.write().unwrap().insert(job_id, job_handle);
+    }
+}
+
+/// dispatch every unit in 'manifest' that isn't already 'Done' through a
+/// bounded worker pool, persisting the manifest after each unit finishes
+/// so a restarted node resumes only what never completed instead of
+/// reprocessing the whole record. 'send' receives a split's geocode,
+/// subdataset index, and dataset, and performs the actual replica
+/// transfer (e.g. 'transfer::send_image' to every address
+/// 'task::dht_lookup_replicas' returns). blocks the calling worker
+/// thread until every unit finishes - mirrors 'store/landsat.rs's own
+/// per-record split pool, which the calling 'Task::process' worker is
+/// already expected to block on - while the returned, registered
+/// 'JobHandle' lets 'rpc::job' poll this job's progress concurrently
+/// from another thread
+pub fn start<F>(job_manager: &JobManager, directory: PathBuf, job_id: u64,
+        manifest: JobManifest, mut datasets: HashMap<String, Dataset>,
+        send: F) -> Arc<RwLock<JobHandle>>
+        where F: Fn(&str, u8, &Dataset) -> Result<(), Box<dyn Error>>
+            + Send + Sync + 'static {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let completed = Arc::new(AtomicBool::new(false));
+    let completed_count = Arc::new(AtomicU32::new(0));
+    let failed_count = Arc::new(AtomicU32::new(0));
+    let running = Arc::new(AtomicBool::new(true));
+
+    let job_handle = Arc::new(RwLock::new(JobHandle {
+        cancelled: cancelled.clone(),
+        completed: completed.clone(),
+        completed_count: completed_count.clone(),
+        failed_count: failed_count.clone(),
+        record: manifest.record.clone(),
+        running: running.clone(),
+        started: Instant::now(),
+        total_count: manifest.units.len() as u32,
+    }));
+
+    job_manager.register(job_id, job_handle.clone());
+
+    // a unit already 'Done' from a prior attempt counts toward progress
+    // immediately and is never re-dispatched
+    let done_count = manifest.units.iter()
+        .filter(|unit| unit.status == UnitStatus::Done)
+        .count();
+    completed_count.fetch_add(done_count as u32, Ordering::SeqCst);
+
+    let manifest = Arc::new(Mutex::new(manifest));
+    let injector: Injector<(usize, SendDataset)> = Injector::new();
+
+    {
+        let manifest = manifest.lock().unwrap();
+        for (index, unit) in manifest.units.iter().enumerate() {
+            if unit.status == UnitStatus::Done {
+                continue;
+            }
+
+            if let Some(dataset) = datasets.remove(&unit.geocode) {
+                injector.push((index, SendDataset(dataset)));
+            }
+        }
+    }
+
+    let send = Arc::new(send);
+    let injector = Arc::new(injector);
+    let locals: Vec<DequeWorker<(usize, SendDataset)>> =
+        (0..JOB_THREAD_COUNT).map(|_| DequeWorker::new_fifo()).collect();
+    let stealers: Arc<Vec<Stealer<(usize, SendDataset)>>> = Arc::new(
+        locals.iter().map(|local| local.stealer()).collect());
+
+    let mut join_handles = Vec::new();
+    for local in locals {
+        let cancelled = cancelled.clone();
+        let completed_count = completed_count.clone();
+        let directory = directory.clone();
+        let failed_count = failed_count.clone();
+        let injector = injector.clone();
+        let manifest = manifest.clone();
+        let send = send.clone();
+        let stealers = stealers.clone();
+
+        let join_handle = std::thread::spawn(move || {
+            // every unit was enqueued before the pool started, so an
+            // exhausted steal means the job is actually done rather
+            // than just momentarily starved
+            while let Some((index, dataset)) = crate::task::find_task(
+                    &local, &injector, &stealers) {
+                // cancellation only stops new units from being sent - a
+                // unit another worker is already sending still runs to
+                // completion and checkpoints normally, while the unit
+                // just dequeued here is dropped unsent and keeps its
+                // prior status in the manifest
+                if cancelled.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                let dataset = dataset.0;
+                let (geocode, subdataset) = {
+                    let manifest = manifest.lock().unwrap();
+                    (manifest.units[index].geocode.clone(),
+                        manifest.units[index].subdataset)
+                };
+
+                let result = send(&geocode, subdataset, &dataset);
+
+                let mut manifest = manifest.lock().unwrap();
+                match result {
+                    Ok(()) => {
+                        manifest.units[index].status = UnitStatus::Done;
+                        completed_count.fetch_add(1, Ordering::SeqCst);
+                    },
+                    Err(e) => {
+                        manifest.units[index].status =
+                            UnitStatus::Failed(e.to_string());
+                        failed_count.fetch_add(1, Ordering::SeqCst);
+                    },
+                };
+
+                if let Err(e) = write_manifest(&directory, job_id, &manifest) {
+                    warn!("failed to checkpoint job {} unit {}: {}",
+                        job_id, index, e);
+                }
+            }
+        });
+
+        join_handles.push(join_handle);
+    }
+
+    for join_handle in join_handles {
+        if let Err(e) = join_handle.join() {
+            warn!("job {} worker panicked: {:?}", job_id, e);
+        }
+    }
+
+    completed.store(true, Ordering::SeqCst);
+    running.store(false, Ordering::SeqCst);
+
+    job_handle
+}
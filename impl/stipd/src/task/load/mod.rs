@@ -1,156 +1,158 @@
+use st_image::prelude::Geocode;
 use swarm::prelude::Dht;
 
 mod modis;
 mod naip;
+mod raster;
 mod sentinel_2;
+pub mod transfer;
 
-use crate::task::{Task, TaskHandle, TaskStatus};
+use crate::identity::NodeIdentity;
+use crate::task::{NonCriticalErrorSink, Task};
+use crate::task::checkpoint::TaskDescriptor;
+use transfer::TransferPool;
 
 use std::error::Error;
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
-use std::sync::atomic::{AtomicU32, Ordering};
 
-#[derive(Clone)]
+// every format below decodes through gdal (via 'st_image', which wraps
+// gdal's own geotransform/split/coverage logic) rather than a
+// pluggable decoder - 'Raster' already covers "any format gdal's
+// installed drivers can open" (including jp2/openjpeg for sentinel,
+// given a driver-enabled build), so a second, 'image'-crate-backed
+// decode path would need to reimplement split/geotransform/coverage
+// from scratch for no current caller. what this format dispatch does
+// guarantee is that an unsupported or corrupt file fails the one
+// record it belongs to (see 'naip::process'/'raster::process', both
+// propagate 'Dataset::open' errors rather than panicking) instead of
+// taking down the decode worker
+#[derive(Clone, Debug)]
 pub enum LoadFormat {
     MODIS,
     NAIP,
+    Raster,
     Sentinel,
 }
 
 pub struct LoadEarthExplorerTask {
+    album: String,
+    // restricts which Sentinel subdatasets get split/hashed/transferred
+    // to those whose SUBDATASETS description matches one of these band
+    // names - ignored by every other 'LoadFormat', which don't expose a
+    // per-subdataset picklist
+    band_filter: Option<Vec<String>>,
+    // zstd level to stream-compress each split tile's raster bytes at
+    // before it's sent - 'None' sends the raster uncompressed, as every
+    // other 'LoadFormat' still does
+    compression: Option<i32>,
     dht: Arc<RwLock<Dht>>,
+    dht_key_length: i8,
+    geocode: Geocode,
     glob: String,
+    identity: Arc<NodeIdentity>,
     load_format: LoadFormat,
+    node_id: u32,
+    non_critical_errors: NonCriticalErrorSink,
     precision: usize,
-    thread_count: u8,
+    transfer_pool: TransferPool,
+    transfer_thread_count: u8,
 }
 
 impl LoadEarthExplorerTask {
-    pub fn new(dht: Arc<RwLock<Dht>>, glob: String,
-            load_format: LoadFormat, precision: usize,
-            thread_count: u8) -> LoadEarthExplorerTask {
+    pub fn new(album: String, band_filter: Option<Vec<String>>,
+            compression: Option<i32>, dht: Arc<RwLock<Dht>>,
+            dht_key_length: i8, geocode: Geocode, glob: String,
+            identity: Arc<NodeIdentity>, load_format: LoadFormat,
+            node_id: u32, precision: usize, transfer_thread_count: u8)
+            -> LoadEarthExplorerTask {
+        // shared with the transfer pool below so a dropped tile (dht
+        // lookup miss, or a send that's still failing after retries)
+        // surfaces through this task's own TaskHandle rather than only
+        // a log line
+        let non_critical_errors = NonCriticalErrorSink::new();
+
+        // the transfer pool owns the dht lookup + network send half of
+        // loading a record, decoupled from the decode/split half below
+        // so a slow send to one node can't stall a thread that could be
+        // decoding the next record
+        let transfer_pool = TransferPool::new(dht.clone(), dht_key_length,
+            identity.clone(), node_id, non_critical_errors.clone(),
+            transfer_thread_count);
+
         LoadEarthExplorerTask {
+            album: album,
+            band_filter: band_filter,
+            compression: compression,
             dht: dht,
+            dht_key_length: dht_key_length,
+            geocode: geocode,
             glob: glob,
+            identity: identity,
             load_format: load_format,
+            node_id: node_id,
+            non_critical_errors: non_critical_errors,
             precision: precision,
-            thread_count: thread_count,
+            transfer_pool: transfer_pool,
+            transfer_thread_count: transfer_thread_count,
         }
     }
 }
 
-impl Task for LoadEarthExplorerTask {
-    fn start(&self) -> Result<Arc<RwLock<TaskHandle>>, Box<dyn Error>> {
+#[tonic::async_trait]
+impl Task<PathBuf> for LoadEarthExplorerTask {
+    // persists the glob/format/precision this task was started with so
+    // a restarted node can recreate it and skip every record already
+    // recorded in the completion log, rather than re-walking a glob of
+    // potentially thousands of scenes from scratch
+    fn descriptor(&self) -> Option<TaskDescriptor> {
+        Some(TaskDescriptor::Load {
+            album: self.album.clone(),
+            band_filter: self.band_filter.clone(),
+            compression: self.compression,
+            dht_key_length: self.dht_key_length,
+            geocode: format!("{:?}", self.geocode),
+            glob: self.glob.clone(),
+            load_format: format!("{:?}", self.load_format),
+            precision: self.precision,
+            transfer_thread_count: self.transfer_thread_count,
+        })
+    }
+
+    // the transfer pool's workers report into this same sink, so a
+    // dropped tile (dht lookup miss, or a send still failing after
+    // retries) shows up alongside any other non-critical error a
+    // status poller checks for
+    fn non_critical_errors(&self) -> NonCriticalErrorSink {
+        self.non_critical_errors.clone()
+    }
+
+    fn process(&self, record: &PathBuf) -> Result<(), Box<dyn Error>> {
+        // compute geohash intervals for given precision
+        let (y_interval, x_interval) =
+            st_image::prelude::get_geohash_intervals(self.precision);
+
+        match self.load_format {
+            LoadFormat::MODIS => modis::process(&self.album, &self.dht,
+                self.precision, record, x_interval, y_interval),
+            LoadFormat::NAIP => naip::process(&self.album,
+                &self.transfer_pool, self.geocode, self.precision, record),
+            LoadFormat::Raster => raster::process(&self.album,
+                &self.transfer_pool, self.geocode, self.precision, record),
+            LoadFormat::Sentinel => sentinel_2::process(&self.album,
+                &self.band_filter, self.compression, &self.dht,
+                self.dht_key_length, self.geocode, &self.identity,
+                self.node_id, self.precision, record, x_interval, y_interval),
+        }
+    }
+
+    async fn records(&self) -> Result<Vec<PathBuf>, Box<dyn Error>> {
         // search for image files
         let mut records = Vec::new();
         for entry in glob::glob(&self.glob)? {
             records.push(entry?);
         }
 
-        // initialize record channel
-        let (sender, receiver) = crossbeam_channel::bounded(256);
-
-        // start worker threads
-        let items_completed = Arc::new(AtomicU32::new(0));
-        let items_skipped = Arc::new(AtomicU32::new(0));
-        let mut join_handles = Vec::new();
-        for _ in 0..self.thread_count {
-            let dht_clone = self.dht.clone();
-            let items_completed = items_completed.clone();
-            let items_skipped = items_skipped.clone();
-            let load_format = self.load_format.clone();
-            let precision = self.precision.clone();
-            let receiver_clone = receiver.clone();
-
-            // compute geohash intervals for given precision
-            let (y_interval, x_interval) =
-                st_image::prelude::get_geohash_intervals(self.precision);
-
-            let join_handle = std::thread::spawn(move || {
-                // iterate over records
-                loop {
-                    // fetch next record
-                    let record: PathBuf = match receiver_clone.recv() {
-                        Ok(record) => record,
-                        Err(_) => break,
-                    };
-
-                    // process record
-                    let result = match load_format {
-                        LoadFormat::MODIS => modis::process(
-                            &dht_clone, precision, &record,
-                            x_interval, y_interval),
-                        LoadFormat::NAIP => naip::process(
-                            &dht_clone, precision, &record,
-                            x_interval, y_interval),
-                        LoadFormat::Sentinel => sentinel_2::process(
-                            &dht_clone, precision, &record,
-                            x_interval, y_interval),
-                    };
-
-                    // process result
-                    match result {
-                        Ok(_) => items_completed.fetch_add(1, Ordering::SeqCst),
-                        Err(e) => {
-                            warn!("skipping record '{}': {}",
-                                &record.to_string_lossy(), e);
-                            items_skipped.fetch_add(1, Ordering::SeqCst)
-                        },
-                    };
-                }
-            });
-
-            join_handles.push(join_handle);
-        }
-
-        // initialize TaskHandle
-        let task_handle = Arc::new( RwLock::new(
-            TaskHandle::new(
-                items_completed,
-                items_skipped,
-                records.len() as u32,
-                TaskStatus::Running
-            )));
-
-        // start management thread
-        let task_handle_clone = task_handle.clone();
-        let _ = std::thread::spawn(move || {
-            // add items to pipeline
-            for record in records {
-                if let Err(e) = sender.send(record) {
-                    // set TaskHandle status to 'failed'
-                    let mut task_handle =
-                        task_handle_clone.write().unwrap();
-                    task_handle.set_status(
-                        TaskStatus::Failure(format!("{:?}", e)));
-
-                    return;
-                }
-            }
- 
-            // drop sender to signal worker threads
-            drop(sender);
-
-            // join worker threads
-            for join_handle in join_handles {
-                if let Err(e) = join_handle.join() {
-                    // set TaskHandle status to 'failed'
-                    let mut task_handle =
-                        task_handle_clone.write().unwrap();
-                    task_handle.set_status(
-                        TaskStatus::Failure(format!("{:?}", e)));
-
-                    return;
-                }
-            }
-
-            // set TaskHandle status to 'completed'
-            let mut task_handle = task_handle_clone.write().unwrap();
-            task_handle.set_status(TaskStatus::Complete);
-        });
-
-        // return task handle
-        Ok(task_handle)
+        Ok(records)
     }
 }
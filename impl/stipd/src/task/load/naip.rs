@@ -1,20 +1,17 @@
 use chrono::prelude::{TimeZone, Utc};
 use failure::ResultExt;
 use gdal::raster::Dataset;
-use geohash::Coordinate;
 use st_image::prelude::Geocode;
-use swarm::prelude::Dht;
 
 use crate::image::RAW_SOURCE;
+use crate::task::load::transfer::{TransferPool, TransferUnit};
 
 use std::error::Error;
 use std::ffi::OsStr;
 use std::path::PathBuf;
-use std::sync::{Arc, RwLock};
 
-pub fn process(album: &str, dht: &Arc<RwLock<Dht>>,
-        dht_key_length: i8, geocode: Geocode, precision: usize,
-        record: &PathBuf) -> Result<(), Box<dyn Error>> {
+pub fn process(album: &str, transfer_pool: &TransferPool, geocode: Geocode,
+        precision: usize, record: &PathBuf) -> Result<(), Box<dyn Error>> {
     // open geotiff file
     let tif_path = record.with_extension("tif");
     let filename = tif_path.file_name().unwrap()
@@ -22,8 +19,11 @@ pub fn process(album: &str, dht: &Arc<RwLock<Dht>>,
 
     let image_path = PathBuf::from(format!("/vsizip/{}/{}",
         record.to_string_lossy(), filename));
-    let dataset = Dataset::open(&image_path)
-        .expect("metadata dataset open");
+
+    // an unsupported or corrupt archive member should fail this one
+    // record - counted by the engine as a skip - rather than taking
+    // down the whole decode worker
+    let dataset = Dataset::open(&image_path).compat()?;
 
     // parse metadata
     let date_string = &filename[filename.len()-12..filename.len()-4];
@@ -59,22 +59,23 @@ pub fn process(album: &str, dht: &Arc<RwLock<Dht>>,
 
         //println!("{} {} {}", tile, geohash, pixel_coverage);
 
-        // lookup geohash in dht
-        let addr = match crate::task::dht_lookup(
-                &dht, dht_key_length, &split_geocode) {
-            Ok(addr) => addr,
-            Err(e) => {
-                warn!("{}", e);
-                continue;
-            },
-        };
-
-        // send image to new host
-        if let Err(e) = crate::transfer::send_image(&addr, album,
-                &dataset, &split_geocode, pixel_coverage, "NAIP",
-                &RAW_SOURCE, 0, &tile, timestamp) {
-            warn!("failed to write image to node {}: {}", addr, e);
-        }
+        // serialize the split here, on the decode thread, so the
+        // transfer pool's workers never touch a gdal 'Dataset' - only
+        // the lookup + network send happen there
+        let mut image = Vec::new();
+        st_image::prelude::write(&dataset, &mut image)?;
+
+        transfer_pool.submit(TransferUnit {
+            album: album.to_string(),
+            geocode: split_geocode,
+            image: image,
+            pixel_coverage: pixel_coverage,
+            platform: "NAIP".to_string(),
+            source: RAW_SOURCE,
+            subdataset: 0,
+            tile: tile.to_string(),
+            timestamp: timestamp,
+        });
     }
 
     Ok(())
@@ -0,0 +1,72 @@
+use failure::ResultExt;
+use gdal::raster::Dataset;
+use st_image::prelude::Geocode;
+
+use crate::image::RAW_SOURCE;
+use crate::task::load::transfer::{TransferPool, TransferUnit};
+
+use std::error::Error;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+/// load an arbitrary gdal-supported raster directly, deriving its split
+/// bounds from the dataset's own geotransform/spatial reference instead
+/// of a per-platform manifest - unlike 'modis'/'naip'/'sentinel_2' this
+/// makes no assumption about filename conventions or embedded product
+/// metadata, so it works against any raster a user points stip at
+pub fn process(album: &str, transfer_pool: &TransferPool, geocode: Geocode,
+        precision: usize, record: &PathBuf) -> Result<(), Box<dyn Error>> {
+    let dataset = Dataset::open(&record).compat()?;
+
+    // the gdal driver stands in for a platform identifier, since there
+    // is no per-format metadata convention to read one from
+    let platform = dataset.driver().short_name();
+
+    // derive tile id from the filename, and timestamp from the file's
+    // own mtime since an arbitrary raster has no embedded capture time
+    let tile_path = record.with_extension("");
+    let tile = tile_path.file_name()
+        .unwrap_or(OsStr::new("")).to_string_lossy();
+
+    let timestamp = record.metadata()?.modified()?
+        .duration_since(std::time::UNIX_EPOCH)?.as_secs() as i64;
+
+    // split image with geohash precision - bounds come straight from
+    // the dataset's geotransform, the same path 'naip' already uses
+    for dataset_split in st_image::prelude::split(&dataset,
+            geocode, precision).compat()? {
+        let (win_min_x, win_max_x, win_min_y, win_max_y) =
+            dataset_split.coordinates();
+        let split_geocode = geocode.get_code(
+            (win_min_x + win_max_x) / 2.0,
+            (win_min_y + win_max_y) / 2.0, precision)?;
+
+        let dataset = dataset_split.dataset().compat()?;
+
+        // if image has 0.0 coverage -> don't process
+        let pixel_coverage = st_image::coverage(&dataset).compat()?;
+        if pixel_coverage == 0f64 {
+            continue;
+        }
+
+        // serialize the split here, on the decode thread, so the
+        // transfer pool's workers never touch a gdal 'Dataset' - only
+        // the lookup + network send happen there
+        let mut image = Vec::new();
+        st_image::prelude::write(&dataset, &mut image)?;
+
+        transfer_pool.submit(TransferUnit {
+            album: album.to_string(),
+            geocode: split_geocode,
+            image: image,
+            pixel_coverage: pixel_coverage,
+            platform: platform.clone(),
+            source: RAW_SOURCE,
+            subdataset: 0,
+            tile: tile.to_string(),
+            timestamp: timestamp,
+        });
+    }
+
+    Ok(())
+}
@@ -7,8 +7,10 @@ use swarm::prelude::Dht;
 use zip::ZipArchive;
 
 use crate::album::Geocode;
+use crate::identity::NodeIdentity;
 use crate::image::RAW_SOURCE;
 
+use std::collections::HashMap;
 use std::error::Error;
 use std::ffi::OsStr;
 use std::fs::File;
@@ -16,9 +18,11 @@ use std::io::BufReader;
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
-pub fn process(album: &str, dht: &Arc<RwLock<Dht>>, dht_key_length: i8,
-        geocode: Geocode, precision: usize, record: &PathBuf,
-        x_interval: f64, y_interval: f64) -> Result<(), Box<dyn Error>> {
+pub fn process(album: &str, band_filter: &Option<Vec<String>>,
+        compression: Option<i32>, dht: &Arc<RwLock<Dht>>, dht_key_length: i8,
+        geocode: Geocode, identity: &Arc<NodeIdentity>, node_id: u32,
+        precision: usize, record: &PathBuf, x_interval: f64, y_interval: f64)
+        -> Result<(), Box<dyn Error>> {
     // compute tile name
     let tile_path = record.with_extension("");
     let tile = tile_path.file_name()
@@ -80,8 +84,22 @@ pub fn process(album: &str, dht: &Arc<RwLock<Dht>>, dht_key_length: i8,
         count += 2;
     }
 
+    // per-geohash (band_id, description) file list and running pixel
+    // coverage sum, accumulated across every subdataset below - sent as
+    // a single tile catalog record once the split loop finishes
+    let mut tile_metadata: HashMap<String, (Vec<(u8, String)>, f64, usize)> =
+        HashMap::new();
+
     // process data subsets
-    for (i, (name, _)) in subdatasets.iter().enumerate() {
+    for (i, (name, description)) in subdatasets.iter().enumerate() {
+        // skip a subdataset that doesn't match the picklist, before
+        // paying to open and split it
+        if let Some(band_filter) = band_filter {
+            if !band_filter.iter().any(|band| description.contains(band.as_str())) {
+                continue;
+            }
+        }
+
         // open dataset
         let path = PathBuf::from(name);
         let dataset = Dataset::open(&path).compat()?;
@@ -102,6 +120,12 @@ pub fn process(album: &str, dht: &Arc<RwLock<Dht>>, dht_key_length: i8,
                 continue;
             }
 
+            let entry = tile_metadata.entry(geohash.clone())
+                .or_insert_with(|| (Vec::new(), 0f64, 0));
+            entry.0.push((i as u8, description.to_string()));
+            entry.1 += pixel_coverage;
+            entry.2 += 1;
+
             // lookup geohash in dht
             let addr = match crate::task::dht_lookup(
                     &dht, dht_key_length, &geohash) {
@@ -113,13 +137,36 @@ pub fn process(album: &str, dht: &Arc<RwLock<Dht>>, dht_key_length: i8,
             };
 
             // send image to new host
-            if let Err(e) = crate::transfer::send_image(&addr, album,
-                    &dataset, &geohash, pixel_coverage, "Sentinel-2",
-                    &RAW_SOURCE, i as u8, &tile, timestamp) {
+            if let Err(e) = crate::transfer::send_image(&addr, identity,
+                    node_id, album, &dataset, &geohash, pixel_coverage,
+                    "Sentinel-2", &RAW_SOURCE, i as u8, &tile, timestamp,
+                    false, compression) {
                 warn!("failed to write image to node {}: {}", addr, e);
             }
         }
     }
 
+    // send one aggregate catalog record per geohash tile, covering
+    // every band split above, so a downstream query can see what
+    // exists at a tile without opening any of its rasters
+    for (geohash, (files, coverage_sum, count)) in tile_metadata {
+        let mean_pixel_coverage = coverage_sum / count as f64;
+
+        let addr = match crate::task::dht_lookup(
+                &dht, dht_key_length, &geohash) {
+            Ok(addr) => addr,
+            Err(e) => {
+                warn!("{}", e);
+                continue;
+            },
+        };
+
+        if let Err(e) = crate::transfer::send_metadata(&addr, identity,
+                node_id, album, &geohash, "Sentinel-2", &tile,
+                mean_pixel_coverage, &files) {
+            warn!("failed to write tile metadata to node {}: {}", addr, e);
+        }
+    }
+
     Ok(())
 }
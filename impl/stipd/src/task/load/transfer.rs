@@ -0,0 +1,138 @@
+use crossbeam_channel::{Receiver, Sender};
+use swarm::prelude::Dht;
+
+use crate::identity::NodeIdentity;
+use crate::task::NonCriticalErrorSink;
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// attempts a failed send gets before it's given up on and recorded as
+/// a permanent non-critical error - transient peer unavailability
+/// during a large load is common enough that a single 'send_image'
+/// failure shouldn't drop a tile outright
+const TRANSFER_RETRY_ATTEMPTS: u32 = 3;
+
+/// base delay doubled on each retry (100ms, then 200ms) - short
+/// enough that a few retried tiles don't meaningfully slow a load, long
+/// enough to ride out a momentary blip on the peer
+const TRANSFER_RETRY_BASE: Duration = Duration::from_millis(100);
+
+/// one already-decoded, already-split raster waiting to be shipped to
+/// its owning dht node - plain bytes rather than a gdal 'Dataset', since
+/// a 'Dataset' wraps a raw handle that can't cross the channel into a
+/// transfer worker's thread
+pub struct TransferUnit {
+    pub album: String,
+    pub geocode: String,
+    pub image: Vec<u8>,
+    pub pixel_coverage: f64,
+    pub platform: String,
+    pub source: &'static str,
+    pub subdataset: u8,
+    pub tile: String,
+    pub timestamp: i64,
+}
+
+/// a small pool of threads dedicated to the dht lookup + network send
+/// half of loading a raster, decoupled from the decode/split half so a
+/// slow transfer to one node no longer stalls a thread that could be
+/// decoding the next record - sized independently of the decode pool's
+/// 'thread_count' via 'worker_count', since the two halves have very
+/// different cpu/io profiles
+pub struct TransferPool {
+    sender: Sender<TransferUnit>,
+}
+
+impl TransferPool {
+    pub fn new(dht: Arc<RwLock<Dht>>, dht_key_length: i8,
+            identity: Arc<NodeIdentity>, node_id: u32,
+            non_critical_errors: NonCriticalErrorSink, worker_count: u8)
+            -> TransferPool {
+        // bounded so a burst of decoded records can't outrun the
+        // transfer workers and grow memory unbounded
+        let (sender, receiver): (Sender<TransferUnit>, Receiver<TransferUnit>)
+            = crossbeam_channel::bounded(256);
+
+        for _ in 0..worker_count {
+            let dht = dht.clone();
+            let identity = identity.clone();
+            let non_critical_errors = non_critical_errors.clone();
+            let receiver = receiver.clone();
+
+            std::thread::spawn(move || {
+                while let Ok(unit) = receiver.recv() {
+                    let addr = match crate::task::dht_lookup(
+                            &dht, dht_key_length, &unit.geocode) {
+                        Ok(addr) => addr,
+                        Err(e) => {
+                            non_critical_errors.push_remote(&unit.tile,
+                                &unit.geocode, None,
+                                format!("dht lookup: {}", e));
+                            continue;
+                        },
+                    };
+
+                    if let Err(e) = send_with_retry(&addr, &identity, node_id,
+                            &unit) {
+                        non_critical_errors.push_remote(&unit.tile,
+                            &unit.geocode, Some(addr.to_string()),
+                            format!("transfer: {}", e));
+                    }
+                }
+            });
+        }
+
+        TransferPool { sender: sender }
+    }
+
+    /// hand a decoded, split raster off to the transfer pool - blocks
+    /// only if every transfer worker is already backed up, applying
+    /// natural backpressure onto the decode pool instead of buffering
+    /// an unbounded number of pending sends
+    pub fn submit(&self, unit: TransferUnit) {
+        if let Err(e) = self.sender.send(unit) {
+            warn!("failed to queue image for transfer: {}", e);
+        }
+    }
+}
+
+impl Clone for TransferPool {
+    fn clone(&self) -> TransferPool {
+        TransferPool { sender: self.sender.clone() }
+    }
+}
+
+/// send one unit, retrying with a jittered exponential backoff so a
+/// momentarily unreachable peer doesn't drop a tile outright - only the
+/// final attempt's error is surfaced, since the earlier ones were
+/// already recoverable
+fn send_with_retry(addr: &std::net::SocketAddr, identity: &NodeIdentity,
+        node_id: u32, unit: &TransferUnit)
+        -> Result<(), Box<dyn std::error::Error>> {
+    use rand::Rng;
+
+    for attempt in 0..TRANSFER_RETRY_ATTEMPTS {
+        match crate::transfer::send_image_bytes(addr, identity, node_id,
+                &unit.album, &unit.image, &unit.geocode, unit.pixel_coverage,
+                &unit.platform, unit.source, unit.subdataset, &unit.tile,
+                unit.timestamp, false, None) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                if attempt + 1 == TRANSFER_RETRY_ATTEMPTS {
+                    return Err(e);
+                }
+
+                let jitter = rand::thread_rng().gen_range(0, 50);
+                let delay = TRANSFER_RETRY_BASE * 2u32.pow(attempt)
+                    + Duration::from_millis(jitter);
+
+                warn!("retrying transfer of '{}' to {} after {:?}: {}",
+                    unit.tile, addr, delay, e);
+                std::thread::sleep(delay);
+            },
+        }
+    }
+
+    unreachable!()
+}
@@ -1,3 +1,4 @@
+use crossbeam_deque::{Injector, Stealer, Worker as DequeWorker};
 use swarm::prelude::Dht;
 use tokio::runtime::Builder;
 
@@ -7,14 +8,26 @@ use std::collections::hash_map::Iter;
 use std::error::Error;
 use std::hash::Hasher;
 use std::net::SocketAddr;
-use std::sync::{Arc, RwLock};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
+pub mod checkpoint;
 pub mod coalesce;
+pub mod deadletter;
 //pub mod fill;
+pub mod job;
+pub mod load;
+pub mod pool;
+pub mod preview;
+pub mod reconcile;
+pub mod repair;
 pub mod split;
 pub mod store;
 pub mod open;
+pub mod verify;
+
+use checkpoint::TaskDescriptor;
 
 #[tonic::async_trait]
 pub trait TaskOg {
@@ -61,7 +74,7 @@ impl TaskHandleOg {
 }
 
 pub struct TaskManager {
-    tasks: HashMap<u64, Arc<RwLock<TaskHandleOg>>>,
+    tasks: HashMap<u64, Arc<RwLock<TaskHandle>>>,
 }
 
 impl TaskManager {
@@ -72,11 +85,10 @@ impl TaskManager {
     }
 
     pub fn clear(&mut self) -> Result<(), Box<dyn Error>> {
-        // retrieve list of 'complete' ids    
+        // retrieve list of 'complete' ids
         let complete_ids: Vec<u64> = self.tasks.iter()
             .filter(|(_, task_handle)|
-                task_handle.read().unwrap().get_status()
-                    == &TaskStatus::Complete)
+                task_handle.read().unwrap().completed())
             .map(|(id, _)| id.clone())
             .collect();
 
@@ -88,11 +100,15 @@ impl TaskManager {
         Ok(())
     }
 
-    pub fn iter(&self) -> Iter<u64, Arc<RwLock<TaskHandleOg>>> {
+    pub fn get(&self, task_id: u64) -> Option<Arc<RwLock<TaskHandle>>> {
+        self.tasks.get(&task_id).cloned()
+    }
+
+    pub fn iter(&self) -> Iter<u64, Arc<RwLock<TaskHandle>>> {
         self.tasks.iter()
     }
 
-    pub fn register(&mut self, task_handle: Arc<RwLock<TaskHandleOg>>,
+    pub fn register(&mut self, task_handle: Arc<RwLock<TaskHandle>>,
             task_id: Option<u64>) -> Result<u64, Box<dyn Error>> {
         // initialize task id
         let task_id = match task_id {
@@ -100,14 +116,32 @@ impl TaskManager {
             None => rand::random::<u64>(),
         };
 
-        // add TaskHandleOg to map
+        // add TaskHandle to map
         self.tasks.insert(task_id, task_handle);
 
         // return task id
         Ok(task_id)
     }
+
+    /// force every running task's completion log to disk - called on a
+    /// graceful shutdown so a record that finished just before the
+    /// signal arrived isn't lost to 'CompletionLog''s fsync-every-16
+    /// batching
+    pub fn flush_all(&self) {
+        for (task_id, task_handle) in self.tasks.iter() {
+            if let Err(e) = task_handle.read().unwrap().flush() {
+                warn!("failed to flush task {}: {}", task_id, e);
+            }
+        }
+    }
 }
 
+// paused/cancelled are deliberately NOT variants here - they're
+// transient control states a task can be in while still 'Running', not
+// a terminal outcome like 'Complete'/'Failure', so they're exposed as
+// separate 'paused()'/'cancelled()' bools on 'TaskHandle' (surfaced
+// alongside 'status' in the list/status rpcs and cli output) instead of
+// folding them into this enum
 #[derive(PartialEq)]
 pub enum TaskStatus {
     Complete,
@@ -115,18 +149,136 @@ pub enum TaskStatus {
     Running,
 }
 
+/// recent non-critical per-record failures are retained up to this many
+/// entries - enough for a status poller to show what's currently going
+/// wrong without the ring growing unbounded over a long-running task
+const MAX_TASK_ERRORS: usize = 32;
+
+/// a single recoverable failure encountered while processing one item
+/// within a record (e.g. one geocode tile split out of a scene) - the
+/// record itself still completes and checkpoints normally, this exists
+/// purely so an operator can tell a "complete" task apart from one that
+/// silently dropped tiles along the way
+#[derive(Clone, Debug)]
+pub struct NonCriticalError {
+    pub record: String,
+    pub geocode: String,
+    pub reason: String,
+    // populated by failures that originate from a specific remote peer
+    // (e.g. the load transfer pool's dht lookup / send_image) - None
+    // for the per-geocode decode failures store/*.rs already reports,
+    // which never reach a remote node to fail against
+    pub node_addr: Option<String>,
+}
+
+/// cheap-to-clone, shared bounded ring of 'NonCriticalError's - tasks
+/// that split a record into multiple geocodes (store/modis.rs,
+/// store/viirs.rs, ...) hand this down into their per-geocode loops
+/// instead of only 'warn!'ing a dropped tile
+#[derive(Clone)]
+pub struct NonCriticalErrorSink {
+    count: Arc<AtomicU32>,
+    errors: Arc<RwLock<std::collections::VecDeque<NonCriticalError>>>,
+}
+
+impl NonCriticalErrorSink {
+    pub fn new() -> NonCriticalErrorSink {
+        NonCriticalErrorSink {
+            count: Arc::new(AtomicU32::new(0)),
+            errors: Arc::new(RwLock::new(
+                std::collections::VecDeque::with_capacity(MAX_TASK_ERRORS))),
+        }
+    }
+
+    /// record a dropped geocode - counted even once the ring below has
+    /// wrapped, so 'count()' always reflects the true total
+    pub fn push(&self, record: impl std::fmt::Debug, geocode: &str,
+            reason: impl std::fmt::Display) {
+        self.push_remote(record, geocode, None, reason);
+    }
+
+    /// same as 'push', but also records the remote peer a failure
+    /// originated from - used by the load transfer pool, where a dht
+    /// lookup miss or a 'send_image' failure is tied to one specific
+    /// node rather than being purely local to this task
+    pub fn push_remote(&self, record: impl std::fmt::Debug, geocode: &str,
+            node_addr: Option<String>, reason: impl std::fmt::Display) {
+        self.count.fetch_add(1, Ordering::SeqCst);
+
+        if let Ok(mut errors) = self.errors.write() {
+            if errors.len() >= MAX_TASK_ERRORS {
+                errors.pop_front();
+            }
+            errors.push_back(NonCriticalError {
+                record: format!("{:?}", record),
+                geocode: geocode.to_string(),
+                reason: reason.to_string(),
+                node_addr: node_addr,
+            });
+        }
+    }
+
+    fn count(&self) -> u32 {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    fn snapshot(&self) -> Vec<NonCriticalError> {
+        self.errors.read().unwrap().iter().cloned().collect()
+    }
+}
+
+// pause/resume/cancel live here, on the generic handle every 'Task<T>'
+// impl returns from 'start' - a load job gets the same control surface
+// as a store/split/open/repair task for free, over the same
+// 'TaskManagement' pause/resume/cancel rpcs, rather than needing its
+// own copy wired through 'DataManagement'
 pub struct TaskHandle {
+    cancelled: Arc<AtomicBool>,
+    completed: Arc<AtomicBool>,
     completed_count: Arc<AtomicU32>,
+    completion_log: Arc<Mutex<checkpoint::CompletionLog>>,
+    errors: Arc<RwLock<std::collections::VecDeque<String>>>,
+    non_critical_errors: NonCriticalErrorSink,
+    paused: Arc<AtomicBool>,
     running: Arc<AtomicBool>,
     skipped_count: Arc<AtomicU32>,
     total_count: Arc<AtomicU32>,
 }
 
 impl TaskHandle {
+    pub fn completed(&self) -> bool {
+        self.completed.load(Ordering::SeqCst)
+    }
+
+    /// force this task's completion log to disk immediately, bypassing
+    /// the normal fsync-every-16-appends batching - see
+    /// 'TaskManager::flush_all'
+    pub fn flush(&self) -> Result<(), Box<dyn Error>> {
+        self.completion_log.lock().unwrap().flush()
+    }
+
     pub fn completed_count(&self) -> u32 {
         self.completed_count.load(Ordering::SeqCst)
     }
 
+    /// snapshot of the most recent non-critical record failures, oldest
+    /// first
+    pub fn errors(&self) -> Vec<String> {
+        self.errors.read().unwrap().iter().cloned().collect()
+    }
+
+    /// total recoverable per-item failures recorded, which may exceed
+    /// the number of entries retained in 'non_critical_errors()' once
+    /// the ring below has wrapped
+    pub fn non_critical_error_count(&self) -> u32 {
+        self.non_critical_errors.count()
+    }
+
+    /// snapshot of the most recent dropped geocodes, oldest first
+    pub fn non_critical_errors(&self) -> Vec<NonCriticalError> {
+        self.non_critical_errors.snapshot()
+    }
+
     pub fn running(&self) -> bool {
         self.running.load(Ordering::SeqCst)
     }
@@ -138,6 +290,88 @@ impl TaskHandle {
     pub fn total_count(&self) -> u32 {
         self.total_count.load(Ordering::SeqCst)
     }
+
+    /// 'completed_count / total_count' as a percentage - 0.0 before
+    /// 'records()' has finished compiling the manifest (total_count is
+    /// still 0), so a freshly (re)started task reads as just getting
+    /// underway rather than already done
+    pub fn completion_percent(&self) -> f32 {
+        let total_count = self.total_count();
+        if total_count == 0 {
+            return 0.0;
+        }
+
+        self.completed_count() as f32 / total_count as f32 * 100f32
+    }
+
+    /// stop draining new records - flips this task's pool registration
+    /// to paused, so shared pool threads simply skip stealing from its
+    /// queue until `resume()` rather than dequeuing a job and blocking
+    /// inside it. a job already dequeued before `pause()` runs to
+    /// completion; everything still queued waits untouched, so a pause
+    /// can never park a shared pool thread
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// stop the task after its in-flight records finish - already
+    /// completed records stay checkpointed, but the task is marked
+    /// complete rather than resumed on the next restart, since the
+    /// operator asked for it to stop rather than crash mid-work
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+
+        // a cancelled task's still-queued records should drain (and be
+        // dropped, see the job closure below) rather than sit behind a
+        // pause forever waiting for a resume() that may never come
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// pop a record from this worker's own deque, falling back to stealing
+/// a batch from the shared injector and, failing that, from a peer
+/// worker - retried until a steal attempt stops reporting `Retry`
+///
+/// 'pub(crate)' so finer-grained work-stealing pools (e.g.
+/// `store::landsat`'s per-split worker pool) can reuse the same
+/// steal-then-retry shape instead of duplicating it
+pub(crate) fn find_task<T>(local: &DequeWorker<T>, global: &Injector<T>,
+        stealers: &[Stealer<T>]) -> Option<T> {
+    local.pop().or_else(|| {
+        std::iter::repeat_with(|| {
+            global.steal_batch_and_pop(local)
+                .or_else(|| steal_from_peer(stealers))
+        }).find(|s| !s.is_retry()).and_then(|s| s.success())
+    })
+}
+
+/// attempt a steal against every peer deque, starting from a randomly
+/// chosen peer each call rather than always favoring the low-index
+/// workers - otherwise a consistently heavy record on worker 0 leaves
+/// every other worker queued up behind it instead of spreading steals
+/// evenly
+fn steal_from_peer<T>(stealers: &[Stealer<T>]) -> crossbeam_deque::Steal<T> {
+    use rand::Rng;
+
+    if stealers.is_empty() {
+        return crossbeam_deque::Steal::Empty;
+    }
+
+    let offset = rand::thread_rng().gen_range(0, stealers.len());
+    stealers.iter().cycle().skip(offset).take(stealers.len())
+        .map(|s| s.steal()).collect()
 }
 
 #[tonic::async_trait]
@@ -145,65 +379,93 @@ pub trait Task<T: 'static + std::fmt::Debug + Send + Sync> {
     fn process(&self, record: &T) -> Result<(), Box<dyn Error>>;
     async fn records(&self) -> Result<Vec<T>, Box<dyn Error>>;
 
-    fn start(self: Arc<Self>, thread_count: u8) 
-            -> Result<Arc<RwLock<TaskHandle>>, Box<dyn Error>>
+    /// reconstructable descriptor persisted alongside the completion
+    /// log - tasks that don't override this aren't resumed across a
+    /// restart, they're simply not rehydrated by the startup scan
+    fn descriptor(&self) -> Option<TaskDescriptor> {
+        None
+    }
+
+    /// shared sink tasks can report recoverable per-item (rather than
+    /// per-record) failures into, surfaced through the 'TaskHandle'
+    /// 'start' returns - tasks that don't override this just have
+    /// nowhere to report, same default-empty pattern as 'descriptor'
+    fn non_critical_errors(&self) -> NonCriticalErrorSink {
+        NonCriticalErrorSink::new()
+    }
+
+    /// start the task, checkpointing progress under
+    /// 'directory/.tasks/task_id' so a crash or restart can resume from
+    /// the last completed record instead of redoing the whole task
+    ///
+    /// records run on the process-wide 'pool::WorkerPool' rather than on
+    /// threads dedicated to this task, so 'thread_count' no longer
+    /// governs how many threads this call spawns - it's kept only as a
+    /// formal parameter so none of the existing call sites need to
+    /// change; the pool's own thread count bounds actual concurrency
+    /// across every task running at once
+    fn start(self: Arc<Self>, directory: PathBuf, task_id: u64,
+            _thread_count: u8) -> Result<Arc<RwLock<TaskHandle>>, Box<dyn Error>>
             where Self: 'static + Send + Sync {
+        // persist the descriptor (if any) so a restarted node knows how
+        // to rebuild this task without the original rpc request
+        if let Some(descriptor) = self.descriptor() {
+            checkpoint::write_descriptor(&directory, task_id, &descriptor)?;
+        }
+
         // initialize instance variables
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let completed = Arc::new(AtomicBool::new(false));
         let completed_count = Arc::new(AtomicU32::new(0));
+        let errors = Arc::new(RwLock::new(
+            std::collections::VecDeque::with_capacity(MAX_TASK_ERRORS)));
+        let non_critical_errors = self.non_critical_errors();
         let running = Arc::new(AtomicBool::new(true));
         let skipped_count = Arc::new(AtomicU32::new(0));
         let total_count = Arc::new(AtomicU32::new(0));
 
-        // initialize record channel
-        let (sender, receiver) = crossbeam_channel::bounded(256);
-
-        // start worker threads
-        let mut join_handles = Vec::new();
-        for _ in 0..thread_count {
-            let completed_count = completed_count.clone();
-            let skipped_count = skipped_count.clone();
-            let receiver = receiver.clone();
-            let self_clone = self.clone();
-
-            let join_handle = std::thread::spawn(move || {
-                // iterate over records
-                loop {
-                    // fetch next record
-                    let record: T = match receiver.recv() {
-                        Ok(record) => record,
-                        Err(_) => break,
-                    };
-
-                    // process record
-                    let result = self_clone.process(&record);
-
-                    // process result
-                    match result {
-                        Ok(_) => completed_count.fetch_add(1, Ordering::SeqCst),
-                        Err(e) => {
-                            println!("skipping record '{:?}': {}", record, e);
-                            skipped_count.fetch_add(1, Ordering::SeqCst)
-                        },
-                    };
-                }
-            });
+        // register this task's own local queue with the shared pool -
+        // the management thread below pushes one job per record onto
+        // 'local', and any pool thread (idle on this task or another)
+        // may steal and run it, attributing progress back to this task
+        // via the atomics each job closure captures. 'paused' gates
+        // stealing from 'local' itself (see 'TaskHandle::pause'), rather
+        // than each job blocking after it's already been dequeued
+        let (local, drained, paused) = pool::global().register();
 
-            join_handles.push(join_handle);
-        }
+        // already-completed records (from a prior crash/restart) are
+        // skipped rather than resent - idempotent for the image-send
+        // tasks this backs, but cheaper to just not redo them
+        let already_completed = checkpoint::read_completed(&directory, task_id)?;
+
+        let completion_log = Arc::new(std::sync::Mutex::new(
+            checkpoint::CompletionLog::open(&directory, task_id)?));
 
         // initialize TaskHandle
         let task_handle = Arc::new( RwLock::new(
             TaskHandle {
-                completed_count: completed_count,
-                skipped_count: skipped_count,
+                cancelled: cancelled.clone(),
+                completed: completed.clone(),
+                completed_count: completed_count.clone(),
+                completion_log: completion_log.clone(),
+                errors: errors.clone(),
+                non_critical_errors: non_critical_errors,
+                paused: paused.clone(),
+                skipped_count: skipped_count.clone(),
                 running: running.clone(),
                 total_count: total_count.clone(),
             }));
 
+        // number of fed-but-not-yet-finished jobs - replaces the old
+        // 'join_handle.join()' on a fixed set of dedicated worker
+        // threads, since jobs now finish on whichever shared pool
+        // thread happened to steal them
+        let remaining = Arc::new(AtomicU32::new(0));
+
         // start management thread
         let _ = std::thread::spawn(move || {
             // compute processing records
-            let mut runtime = match 
+            let mut runtime = match
                     Builder::new().basic_scheduler().build() {
                 Ok(runtime) => runtime,
                 Err(e) => {
@@ -222,27 +484,97 @@ pub trait Task<T: 'static + std::fmt::Debug + Send + Sync> {
                 },
             };
 
+            // subtract already-completed records so a resumed task only
+            // re-enqueues the remainder
+            let records: Vec<T> = records.into_iter()
+                .filter(|record| !already_completed.contains(
+                    &checkpoint::record_key(record)))
+                .collect();
+
             total_count.store(records.len() as u32, Ordering::SeqCst);
 
-            // add items to pipeline
+            // feed one job per record onto our local queue, stopping
+            // early if the task was cancelled while records were still
+            // being compiled
             for record in records {
-                if let Err(e) = sender.send(record) {
-                    warn!("task failed to send record: {}", e);
+                if cancelled.load(Ordering::SeqCst) {
                     break;
                 }
+
+                remaining.fetch_add(1, Ordering::SeqCst);
+
+                let cancelled = cancelled.clone();
+                let completed_count = completed_count.clone();
+                let completion_log = completion_log.clone();
+                let errors = errors.clone();
+                let remaining = remaining.clone();
+                let self_clone = self.clone();
+                let skipped_count = skipped_count.clone();
+
+                local.push(Box::new(move || {
+                    // a cancelled task drops any record it hasn't
+                    // started yet rather than processing it
+                    if !cancelled.load(Ordering::SeqCst) {
+                        let key = checkpoint::record_key(&record);
+
+                        match self_clone.process(&record) {
+                            Ok(_) => {
+                                completed_count.fetch_add(1, Ordering::SeqCst);
+
+                                if let Ok(mut log) = completion_log.lock() {
+                                    if let Err(e) = log.append(&key) {
+                                        warn!("failed to checkpoint record \
+                                            '{}': {}", key, e);
+                                    }
+                                }
+                            },
+                            Err(e) => {
+                                println!("skipping record '{:?}': {}",
+                                    record, e);
+                                skipped_count.fetch_add(1, Ordering::SeqCst);
+
+                                // a record failure is non-critical - note
+                                // it in the bounded ring for status
+                                // reporting and keep draining the rest
+                                // of the task
+                                if let Ok(mut errors) = errors.write() {
+                                    if errors.len() >= MAX_TASK_ERRORS {
+                                        errors.pop_front();
+                                    }
+                                    errors.push_back(format!(
+                                        "{:?}: {}", record, e));
+                                }
+                            },
+                        };
+                    }
+
+                    remaining.fetch_sub(1, Ordering::SeqCst);
+                }));
             }
- 
-            // drop sender to signal worker threads
-            drop(sender);
-
-            // join worker threads
-            for join_handle in join_handles {
-                if let Err(e) = join_handle.join() {
-                    warn!("task failed to join worker: {:?}", e);
+
+            // wait for every fed job to finish, wherever in the pool it
+            // ran - only then is it safe to flush and mark this task
+            // complete
+            while remaining.load(Ordering::SeqCst) > 0 {
+                std::thread::sleep(std::time::Duration::from_millis(20));
+            }
+
+            // nothing left to steal from our queue - let the pool prune
+            // this registration instead of stealing against it forever
+            drained.store(true, Ordering::SeqCst);
+
+            if let Ok(mut log) = completion_log.lock() {
+                if let Err(e) = log.flush() {
+                    warn!("failed to flush completion log: {}", e);
                 }
             }
 
+            if let Err(e) = checkpoint::mark_complete(&directory, task_id) {
+                warn!("failed to mark task {} complete: {}", task_id, e);
+            }
+
             // complete TaskHandle
+            completed.store(true, Ordering::SeqCst);
             running.store(false, Ordering::SeqCst);
         });
 
@@ -250,18 +582,23 @@ pub trait Task<T: 'static + std::fmt::Debug + Send + Sync> {
     }
 }
 
-fn dht_lookup(dht: &Arc<RwLock<Dht>>, dht_key_length: i8,
-        geocode: &str) -> Result<SocketAddr, Box<dyn Error>> {
-    // compute dht geocode using dht_key_length
-    let geocode = match dht_key_length {
-        0 => geocode,
+fn truncate_geocode<'a>(dht_key_length: i8, geocode: &'a str)
+        -> Result<&'a str, Box<dyn Error>> {
+    match dht_key_length {
+        0 => Ok(geocode),
         x if x > 0 && x < geocode.len() as i8 =>
-            &geocode[x as usize..],
+            Ok(&geocode[x as usize..]),
         x if x < 0 && x > (-1 * geocode.len() as i8) =>
-            &geocode[..(geocode.len() as i8 + x) as usize],
-        _ => return Err(format!("dht key length '{}' invalid for '{}'",
+            Ok(&geocode[..(geocode.len() as i8 + x) as usize]),
+        _ => Err(format!("dht key length '{}' invalid for '{}'",
                 dht_key_length, geocode).into()),
-    };
+    }
+}
+
+fn dht_lookup(dht: &Arc<RwLock<Dht>>, dht_key_length: i8,
+        geocode: &str) -> Result<SocketAddr, Box<dyn Error>> {
+    // compute dht geocode using dht_key_length
+    let geocode = truncate_geocode(dht_key_length, geocode)?;
 
     // compute geocode hash
     let mut hasher = DefaultHasher::new();
@@ -269,7 +606,7 @@ fn dht_lookup(dht: &Arc<RwLock<Dht>>, dht_key_length: i8,
     let hash = hasher.finish();
 
     // discover hash location
-    let dht = dht.read().unwrap(); 
+    let dht = dht.read().unwrap();
     match dht.locate(hash) {
         Some((node_id, addrs)) => {
             match addrs.1 {
@@ -281,3 +618,136 @@ fn dht_lookup(dht: &Arc<RwLock<Dht>>, dht_key_length: i8,
         None => Err(format!("no dht node for hash {}", hash).into()),
     }
 }
+
+/// default number of replicas placed for each split tile
+pub const DEFAULT_REPLICATION_FACTOR: u8 = 3;
+
+/// select up to 'replication_factor' distinct nodes to hold a copy of
+/// 'geocode', using weighted sampling without replacement seeded by
+/// hash(geocode) so the replica set is stable across calls. each node's
+/// weight is its gossiped 'free_capacity' metadata (see main.rs), so a
+/// node with more headroom is proportionally more likely to be chosen;
+/// candidates are drawn zone-by-zone (each node's gossiped 'zone'
+/// metadata, "default" if unconfigured) so replicas spread across fault
+/// domains before a zone is ever used twice.
+pub(crate) fn dht_lookup_replicas(dht: &Arc<RwLock<Dht>>, dht_key_length: i8,
+        geocode: &str, replication_factor: u8)
+        -> Result<Vec<SocketAddr>, Box<dyn Error>> {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    let geocode = truncate_geocode(dht_key_length, geocode)?;
+
+    // seed deterministically so replica placement is stable across runs
+    let mut hasher = DefaultHasher::new();
+    hasher.write(geocode.as_bytes());
+    let seed = hasher.finish();
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    // each node's gossiped free-capacity weight - a node that hasn't
+    // reported one (or reports 0, i.e. full) defaults to 0 and is only
+    // used below if no node with headroom is available
+    let weights: HashMap<u32, f64> = {
+        let dht = dht.read().unwrap();
+        dht.nodes().into_iter()
+            .map(|node| (node.get_id(), node.get_metadata("free_capacity")
+                .and_then(|value| value.parse::<f64>().ok())
+                .unwrap_or(0f64)))
+            .collect()
+    };
+
+    // each node's gossiped fault domain - a node that hasn't reported
+    // one defaults to "default", so a cluster that hasn't configured
+    // zones behaves exactly as before (every node in the same zone)
+    let zones: HashMap<u32, String> = {
+        let dht = dht.read().unwrap();
+        dht.nodes().into_iter()
+            .map(|node| (node.get_id(), node.get_metadata("zone")
+                .unwrap_or_else(|| "default".to_string())))
+            .collect()
+    };
+
+    // gather candidate nodes - (addr, zone, capacity weight)
+    let candidates: Vec<(SocketAddr, String, f64)> = {
+        let dht = dht.read().unwrap();
+        dht.iter().filter_map(|(node_id, addrs)|
+            addrs.1.map(|addr| (addr.clone(),
+                zones.get(node_id).cloned()
+                    .unwrap_or_else(|| "default".to_string()),
+                weights.get(node_id).cloned().unwrap_or(0f64))))
+            .collect()
+    };
+
+    if candidates.is_empty() {
+        return Err("no dht nodes with an advertised xfer_addr".into());
+    }
+
+    // prefer nodes that still have room, but fall back to the full
+    // ones rather than failing outright if that's all there is
+    let eligible: Vec<&(SocketAddr, String, f64)> = candidates.iter()
+        .filter(|(_, _, weight)| *weight > 0f64).collect();
+    let pool: Vec<(SocketAddr, String, f64)> = if eligible.is_empty() {
+        candidates.clone()
+    } else {
+        eligible.into_iter().cloned().collect()
+    };
+
+    // draw u in (0,1), rank by u^(1/weight) descending - a weight of 0
+    // (every candidate is full) is floored so the exponent stays finite
+    let mut keyed: Vec<(f64, SocketAddr, String)> = pool.iter()
+        .map(|(addr, zone, weight)| {
+            let u: f64 = rng.gen_range(0f64, 1f64);
+            let weight = weight.max(f64::MIN_POSITIVE);
+            (u.powf(1f64 / weight), *addr, zone.clone())
+        }).collect();
+    keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+    // take the top candidates, skipping repeat zones until exhausted
+    let mut replicas = Vec::new();
+    let mut used_zones: std::collections::HashSet<String> =
+        std::collections::HashSet::new();
+    for (_, addr, zone) in keyed.iter() {
+        if replicas.len() >= replication_factor as usize {
+            break;
+        }
+
+        if used_zones.contains(zone)
+                && used_zones.len() < pool.len() {
+            continue;
+        }
+
+        used_zones.insert(zone.clone());
+        replicas.push(*addr);
+    }
+
+    // if zone-skipping left us short (too few zones for R), fill in
+    // with the remaining ranked candidates regardless of zone
+    for (_, addr, _) in keyed.iter() {
+        if replicas.len() >= replication_factor as usize {
+            break;
+        }
+
+        if !replicas.contains(addr) {
+            replicas.push(*addr);
+        }
+    }
+
+    Ok(replicas)
+}
+
+/// minimum number of successful replica writes for a tile write to count
+/// as durable - a strict majority of 'replication_factor', so the tile
+/// survives the loss of any minority of its replicas
+pub(crate) fn write_quorum(replication_factor: u8) -> usize {
+    (replication_factor / 2 + 1) as usize
+}
+
+/// send a tile to every address in 'addrs' via 'send', returning the
+/// number of replicas that accepted it - losing a minority of replicas
+/// is the expected case in a fault-tolerant store, so callers compare
+/// the result against 'write_quorum' rather than 'warn!'ing on every
+/// individual replica failure
+pub(crate) fn send_to_replicas<F>(addrs: &[SocketAddr], mut send: F) -> usize
+        where F: FnMut(&SocketAddr) -> Result<(), Box<dyn Error>> {
+    addrs.iter().filter(|addr| send(addr).is_ok()).count()
+}
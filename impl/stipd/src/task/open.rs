@@ -2,6 +2,7 @@ use gdal::{Dataset, Metadata};
 
 use crate::album::Album;
 use crate::task::Task;
+use crate::task::checkpoint::TaskDescriptor;
 
 use std::error::Error;
 use std::path::PathBuf;
@@ -9,10 +10,11 @@ use std::sync::{Arc, RwLock};
 
 pub struct OpenTask {
     album: Arc<RwLock<Album>>,
+    thread_count: u8,
 }
 
 impl OpenTask {
-    pub fn new(album: Arc<RwLock<Album>>) -> OpenTask {
+    pub fn new(album: Arc<RwLock<Album>>, thread_count: u8) -> OpenTask {
         {
             let album = album.read().unwrap();
             info!("initailizing open task [album={}]", album.get_id());
@@ -20,15 +22,29 @@ impl OpenTask {
 
         OpenTask {
             album: album,
+            thread_count: thread_count,
         }
     }
 }
 
 #[tonic::async_trait]
 impl Task<PathBuf> for OpenTask {
+    // persists the thread count alongside the album id so the
+    // descriptor carries it across a restart - `Task::start` does not
+    // read it, since records run on the process-wide pool
+    fn descriptor(&self) -> Option<TaskDescriptor> {
+        let album = self.album.read().unwrap();
+        Some(TaskDescriptor::Open {
+            album: album.get_id().to_string(),
+            thread_count: self.thread_count,
+        })
+    }
+
     fn process(&self, record: &PathBuf) -> Result<(), Box<dyn Error>> {
         let dataset = Dataset::open(&record)?;
 
+        let checksum = dataset.metadata_item("CHECKSUM", "STIP")
+            .ok_or("image checksum metadata not found")?.parse::<u64>()?;
         let cloud_coverage =
                 match dataset.metadata_item("CLOUD_COVERAGE", "STIP") {
             Some(cloud_coverage) => Some(cloud_coverage.parse::<f64>()?),
@@ -48,17 +64,27 @@ impl Task<PathBuf> for OpenTask {
             .ok_or("image tile metadata not found")?;
         let timestamp = dataset.metadata_item("TIMESTAMP", "STIP")
             .ok_or("image timestamp metadata not found")?.parse::<i64>()?;
+        let preview = dataset.metadata_item("PREVIEW", "STIP")
+            .map_or(false, |value| value == "true");
 
         let mut album = self.album.write().unwrap();
-        album.load(cloud_coverage, &geocode, pixel_coverage,
-            &platform, &source, subdataset, &tile, timestamp)?;
+        album.load(Some(checksum), cloud_coverage, &geocode, pixel_coverage,
+            &platform, &source, subdataset, &tile, timestamp, preview)?;
 
         Ok(())
     }
 
     async fn records(&self) -> Result<Vec<PathBuf>, Box<dyn Error>> {
-        // search for paths using Album
         let album = self.album.read().unwrap();
+
+        // a fresh index cache was already replayed in full by
+        // 'Album::open' - skip the expensive glob and per-file gdal
+        // reopen below and report no records for this task to process
+        if !album.needs_rescan() {
+            return Ok(Vec::new());
+        }
+
+        // search for paths using Album
         album.get_paths()
     }
 }
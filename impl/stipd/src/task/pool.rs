@@ -0,0 +1,142 @@
+use crossbeam_deque::{Injector, Steal, Stealer, Worker as DequeWorker};
+
+use std::sync::{Arc, OnceLock, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// one unit of queued work, type-erased so the shared pool's worker
+/// threads can run records belonging to any `Task<T>` impl side by side -
+/// a per-task `Injector<T>` can't do this since it's generic over a
+/// single concrete `T`
+pub type Job = Box<dyn FnOnce() + Send>;
+
+/// worker threads the process-wide pool runs - independent of any one
+/// task's `thread_count`, which no longer spawns dedicated threads of
+/// its own and is not read by `Task::start` at all
+const POOL_THREAD_COUNT: usize = 8;
+
+/// a still-registered task's stealable queue - `drained` flips once the
+/// owning task has fed every record and they've all finished executing,
+/// so pool threads stop paying the (small) cost of stealing against a
+/// queue that will never produce work again. `paused` flips while the
+/// owning task is paused, so pool threads simply skip stealing from this
+/// queue rather than dequeuing a job and blocking inside it - a paused
+/// task can't park every shared pool thread, since its jobs are never
+/// taken off the queue in the first place
+struct Registration {
+    drained: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    stealer: Stealer<Job>,
+}
+
+/// process-wide work-stealing pool shared by every running `Task<T>` -
+/// replaces the old per-task dedicated thread spawn (one dedicated OS
+/// thread per task per `thread_count`), so a node running several tasks
+/// concurrently doesn't oversubscribe far past its core count, and an
+/// idle task's would-be threads can pick up a busy task's backlog
+/// instead of sitting parked. each task still owns its own local deque
+/// (registered via `register`) for steal locality; only the worker
+/// threads themselves are shared
+pub struct WorkerPool {
+    global: Arc<Injector<Job>>,
+    registry: Arc<RwLock<Vec<Registration>>>,
+}
+
+impl WorkerPool {
+    fn new(thread_count: usize) -> WorkerPool {
+        let global = Arc::new(Injector::new());
+        let registry: Arc<RwLock<Vec<Registration>>> =
+            Arc::new(RwLock::new(Vec::new()));
+
+        for _ in 0..thread_count {
+            let global = global.clone();
+            let registry = registry.clone();
+
+            std::thread::spawn(move || {
+                loop {
+                    match find_job(&global, &registry) {
+                        Some(job) => job(),
+                        None => std::thread::sleep(
+                            std::time::Duration::from_millis(20)),
+                    }
+                }
+            });
+        }
+
+        WorkerPool {
+            global: global,
+            registry: registry,
+        }
+    }
+
+    /// register a new task's local queue with the pool, returning the
+    /// `DequeWorker` the caller (the task's management thread) owns and
+    /// feeds directly, the `drained` flag the caller should set once
+    /// feeding is done and every fed job has finished, and the `paused`
+    /// flag the caller should toggle from `TaskHandle::pause`/`resume` -
+    /// pool threads steal from this queue for the lifetime of the
+    /// registration (skipping it entirely while `paused` is set),
+    /// attributing completion back to the task via the closure each job
+    /// already captures
+    pub fn register(&self) -> (DequeWorker<Job>, Arc<AtomicBool>, Arc<AtomicBool>) {
+        let local = DequeWorker::new_fifo();
+        let drained = Arc::new(AtomicBool::new(false));
+        let paused = Arc::new(AtomicBool::new(false));
+
+        let mut registry = self.registry.write().unwrap();
+
+        // prune already-drained registrations so a long-running node
+        // doesn't accumulate one stale entry per historical task
+        registry.retain(|registration|
+            !registration.drained.load(Ordering::SeqCst));
+
+        registry.push(Registration {
+            drained: drained.clone(),
+            paused: paused.clone(),
+            stealer: local.stealer(),
+        });
+
+        (local, drained, paused)
+    }
+}
+
+/// pop a job from the global injector, falling back to stealing from a
+/// randomly chosen still-registered task's queue - mirrors
+/// `super::find_task`, generalized over the type-erased `Job` and a
+/// dynamic set of peer queues rather than a fixed slice of sibling
+/// threads belonging to one task
+fn find_job(global: &Injector<Job>, registry: &RwLock<Vec<Registration>>)
+        -> Option<Job> {
+    std::iter::repeat_with(|| {
+        global.steal().or_else(|| steal_from_registry(registry))
+    }).find(|s| !s.is_retry()).and_then(|s| s.success())
+}
+
+/// attempt a steal against every registered task queue, starting from a
+/// randomly chosen one each call rather than always favoring the
+/// earliest-registered task - otherwise a long-queued task starves
+/// later ones whenever the global injector is momentarily empty
+fn steal_from_registry(registry: &RwLock<Vec<Registration>>) -> Steal<Job> {
+    use rand::Rng;
+
+    let registry = registry.read().unwrap();
+    if registry.is_empty() {
+        return Steal::Empty;
+    }
+
+    let offset = rand::thread_rng().gen_range(0, registry.len());
+    registry.iter().cycle().skip(offset).take(registry.len())
+        .filter(|registration| !registration.paused.load(Ordering::SeqCst))
+        .map(|registration| registration.stealer.steal()).collect()
+}
+
+static POOL: OnceLock<WorkerPool> = OnceLock::new();
+
+/// the process-wide pool every `Task<T>::start` registers against -
+/// lazily built on first use rather than threaded through `TaskManager`
+/// (which would mean updating every existing
+/// `.start(directory, task_id, thread_count)` call site). equivalent in
+/// practice, since exactly one `TaskManager` is ever constructed per
+/// process (see `main.rs`)
+pub fn global() -> &'static WorkerPool {
+    POOL.get_or_init(|| WorkerPool::new(POOL_THREAD_COUNT))
+}
@@ -0,0 +1,101 @@
+use gdal::raster::Dataset;
+
+use crate::{Image, StFile};
+use crate::album::Album;
+use crate::task::Task;
+use crate::task::store::overview;
+
+use std::error::Error;
+use std::sync::{Arc, RwLock};
+
+/// generates (and caches) a downsampled preview raster for every tile
+/// matching a selector, so a client can pull a cheap thumbnail instead
+/// of a full-resolution scene - on-demand and size-parameterized, unlike
+/// the fixed 256px preview 'StoreEarthExplorerTask' bakes in alongside
+/// MODIS/VIIRS splits at store time
+pub struct PreviewTask {
+    album: Arc<RwLock<Album>>,
+    end_timestamp: Option<i64>,
+    geocode: Option<String>,
+    max_dimension: u32,
+    platform: Option<String>,
+    recurse: bool,
+    source: Option<String>,
+    start_timestamp: Option<i64>,
+}
+
+impl PreviewTask {
+    pub fn new(album: Arc<RwLock<Album>>, end_timestamp: Option<i64>,
+            geocode: Option<String>, max_dimension: u32,
+            platform: Option<String>, recurse: bool,
+            source: Option<String>, start_timestamp: Option<i64>)
+            -> PreviewTask {
+        {
+            let album = album.read().unwrap();
+            info!("initializing preview task [album={}, max_dimension={}]",
+                album.get_id(), max_dimension);
+        }
+
+        PreviewTask {
+            album: album,
+            end_timestamp: end_timestamp,
+            geocode: geocode,
+            max_dimension: max_dimension,
+            platform: platform,
+            recurse: recurse,
+            source: source,
+            start_timestamp: start_timestamp,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Task<(Image, StFile)> for PreviewTask {
+    fn process(&self, record: &(Image, StFile)) -> Result<(), Box<dyn Error>> {
+        let (image, file) = record;
+        let (geocode, platform, source, tile) =
+            (&image.1, &image.2, &image.3, &image.4);
+        let subdataset = file.2;
+
+        let album = self.album.read().unwrap();
+
+        // already generated by an earlier request for this exact
+        // (geocode, tile, source, subdataset, max_dimension) tuple
+        if album.get_preview(geocode, tile, source,
+                subdataset, self.max_dimension)?.is_some() {
+            return Ok(());
+        }
+
+        let path = album.get_image_path(false, geocode, platform,
+            source, subdataset, tile, file.3)?;
+        let dataset = Dataset::open(&path)?;
+
+        let band_count = unsafe {
+            gdal_sys::GDALGetRasterCount(dataset.c_dataset())
+        } as usize;
+
+        let preview_dataset = overview::generate(&dataset,
+            band_count, self.max_dimension as usize)?;
+
+        album.write_preview(&preview_dataset, geocode,
+            platform, source, subdataset, tile, self.max_dimension)?;
+
+        Ok(())
+    }
+
+    async fn records(&self) -> Result<Vec<(Image, StFile)>, Box<dyn Error>> {
+        let album = self.album.read().unwrap();
+        let images = album.list(&self.end_timestamp, &self.geocode,
+            &None, &None, &None, &None, &None, &None, &self.platform,
+            self.recurse, &self.source, &self.start_timestamp)?;
+
+        let mut records = Vec::new();
+        for (image, files) in images.into_iter() {
+            for file in files.into_iter() {
+                records.push((image.clone(), file));
+            }
+        }
+
+        Ok(records)
+    }
+}
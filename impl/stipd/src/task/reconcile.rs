@@ -0,0 +1,254 @@
+use protobuf::{ImageManagementClient, ImageMerkleRequest};
+use swarm::prelude::Dht;
+use tonic::Request;
+
+use crate::album::{Album, AlbumManager};
+use crate::identity::NodeIdentity;
+use crate::index::MerkleRow;
+use crate::merkle;
+use crate::task::{Task, TaskHandle, TaskManager};
+use crate::task::checkpoint::TaskDescriptor;
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// periodically reconciles this node's catalog against every other dht
+/// node's, one top-level geocode prefix bucket at a time, so a missed
+/// 'load()' (a dropped gossip write, a node that was down during a
+/// store) is eventually detected without anyone having to re-run a full
+/// `repair` search - the missing rows are logged, not inserted. record
+/// type is a top-level geocode prefix this node
+/// holds at least one row under, the same generic `Task` machinery as
+/// `RepairTask`
+pub struct ReconcileTask {
+    album: Arc<RwLock<Album>>,
+    dht: Arc<Dht>,
+    identity: Arc<NodeIdentity>,
+    node_id: u32,
+}
+
+impl ReconcileTask {
+    pub fn new(album: Arc<RwLock<Album>>, dht: Arc<Dht>,
+            identity: Arc<NodeIdentity>, node_id: u32) -> ReconcileTask {
+        ReconcileTask {
+            album: album,
+            dht: dht,
+            identity: identity,
+            node_id: node_id,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Task<String> for ReconcileTask {
+    fn descriptor(&self) -> Option<TaskDescriptor> {
+        let album = self.album.read().unwrap();
+        Some(TaskDescriptor::Reconcile {
+            album: album.get_id().to_string(),
+        })
+    }
+
+    fn process(&self, prefix: &String) -> Result<(), Box<dyn Error>> {
+        let album_id = {
+            let album = self.album.read().unwrap();
+            album.get_id().to_string()
+        };
+
+        // every other node's rpc address - reconcile doesn't care which
+        // node is supposed to own 'prefix', only that its catalog
+        // agrees with ours
+        let mut peer_addrs = Vec::new();
+        for (node_id, addrs) in self.dht.iter() {
+            if *node_id as u32 == self.node_id {
+                continue;
+            }
+
+            if let Some(addr) = addrs.1 {
+                peer_addrs.push(addr);
+            }
+        }
+
+        for addr in peer_addrs {
+            if let Err(e) = futures::executor::block_on(reconcile_prefix(
+                    self.album.clone(), addr, album_id.clone(),
+                    prefix.clone())) {
+                warn!("reconcile of '{}' against {} failed: {}",
+                    prefix, addr, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn records(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        // every top-level geocode prefix this node holds at least one
+        // row under - the starting point each peer's catalog is diffed
+        // against
+        let rows = {
+            let album = self.album.read().unwrap();
+            album.merkle_rows(&None)?
+        };
+
+        let mut prefixes: Vec<String> = rows.iter()
+            .filter_map(|row| row.geocode.chars().next())
+            .map(|c| c.to_string())
+            .collect();
+        prefixes.sort();
+        prefixes.dedup();
+
+        Ok(prefixes)
+    }
+}
+
+/// descend the reconciliation tree rooted at 'prefix' against 'addr',
+/// fetching this node's matching subtree fresh on every call so a
+/// concurrent load mid-reconciliation is picked up rather than compared
+/// against a stale snapshot. recurses one geocode character at a time
+/// until either side's hash agrees or a leaf bucket is reached, at which
+/// point the rows the peer holds that this node doesn't are counted and
+/// logged as a warning - they are not inserted into this node's catalog
+fn reconcile_prefix(album: Arc<RwLock<Album>>, addr: SocketAddr,
+        album_id: String, prefix: String)
+        -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error>>> + Send>> {
+    Box::pin(async move {
+        let local = {
+            let album = album.read().unwrap();
+            let rows = album.merkle_rows(&Some(prefix.clone()))?;
+            merkle::build(rows, &prefix)
+        };
+
+        let mut client = ImageManagementClient::connect(
+                format!("http://{}", addr)).await?;
+        let reply = client.merkle(Request::new(ImageMerkleRequest {
+            album: album_id.clone(),
+            prefix: prefix.clone(),
+        })).await?.into_inner();
+
+        if local.hash().to_vec() == reply.hash {
+            // converged at this subtree - nothing more to do
+            return Ok(());
+        }
+
+        match local.rows() {
+            Some(local_rows) => {
+                // leaf - the peer holds rows we don't, but 'album.load'
+                // only ever writes a catalog row, never fetches the
+                // underlying tile bytes (stipd has no pull mechanism -
+                // see the equivalent note in 'task::repair', which can
+                // only push to a missing replica, never pull one in).
+                // cataloging these rows without the bytes behind them
+                // would create a phantom replica that a later read
+                // fails against, so just flag the gap and leave it for
+                // `repair` (running on whichever node actually holds a
+                // copy) to push a real replica here instead
+                let local_keys: HashSet<String> = local_rows.iter()
+                    .map(|row| row.key()).collect();
+
+                let mut missing = 0;
+                for row in reply.rows {
+                    let row = MerkleRow {
+                        cloud_coverage: row.cloud_coverage,
+                        geocode: row.geocode,
+                        pixel_coverage: row.pixel_coverage,
+                        platform: row.platform,
+                        source: row.source,
+                        subdataset: row.subdataset as u8,
+                        tile: row.tile,
+                        timestamp: row.timestamp,
+                    };
+
+                    if !local_keys.contains(&row.key()) {
+                        missing += 1;
+                    }
+                }
+
+                if missing > 0 {
+                    warn!("reconcile: {} row(s) under '{}' held by {} are \
+                        missing locally - no pull mechanism to fetch them, \
+                        leaving for repair to push a replica here",
+                        missing, prefix, addr);
+                }
+            },
+            None => {
+                // interior - descend into every child either side knows
+                let mut chars: HashSet<char> = local.children()
+                    .map(|children| children.keys().cloned().collect())
+                    .unwrap_or_default();
+                chars.extend(reply.children.keys()
+                    .filter_map(|c| c.chars().next()));
+
+                for c in chars {
+                    let mut child_prefix = prefix.clone();
+                    child_prefix.push(c);
+                    reconcile_prefix(album.clone(), addr, album_id.clone(),
+                        child_prefix).await?;
+                }
+            },
+        }
+
+        Ok(())
+    })
+}
+
+/// spawn the periodic cross-node reconciliation loop - mirrors
+/// `repair::start`, starting a fresh `ReconcileTask` for each album that
+/// isn't still reconciling from the previous round
+pub fn start(album_manager: Arc<RwLock<AlbumManager>>, dht: Arc<Dht>,
+        identity: Arc<NodeIdentity>, node_id: u32,
+        task_manager: Arc<RwLock<TaskManager>>, period_secs: u64,
+        thread_count: u8) {
+    std::thread::spawn(move || {
+        let mut running: HashMap<String, Arc<RwLock<TaskHandle>>>
+            = HashMap::new();
+
+        loop {
+            std::thread::sleep(Duration::from_secs(period_secs));
+
+            let albums: Vec<(String, Arc<RwLock<Album>>)> = {
+                let album_manager = album_manager.read().unwrap();
+                album_manager.iter()
+                    .map(|(album_id, album)|
+                        (album_id.clone(), album.clone()))
+                    .collect()
+            };
+
+            for (album_id, album) in albums {
+                if let Some(task_handle) = running.get(&album_id) {
+                    if task_handle.read().unwrap().running() {
+                        trace!("reconcile: '{}' still running, skipping \
+                            this round", album_id);
+                        continue;
+                    }
+                }
+
+                let directory = album.read().unwrap()
+                    .get_directory().clone();
+                let task = Arc::new(ReconcileTask::new(album, dht.clone(),
+                    identity.clone(), node_id));
+
+                let task_handle = match task.start(directory,
+                        rand::random::<u64>(), thread_count) {
+                    Ok(task_handle) => task_handle,
+                    Err(e) => {
+                        warn!("reconcile: failed to start task for '{}': {}",
+                            album_id, e);
+                        continue;
+                    },
+                };
+
+                running.insert(album_id.clone(), task_handle.clone());
+
+                let mut task_manager = task_manager.write().unwrap();
+                if let Err(e) = task_manager.register(task_handle, None) {
+                    warn!("reconcile: failed to register task for '{}': {}",
+                        album_id, e);
+                }
+            }
+        }
+    });
+}
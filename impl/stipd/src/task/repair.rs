@@ -0,0 +1,288 @@
+use gdal::Dataset;
+use protobuf::{Filter, ImageManagementClient, ImageSearchRequest};
+use swarm::prelude::Dht;
+use tonic::Request;
+
+use crate::album::{Album, AlbumManager};
+use crate::identity::NodeIdentity;
+use crate::task::{Task, TaskHandle, TaskManager};
+use crate::task::checkpoint::TaskDescriptor;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// default pause, in milliseconds, that a repair run waits between
+/// successive re-transfers to missing replicas
+pub const DEFAULT_REPAIR_RATE_LIMIT_MS: u64 = 50;
+
+/// periodically reconciles expected vs. actual replica placement for
+/// every geocode this node holds a local copy of, re-transferring to
+/// any expected node that doesn't yet report holding it. record type is
+/// the geocode, so checkpointing/resume and pause/cancel all come from
+/// the generic `Task` machinery the same as `SplitTask`/`StoreEarthExplorerTask`.
+pub struct RepairTask {
+    album: Arc<RwLock<Album>>,
+    dht: Arc<Dht>,
+    identity: Arc<NodeIdentity>,
+    node_id: u32,
+    rate_limit_ms: u64,
+    replication_factor: u8,
+}
+
+impl RepairTask {
+    pub fn new(album: Arc<RwLock<Album>>, dht: Arc<Dht>,
+            identity: Arc<NodeIdentity>, node_id: u32, rate_limit_ms: u64,
+            replication_factor: u8) -> RepairTask {
+        RepairTask {
+            album: album,
+            dht: dht,
+            identity: identity,
+            node_id: node_id,
+            rate_limit_ms: rate_limit_ms,
+            replication_factor: replication_factor,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Task<String> for RepairTask {
+    fn descriptor(&self) -> Option<TaskDescriptor> {
+        let album = self.album.read().unwrap();
+        Some(TaskDescriptor::Repair {
+            album: album.get_id().to_string(),
+            rate_limit_ms: self.rate_limit_ms,
+            replication_factor: self.replication_factor,
+        })
+    }
+
+    fn process(&self, geocode: &String) -> Result<(), Box<dyn Error>> {
+        let (album_id, dht_key_length) = {
+            let album = self.album.read().unwrap();
+            (album.get_id().to_string(), album.get_dht_key_length())
+        };
+
+        // who should hold this geocode, and what address maps to what
+        // dht node id
+        let expected_addrs = crate::task::dht_lookup_replicas(&self.dht,
+            dht_key_length, geocode, self.replication_factor)?;
+
+        let addr_to_node_id: HashMap<SocketAddr, u32> = self.dht.iter()
+            .filter_map(|(node_id, addrs)|
+                addrs.1.map(|addr| (addr.clone(), *node_id as u32)))
+            .collect();
+
+        // every (platform, source) combination held locally at this
+        // geocode - the set we're actually able to heal, since there's
+        // no remote-read path to pull a copy from a peer
+        let local_extents = {
+            let album = self.album.read().unwrap();
+            album.search(&None, &Some(geocode.clone()), &None,
+                &None, &None, &None, &None,
+                &None, &None, false, &None, &None)?
+        };
+
+        for (_, extent_geocode, platform, _precision, source)
+                in local_extents {
+            if extent_geocode != *geocode {
+                continue;
+            }
+
+            let actual_nodes = futures::executor::block_on(
+                query_actual_nodes(&album_id, geocode, &platform, &source,
+                    &expected_addrs, &addr_to_node_id));
+
+            let missing_addrs: Vec<SocketAddr> = expected_addrs.iter()
+                .filter(|addr| match addr_to_node_id.get(addr) {
+                    Some(node_id) => !actual_nodes.contains(node_id),
+                    None => true,
+                })
+                .cloned()
+                .collect();
+
+            if missing_addrs.is_empty() {
+                continue;
+            }
+
+            if !actual_nodes.contains(&self.node_id) {
+                warn!("repair has no local copy of '{}/{}/{}' to push to \
+                    {} missing replica(s)", platform, geocode,
+                    source, missing_addrs.len());
+                continue;
+            }
+
+            info!("repair: '{}/{}/{}' under-replicated, missing {} of {} \
+                replicas", platform, geocode, source,
+                missing_addrs.len(), expected_addrs.len());
+
+            let files = {
+                let album = self.album.read().unwrap();
+                album.list(&None, &Some(geocode.clone()), &None,
+                    &None, &None, &None, &None, &None,
+                    &Some(platform.clone()), false,
+                    &Some(source.clone()), &None)?
+            };
+
+            for (image, st_files) in files.iter() {
+                for st_file in st_files.iter() {
+                    let dataset = Dataset::open(
+                        std::path::Path::new(&st_file.0))?;
+
+                    for addr in missing_addrs.iter() {
+                        // rate limit outgoing transfers so a large
+                        // repair run doesn't saturate xfer_port with a
+                        // burst all at once
+                        std::thread::sleep(
+                            Duration::from_millis(self.rate_limit_ms));
+
+                        if let Err(e) = crate::transfer::send_image(addr,
+                                &self.identity, self.node_id, &album_id,
+                                &dataset, geocode, st_file.1, &platform,
+                                &source, st_file.2, &image.4, image.5,
+                                st_file.3, None) {
+                            warn!("repair failed to replicate '{}' to \
+                                {}: {}", st_file.0, addr, e);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn records(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        // every distinct geocode this node currently holds a local copy
+        // of for the album - the candidates for 'is this converged?'
+        let extents = {
+            let album = self.album.read().unwrap();
+            album.search(&None, &None, &None, &None, &None, &None, &None,
+                &None, &None, true, &None, &None)?
+        };
+
+        let mut geocodes: Vec<String> = extents.into_iter()
+            .map(|(_, geocode, _, _, _)| geocode)
+            .collect();
+        geocodes.sort();
+        geocodes.dedup();
+
+        Ok(geocodes)
+    }
+}
+
+/// ask every node expected to hold 'geocode' whether it actually reports
+/// one - the cheap, targeted version of the full-cluster fan out
+/// `ImageManagementImpl::repair` does, since we already know who to ask
+async fn query_actual_nodes(album_id: &str, geocode: &str, platform: &str,
+        source: &str, expected_addrs: &[SocketAddr],
+        addr_to_node_id: &HashMap<SocketAddr, u32>) -> Vec<u32> {
+    let mut actual = Vec::new();
+    for addr in expected_addrs.iter() {
+        let mut client = match ImageManagementClient::connect(
+                format!("http://{}", addr)).await {
+            Ok(client) => client,
+            Err(e) => {
+                warn!("repair could not reach node {}: {}", addr, e);
+                continue;
+            },
+        };
+
+        let filter = Filter {
+            end_timestamp: None,
+            geocode: Some(geocode.to_string()),
+            max_cloud_coverage: None,
+            max_lat: None,
+            max_lon: None,
+            min_lat: None,
+            min_lon: None,
+            min_pixel_coverage: None,
+            platform: Some(platform.to_string()),
+            recurse: false,
+            source: Some(source.to_string()),
+            start_timestamp: None,
+        };
+
+        let mut stream = match client.search(Request::new(ImageSearchRequest {
+                album: album_id.to_string(),
+                filter: filter,
+            })).await {
+            Ok(reply) => reply.into_inner(),
+            Err(e) => {
+                warn!("repair search failed on node {}: {}", addr, e);
+                continue;
+            },
+        };
+
+        if let Ok(Some(_)) = stream.message().await {
+            if let Some(node_id) = addr_to_node_id.get(addr) {
+                actual.push(*node_id);
+            }
+        }
+    }
+
+    actual
+}
+
+/// spawn the periodic repair convergence loop - every round, starts a
+/// fresh `RepairTask` for each album that isn't still converging from
+/// the previous round. skipping an album whose last run is still
+/// running is the backoff: a cluster that's slow to heal naturally
+/// spaces its own repair storms out instead of piling new runs on top
+/// of ones still in flight.
+pub fn start(album_manager: Arc<RwLock<AlbumManager>>,
+        dht: Arc<Dht>, identity: Arc<NodeIdentity>, node_id: u32,
+        replication_factor: u8, task_manager: Arc<RwLock<TaskManager>>,
+        period_secs: u64, thread_count: u8) {
+    std::thread::spawn(move || {
+        let mut running: HashMap<String, Arc<RwLock<TaskHandle>>>
+            = HashMap::new();
+
+        loop {
+            std::thread::sleep(Duration::from_secs(period_secs));
+
+            let albums: Vec<(String, Arc<RwLock<Album>>)> = {
+                let album_manager = album_manager.read().unwrap();
+                album_manager.iter()
+                    .map(|(album_id, album)|
+                        (album_id.clone(), album.clone()))
+                    .collect()
+            };
+
+            for (album_id, album) in albums {
+                if let Some(task_handle) = running.get(&album_id) {
+                    if task_handle.read().unwrap().running() {
+                        trace!("repair: '{}' still converging, skipping \
+                            this round", album_id);
+                        continue;
+                    }
+                }
+
+                let directory = album.read().unwrap()
+                    .get_directory().clone();
+                let task = Arc::new(RepairTask::new(album, dht.clone(),
+                    identity.clone(), node_id, DEFAULT_REPAIR_RATE_LIMIT_MS,
+                    replication_factor));
+
+                let task_handle = match task.start(directory,
+                        rand::random::<u64>(), thread_count) {
+                    Ok(task_handle) => task_handle,
+                    Err(e) => {
+                        warn!("repair: failed to start task for '{}': {}",
+                            album_id, e);
+                        continue;
+                    },
+                };
+
+                running.insert(album_id.clone(), task_handle.clone());
+
+                let mut task_manager = task_manager.write().unwrap();
+                if let Err(e) = task_manager.register(task_handle, None) {
+                    warn!("repair: failed to register task for '{}': {}",
+                        album_id, e);
+                }
+            }
+        }
+    });
+}
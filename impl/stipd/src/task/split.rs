@@ -3,7 +3,9 @@ use swarm::prelude::Dht;
 
 use crate::{Image, StFile, RAW_SOURCE, SPLIT_SOURCE};
 use crate::album::Album;
+use crate::identity::NodeIdentity;
 use crate::task::Task;
+use crate::task::checkpoint::TaskDescriptor;
 
 use std::error::Error;
 use std::sync::{Arc, RwLock};
@@ -14,6 +16,8 @@ pub struct SplitTask {
     end_timestamp: Option<i64>,
     geocode: Option<String>,
     geocode_bound: Option<String>,
+    identity: Arc<NodeIdentity>,
+    node_id: u32,
     platform: Option<String>,
     precision: usize,
     recurse: bool,
@@ -23,7 +27,8 @@ pub struct SplitTask {
 impl SplitTask {
     pub fn new(album: Arc<RwLock<Album>>, dht: Arc<Dht>,
             end_timestamp: Option<i64>, geocode: Option<String>,
-            geocode_bound: Option<String>, platform: Option<String>,
+            geocode_bound: Option<String>, identity: Arc<NodeIdentity>,
+            node_id: u32, platform: Option<String>,
             precision: usize, recurse: bool,
             start_timestamp: Option<i64>) -> SplitTask {
         {
@@ -39,6 +44,8 @@ impl SplitTask {
             end_timestamp: end_timestamp,
             geocode: geocode,
             geocode_bound: geocode_bound,
+            identity: identity,
+            node_id: node_id,
             platform: platform,
             precision: precision,
             recurse: recurse,
@@ -49,15 +56,29 @@ impl SplitTask {
 
 #[tonic::async_trait]
 impl Task<(Image, Vec<StFile>)> for SplitTask {
+    fn descriptor(&self) -> Option<TaskDescriptor> {
+        let album = self.album.read().unwrap();
+        Some(TaskDescriptor::Split {
+            album: album.get_id().to_string(),
+            end_timestamp: self.end_timestamp,
+            geocode: self.geocode.clone(),
+            geocode_bound: self.geocode_bound.clone(),
+            platform: self.platform.clone(),
+            precision: self.precision,
+            recurse: self.recurse,
+            start_timestamp: self.start_timestamp,
+        })
+    }
+
     fn process(&self, record: &(Image, Vec<StFile>))
             -> Result<(), Box<dyn Error>> {
         let image = &record.0;
 
         // retrieve album metadata
-        let (album_id, dht_key_length, geocode) = {
+        let (album_id, dht_key_length, geocode, replication_factor) = {
             let album = self.album.read().unwrap();
             (album.get_id().to_string(), album.get_dht_key_length(),
-                album.get_geocode().clone())
+                album.get_geocode().clone(), album.get_replication_factor())
         };
 
         for file in record.1.iter() {
@@ -113,21 +134,28 @@ impl Task<(Image, Vec<StFile>)> for SplitTask {
                     continue;
                 }
 
-                // lookup geocode in dht
-                let addr = match crate::task::dht_lookup(
-                        &self.dht, dht_key_length, &split_geocode) {
-                    Ok(addr) => addr,
+                // lookup replica nodes in dht
+                let addrs = match crate::task::dht_lookup_replicas(
+                        &self.dht, dht_key_length, &split_geocode,
+                        replication_factor) {
+                    Ok(addrs) => addrs,
                     Err(e) => {
                         warn!("{}", e);
                         continue;
                     },
                 };
 
-                // send image to new host
-                if let Err(e) = crate::transfer::send_image(&addr, &album_id,
-                        &split_dataset, &split_geocode, file.1, &image.2,
-                        SPLIT_SOURCE, file.2, &image.4, image.5) {
-                    warn!("failed to write image to node {}: {}", addr, e);
+                // send image to each replica, logging per-replica
+                // failures independently rather than losing the tile
+                // if at least one replica accepts it
+                for addr in addrs {
+                    if let Err(e) = crate::transfer::send_image(&addr,
+                            &self.identity, self.node_id, &album_id,
+                            &split_dataset, &split_geocode, file.1, &image.2,
+                            SPLIT_SOURCE, file.2, &image.4, image.5, false,
+                            None) {
+                        warn!("failed to write image to node {}: {}", addr, e);
+                    }
                 }
             }
         }
@@ -140,8 +168,9 @@ impl Task<(Image, Vec<StFile>)> for SplitTask {
         // search for images using Album
         let mut records: Vec<(Image, Vec<StFile>)> = {
             let album = self.album.read().unwrap();
-            album.list(&self.end_timestamp, &self.geocode, &None, &None,
-                &self.platform, self.recurse, 
+            album.list(&self.end_timestamp, &self.geocode, &None,
+                &None, &None, &None, &None, &None,
+                &self.platform, self.recurse,
                 &Some(RAW_SOURCE.to_string()), &self.start_timestamp)?
         };
 
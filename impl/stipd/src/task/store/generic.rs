@@ -74,21 +74,25 @@ pub fn process(album: &Arc<RwLock<Album>>, dht: &Arc<Dht>,
         }
 
         // lookup geocode in dht
-        let addr = match crate::task::dht_lookup(
-                &dht, dht_key_length, &split_geocode) {
-            Ok(addr) => addr,
+        let addrs = match crate::task::dht_lookup_replicas(&dht,
+                dht_key_length, &split_geocode,
+                crate::task::DEFAULT_REPLICATION_FACTOR) {
+            Ok(addrs) => addrs,
             Err(e) => {
                 warn!("{}", e);
                 continue;
             },
         };
 
-        // send image to new host
-        if let Err(e) = crate::transfer::send_image(&addr,
-                &album_id, &split_dataset, &split_geocode,
-                pixel_coverage, &platform,
-                &RAW_SOURCE, subdataset, &tile, timestamp) {
-            warn!("failed to write image to node {}: {}", addr, e);
+        // send image to each replica, logging per-replica failures
+        // independently rather than aborting the whole tile
+        for addr in addrs {
+            if let Err(e) = crate::transfer::send_image(&addr,
+                    &album_id, &split_dataset, &split_geocode,
+                    pixel_coverage, &platform,
+                    &RAW_SOURCE, subdataset, &tile, timestamp) {
+                warn!("failed to write image to node {}: {}", addr, e);
+            }
         }
     }
 
@@ -1,4 +1,5 @@
 use chrono::prelude::{TimeZone, Utc};
+use crossbeam_deque::{Injector, Stealer, Worker as DequeWorker};
 use flate2::read::GzDecoder;
 use gdal::{Dataset, Driver};
 use gdal::raster::GdalType;
@@ -15,18 +16,69 @@ use std::ffi::OsStr;
 use std::fs::File;
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU32, Ordering};
 
 const TMP_DIR: &str = "/tmp";
 
+/// per-record split-unit progress - analogous to `TaskHandle`'s
+/// `completed_count`/`total_count`, but scoped to the (geocode, dataset)
+/// splits produced from a single record rather than whole records, since
+/// `process` now drains every split for a record across a worker pool
+/// instead of handling one dimension-group's geocodes at a time
+pub struct SplitProgress {
+    completed: Arc<AtomicU32>,
+    total: Arc<AtomicU32>,
+}
+
+impl SplitProgress {
+    pub fn new() -> SplitProgress {
+        SplitProgress {
+            completed: Arc::new(AtomicU32::new(0)),
+            total: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    /// 'completed / total' as a percentage - 0.0 until `process` has
+    /// finished enqueueing every split for the record, same convention
+    /// as `TaskHandle::completion_percent`
+    pub fn completion_percent(&self) -> f32 {
+        let total = self.total.load(Ordering::SeqCst);
+        if total == 0 {
+            return 0.0;
+        }
+
+        self.completed.load(Ordering::SeqCst) as f32 / total as f32 * 100f32
+    }
+}
+
+/// wraps a GDAL 'Mem' dataset produced by `split_subdatasets` so it can
+/// cross the worker pool's queue - gdal's `Dataset` wraps a raw pointer
+/// and isn't `Send`, but each split dataset has exactly one owner (the
+/// unit carrying it) and is never touched by more than one thread, so
+/// moving it across the boundary is sound
+struct SendDataset(Dataset);
+unsafe impl Send for SendDataset {}
+
+/// one (geocode, dataset) split ready for `dht_lookup` + `send_image` -
+/// the unit of work stolen and processed by the worker pool below
+struct SplitUnit {
+    dataset: SendDataset,
+    geocode: String,
+    group_paths: Arc<Vec<PathBuf>>,
+    group_remaining: Arc<AtomicU32>,
+    subdataset: u8,
+}
+
 pub fn process(album: &Arc<RwLock<Album>>, dht: &Arc<Dht>,
-        precision: usize, record: &PathBuf) -> Result<(), Box<dyn Error>> {
+        precision: usize, record: &PathBuf, thread_count: u8,
+        progress: &SplitProgress) -> Result<(), Box<dyn Error>> {
     // retrieve album metadata
     let (album_id, dht_key_length, geocode) = {
         let album = album.read().unwrap();
         (album.get_id().to_string(), album.get_dht_key_length(),
             album.get_geocode().clone())
     };
- 
+
     // parse metadata
     let tile_path = record.with_extension("").with_extension("");
     let tile = tile_path.file_name()
@@ -64,54 +116,124 @@ pub fn process(album: &Arc<RwLock<Album>>, dht: &Arc<Dht>,
         }
     }
 
-    // iterate over datasets
+    // split every dimension group up front and enqueue one unit per
+    // resulting (geocode, dataset) split, instead of walking the
+    // dimension groups one at a time and handling their geocodes
+    // serially - workers then drain the whole record concurrently
+    let injector: Injector<SplitUnit> = Injector::new();
+    let mut total_units = 0u32;
+
     for (i, (_, path_vec)) in paths.iter().enumerate() {
-        // split datasets
         let datasets = split_subdatasets::<u16>(
             geocode, precision, path_vec)?;
 
-        // processes dataset splits
-        process_splits(&album_id, &datasets, &dht,
-            dht_key_length, i as u8, &tile, timestamp)?;
+        // tif cleanup for this dimension group can only run once every
+        // split derived from it has drained, since sibling splits
+        // sharing the group may still be mid-flight on another worker
+        let group_paths = Arc::new(path_vec.clone());
+        let group_remaining = Arc::new(AtomicU32::new(datasets.len() as u32));
+
+        for (split_geocode, dataset) in datasets {
+            injector.push(SplitUnit {
+                dataset: SendDataset(dataset),
+                geocode: split_geocode,
+                group_paths: group_paths.clone(),
+                group_remaining: group_remaining.clone(),
+                subdataset: i as u8,
+            });
+            total_units += 1;
+        }
+    }
+
+    progress.total.store(total_units, Ordering::SeqCst);
+
+    // fixed-size worker pool draining the shared injector, stealing from
+    // each other's local deque once the injector runs dry - mirrors the
+    // work-stealing shape 'Task::start' runs per record, scoped here to
+    // the splits within this one record
+    let injector = Arc::new(injector);
+    let locals: Vec<DequeWorker<SplitUnit>> = (0..thread_count.max(1))
+        .map(|_| DequeWorker::new_fifo()).collect();
+    let stealers: Arc<Vec<Stealer<SplitUnit>>> = Arc::new(
+        locals.iter().map(|local| local.stealer()).collect());
+
+    let mut join_handles = Vec::new();
+    for local in locals {
+        let album_id = album_id.clone();
+        let completed = progress.completed.clone();
+        let dht = dht.clone();
+        let injector = injector.clone();
+        let stealers = stealers.clone();
+        let tile = tile.to_string();
+
+        let join_handle = std::thread::spawn(move || {
+            // every unit was enqueued before the pool started, so an
+            // exhausted steal means the record is actually done rather
+            // than just momentarily starved
+            while let Some(unit) = crate::task::find_task(
+                    &local, &injector, &stealers) {
+                process_unit(&album_id, unit, &dht,
+                    dht_key_length, &tile, timestamp);
+                completed.fetch_add(1, Ordering::SeqCst);
+            }
+        });
 
-        // delete temporary tif files
-        for path in path_vec.iter() {
-            std::fs::remove_file(path)?;
+        join_handles.push(join_handle);
+    }
+
+    for join_handle in join_handles {
+        if let Err(e) = join_handle.join() {
+            warn!("landsat split worker panicked: {:?}", e);
         }
     }
 
     Ok(())
 }
 
-fn process_splits(album_id: &str, datasets: &HashMap<String, Dataset>,
-        dht: &Arc<Dht>, dht_key_length: i8, subdataset: u8,
-        tile: &str, timestamp: i64) -> Result<(), Box<dyn Error>> {
-    for (geocode, dataset) in datasets.iter() {
-        // if image has 0.0 coverage -> don't process
-        let pixel_coverage = st_image::get_coverage(&dataset)?;
-        if pixel_coverage == 0f64 {
-            continue;
-        }
-
-        // lookup geocode in dht
-        let addr = match crate::task::dht_lookup(
-                &dht, dht_key_length, &geocode) {
-            Ok(addr) => addr,
-            Err(e) => {
-                warn!("{}", e);
-                continue;
+/// send a single split's replicas and, once every split derived from
+/// its dimension group has drained, delete that group's decompressed
+/// TIF files
+fn process_unit(album_id: &str, unit: SplitUnit, dht: &Arc<Dht>,
+        dht_key_length: i8, tile: &str, timestamp: i64) {
+    let SplitUnit {
+        dataset, geocode, group_paths, group_remaining, subdataset,
+    } = unit;
+    let dataset = dataset.0;
+
+    // if image has 0.0 coverage -> don't process
+    let pixel_coverage = st_image::get_coverage(&dataset).unwrap_or(0f64);
+    if pixel_coverage != 0f64 {
+        // lookup replica nodes in dht
+        match crate::task::dht_lookup_replicas(&dht, dht_key_length,
+                &geocode, crate::task::DEFAULT_REPLICATION_FACTOR) {
+            Ok(addrs) => {
+                // send image to each replica, logging per-replica
+                // failures independently rather than aborting the split
+                for addr in addrs {
+                    if let Err(e) = crate::transfer::send_image(&addr,
+                            album_id, &dataset, &geocode, pixel_coverage,
+                            "Landsat8C1L1", &RAW_SOURCE, subdataset,
+                            tile, timestamp) {
+                        warn!("failed to write image to node {}: {}",
+                            addr, e);
+                    }
+                }
             },
-        };
-
-        // send image to new host
-        if let Err(e) = crate::transfer::send_image(&addr, album_id,
-                &dataset, &geocode, pixel_coverage, "Landsat8C1L1",
-                &RAW_SOURCE, subdataset, &tile, timestamp) {
-            warn!("failed to write image to node {}: {}", addr, e);
+            Err(e) => warn!("{}", e),
         }
     }
 
-    Ok(())
+    // the last unit to drain from this dimension group deletes its
+    // decompressed TIF files - every other unit just decrements and
+    // moves on
+    if group_remaining.fetch_sub(1, Ordering::SeqCst) == 1 {
+        for path in group_paths.iter() {
+            if let Err(e) = std::fs::remove_file(path) {
+                warn!("failed to remove temporary file '{:?}': {}",
+                    path, e);
+            }
+        }
+    }
 }
 
 fn split_subdatasets<T: GdalType>(geocode: Geocode,
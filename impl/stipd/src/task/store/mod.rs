@@ -4,11 +4,18 @@ mod gridmet;
 mod modis;
 mod naip;
 mod nlcd;
+pub(crate) mod overview;
+pub mod s3;
 mod sentinel2;
+mod validate;
 mod viirs;
 
 use crate::album::Album;
-use crate::task::Task;
+use crate::identity::NodeIdentity;
+use crate::task::{NonCriticalErrorSink, Task};
+use crate::task::checkpoint::TaskDescriptor;
+use crate::task::deadletter::DeadLetterQueue;
+use crate::task::job::JobManager;
 
 use std::error::Error;
 use std::path::PathBuf;
@@ -26,58 +33,159 @@ pub enum ImageFormat {
     VNP21V001,
 }
 
+impl ImageFormat {
+    pub fn parse(value: &str) -> Result<ImageFormat, Box<dyn Error>> {
+        match value {
+            "GridMET" => Ok(ImageFormat::GridMET),
+            "MCD43A4" => Ok(ImageFormat::MCD43A4),
+            "MOD11A1" => Ok(ImageFormat::MOD11A1),
+            "MOD11A2" => Ok(ImageFormat::MOD11A2),
+            "NAIP" => Ok(ImageFormat::NAIP),
+            "NLCD" => Ok(ImageFormat::NLCD),
+            "Sentinel2" => Ok(ImageFormat::Sentinel2),
+            "VNP21V001" => Ok(ImageFormat::VNP21V001),
+            _ => Err(format!("unknown image format '{}'", value).into()),
+        }
+    }
+}
+
 pub struct StoreEarthExplorerTask {
     album: Arc<RwLock<Album>>,
+    dead_letter_queue: DeadLetterQueue,
     dht: Arc<Dht>,
+    directory: PathBuf,
     format: ImageFormat,
     glob: String,
+    identity: Arc<NodeIdentity>,
+    job_manager: JobManager,
+    node_id: u32,
+    non_critical_errors: NonCriticalErrorSink,
     precision: usize,
+    replication_factor: u8,
+    s3_access_key: Option<String>,
+    s3_endpoint: Option<String>,
+    s3_region: Option<String>,
+    s3_secret_key: Option<String>,
+    strict: bool,
 }
 
 impl StoreEarthExplorerTask {
-    pub fn new(album: Arc<RwLock<Album>>, dht: Arc<Dht>,
-            format: ImageFormat, glob: String, precision: usize)
-            -> StoreEarthExplorerTask {
+    pub fn new(album: Arc<RwLock<Album>>, dead_letter_queue: DeadLetterQueue,
+            dht: Arc<Dht>, directory: PathBuf, format: ImageFormat,
+            glob: String, identity: Arc<NodeIdentity>,
+            job_manager: JobManager, node_id: u32, precision: usize,
+            replication_factor: u8, s3_access_key: Option<String>,
+            s3_endpoint: Option<String>, s3_region: Option<String>,
+            s3_secret_key: Option<String>,
+            strict: bool) -> StoreEarthExplorerTask {
         {
             let album = album.read().unwrap();
-            info!("initailizing store task [album={}, format={:?}, glob={}, precision={}]",
-                album.get_id(), format, glob, precision)
+            info!("initailizing store task [album={}, format={:?}, glob={}, precision={}, replication_factor={}, strict={}]",
+                album.get_id(), format, glob, precision,
+                replication_factor, strict)
         }
-            
+
         StoreEarthExplorerTask {
             album: album,
+            dead_letter_queue: dead_letter_queue,
             dht: dht,
+            directory: directory,
             format: format,
             glob: glob,
+            identity: identity,
+            job_manager: job_manager,
+            node_id: node_id,
+            non_critical_errors: NonCriticalErrorSink::new(),
             precision: precision,
+            replication_factor: replication_factor,
+            s3_access_key: s3_access_key,
+            s3_endpoint: s3_endpoint,
+            s3_region: s3_region,
+            s3_secret_key: s3_secret_key,
+            strict: strict,
         }
     }
 }
 
 #[tonic::async_trait]
 impl Task<PathBuf> for StoreEarthExplorerTask {
+    fn descriptor(&self) -> Option<TaskDescriptor> {
+        let album = self.album.read().unwrap();
+        Some(TaskDescriptor::Store {
+            album: album.get_id().to_string(),
+            format: format!("{:?}", self.format),
+            glob: self.glob.clone(),
+            precision: self.precision,
+            replication_factor: self.replication_factor,
+            strict: self.strict,
+        })
+    }
+
     fn process(&self, record: &PathBuf) -> Result<(), Box<dyn Error>> {
         match self.format {
-            ImageFormat::GridMET => gridmet::process(
-                &self.album, &self.dht, self.precision, &record),
+            ImageFormat::GridMET => gridmet::process(&self.album, &self.dht,
+                &self.identity, self.node_id, self.precision,
+                self.replication_factor, self.strict, &record),
             ImageFormat::MCD43A4 => modis::process(&self.album,
-                "MCD43A4", &self.dht, self.precision, &record),
+                &self.dht, &self.identity, self.node_id,
+                self.precision, self.replication_factor,
+                self.strict, &record, &self.directory,
+                &self.dead_letter_queue, &self.job_manager,
+                &self.non_critical_errors),
             ImageFormat::MOD11A1 => modis::process(&self.album,
-                "MOD11A1", &self.dht, self.precision, &record),
+                &self.dht, &self.identity, self.node_id,
+                self.precision, self.replication_factor,
+                self.strict, &record, &self.directory,
+                &self.dead_letter_queue, &self.job_manager,
+                &self.non_critical_errors),
             ImageFormat::MOD11A2 => modis::process(&self.album,
-                "MOD11A2", &self.dht, self.precision, &record),
-            ImageFormat::NAIP => naip::process(
-                &self.album, &self.dht, self.precision, &record),
-            ImageFormat::NLCD => nlcd::process(
-                &self.album, &self.dht, self.precision, &record),
-            ImageFormat::Sentinel2 => sentinel2::process(
-                &self.album, &self.dht, self.precision, &record),
-            ImageFormat::VNP21V001 => viirs::process(
-                &self.album, &self.dht, self.precision, &record),
+                &self.dht, &self.identity, self.node_id,
+                self.precision, self.replication_factor,
+                self.strict, &record, &self.directory,
+                &self.dead_letter_queue, &self.job_manager,
+                &self.non_critical_errors),
+            ImageFormat::NAIP => naip::process(&self.album, &self.dht,
+                &self.identity, self.node_id, self.precision,
+                self.replication_factor, self.strict, &record),
+            ImageFormat::NLCD => nlcd::process(&self.album, &self.dht,
+                &self.identity, self.node_id, self.precision,
+                self.replication_factor, self.strict, &record,
+                &self.non_critical_errors),
+            ImageFormat::Sentinel2 => sentinel2::process(&self.album,
+                &self.dht, &self.identity, self.node_id,
+                self.precision, self.replication_factor,
+                self.strict, &record, &self.non_critical_errors),
+            ImageFormat::VNP21V001 => viirs::process(&self.album, &self.dht,
+                &self.identity, self.node_id, self.precision,
+                self.replication_factor, self.strict, &record,
+                &self.non_critical_errors),
         }
     }
 
+    /// dropped geocodes recorded during 'process' (DHT lookup or send
+    /// failures that don't fail the whole record) surface here so
+    /// 'task_list'/'task_status' can report "completed_with_errors"
+    /// instead of a plain "complete" that hides them
+    fn non_critical_errors(&self) -> NonCriticalErrorSink {
+        self.non_critical_errors.clone()
+    }
+
     async fn records(&self) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        // an 's3://' glob is listed server-side against the object
+        // store and turned into '/vsis3/' paths GDAL can open directly,
+        // rather than requiring files to be staged on local disk first
+        if self.glob.starts_with("s3://") {
+            let parsed = s3::parse_glob(&self.glob)?;
+            let keys = s3::list_objects(&self.s3_endpoint, &self.s3_region,
+                &self.s3_access_key, &self.s3_secret_key,
+                &parsed.bucket, &parsed.prefix, &parsed.suffix)?;
+
+            return Ok(keys.into_iter()
+                .map(|key| PathBuf::from(
+                    format!("/vsis3/{}/{}", parsed.bucket, key)))
+                .collect());
+        }
+
         // search for image files
         let mut records = Vec::new();
         for entry in glob::glob(&self.glob)? {
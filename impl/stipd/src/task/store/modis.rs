@@ -8,15 +8,27 @@ use swarm::prelude::Dht;
 
 use crate::RAW_SOURCE;
 use crate::album::Album;
+use crate::identity::NodeIdentity;
+use crate::task::NonCriticalErrorSink;
+use crate::task::deadletter::DeadLetterQueue;
+use crate::task::job::{self, JobManager};
+use crate::task::store::overview;
+use crate::task::store::validate;
 
 use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
 use std::error::Error;
 use std::ffi::OsStr;
-use std::path::PathBuf;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 
 pub fn process(album: &Arc<RwLock<Album>>, dht: &Arc<RwLock<Dht>>,
-        precision: usize, record: &PathBuf) -> Result<(), Box<dyn Error>> {
+        identity: &Arc<NodeIdentity>, node_id: u32, precision: usize,
+        replication_factor: u8, strict: bool, record: &PathBuf,
+        directory: &Path, dead_letter_queue: &DeadLetterQueue,
+        job_manager: &JobManager, non_critical_errors: &NonCriticalErrorSink)
+        -> Result<(), Box<dyn Error>> {
     // retrieve album metadata
     let (album_id, dht_key_length, geocode) = {
         let album = album.read().unwrap();
@@ -24,8 +36,14 @@ pub fn process(album: &Arc<RwLock<Album>>, dht: &Arc<RwLock<Dht>>,
             album.get_geocode().clone())
     };
 
-    let dataset = Dataset::open(&record).compat()?;
- 
+    // open file - a truncated download or product with no subdatasets
+    // is skipped (or fails the record outright in strict mode) rather
+    // than propagating a gdal panic up through this worker
+    let dataset = match validate::open_checked(record, true, strict)? {
+        Some(dataset) => dataset,
+        None => return Ok(()),
+    };
+
     // parse metadata
     let tile_path = record.with_extension("");
     let tile = tile_path.file_name()
@@ -61,47 +79,150 @@ pub fn process(album: &Arc<RwLock<Album>>, dht: &Arc<RwLock<Dht>>,
     }
 
     // process quality subdatasets
+    let quality_band_count = quality_subdatasets.len();
     let quality_datasets = split_subdatasets::<u8>(geocode,
         precision, quality_subdatasets)?;
-    process_splits(&album_id, &quality_datasets,
-        &dht, dht_key_length, 0, &tile, timestamp)?;
+    process_splits(&album_id, quality_datasets, quality_band_count,
+        &dht, dht_key_length, 0, &tile, timestamp, identity, node_id,
+        replication_factor, directory, dead_letter_queue, job_manager,
+        non_critical_errors)?;
 
     // process reflectance subdatasets
+    let reflectance_band_count = reflectance_subdatasets.len();
     let reflectance_datasets = split_subdatasets::<i16>(geocode,
         precision, reflectance_subdatasets)?;
-    process_splits(&album_id, &reflectance_datasets,
-        &dht, dht_key_length, 1, &tile, timestamp)?;
+    process_splits(&album_id, reflectance_datasets, reflectance_band_count,
+        &dht, dht_key_length, 1, &tile, timestamp, identity, node_id,
+        replication_factor, directory, dead_letter_queue, job_manager,
+        non_critical_errors)?;
 
     Ok(())
 }
 
-fn process_splits(album_id: &str, datasets: &HashMap<String, Dataset>,
-        dht: &Arc<RwLock<Dht>>, dht_key_length: i8, subdataset: u8, 
-        tile: &str, timestamp: i64) -> Result<(), Box<dyn Error>> {
-    for (geocode, dataset) in datasets.iter() {
+fn process_splits(album_id: &str, datasets: HashMap<String, Dataset>,
+        band_count: usize, dht: &Arc<RwLock<Dht>>, dht_key_length: i8,
+        subdataset: u8, tile: &str, timestamp: i64,
+        identity: &Arc<NodeIdentity>, node_id: u32, replication_factor: u8,
+        directory: &Path, dead_letter_queue: &DeadLetterQueue,
+        job_manager: &JobManager, non_critical_errors: &NonCriticalErrorSink)
+        -> Result<(), Box<dyn Error>> {
+    // deterministic job id, keyed on the tile and subdataset tag, so a
+    // restarted node resumes this job's unfinished splits rather than
+    // starting a duplicate one
+    let mut hasher = DefaultHasher::new();
+    tile.hash(&mut hasher);
+    subdataset.hash(&mut hasher);
+    let job_id = hasher.finish();
+
+    let units: Vec<(String, u8)> = datasets.keys()
+        .map(|geocode| (geocode.clone(), subdataset))
+        .collect();
+
+    let manifest = job::read_manifest(directory, job_id)
+        .unwrap_or_else(|_| job::JobManifest::new(
+            PathBuf::from(tile), units));
+
+    let album_id = album_id.to_string();
+    let dead_letter_directory = directory.to_path_buf();
+    let dead_letter_queue = dead_letter_queue.clone();
+    let dht = dht.clone();
+    let identity = identity.clone();
+    let tile = tile.to_string();
+    let non_critical_errors = non_critical_errors.clone();
+
+    let send = move |geocode: &str, subdataset: u8, dataset: &Dataset|
+            -> Result<(), Box<dyn Error>> {
         // if image has 0.0 coverage -> don't process
-        let pixel_coverage = st_image::coverage(&dataset)?;
+        let pixel_coverage = st_image::coverage(dataset)?;
         if pixel_coverage == 0f64 {
-            continue;
+            return Ok(());
         }
 
-        // lookup geocode in dht
-        let addr = match crate::task::dht_lookup(
-                &dht, dht_key_length, &geocode) {
-            Ok(addr) => addr,
+        // lookup geocode in dht - on failure the tile is handed to the
+        // dead-letter queue rather than dropped, which re-runs this
+        // same lookup on every retry so it lands correctly once the
+        // ring recovers
+        let addrs = match crate::task::dht_lookup_replicas(&dht,
+                dht_key_length, geocode, replication_factor) {
+            Ok(addrs) => addrs,
             Err(e) => {
                 warn!("{}", e);
-                continue;
+                non_critical_errors.push(&tile, geocode, &e);
+                if let Err(e) = dead_letter_queue.push(
+                        &dead_letter_directory, &album_id, dataset,
+                        dht_key_length, geocode, pixel_coverage, "MODIS",
+                        false, replication_factor, &RAW_SOURCE, subdataset,
+                        &tile, timestamp) {
+                    warn!("failed to queue '{}' for retry: {}", geocode, e);
+                }
+                return Ok(());
+            },
+        };
+
+        // downsample a browsable preview alongside the full-resolution
+        // tile, so a UI grid can fetch a thumbnail instead of pulling
+        // every raster wholesale
+        let preview_dataset = match overview::generate(dataset,
+                band_count, overview::PREVIEW_MAX_DIMENSION) {
+            Ok(preview_dataset) => Some(preview_dataset),
+            Err(e) => {
+                warn!("failed to generate preview for '{}': {}", geocode, e);
+                None
             },
         };
 
-        // send image to new host
-        if let Err(e) = crate::transfer::send_image(&addr, album_id,
-                &dataset, &geocode, pixel_coverage, "MODIS",
-                &RAW_SOURCE, subdataset, &tile, timestamp) {
-            warn!("failed to write image to node {}: {}", addr, e);
+        // send image to each replica - losing a minority of replicas is
+        // expected in a fault-tolerant store, so a short-of-quorum write
+        // is handed to the dead-letter queue for backed-off retry
+        // rather than failing (and re-sending) the whole split unit
+        let successes = crate::task::send_to_replicas(&addrs, |addr|
+            crate::transfer::send_image(addr, &identity, node_id, &album_id,
+                dataset, geocode, pixel_coverage, "MODIS",
+                &RAW_SOURCE, subdataset, &tile, timestamp, false, None));
+
+        let quorum = crate::task::write_quorum(replication_factor);
+        if successes < quorum {
+            let err_msg = format!("wrote image to only {}/{} replicas \
+                (quorum {})", successes, addrs.len(), quorum);
+            warn!("{}", err_msg);
+            non_critical_errors.push(&tile, geocode, err_msg);
+            if let Err(e) = dead_letter_queue.push(&dead_letter_directory,
+                    &album_id, dataset, dht_key_length, geocode,
+                    pixel_coverage, "MODIS", false, replication_factor,
+                    &RAW_SOURCE, subdataset, &tile, timestamp) {
+                warn!("failed to queue '{}' for retry: {}", geocode, e);
+            }
+            return Ok(());
         }
-    }
+
+        if let Some(preview_dataset) = &preview_dataset {
+            let successes = crate::task::send_to_replicas(&addrs, |addr|
+                crate::transfer::send_image(addr, &identity, node_id,
+                    &album_id, preview_dataset, geocode, pixel_coverage,
+                    "MODIS", &RAW_SOURCE, subdataset, &tile, timestamp,
+                    true, None));
+
+            if successes < quorum {
+                let err_msg = format!("wrote preview to only {}/{} \
+                    replicas (quorum {})", successes, addrs.len(), quorum);
+                warn!("{}", err_msg);
+                non_critical_errors.push(&tile, geocode, err_msg);
+                if let Err(e) = dead_letter_queue.push(
+                        &dead_letter_directory, &album_id, preview_dataset,
+                        dht_key_length, geocode, pixel_coverage, "MODIS",
+                        true, replication_factor, &RAW_SOURCE, subdataset,
+                        &tile, timestamp) {
+                    warn!("failed to queue '{}' preview for retry: {}",
+                        geocode, e);
+                }
+            }
+        }
+
+        Ok(())
+    };
+
+    job::start(job_manager, directory.to_path_buf(), job_id, manifest,
+        datasets, send);
 
     Ok(())
 }
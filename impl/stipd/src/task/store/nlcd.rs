@@ -1,9 +1,11 @@
 use chrono::prelude::{TimeZone, Utc};
-use gdal::Dataset;
 use swarm::prelude::Dht;
 
 use crate::RAW_SOURCE;
 use crate::album::Album;
+use crate::identity::NodeIdentity;
+use crate::task::NonCriticalErrorSink;
+use crate::task::store::validate;
 
 use std::error::Error;
 use std::ffi::OsStr;
@@ -11,7 +13,10 @@ use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
 pub fn process(album: &Arc<RwLock<Album>>, dht: &Arc<Dht>,
-        precision: usize, record: &PathBuf) -> Result<(), Box<dyn Error>> {
+        identity: &Arc<NodeIdentity>, node_id: u32, precision: usize,
+        replication_factor: u8, strict: bool, record: &PathBuf,
+        non_critical_errors: &NonCriticalErrorSink)
+        -> Result<(), Box<dyn Error>> {
     // retrieve album metadata
     let (album_id, dht_key_length, geocode) = {
         let album = album.read().unwrap();
@@ -19,8 +24,13 @@ pub fn process(album: &Arc<RwLock<Album>>, dht: &Arc<Dht>,
             album.get_geocode().clone())
     };
 
-    // open file
-    let dataset = Dataset::open(record)?;
+    // open file - a truncated download or an otherwise unreadable
+    // product is skipped (or fails the record outright in strict mode)
+    // rather than propagating a gdal panic up through this worker
+    let dataset = match validate::open_checked(record, false, strict)? {
+        Some(dataset) => dataset,
+        None => return Ok(()),
+    };
     let filename = record.file_name().unwrap()
         .to_string_lossy().to_lowercase();
 
@@ -68,20 +78,30 @@ pub fn process(album: &Arc<RwLock<Album>>, dht: &Arc<Dht>,
         }
 
         // lookup geocode in dht
-        let addr = match crate::task::dht_lookup(
-                &dht, dht_key_length, &split_geocode) {
-            Ok(addr) => addr,
+        let addrs = match crate::task::dht_lookup_replicas(&dht,
+                dht_key_length, &split_geocode, replication_factor) {
+            Ok(addrs) => addrs,
             Err(e) => {
                 warn!("{}", e);
+                non_critical_errors.push(&tile, &split_geocode, &e);
                 continue;
             },
         };
 
-        // send image to new host
-        if let Err(e) = crate::transfer::send_image(&addr, &album_id,
+        // send image to each replica - losing a minority of replicas is
+        // expected in a fault-tolerant store, so only a short-of-quorum
+        // write is worth reporting, not every individual replica failure
+        let successes = crate::task::send_to_replicas(&addrs, |addr|
+            crate::transfer::send_image(addr, identity, node_id, &album_id,
                 &split_dataset, &split_geocode, pixel_coverage, "NLCD",
-                &RAW_SOURCE, 0, &tile, timestamp) {
-            warn!("failed to write image to node {}: {}", addr, e);
+                &RAW_SOURCE, 0, &tile, timestamp, false, None));
+
+        let quorum = crate::task::write_quorum(replication_factor);
+        if successes < quorum {
+            let err_msg = format!("wrote image to only {}/{} replicas \
+                (quorum {})", successes, addrs.len(), quorum);
+            warn!("{}", err_msg);
+            non_critical_errors.push(&tile, &split_geocode, err_msg);
         }
     }
 
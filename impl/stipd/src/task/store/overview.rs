@@ -0,0 +1,121 @@
+use failure::ResultExt;
+use gdal::raster::{Dataset, Driver};
+
+use std::error::Error;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_int;
+
+/// long edge, in pixels, that a generated preview raster is scaled down
+/// to - small enough that pulling every tile's preview for a coverage
+/// grid stays cheap, without being so small the thumbnail is useless
+pub const PREVIEW_MAX_DIMENSION: usize = 256;
+
+/// build a decimated, average-resampled copy of 'dataset' with its
+/// longest edge scaled to at most 'max_dimension' pixels - modeled on a
+/// media thumbnailer's preview pass, so a client browsing coverage can
+/// pull a lightweight raster instead of the full-resolution tile. the
+/// geo_transform/projection are scaled to the reduced pixel grid so the
+/// preview stays geospatially aligned with the tile it was derived from
+pub fn generate(dataset: &Dataset, band_count: usize, max_dimension: usize)
+        -> Result<Dataset, Box<dyn Error>> {
+    let (src_x, src_y) = dataset.size();
+    let scale = (max_dimension as f64 / src_x.max(src_y) as f64).min(1.0);
+
+    let dst_x = ((src_x as f64 * scale).round() as usize).max(1);
+    let dst_y = ((src_y as f64 * scale).round() as usize).max(1);
+
+    // match the source raster's band type rather than taking it as a
+    // generic parameter - the caller already has a concrete, type-erased
+    // 'Dataset' by the time a geocode's splits have been assembled
+    let band_type = unsafe {
+        let src_band = gdal_sys::GDALGetRasterBand(dataset.c_dataset(), 1);
+        gdal_sys::GDALGetRasterDataType(src_band)
+    };
+
+    let driver = Driver::get("Mem").expect("get mem driver");
+    let c_name = CString::new("")?;
+    let c_dataset = unsafe {
+        gdal_sys::GDALCreate(driver.c_driver(), c_name.as_ptr(),
+            dst_x as c_int, dst_y as c_int, band_count as c_int,
+            band_type, std::ptr::null_mut())
+    };
+
+    if c_dataset.is_null() {
+        return Err(last_gdal_error("failed to create preview dataset"));
+    }
+
+    let dst_dataset = unsafe { Dataset::from_c_dataset(c_dataset) };
+
+    // scale the pixel size component of the geo_transform to the
+    // reduced grid so the preview's origin/extent still line up with
+    // the full-resolution tile it was derived from
+    let mut geo_transform = dataset.geo_transform().compat()?;
+    geo_transform[1] *= src_x as f64 / dst_x as f64;
+    geo_transform[5] *= src_y as f64 / dst_y as f64;
+    dst_dataset.set_geo_transform(&geo_transform).compat()?;
+    dst_dataset.set_projection(&dataset.projection()).compat()?;
+
+    for band_index in 1..=band_count as c_int {
+        let pixel_size = gdal_data_type_size(band_type);
+        let mut buf = vec![0u8; dst_x * dst_y * pixel_size];
+
+        let read_result = unsafe {
+            let src_band = gdal_sys::GDALGetRasterBand(
+                dataset.c_dataset(), band_index);
+
+            let mut extra_arg = gdal_sys::GDALRasterIOExtraArg {
+                nVersion: 1,
+                eResampleAlg: gdal_sys::GDALRIOResampleAlg_GRIORA_Average,
+                pfnProgress: None,
+                pProgressData: std::ptr::null_mut(),
+                bFloatingPointWindowValidity: 0,
+                dfXOff: 0.0,
+                dfYOff: 0.0,
+                dfXSize: src_x as f64,
+                dfYSize: src_y as f64,
+            };
+
+            gdal_sys::GDALRasterIOEx(src_band, gdal_sys::GDALRWFlag_GF_Read,
+                0, 0, src_x as c_int, src_y as c_int,
+                buf.as_mut_ptr() as *mut std::ffi::c_void,
+                dst_x as c_int, dst_y as c_int, band_type,
+                0, 0, &mut extra_arg)
+        };
+
+        if read_result as i32 != 0 { // CE_None == 0
+            return Err(last_gdal_error(&format!(
+                "failed to read decimated band {}", band_index)));
+        }
+
+        let write_result = unsafe {
+            let dst_band = gdal_sys::GDALGetRasterBand(
+                dst_dataset.c_dataset(), band_index);
+
+            gdal_sys::GDALRasterIO(dst_band, gdal_sys::GDALRWFlag_GF_Write,
+                0, 0, dst_x as c_int, dst_y as c_int,
+                buf.as_mut_ptr() as *mut std::ffi::c_void,
+                dst_x as c_int, dst_y as c_int, band_type, 0, 0)
+        };
+
+        if write_result as i32 != 0 {
+            return Err(last_gdal_error(&format!(
+                "failed to write preview band {}", band_index)));
+        }
+    }
+
+    Ok(dst_dataset)
+}
+
+fn gdal_data_type_size(data_type: gdal_sys::GDALDataType::Type) -> usize {
+    (unsafe { gdal_sys::GDALGetDataTypeSizeBytes(data_type) }) as usize
+}
+
+fn last_gdal_error(context: &str) -> Box<dyn Error> {
+    let err_msg = unsafe {
+        let c_ptr = gdal_sys::CPLGetLastErrorMsg();
+        CStr::from_ptr(c_ptr).to_string_lossy().into_owned()
+    };
+
+    unsafe { gdal_sys::CPLErrorReset() };
+    format!("{}: {}", context, err_msg).into()
+}
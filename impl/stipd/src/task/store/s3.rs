@@ -0,0 +1,96 @@
+use rusoto_core::{HttpClient, Region};
+use rusoto_credential::StaticProvider;
+use rusoto_s3::{ListObjectsV2Request, S3, S3Client};
+
+use std::error::Error;
+
+/// an 's3://bucket/prefix/pattern' glob split into its constituent parts
+pub struct S3Glob {
+    pub bucket: String,
+    pub prefix: String,
+    pub suffix: String,
+}
+
+/// parse an 's3://bucket/prefix/**.ext' glob into a bucket, a literal
+/// key prefix to list under, and a suffix each listed key must match -
+/// this repo's globs only ever anchor on a file extension, so a full
+/// glob matcher isn't needed for the object-store case
+pub fn parse_glob(glob: &str) -> Result<S3Glob, Box<dyn Error>> {
+    let rest = match glob.strip_prefix("s3://") {
+        Some(rest) => rest,
+        None => return Err(format!(
+            "'{}' is not an s3:// glob", glob).into()),
+    };
+
+    let mut fields = rest.splitn(2, '/');
+    let bucket = fields.next()
+        .ok_or("s3 glob missing bucket")?.to_string();
+    let path = fields.next().unwrap_or("");
+
+    // split the path into a literal prefix and the trailing glob
+    // pattern - e.g. 'prefix/**.zip' -> prefix='prefix/', suffix='.zip'
+    let split = path.rfind('/').map(|i| i + 1).unwrap_or(0);
+    let prefix = path[..split].to_string();
+    let suffix = path[split..].trim_start_matches('*').to_string();
+
+    Ok(S3Glob {
+        bucket: bucket,
+        prefix: prefix,
+        suffix: suffix,
+    })
+}
+
+/// list every object under 'prefix' in 'bucket' whose key ends with
+/// 'suffix', paging through ListObjectsV2 until the listing is
+/// exhausted
+pub fn list_objects(endpoint: &Option<String>, region: &Option<String>,
+        access_key: &Option<String>, secret_key: &Option<String>,
+        bucket: &str, prefix: &str, suffix: &str)
+        -> Result<Vec<String>, Box<dyn Error>> {
+    let region = match (endpoint, region) {
+        (Some(endpoint), region) => Region::Custom {
+            name: region.clone().unwrap_or_else(|| "custom".to_string()),
+            endpoint: endpoint.clone(),
+        },
+        (None, Some(region)) => region.parse()?,
+        (None, None) => Region::default(),
+    };
+
+    let client = match (access_key, secret_key) {
+        (Some(access_key), Some(secret_key)) => S3Client::new_with(
+            HttpClient::new()?,
+            StaticProvider::new_minimal(
+                access_key.clone(), secret_key.clone()),
+            region),
+        _ => S3Client::new(region),
+    };
+
+    let mut keys = Vec::new();
+    let mut continuation_token = None;
+    loop {
+        let request = ListObjectsV2Request {
+            bucket: bucket.to_string(),
+            prefix: Some(prefix.to_string()),
+            continuation_token: continuation_token.clone(),
+            ..Default::default()
+        };
+
+        let response = futures::executor::block_on(
+            client.list_objects_v2(request))?;
+
+        for object in response.contents.unwrap_or_default() {
+            if let Some(key) = object.key {
+                if key.ends_with(suffix) {
+                    keys.push(key);
+                }
+            }
+        }
+
+        continuation_token = response.next_continuation_token;
+        if continuation_token.is_none() {
+            break;
+        }
+    }
+
+    Ok(keys)
+}
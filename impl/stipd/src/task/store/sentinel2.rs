@@ -5,16 +5,182 @@ use zip::ZipArchive;
 
 use crate::RAW_SOURCE;
 use crate::album::Album;
+use crate::identity::NodeIdentity;
+use crate::task::NonCriticalErrorSink;
+use crate::task::store::validate;
 
 use std::error::Error;
-use std::ffi::OsStr;
+use std::ffi::{CStr, CString, OsStr};
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Read};
+use std::os::raw::c_void;
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
+/// ingest failures specific enough to act on programmatically - a
+/// missing xml sidecar or an unparseable timestamp shouldn't be
+/// indistinguishable from an arbitrary io/gdal error once it reaches
+/// 'non_critical_errors'/the dead letter queue, since the two call for
+/// different operator responses (re-supply the archive vs. investigate
+/// gdal/metadata drift)
+#[derive(Debug, thiserror::Error)]
+pub enum Sentinel2Error {
+    #[error("unable to find xml metadata file in '{0:?}'")]
+    MissingMetadataFile(PathBuf),
+
+    #[error("start time metadata not found in '{0:?}'")]
+    MissingStartTime(PathBuf),
+
+    #[error("failed to parse start time metadata in '{path:?}': {source}")]
+    InvalidStartTime {
+        path: PathBuf,
+        #[source] source: chrono::ParseError,
+    },
+
+    #[error("failed to find subdatasets for '{0:?}'")]
+    MissingSubdatasets(PathBuf),
+
+    #[error("unrecognized archive format for '{0:?}'")]
+    UnsupportedArchive(PathBuf),
+}
+
+/// the container format a Sentinel-2 record ships in - determines which
+/// of GDAL's virtual filesystem prefixes ('/vsizip/', '/vsitar/',
+/// '/vsigzip/', chained for compressed tarballs) to read it through
+#[derive(Debug, PartialEq)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+    Gzip,
+    Directory,
+}
+
+/// identify a record's container format by magic bytes rather than its
+/// extension, so a mislabeled or extension-less delivery product is
+/// still ingestible - the one exception is distinguishing a bare '.gz'
+/// from a '.tar.gz'/'.tgz', since that distinction only exists in the
+/// decompressed stream and isn't worth a second decompression pass here
+fn detect_archive_kind(record: &PathBuf) -> Result<ArchiveKind, Box<dyn Error>> {
+    if record.is_dir() {
+        return Ok(ArchiveKind::Directory);
+    }
+
+    // read enough of the header to see a tar's "ustar" magic at offset
+    // 257, in addition to the zip/gzip signatures at the start
+    let header = read_magic_bytes(record, 262)?;
+
+    let is_zip = header.len() >= 4 && &header[0..4] == b"PK\x03\x04";
+    let is_gzip = header.len() >= 2 && header[0] == 0x1f && header[1] == 0x8b;
+    let is_tar = header.len() >= 262 && &header[257..262] == b"ustar";
+
+    let record_str = record.to_string_lossy();
+    let is_tar_name = record_str.ends_with(".tar.gz") || record_str.ends_with(".tgz");
+
+    if is_zip {
+        Ok(ArchiveKind::Zip)
+    } else if is_tar {
+        Ok(ArchiveKind::Tar)
+    } else if is_gzip && is_tar_name {
+        Ok(ArchiveKind::TarGz)
+    } else if is_gzip {
+        Ok(ArchiveKind::Gzip)
+    } else {
+        Err(Sentinel2Error::UnsupportedArchive(record.clone()).into())
+    }
+}
+
+/// read the first 'length' bytes of a record, transparently handling
+/// object-store paths ('/vsis3/...') through GDAL's vsi layer since
+/// they're never staged to local disk
+fn read_magic_bytes(record: &PathBuf, length: usize)
+        -> Result<Vec<u8>, Box<dyn Error>> {
+    let record_str = record.to_string_lossy();
+    if record_str.starts_with("/vsis3/") {
+        read_vsi_bytes(&record_str, length)
+    } else {
+        let mut file = File::open(record)?;
+        let mut buffer = vec![0u8; length];
+        let read = file.read(&mut buffer)?;
+        buffer.truncate(read);
+        Ok(buffer)
+    }
+}
+
+/// read the first 'length' bytes of a gdal virtual filesystem path -
+/// goes through gdal_sys because the 'gdal' crate doesn't expose raw
+/// VSI file reads
+fn read_vsi_bytes(path: &str, length: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    let c_path = CString::new(path)?;
+    let mode = CString::new("rb")?;
+
+    unsafe {
+        let handle = gdal_sys::VSIFOpenL(c_path.as_ptr(), mode.as_ptr());
+        if handle.is_null() {
+            return Err(format!("failed to open '{}' for reading", path).into());
+        }
+
+        let mut buffer = vec![0u8; length];
+        let read = gdal_sys::VSIFReadL(
+            buffer.as_mut_ptr() as *mut c_void, 1, length, handle);
+        gdal_sys::VSIFCloseL(handle);
+
+        buffer.truncate(read);
+        Ok(buffer)
+    }
+}
+
+/// list the entries of an archive reachable through one of GDAL's
+/// virtual filesystems (e.g. '/vsitar//vsigzip/bucket/key.tar.gz'),
+/// covering both local and object-store records uniformly - goes
+/// through gdal_sys because the 'gdal' crate doesn't expose VSIReadDir
+fn vsi_archive_entries(vsi_path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let c_path = CString::new(vsi_path)?;
+
+    let mut entries = Vec::new();
+    unsafe {
+        let list = gdal_sys::VSIReadDir(c_path.as_ptr());
+        if list.is_null() {
+            return Err(format!(
+                "failed to list archive entries for '{}'", vsi_path).into());
+        }
+
+        let mut i = 0;
+        loop {
+            let entry_ptr = *list.offset(i);
+            if entry_ptr.is_null() {
+                break;
+            }
+
+            entries.push(CStr::from_ptr(entry_ptr)
+                .to_string_lossy().to_string());
+            i += 1;
+        }
+
+        gdal_sys::CSLDestroy(list);
+    }
+
+    Ok(entries)
+}
+
+/// the GDAL virtual filesystem prefix a record's container format reads
+/// through - tar.gz chains '/vsitar/' over '/vsigzip/' since GDAL treats
+/// the latter as a nested filesystem, same as it would a local path
+fn vsi_prefix(kind: &ArchiveKind, record_str: &str) -> String {
+    match kind {
+        ArchiveKind::Zip => format!("/vsizip/{}", record_str),
+        ArchiveKind::Tar => format!("/vsitar/{}", record_str),
+        ArchiveKind::TarGz => format!("/vsitar//vsigzip/{}", record_str),
+        ArchiveKind::Gzip => format!("/vsigzip/{}", record_str),
+        ArchiveKind::Directory => record_str.to_string(),
+    }
+}
+
 pub fn process(album: &Arc<RwLock<Album>>, dht: &Arc<Dht>,
-        precision: usize, record: &PathBuf) -> Result<(), Box<dyn Error>> {
+        identity: &Arc<NodeIdentity>, node_id: u32, precision: usize,
+        replication_factor: u8, strict: bool, record: &PathBuf,
+        non_critical_errors: &NonCriticalErrorSink)
+        -> Result<(), Box<dyn Error>> {
     // retrieve album metadata
     let (album_id, dht_key_length, geocode) = {
         let album = album.read().unwrap();
@@ -29,44 +195,80 @@ pub fn process(album: &Arc<RwLock<Album>>, dht: &Arc<Dht>,
 
     //println!("TILE: '{}'", tile);
 
-    // open zip archive
-    let file = File::open(&record)?;
-    let reader = BufReader::new(file);
-    let archive = ZipArchive::new(reader)?;
+    // identify the container format by magic bytes so a record may be a
+    // '.zip', '.tar', '.tar.gz'/'.tgz', a bare '.gz', or an already
+    // unpacked directory, rather than hard-assuming '.zip'
+    let record_str = record.to_string_lossy();
+    let archive_kind = detect_archive_kind(record)?;
 
-    // identify metadata xml file and band image files
-    let mut zip_metadata_option = None;
-    for filename in archive.file_names() {
-        let path = PathBuf::from(&filename);
+    // a local, uncompressed '.zip' is read directly through the 'zip'
+    // crate rather than round-tripping through GDAL's virtual
+    // filesystem; every other container (including a '.zip' living in
+    // an object store) is listed through GDAL's vsi layer, which
+    // handles local and object-store records uniformly
+    let metadata_entry = if archive_kind == ArchiveKind::Zip
+            && !record_str.starts_with("/vsis3/") {
+        // open zip archive
+        let file = File::open(&record)?;
+        let reader = BufReader::new(file);
+        let archive = ZipArchive::new(reader)?;
 
-        if path.file_name() == Some(OsStr::new("MTD_MSIL1C.xml")) {
-            zip_metadata_option = Some(filename);
-        }
-    }
+        // identify metadata xml file and band image files
+        archive.file_names()
+            .find(|filename| PathBuf::from(filename).file_name()
+                == Some(OsStr::new("MTD_MSIL1C.xml")))
+            .map(|filename| filename.to_string())
+    } else if archive_kind == ArchiveKind::Directory {
+        std::fs::read_dir(record)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .find(|filename| PathBuf::from(filename).file_name()
+                == Some(OsStr::new("MTD_MSIL1C.xml")))
+    } else {
+        vsi_archive_entries(&vsi_prefix(&archive_kind, &record_str))?
+            .into_iter()
+            .find(|filename| PathBuf::from(filename).file_name()
+                == Some(OsStr::new("MTD_MSIL1C.xml")))
+    };
 
     // check if we identified xml metadata file and band image files
-    if zip_metadata_option == None {
-        return Err("unable to find xml metadata file".into());
-    }
+    let metadata_entry = match metadata_entry {
+        Some(metadata_entry) => metadata_entry,
+        None => return Err(
+            Sentinel2Error::MissingMetadataFile(record.clone()).into()),
+    };
 
-    // open gdal metadata dataset
-    let zip_metadata = zip_metadata_option.unwrap();
-    let metadata_filename = format!("/vsizip/{}/{}",
-        record.to_string_lossy(), zip_metadata);
+    // open gdal metadata dataset - a truncated or otherwise corrupt
+    // archive is skipped (or fails the record outright in strict mode)
+    // rather than propagating a gdal panic up through this worker
+    let metadata_filename = match archive_kind {
+        ArchiveKind::Directory =>
+            record.join(&metadata_entry).to_string_lossy().to_string(),
+        _ => format!("{}/{}",
+            vsi_prefix(&archive_kind, &record_str), metadata_entry),
+    };
     let metadata_path = PathBuf::from(&metadata_filename);
-    let dataset = Dataset::open(&metadata_path)?;
+    let dataset = match validate::open_checked(
+            &metadata_path, true, strict)? {
+        Some(dataset) => dataset,
+        None => return Ok(()),
+    };
 
     // parse metadata
     let timestamp = match dataset.metadata_item("PRODUCT_START_TIME", "") {
-        Some(time) => time.parse::<DateTime<Utc>>()?.timestamp(),
-        None => return Err("start time metadata not found".into()),
+        Some(time) => time.parse::<DateTime<Utc>>()
+            .map_err(|source| Sentinel2Error::InvalidStartTime {
+                path: metadata_path.clone(), source: source })?
+            .timestamp(),
+        None => return Err(
+            Sentinel2Error::MissingStartTime(metadata_path).into()),
     };
 
     // populate subdatasets collection
     let metadata = match dataset.metadata_domain("SUBDATASETS") {
         Some(metadata) => metadata,
-        None => return Err(format!(
-            "failed to find subdatasets for '{:?}'", &record).into()),
+        None => return Err(
+            Sentinel2Error::MissingSubdatasets(record.clone()).into()),
     };
 
     let mut subdatasets: Vec<(&str, &str)> = Vec::new();
@@ -125,21 +327,32 @@ pub fn process(album: &Arc<RwLock<Album>>, dht: &Arc<Dht>,
             }
 
             // lookup geocode in dht
-            let addr = match crate::task::dht_lookup(
-                    &dht, dht_key_length, &split_geocode) {
-                Ok(addr) => addr,
+            let addrs = match crate::task::dht_lookup_replicas(&dht,
+                    dht_key_length, &split_geocode, replication_factor) {
+                Ok(addrs) => addrs,
                 Err(e) => {
                     warn!("{}", e);
+                    non_critical_errors.push(&tile, &split_geocode, &e);
                     continue;
                 },
             };
 
-            // send image to new host
-            if let Err(e) = crate::transfer::send_image(&addr,
+            // send image to each replica - losing a minority of replicas
+            // is expected in a fault-tolerant store, so only a
+            // short-of-quorum write is worth reporting, not every
+            // individual replica failure
+            let successes = crate::task::send_to_replicas(&addrs, |addr|
+                crate::transfer::send_image(addr, identity, node_id,
                     &album_id, &split_dataset, &split_geocode,
                     pixel_coverage, "Sentinel-2",
-                    &RAW_SOURCE, i as u8, &tile, timestamp) {
-                warn!("failed to write image to node {}: {}", addr, e);
+                    &RAW_SOURCE, i as u8, &tile, timestamp, false, None));
+
+            let quorum = crate::task::write_quorum(replication_factor);
+            if successes < quorum {
+                let err_msg = format!("wrote image to only {}/{} replicas \
+                    (quorum {})", successes, addrs.len(), quorum);
+                warn!("{}", err_msg);
+                non_critical_errors.push(&tile, &split_geocode, err_msg);
             }
         }
     }
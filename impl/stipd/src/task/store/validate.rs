@@ -0,0 +1,70 @@
+use gdal::{Dataset, Metadata};
+
+use std::error::Error;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+/// outcome of probing a record before a store task commits it to an
+/// album, analogous to probing a media file for empty stream metadata
+/// before transcoding it
+enum Health {
+    Valid,
+    EmptySubdatasets,
+    Unreadable(String),
+}
+
+impl Health {
+    fn reason(&self) -> Option<String> {
+        match self {
+            Health::Valid => None,
+            Health::EmptySubdatasets =>
+                Some("has no subdatasets".to_string()),
+            Health::Unreadable(e) => Some(format!("is unreadable: {}", e)),
+        }
+    }
+}
+
+/// open 'record' with gdal and classify it, catching a panic raised
+/// deep in the bindings on a severely truncated file rather than
+/// letting it take down the worker thread processing it
+fn probe(record: &Path, subdatasets_required: bool)
+        -> (Health, Option<Dataset>) {
+    let opened = panic::catch_unwind(AssertUnwindSafe(||
+        Dataset::open(record).map_err(|e| e.to_string())));
+
+    match opened {
+        Err(_) => (Health::Unreadable(
+            "gdal panicked while opening dataset".to_string()), None),
+        Ok(Err(e)) => (Health::Unreadable(e), None),
+        Ok(Ok(dataset)) => {
+            if subdatasets_required
+                    && dataset.metadata("SUBDATASETS").is_empty() {
+                (Health::EmptySubdatasets, None)
+            } else {
+                (Health::Valid, Some(dataset))
+            }
+        },
+    }
+}
+
+/// probe 'record' and log an empty or unreadable dataset as a warning
+/// before skipping it, or fail the record outright when 'strict' is set -
+/// a skip is not recorded in the task's non-critical errors. returns the
+/// opened dataset only when it is healthy
+pub fn open_checked(record: &Path, subdatasets_required: bool, strict: bool)
+        -> Result<Option<Dataset>, Box<dyn Error>> {
+    let (health, dataset) = probe(record, subdatasets_required);
+
+    match health.reason() {
+        None => Ok(dataset),
+        Some(reason) => {
+            let msg = format!("skipping '{:?}': {}", record, reason);
+            if strict {
+                Err(msg.into())
+            } else {
+                warn!("{}", msg);
+                Ok(None)
+            }
+        },
+    }
+}
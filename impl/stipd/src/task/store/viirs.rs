@@ -7,6 +7,10 @@ use swarm::prelude::Dht;
 
 use crate::RAW_SOURCE;
 use crate::album::Album;
+use crate::identity::NodeIdentity;
+use crate::task::NonCriticalErrorSink;
+use crate::task::store::overview;
+use crate::task::store::validate;
 
 use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
@@ -14,8 +18,10 @@ use std::ffi::OsStr;
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
-pub fn process(album: &Arc<RwLock<Album>>, dht: &Arc<Dht>, 
-        precision: usize, record: &PathBuf) 
+pub fn process(album: &Arc<RwLock<Album>>, dht: &Arc<Dht>,
+        identity: &Arc<NodeIdentity>, node_id: u32, precision: usize,
+        replication_factor: u8, strict: bool, record: &PathBuf,
+        non_critical_errors: &NonCriticalErrorSink)
         -> Result<(), Box<dyn Error>> {
     // retrieve album metadata
     let (album_id, dht_key_length, geocode) = {
@@ -24,8 +30,14 @@ pub fn process(album: &Arc<RwLock<Album>>, dht: &Arc<Dht>,
             album.get_geocode().clone())
     };
 
-    let dataset = Dataset::open(&record)?;
- 
+    // open file - a truncated download or product with no subdatasets
+    // is skipped (or fails the record outright in strict mode) rather
+    // than propagating a gdal panic up through this worker
+    let dataset = match validate::open_checked(record, true, strict)? {
+        Some(dataset) => dataset,
+        None => return Ok(()),
+    };
+
     // parse metadata
     let tile_path = record.with_extension("");
     let tile = tile_path.file_name()
@@ -68,7 +80,9 @@ pub fn process(album: &Arc<RwLock<Album>>, dht: &Arc<Dht>,
         let data_type = match type_desc {
             "8-bit unsigned character" => GDALDataType::GDT_Byte,
             "16-bit unsigned integer" => GDALDataType::GDT_UInt16,
-            "32-bit floating-point" => continue,
+            "16-bit integer" => GDALDataType::GDT_Int16,
+            "32-bit floating-point" => GDALDataType::GDT_Float32,
+            "64-bit floating-point" => GDALDataType::GDT_Float64,
             _ => return Err(format!(
                 "unsupported data type: '{}'", type_desc).into()),
         };
@@ -79,27 +93,39 @@ pub fn process(album: &Arc<RwLock<Album>>, dht: &Arc<Dht>,
     }
 
     // process subdatasets
-    for (i, (data_type, subdatasets)) in 
+    for (i, (data_type, subdatasets)) in
             subdatasets.into_iter().enumerate() {
+        let band_count = subdatasets.len();
+
         // split datasets
         let datasets = match data_type {
             GDALDataType::GDT_Byte => split_subdatasets::<u8>(
                 geocode, precision, subdatasets)?,
             GDALDataType::GDT_UInt16 => split_subdatasets::<u16>(
                 geocode, precision, subdatasets)?,
+            GDALDataType::GDT_Int16 => split_subdatasets::<i16>(
+                geocode, precision, subdatasets)?,
+            GDALDataType::GDT_Float32 => split_subdatasets::<f32>(
+                geocode, precision, subdatasets)?,
+            GDALDataType::GDT_Float64 => split_subdatasets::<f64>(
+                geocode, precision, subdatasets)?,
             _ => unreachable!(),
         };
 
-        process_splits(&album_id, &datasets, &dht,
-            dht_key_length, i as u8, &tile, timestamp)?;
+        process_splits(&album_id, &datasets, band_count, &dht,
+            dht_key_length, i as u8, &tile, timestamp, identity, node_id,
+            replication_factor, non_critical_errors)?;
     }
 
     Ok(())
 }
 
 fn process_splits(album_id: &str, datasets: &HashMap<String, Dataset>,
-        dht: &Arc<Dht>, dht_key_length: i8, subdataset: u8, 
-        tile: &str, timestamp: i64) -> Result<(), Box<dyn Error>> {
+        band_count: usize, dht: &Arc<Dht>, dht_key_length: i8,
+        subdataset: u8, tile: &str, timestamp: i64,
+        identity: &Arc<NodeIdentity>, node_id: u32, replication_factor: u8,
+        non_critical_errors: &NonCriticalErrorSink)
+        -> Result<(), Box<dyn Error>> {
     for (geocode, dataset) in datasets.iter() {
         // if image has 0.0 coverage -> don't process
         let pixel_coverage = st_image::get_coverage(&dataset)?;
@@ -108,20 +134,57 @@ fn process_splits(album_id: &str, datasets: &HashMap<String, Dataset>,
         }
 
         // lookup geocode in dht
-        let addr = match crate::task::dht_lookup(
-                &dht, dht_key_length, &geocode) {
-            Ok(addr) => addr,
+        let addrs = match crate::task::dht_lookup_replicas(&dht,
+                dht_key_length, &geocode, replication_factor) {
+            Ok(addrs) => addrs,
             Err(e) => {
                 warn!("{}", e);
+                non_critical_errors.push(tile, geocode, &e);
                 continue;
             },
         };
 
-        // send image to new host
-        if let Err(e) = crate::transfer::send_image(&addr, album_id,
+        // downsample a browsable preview alongside the full-resolution
+        // tile, so a UI grid can fetch a thumbnail instead of pulling
+        // every raster wholesale
+        let preview_dataset = match overview::generate(&dataset,
+                band_count, overview::PREVIEW_MAX_DIMENSION) {
+            Ok(preview_dataset) => Some(preview_dataset),
+            Err(e) => {
+                warn!("failed to generate preview for '{}': {}", geocode, e);
+                None
+            },
+        };
+
+        // send image to each replica - losing a minority of replicas is
+        // expected in a fault-tolerant store, so only a short-of-quorum
+        // write is worth reporting, not every individual replica failure
+        let successes = crate::task::send_to_replicas(&addrs, |addr|
+            crate::transfer::send_image(addr, identity, node_id, album_id,
                 &dataset, &geocode, pixel_coverage, "VNP21V001",
-                &RAW_SOURCE, subdataset, &tile, timestamp) {
-            warn!("failed to write image to node {}: {}", addr, e);
+                &RAW_SOURCE, subdataset, &tile, timestamp, false, None));
+
+        let quorum = crate::task::write_quorum(replication_factor);
+        if successes < quorum {
+            let err_msg = format!("wrote image to only {}/{} replicas \
+                (quorum {})", successes, addrs.len(), quorum);
+            warn!("{}", err_msg);
+            non_critical_errors.push(tile, geocode, err_msg);
+        }
+
+        if let Some(preview_dataset) = &preview_dataset {
+            let successes = crate::task::send_to_replicas(&addrs, |addr|
+                crate::transfer::send_image(addr, identity, node_id,
+                    album_id, preview_dataset, &geocode, pixel_coverage,
+                    "VNP21V001", &RAW_SOURCE, subdataset, &tile,
+                    timestamp, true, None));
+
+            if successes < quorum {
+                let err_msg = format!("wrote preview to only {}/{} \
+                    replicas (quorum {})", successes, addrs.len(), quorum);
+                warn!("{}", err_msg);
+                non_critical_errors.push(tile, geocode, err_msg);
+            }
         }
     }
 
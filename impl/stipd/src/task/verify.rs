@@ -0,0 +1,68 @@
+use gdal::{Dataset, Metadata};
+
+use crate::album::Album;
+use crate::task::Task;
+
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+/// walks a node's local tiles, recomputes checksums, and logs (rather
+/// than silently tolerating) missing or corrupt ones so operators can
+/// catch bit-rot instead of discovering it on read
+pub struct VerifyTask {
+    album: Arc<RwLock<Album>>,
+}
+
+impl VerifyTask {
+    pub fn new(album: Arc<RwLock<Album>>) -> VerifyTask {
+        {
+            let album = album.read().unwrap();
+            info!("initailizing verify task [album={}]", album.get_id());
+        }
+
+        VerifyTask {
+            album: album,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Task<PathBuf> for VerifyTask {
+    fn process(&self, record: &PathBuf) -> Result<(), Box<dyn Error>> {
+        let dataset = match Dataset::open(&record) {
+            Ok(dataset) => dataset,
+            Err(e) => return Err(format!(
+                "missing or unreadable tile '{:?}': {}",
+                record, e).into()),
+        };
+
+        let expected_checksum = dataset
+            .metadata_item("CHECKSUM", "STIP")
+            .ok_or("image checksum metadata not found")?
+            .parse::<u64>()?;
+
+        // recompute the checksum over the raster bytes as currently
+        // stored on disk
+        let mut buf = Vec::new();
+        st_image::prelude::write(&dataset, &mut buf)?;
+        let actual_checksum = crate::transfer::checksum(&buf);
+
+        if actual_checksum != expected_checksum {
+            // TODO: re-run the placement lookup for this tile's geocode
+            // and pull a good copy from a replica rather than just
+            // reporting the corruption
+            return Err(format!("checksum mismatch for '{:?}': \
+                expected {} got {}", record,
+                expected_checksum, actual_checksum).into());
+        }
+
+        Ok(())
+    }
+
+    async fn records(&self) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        // search for paths using Album
+        let album = self.album.read().unwrap();
+        album.get_paths()
+    }
+}
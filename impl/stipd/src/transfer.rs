@@ -1,34 +1,283 @@
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use chacha20poly1305::aead::{Aead, NewAead};
 use comm::StreamHandler;
 use failure::ResultExt;
 use gdal::raster::Dataset;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
+use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
 use st_image::prelude::Geocode;
+use x25519_dalek::{EphemeralSecret, PublicKey as X25519PublicKey};
 
 use crate::album::AlbumManager;
+use crate::gossip::GossipState;
+use crate::identity::{self, NodeIdentity};
 
+use std::collections::hash_map::DefaultHasher;
 use std::error::Error;
-use std::io::{Read, Write};
+use std::hash::Hasher;
+use std::io::{Cursor, Read, Write};
 use std::net::{TcpStream, SocketAddr};
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
+/// compute the on-disk 'CHECKSUM' content checksum over the serialized
+/// raster bytes - also used to decode the version 0 wire format from a
+/// peer that hasn't upgraded to the 'TRANSFER_PROTOCOL_VERSION' 1 sha256
+/// digest below
+pub(crate) fn checksum(buf: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(buf);
+    hasher.finish()
+}
+
+/// length in bytes of the random challenge issued at the start of every
+/// transfer connection
+const CHALLENGE_LEN: usize = 32;
+
+/// wire protocol version, written right after the 'TransferOp' byte -
+/// version 0 is a peer that predates the end-to-end digest below and
+/// sends an 8-byte checksum ahead of the image body instead of a 32-byte
+/// digest after it; version 1 adds that digest but still sends the
+/// image body in cleartext; version 2 additionally negotiates a
+/// per-connection key (see 'negotiate_server'/'negotiate_client') and
+/// sends the body as encrypted AEAD chunks (see 'write_encrypted');
+/// version 3 additionally writes a one-byte compression format flag
+/// right after the key negotiation (see 'CompressionFormat'), so the
+/// image body may be zstd-compressed before it's encrypted; version 4
+/// replaces the whole-body transfer with content-defined chunks (see
+/// 'cdc::chunk') - the sender writes only the chunks' hashes first, the
+/// receiver answers with the indices it doesn't already hold in its
+/// 'cdc::ChunkStore', and only those chunks' bytes actually cross the
+/// wire, each still AEAD-encrypted the same as before, and the trailing
+/// digest is blake3 rather than sha256. chunk boundaries are found over
+/// the raw raster, since compressing first would scramble the very byte
+/// runs CDC is trying to recognize as shared; when the caller asks for
+/// compression, each chunk that does cross the wire is zstd-compressed
+/// on its own before it's encrypted. a mixed-version cluster keeps transferring tiles while nodes roll
+/// forward
+const TRANSFER_PROTOCOL_VERSION: u8 = 4;
+
+/// zstd level used when a caller enables compression without pinning a
+/// specific level - matches the zstd cli's own default trade-off of
+/// speed against ratio
+const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// the version >= 3 compression format flag, written right after the
+/// cipher key negotiation and before the image body itself
+#[derive(Clone, Copy, FromPrimitive)]
+enum CompressionFormat {
+    None = 0,
+    Zstd = 1,
+}
+
+/// length in bytes of the digest trailing a version >= 1 image transfer
+/// - sha256 for versions 1-3, blake3 for version 4
+const DIGEST_LEN: usize = 32;
+
+/// plaintext bytes per AEAD chunk - a version >= 2 image body is
+/// streamed in fixed windows so an enormous raster never has to be held
+/// as one ciphertext buffer, and so each chunk has a sequence number to
+/// fold into its nonce
+const AEAD_CHUNK_LEN: usize = 64 * 1024;
+
+/// derive a per-connection ChaCha20-Poly1305 key from the raw X25519
+/// diffie-hellman output - the DH output is never used directly as a
+/// cipher key, it's passed through sha256 first
+fn derive_cipher(shared_secret: x25519_dalek::SharedSecret) -> ChaCha20Poly1305 {
+    let mut hasher = Sha256::new();
+    hasher.update(shared_secret.as_bytes());
+    let key_bytes = hasher.finalize();
+
+    ChaCha20Poly1305::new(Key::from_slice(&key_bytes))
+}
+
+/// nonce for AEAD chunk 'seq' - the sequence number occupies the low 8
+/// bytes so a reordered, replayed, or dropped chunk fails to decrypt
+/// instead of silently being accepted out of order
+fn chunk_nonce(seq: u64) -> Nonce {
+    let mut bytes = [0u8; 12];
+    bytes[4..].copy_from_slice(&seq.to_be_bytes());
+    *Nonce::from_slice(&bytes)
+}
+
+/// server side of the per-connection X25519 ephemeral key exchange,
+/// performed once a peer announces protocol version >= 2 - the server
+/// writes its ephemeral public key first, mirroring the
+/// challenge/signature handshake above, then reads the peer's
+fn negotiate_server(stream: &mut TcpStream)
+        -> Result<ChaCha20Poly1305, Box<dyn Error>> {
+    let secret = EphemeralSecret::new(OsRng);
+    let public = X25519PublicKey::from(&secret);
+    stream.write_all(public.as_bytes())?;
+
+    let mut peer_public = [0u8; 32];
+    stream.read_exact(&mut peer_public)?;
+
+    let shared_secret = secret.diffie_hellman(
+        &X25519PublicKey::from(peer_public));
+    Ok(derive_cipher(shared_secret))
+}
+
+/// client side of the exchange - reads the server's ephemeral public key
+/// before sending its own, the reverse order of 'negotiate_server'
+fn negotiate_client(stream: &mut TcpStream)
+        -> Result<ChaCha20Poly1305, Box<dyn Error>> {
+    let mut peer_public = [0u8; 32];
+    stream.read_exact(&mut peer_public)?;
+
+    let secret = EphemeralSecret::new(OsRng);
+    let public = X25519PublicKey::from(&secret);
+    stream.write_all(public.as_bytes())?;
+
+    let shared_secret = secret.diffie_hellman(
+        &X25519PublicKey::from(peer_public));
+    Ok(derive_cipher(shared_secret))
+}
+
+/// encrypt 'plaintext' as a sequence of AEAD chunks, each prefixed by
+/// its ciphertext length (which includes the trailing 16-byte tag) and
+/// terminated by a zero-length chunk - returns the sha256 digest
+/// computed over the plaintext as it's consumed, the same role
+/// 'HashingWriter' plays for the unencrypted wire format
+fn write_encrypted<W: Write>(writer: &mut W, cipher: &ChaCha20Poly1305,
+        plaintext: &[u8]) -> Result<[u8; DIGEST_LEN], Box<dyn Error>> {
+    let mut hasher = Sha256::new();
+
+    for (seq, chunk) in plaintext.chunks(AEAD_CHUNK_LEN).enumerate() {
+        hasher.update(chunk);
+
+        let ciphertext = cipher.encrypt(&chunk_nonce(seq as u64), chunk)
+            .map_err(|_| "failed to encrypt transfer chunk")?;
+        writer.write_u32::<BigEndian>(ciphertext.len() as u32)?;
+        writer.write_all(&ciphertext)?;
+    }
+
+    // zero-length chunk marks the end of the stream
+    writer.write_u32::<BigEndian>(0)?;
+    Ok(hasher.finalize().into())
+}
+
+/// counterpart to 'write_encrypted' - reads and decrypts chunks until
+/// the zero-length terminator, returning the reassembled plaintext
+/// alongside the sha256 digest recomputed over it as each chunk is
+/// decrypted
+fn read_encrypted<R: Read>(reader: &mut R, cipher: &ChaCha20Poly1305)
+        -> Result<(Vec<u8>, [u8; DIGEST_LEN]), Box<dyn Error>> {
+    let mut hasher = Sha256::new();
+    let mut plaintext = Vec::new();
+    let mut seq = 0u64;
+
+    loop {
+        let chunk_len = reader.read_u32::<BigEndian>()? as usize;
+        if chunk_len == 0 {
+            break;
+        }
+
+        let mut ciphertext = vec![0u8; chunk_len];
+        reader.read_exact(&mut ciphertext)?;
+
+        let chunk = cipher.decrypt(&chunk_nonce(seq), ciphertext.as_ref())
+            .map_err(|_| "failed to decrypt transfer chunk")?;
+        hasher.update(&chunk);
+        plaintext.extend_from_slice(&chunk);
+        seq += 1;
+    }
+
+    Ok((plaintext, hasher.finalize().into()))
+}
+
 #[derive(FromPrimitive)]
 enum TransferOp {
     ReadImage = 0,
     WriteImage = 1,
+    WriteMetadata = 2,
+}
+
+/// wraps a writer, feeding every byte passed through into a running
+/// sha256 hasher - lets the image transfer protocol compute an
+/// end-to-end digest over exactly the bytes that cross the wire instead
+/// of hashing a buffer in a separate pass
+struct HashingWriter<'a, W: Write> {
+    inner: &'a mut W,
+    hasher: Sha256,
+}
+
+impl<'a, W: Write> HashingWriter<'a, W> {
+    fn new(inner: &'a mut W) -> HashingWriter<'a, W> {
+        HashingWriter {
+            inner: inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    fn finish(self) -> [u8; DIGEST_LEN] {
+        self.hasher.finalize().into()
+    }
+}
+
+impl<'a, W: Write> Write for HashingWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// the 'HashingWriter' counterpart for the receiving side of an image
+/// transfer - recomputes the same digest while the image body is read
+/// off the wire, so the two sides can compare without either buffering
+/// twice
+struct HashingReader<'a, R: Read> {
+    inner: &'a mut R,
+    hasher: Sha256,
+}
+
+impl<'a, R: Read> HashingReader<'a, R> {
+    fn new(inner: &'a mut R) -> HashingReader<'a, R> {
+        HashingReader {
+            inner: inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    fn finish(self) -> [u8; DIGEST_LEN] {
+        self.hasher.finalize().into()
+    }
+}
+
+impl<'a, R: Read> Read for HashingReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
 }
 
 pub struct TransferStreamHandler {
     album_manager: Arc<RwLock<AlbumManager>>,
+    gossip: Arc<GossipState>,
+    require_encryption: bool,
 }
 
 impl TransferStreamHandler {
-    pub fn new(album_manager: Arc<RwLock<AlbumManager>>)
-            -> TransferStreamHandler {
+    /// 'require_encryption' rejects a peer speaking protocol version < 2
+    /// instead of falling back to a cleartext transfer - set from the
+    /// environment's own encryption requirement once callers plumb one
+    /// through (see 'environment::Environment::require_encryption')
+    pub fn new(album_manager: Arc<RwLock<AlbumManager>>,
+            gossip: Arc<GossipState>,
+            require_encryption: bool) -> TransferStreamHandler {
         TransferStreamHandler {
             album_manager: album_manager,
+            gossip: gossip,
+            require_encryption: require_encryption,
         }
     }
 }
@@ -36,8 +285,60 @@ impl TransferStreamHandler {
 impl StreamHandler for TransferStreamHandler {
     fn process(&self, stream: &mut TcpStream)
             -> Result<(), Box<dyn Error>> {
-        // read operation type
+        // authenticate the peer before touching any tile data - it
+        // must prove it holds the private key recorded for the node
+        // id it claims, so a rogue host can't impersonate a dht
+        // member and push tiles into another node's album
+        let mut challenge = vec![0u8; CHALLENGE_LEN];
+        for byte in challenge.iter_mut() {
+            *byte = rand::random::<u8>();
+        }
+        stream.write_all(&challenge)?;
+
+        let node_id = stream.read_u32::<BigEndian>()?;
+        let signature_len = stream.read_u8()?;
+        let mut signature = vec![0u8; signature_len as usize];
+        stream.read_exact(&mut signature)?;
+
+        let verified = match self.gossip.public_key_of(node_id) {
+            Some(public_key) => identity::verify(
+                &public_key, &challenge, &signature).is_ok(),
+            None => false,
+        };
+
+        if !verified {
+            let err_msg = format!(
+                "node {} failed transfer authentication", node_id);
+            stream.write_u8(1)?;
+            write_string(&err_msg, stream)?;
+            return Err(err_msg.into());
+        }
+        stream.write_u8(0)?;
+
+        // read operation type, then the protocol version the peer is
+        // speaking - version 0 predates the end-to-end digest and is
+        // handled as a special case below so a mixed-version cluster
+        // still transfers tiles while nodes roll forward
         let op_type = stream.read_u8()?;
+        let protocol_version = stream.read_u8()?;
+
+        if self.require_encryption && protocol_version < 2 {
+            let err_msg = format!("node {} must use transfer protocol \
+                version >= 2 (encrypted) in this environment", node_id);
+            stream.write_u8(1)?;
+            write_string(&err_msg, stream)?;
+            return Err(err_msg.into());
+        }
+
+        // negotiate a per-connection key before any tile bytes cross the
+        // wire, so an eavesdropper on the network between nodes sees
+        // only ciphertext chunks rather than the raw raster
+        let cipher = if protocol_version >= 2 {
+            Some(negotiate_server(stream)?)
+        } else {
+            None
+        };
+
         match FromPrimitive::from_u8(op_type) {
             Some(TransferOp::ReadImage) => {
                 // read path
@@ -59,7 +360,7 @@ impl StreamHandler for TransferStreamHandler {
                 if subgeocode_indicator == 0 {
                     // no need to split image -> write image
                     stream.write_u8(0)?;
-                    st_image::prelude::write(&dataset, stream)?;
+                    write_dataset(&dataset, stream, cipher.as_ref())?;
                 } else {
                     // read subgeocode metadata
                     let geocode_value = stream.read_u8()?;
@@ -97,8 +398,8 @@ impl StreamHandler for TransferStreamHandler {
 
                         // process valid subdataset
                         stream.write_u8(0)?;
-                        st_image::prelude::write(
-                            &dataset_split.dataset()?, stream)?;
+                        write_dataset(&dataset_split.dataset()?,
+                            stream, cipher.as_ref())?;
                         return Ok(())
                     }
 
@@ -111,9 +412,225 @@ impl StreamHandler for TransferStreamHandler {
                 }
             },
             Some(TransferOp::WriteImage) => {
+                // version >= 3 peers write a compression format flag
+                // right after the cipher negotiation, before anything
+                // else - older peers never compress, so there's nothing
+                // to read for them
+                let compression_format = if protocol_version >= 3 {
+                    let format_byte = stream.read_u8()?;
+                    match FromPrimitive::from_u8(format_byte) {
+                        Some(format) => format,
+                        None => {
+                            let err_msg = format!(
+                                "unsupported compression format '{}'",
+                                format_byte);
+                            stream.write_u8(1)?;
+                            write_string(&err_msg, stream)?;
+                            return Err(err_msg.into());
+                        },
+                    }
+                } else {
+                    CompressionFormat::None
+                };
+
                 // read everything
                 let album = read_string(stream)?;
-                let mut dataset = st_image::prelude::read(stream)?;
+
+                let (buf, content_digest) = if protocol_version < 4 {
+                    let buf_len = stream.read_u64::<BigEndian>()?;
+
+                    if protocol_version == 0 {
+                        // legacy peer: an 8-byte checksum precedes the
+                        // image body rather than a digest trailing it
+                        let expected_checksum = stream.read_u64::<BigEndian>()?;
+
+                        let mut buf = vec![0u8; buf_len as usize];
+                        stream.read_exact(&mut buf)?;
+
+                        let actual_checksum = checksum(&buf);
+                        if actual_checksum != expected_checksum {
+                            let err_msg = format!("checksum mismatch: \
+                                expected {} got {}",
+                                expected_checksum, actual_checksum);
+                            stream.write_u8(1)?;
+                            return Err(err_msg.into());
+                        }
+
+                        // content address the tile so an identical
+                        // reprocessed tile lands as a dedup hardlink
+                        // rather than a second on-disk copy
+                        let content_digest = crate::block::digest(&buf);
+                        (buf, content_digest)
+                    } else if protocol_version == 1 {
+                        // recompute the sha256 digest while the image
+                        // body is read off the wire, then compare it
+                        // against the digest the sender computed over
+                        // the same bytes as it wrote them - a dropped
+                        // or flipped byte anywhere in transit surfaces
+                        // here instead of silently landing in the album
+                        let mut buf = vec![0u8; buf_len as usize];
+                        let actual_digest = {
+                            let mut hashing_reader = HashingReader::new(stream);
+                            hashing_reader.read_exact(&mut buf)?;
+                            hashing_reader.finish()
+                        };
+
+                        let mut expected_digest = [0u8; DIGEST_LEN];
+                        stream.read_exact(&mut expected_digest)?;
+
+                        if actual_digest != expected_digest {
+                            let err_msg = format!("digest mismatch: \
+                                expected {} got {}",
+                                hex::encode(expected_digest),
+                                hex::encode(actual_digest));
+                            stream.write_u8(1)?;
+                            write_string(&err_msg, stream)?;
+                            return Err(err_msg.into());
+                        }
+
+                        (buf, hex::encode(actual_digest))
+                    } else {
+                        // version 2/3: the image body was negotiated as
+                        // encrypted AEAD chunks above - decrypt while
+                        // recomputing the same end-to-end digest the
+                        // cleartext version 1 format computes directly
+                        let cipher = cipher.as_ref().expect(
+                            "cipher negotiated for protocol_version >= 2");
+                        let (buf, actual_digest) =
+                            read_encrypted(stream, cipher)?;
+
+                        let mut expected_digest = [0u8; DIGEST_LEN];
+                        stream.read_exact(&mut expected_digest)?;
+
+                        if actual_digest != expected_digest {
+                            let err_msg = format!("digest mismatch: \
+                                expected {} got {}",
+                                hex::encode(expected_digest),
+                                hex::encode(actual_digest));
+                            stream.write_u8(1)?;
+                            write_string(&err_msg, stream)?;
+                            return Err(err_msg.into());
+                        }
+
+                        (buf, hex::encode(actual_digest))
+                    }
+                } else {
+                    // version >= 4: the sender wrote only the hashes of
+                    // its content-defined chunks (see 'cdc::chunk') -
+                    // answer with the indices this album's chunk store
+                    // doesn't already hold, so only those chunks'
+                    // bytes, each still AEAD-encrypted, actually cross
+                    // the wire
+                    let cipher = cipher.as_ref().expect(
+                        "cipher negotiated for protocol_version >= 2");
+
+                    let chunk_store = {
+                        let album_manager = self.album_manager.read().unwrap();
+                        let directory = match album_manager.get(&album) {
+                            Some(album) =>
+                                album.read().unwrap().get_directory().clone(),
+                            None => {
+                                let err_msg = format!(
+                                    "album '{}' does not exist", album);
+                                stream.write_u8(1)?;
+                                write_string(&err_msg, stream)?;
+                                return Err(err_msg.into());
+                            },
+                        };
+
+                        crate::cdc::ChunkStore::open(directory)?
+                    };
+
+                    let chunk_count = stream.read_u32::<BigEndian>()?;
+                    let mut hashes = Vec::with_capacity(chunk_count as usize);
+                    for _ in 0..chunk_count {
+                        let mut hash = [0u8; 32];
+                        stream.read_exact(&mut hash)?;
+                        hashes.push(hash);
+                    }
+
+                    // tell the sender which chunks we don't already hold
+                    // - 'contains_valid' (rather than 'has') also
+                    // catches a chunk whose on-disk bytes have rotted
+                    // since it was written, re-requesting it instead of
+                    // trusting a copy that would fail the digest check
+                    // below anyway
+                    let missing: Vec<u32> = hashes.iter().enumerate()
+                        .filter(|(_, hash)| !chunk_store.contains_valid(hash))
+                        .map(|(index, _)| index as u32)
+                        .collect();
+
+                    stream.write_u32::<BigEndian>(missing.len() as u32)?;
+                    for index in &missing {
+                        stream.write_u32::<BigEndian>(*index)?;
+                    }
+
+                    // receive exactly the missing chunks, in the same
+                    // order they were requested, and persist each into
+                    // the chunk store so a later overlapping transfer
+                    // never has to ask for it again - a chunk is stored
+                    // (and content-addressed) by its decompressed
+                    // bytes, since 'compression_format' only describes
+                    // what crossed the wire, not what the chunk hash
+                    // was computed over
+                    for index in &missing {
+                        let (body, _) = read_encrypted(stream, cipher)?;
+                        let data = match compression_format {
+                            CompressionFormat::None => body,
+                            CompressionFormat::Zstd =>
+                                zstd::decode_all(&body[..])?,
+                        };
+                        chunk_store.write(&hashes[*index as usize], &data)?;
+                    }
+
+                    // reassemble - every hash now resolves, whether it
+                    // was already resident or was just written above
+                    let mut buf = Vec::new();
+                    for hash in &hashes {
+                        buf.extend_from_slice(&chunk_store.read(hash)?);
+                    }
+
+                    let mut expected_digest = [0u8; DIGEST_LEN];
+                    stream.read_exact(&mut expected_digest)?;
+
+                    let actual_digest: [u8; DIGEST_LEN] =
+                        blake3::hash(&buf).into();
+                    if actual_digest != expected_digest {
+                        let err_msg = format!("digest mismatch: \
+                            expected {} got {}",
+                            hex::encode(expected_digest),
+                            hex::encode(actual_digest));
+                        stream.write_u8(1)?;
+                        write_string(&err_msg, stream)?;
+                        return Err(err_msg.into());
+                    }
+
+                    (buf, hex::encode(actual_digest))
+                };
+
+                // the wire digest above is computed over exactly the
+                // bytes that crossed the wire, so a compressed body has
+                // to come back off before anything downstream (the
+                // on-disk checksum, the dataset itself) sees it - a
+                // version >= 4 transfer already decompressed each chunk
+                // as it was stored, so 'buf' here is already raw
+                let buf = if protocol_version < 4 {
+                    match compression_format {
+                        CompressionFormat::None => buf,
+                        CompressionFormat::Zstd => zstd::decode_all(&buf[..])?,
+                    }
+                } else {
+                    buf
+                };
+
+                // the on-disk 'CHECKSUM' metadata tag ('verify_tile'
+                // recomputes it against 'checksum' the same way)
+                // is independent of whichever wire digest this peer
+                // spoke, so it's always derived from the received bytes
+                let content_checksum = checksum(&buf);
+
+                let mut dataset = st_image::prelude::read(
+                    &mut Cursor::new(buf))?;
                 let geocode = read_string(stream)?;
                 let pixel_coverage = stream.read_f64::<BigEndian>()?;
                 let platform = read_string(stream)?;
@@ -121,6 +638,7 @@ impl StreamHandler for TransferStreamHandler {
                 let subdataset = stream.read_u8()?;
                 let tile = read_string(stream)?;
                 let timestamp = stream.read_i64::<BigEndian>()?;
+                let preview = stream.read_u8()? != 0;
 
                 // write image using AlbumManager
                 let album_manager = self.album_manager.read().unwrap();
@@ -129,7 +647,39 @@ impl StreamHandler for TransferStreamHandler {
                         let mut album = album.write().unwrap();
                         album.write(&mut dataset, &geocode,
                             pixel_coverage, &platform, &source,
-                            subdataset, &tile, timestamp)?;
+                            subdataset, &tile, timestamp,
+                            content_checksum, &content_digest, node_id,
+                            preview)?;
+                    },
+                    None => warn!("album '{}' does not exist", album),
+                }
+
+                // write success
+                stream.write_u8(1)?;
+            },
+            Some(TransferOp::WriteMetadata) => {
+                // read everything
+                let album = read_string(stream)?;
+                let geocode = read_string(stream)?;
+                let platform = read_string(stream)?;
+                let tile = read_string(stream)?;
+                let mean_pixel_coverage = stream.read_f64::<BigEndian>()?;
+
+                let file_count = stream.read_u8()?;
+                let mut files = Vec::new();
+                for _ in 0..file_count {
+                    let band_id = stream.read_u8()?;
+                    let description = read_string(stream)?;
+                    files.push((band_id, description));
+                }
+
+                // write tile metadata using AlbumManager
+                let album_manager = self.album_manager.read().unwrap();
+                match album_manager.get(&album) {
+                    Some(album) => {
+                        let album = album.read().unwrap();
+                        album.write_tile_metadata(&geocode, &tile,
+                            &platform, mean_pixel_coverage, &files)?;
                     },
                     None => warn!("album '{}' does not exist", album),
                 }
@@ -146,6 +696,73 @@ impl StreamHandler for TransferStreamHandler {
     }
 }
 
+/// write 'dataset' to 'writer', encrypting it as AEAD chunks when
+/// 'cipher' is negotiated (protocol_version >= 2) and writing it
+/// directly otherwise
+fn write_dataset<W: Write>(dataset: &Dataset, writer: &mut W,
+        cipher: Option<&ChaCha20Poly1305>) -> Result<(), Box<dyn Error>> {
+    match cipher {
+        Some(cipher) => {
+            let mut buf = Vec::new();
+            st_image::prelude::write(dataset, &mut buf)?;
+            write_encrypted(writer, cipher, &buf)?;
+        },
+        None => st_image::prelude::write(dataset, writer)?,
+    }
+
+    Ok(())
+}
+
+/// send one tile's aggregate band/file catalog - mirrors 'send_image's
+/// authentication handshake and protocol version negotiation, but the
+/// catalog itself (a handful of strings and an f64) is sent in the
+/// clear rather than as an AEAD-chunked body
+pub fn send_metadata(addr: &SocketAddr, identity: &NodeIdentity,
+        node_id: u32, album: &str, geocode: &str, platform: &str,
+        tile: &str, mean_pixel_coverage: f64, files: &[(u8, String)])
+        -> Result<(), Box<dyn Error>> {
+    // open connection and complete the authentication handshake
+    let mut stream = TcpStream::connect(addr)?;
+
+    let mut challenge = vec![0u8; CHALLENGE_LEN];
+    stream.read_exact(&mut challenge)?;
+
+    let signature = identity.sign(&challenge);
+    stream.write_u32::<BigEndian>(node_id)?;
+    stream.write_u8(signature.len() as u8)?;
+    stream.write_all(&signature)?;
+
+    if stream.read_u8()? != 0 {
+        let err_msg = read_string(&mut stream)?;
+        return Err(err_msg.into());
+    }
+
+    stream.write_u8(TransferOp::WriteMetadata as u8)?;
+    stream.write_u8(TRANSFER_PROTOCOL_VERSION)?;
+
+    // the server negotiates a key for every connection >= version 2
+    // regardless of operation, so this handshake still has to happen
+    // even though the catalog payload below isn't encrypted
+    let _ = negotiate_client(&mut stream)?;
+
+    write_string(album, &mut stream)?;
+    write_string(geocode, &mut stream)?;
+    write_string(platform, &mut stream)?;
+    write_string(tile, &mut stream)?;
+    stream.write_f64::<BigEndian>(mean_pixel_coverage)?;
+
+    stream.write_u8(files.len() as u8)?;
+    for (band_id, description) in files {
+        stream.write_u8(*band_id)?;
+        write_string(description, &mut stream)?;
+    }
+
+    // read success
+    let _ = stream.read_u8()?;
+
+    Ok(())
+}
+
 pub fn read_string<T: Read>(reader: &mut T)
         -> Result<String, Box<dyn Error>> {
     let len = reader.read_u8()?;
@@ -154,17 +771,107 @@ pub fn read_string<T: Read>(reader: &mut T)
     Ok(String::from_utf8(buf)?)
 }
 
-pub fn send_image(addr: &SocketAddr, album: &str, dataset: &Dataset,
-        geocode: &str, pixel_coverage: f64, platform: &str,
-        source: &str, subdataset: u8, tile: &str, timestamp: i64)
+pub fn send_image(addr: &SocketAddr, identity: &NodeIdentity, node_id: u32,
+        album: &str, dataset: &Dataset, geocode: &str, pixel_coverage: f64,
+        platform: &str, source: &str, subdataset: u8, tile: &str,
+        timestamp: i64, preview: bool, compression: Option<i32>)
+        -> Result<(), Box<dyn Error>> {
+    // serialize the raster into a buffer - 'send_image_bytes' splits
+    // it into content-defined chunks and computes the blake3 digest
+    // over this same buffer
+    let mut buf = Vec::new();
+    st_image::prelude::write(&dataset, &mut buf)?;
+
+    send_image_bytes(addr, identity, node_id, album, &buf, geocode,
+        pixel_coverage, platform, source, subdataset, tile, timestamp,
+        preview, compression)
+}
+
+/// identical to 'send_image', but takes an already-serialized raster
+/// rather than a gdal 'Dataset' - 'Dataset' wraps a raw gdal handle that
+/// can't cross a thread boundary, so a caller that decodes and
+/// transfers on separate thread pools (see 'task::load::transfer')
+/// serializes the split raster before handing it off
+pub fn send_image_bytes(addr: &SocketAddr, identity: &NodeIdentity,
+        node_id: u32, album: &str, buf: &[u8], geocode: &str,
+        pixel_coverage: f64, platform: &str, source: &str, subdataset: u8,
+        tile: &str, timestamp: i64, preview: bool, compression: Option<i32>)
         -> Result<(), Box<dyn Error>> {
-    // open connection
+    // open connection and complete the authentication handshake by
+    // signing the server's challenge with our node identity, proving
+    // we are the node_id we claim to be
     let mut stream = TcpStream::connect(addr)?;
+
+    let mut challenge = vec![0u8; CHALLENGE_LEN];
+    stream.read_exact(&mut challenge)?;
+
+    let signature = identity.sign(&challenge);
+    stream.write_u32::<BigEndian>(node_id)?;
+    stream.write_u8(signature.len() as u8)?;
+    stream.write_all(&signature)?;
+
+    if stream.read_u8()? != 0 {
+        let err_msg = read_string(&mut stream)?;
+        return Err(err_msg.into());
+    }
+
     stream.write_u8(TransferOp::WriteImage as u8)?;
+    stream.write_u8(TRANSFER_PROTOCOL_VERSION)?;
+
+    // negotiate a per-connection key before any tile bytes cross the
+    // wire, so an eavesdropper on the network between nodes sees only
+    // ciphertext chunks rather than the raw raster
+    let cipher = negotiate_client(&mut stream)?;
+
+    // version 4 dedups at the content-defined-chunk level - chunk
+    // boundaries are found over the raw raster, since compressing
+    // first would scramble the very byte runs CDC is trying to
+    // recognize as shared with an already-transferred tile, but
+    // whichever chunks actually do end up crossing the wire are still
+    // compressed individually when the caller asked for it
+    let compression_format = match compression {
+        Some(_) => CompressionFormat::Zstd,
+        None => CompressionFormat::None,
+    };
+    stream.write_u8(compression_format as u8)?;
 
-    // write everything
     write_string(&album, &mut stream)?;
-    st_image::prelude::write(&dataset, &mut stream)?;
+
+    // split the raster into content-defined chunks and send only their
+    // hashes first, so the receiver can tell us which ones its chunk
+    // store is missing before any tile bytes actually cross the wire
+    let chunks = crate::cdc::chunk(buf);
+    stream.write_u32::<BigEndian>(chunks.len() as u32)?;
+    for chunk in &chunks {
+        stream.write_all(&chunk.hash)?;
+    }
+
+    let missing_count = stream.read_u32::<BigEndian>()?;
+    let mut missing = Vec::with_capacity(missing_count as usize);
+    for _ in 0..missing_count {
+        missing.push(stream.read_u32::<BigEndian>()?);
+    }
+
+    for index in &missing {
+        let data = &chunks[*index as usize].data;
+        let compressed;
+        let body: &[u8] = match compression {
+            Some(level) => {
+                compressed = zstd::encode_all(&data[..], level)?;
+                &compressed
+            },
+            None => data,
+        };
+
+        write_encrypted(&mut stream, &cipher, body)?;
+    }
+
+    // end-to-end digest over the whole reassembled raster, so a chunk
+    // the receiver already held (and so never saw on this connection)
+    // is still covered by the integrity check
+    let digest: [u8; DIGEST_LEN] = blake3::hash(buf).into();
+    stream.write_all(&digest)?;
+
     write_string(&geocode, &mut stream)?;
     stream.write_f64::<BigEndian>(pixel_coverage)?;
     write_string(&platform, &mut stream)?;
@@ -172,7 +879,8 @@ pub fn send_image(addr: &SocketAddr, album: &str, dataset: &Dataset,
     stream.write_u8(subdataset)?;
     write_string(&tile, &mut stream)?;
     stream.write_i64::<BigEndian>(timestamp)?;
- 
+    stream.write_u8(preview as u8)?;
+
     // read success
     let _ = stream.read_u8()?;
 